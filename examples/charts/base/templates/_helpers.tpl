@@ -2,12 +2,14 @@
 
 
 {{/*
-Create chart name and version as used by the chart label.
+Recommended app.kubernetes.io labels (populated by shipcat) and owner references.
 */}}
 {{- define "chart.shipcatRefs" }}
-    app.kubernetes.io/name: {{ .Values.name }}
-    app.kubernetes.io/version: {{ .Values.version }}
-    app.kubernetes.io/managed-by: shipcat
+{{- range $k, $v := .Values.labels }}
+{{- if hasPrefix "app.kubernetes.io/" $k }}
+    {{ $k }}: {{ $v | quote }}
+{{- end }}
+{{- end }}
   ownerReferences:
   - apiVersion: babylontech.co.uk/v1
     kind: ShipcatManifest
@@ -17,6 +19,43 @@ Create chart name and version as used by the chart label.
 {{- end }}
 
 
+{{/*
+User supplied labels, excluding the recommended ones from chart.shipcatRefs.
+*/}}
+{{- define "chart.customLabels" }}
+{{- range $k, $v := .Values.labels }}
+{{- if not (hasPrefix "app.kubernetes.io/" $k) }}
+    {{ $k }}: {{ $v | quote }}
+{{- end }}
+{{- end }}
+{{- end }}
+
+
+{{/*
+Name of the ServiceAccount the pods run as.
+*/}}
+{{- define "chart.serviceAccountName" -}}
+{{- if .Values.serviceAccount -}}
+{{ .Values.serviceAccount.name }}
+{{- else -}}
+{{ .Values.name }}
+{{- end -}}
+{{- end }}
+
+{{/* A volume, without the fields shipcat uses to create claims */}}
+{{- define "chart.volume" -}}
+{{- if .persistentVolumeClaim -}}
+- name: {{ .name }}
+  persistentVolumeClaim:
+    claimName: {{ .persistentVolumeClaim.claimName }}
+{{- if .persistentVolumeClaim.readOnly }}
+    readOnly: true
+{{- end }}
+{{- else -}}
+{{ toYaml (list .) }}
+{{- end -}}
+{{- end }}
+
 {{- define "container-env" -}}
 {{- range $k, $v := .plain }}
 - name: {{ $k }}
@@ -30,4 +69,12 @@ Create chart name and version as used by the chart label.
       name: {{ $service }}-secrets
       key: {{ $name }}
 {{- end }}
+{{- range $k, $v := .valueFrom }}
+- name: {{ $k }}
+  valueFrom:
+{{ toYaml $v | indent 4 }}
+{{- end }}
+{{- range $i, $name := .external }}
+# {{ $name }} is provided externally
+{{- end }}
 {{- end -}}
@@ -153,7 +153,7 @@ impl State {
             .state()
             .await?
             .into_iter()
-            .filter(|crd| crd.spec.metadata.clone().unwrap().team == team)
+            .filter(|crd| crd.spec.metadata.as_ref().map_or(false, |md| md.owned_by(team)))
             .map(|crd| crd.spec.name)
             .collect();
         Ok(mfs)
@@ -0,0 +1,296 @@
+extern crate proc_macro;
+
+use crate::proc_macro::TokenStream;
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Attribute, Data, DataEnum, DeriveInput, Fields, GenericParam, Lit, Meta, NestedMeta};
+
+/// Derive `shipcat_definitions::schema::JsonSchema`
+///
+/// Reads the `serde` attributes and doc comments of the type, so the schema
+/// follows what the type actually deserializes from.
+/// Generated code refers to `crate::schema`, so this is only usable inside `shipcat_definitions`.
+#[proc_macro_derive(JsonSchema)]
+pub fn schema_derive(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+    impl_schema(&ast)
+}
+
+fn impl_schema(ast: &DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let name_str = name.to_string();
+    let container = SerdeAttrs::from(&ast.attrs);
+    let doc = doc_string(&ast.attrs);
+
+    let mut generics = ast.generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(t) = param {
+            t.bounds.push(syn::parse_quote!(crate::schema::JsonSchema));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match (&container.from, &ast.data) {
+        // types deserialized through another type have the schema of that type
+        (Some(from), _) => {
+            let from: syn::Type = syn::parse_str(from).expect("serde(from) is a type");
+            quote! { <#from as crate::schema::JsonSchema>::inline(defs) }
+        }
+        (None, Data::Struct(s)) => match &s.fields {
+            Fields::Named(_) => impl_object(&s.fields, &container),
+            Fields::Unnamed(f) if f.unnamed.len() == 1 => {
+                let ty = &f.unnamed[0].ty;
+                quote! { <#ty as crate::schema::JsonSchema>::inline(defs) }
+            }
+            _ => panic!("Only structs with named fields or newtypes are supported"),
+        },
+        (None, Data::Enum(e)) => impl_enum(e, &container),
+        (None, Data::Union(_)) => panic!("Unions are not supported"),
+    };
+
+    let gen = quote! {
+        impl #impl_generics crate::schema::JsonSchema for #name #ty_generics #where_clause {
+            fn schema(defs: &mut crate::schema::Definitions) -> serde_json::Value {
+                crate::schema::reference::<Self>(#name_str, defs)
+            }
+            fn inline(defs: &mut crate::schema::Definitions) -> serde_json::Value {
+                crate::schema::describe(#body, #doc)
+            }
+        }
+    };
+    gen.into()
+}
+
+/// Build an object schema from named fields
+fn impl_object(fields: &Fields, container: &SerdeAttrs) -> TokenStream2 {
+    let mut field_tokens = TokenStream2::new();
+    for field in fields.iter() {
+        let attrs = SerdeAttrs::from(&field.attrs);
+        if attrs.skip_deserializing {
+            continue;
+        }
+        let ty = &field.ty;
+        if attrs.flatten {
+            field_tokens.extend(quote! {
+                crate::schema::flatten_into(
+                    &mut properties,
+                    &mut required,
+                    <#ty as crate::schema::JsonSchema>::inline(defs),
+                );
+            });
+            continue;
+        }
+        let ident = field.ident.as_ref().expect("named field");
+        let key = attrs.rename.clone().unwrap_or_else(|| {
+            let raw = ident.to_string().trim_start_matches("r#").to_string();
+            rename_field(&raw, container.rename_all.as_ref())
+        });
+        let doc = doc_string(&field.attrs);
+        let required = if attrs.default || container.default {
+            quote! { false }
+        } else {
+            quote! { !<#ty as crate::schema::JsonSchema>::optional() }
+        };
+        field_tokens.extend(quote! {
+            crate::schema::add_property(
+                &mut properties,
+                &mut required,
+                #key,
+                crate::schema::describe(<#ty as crate::schema::JsonSchema>::schema(defs), #doc),
+                #required,
+            );
+        });
+    }
+    let deny = container.deny_unknown_fields;
+    quote! {
+        {
+            let mut properties = serde_json::Map::new();
+            let mut required = vec![];
+            #field_tokens
+            crate::schema::object(properties, required, #deny)
+        }
+    }
+}
+
+fn impl_enum(data: &DataEnum, container: &SerdeAttrs) -> TokenStream2 {
+    let all_unit = data.variants.iter().all(|v| matches!(v.fields, Fields::Unit));
+    let mut names = vec![];
+    let mut variant_tokens = TokenStream2::new();
+    for v in &data.variants {
+        let attrs = SerdeAttrs::from(&v.attrs);
+        if attrs.skip_deserializing {
+            continue;
+        }
+        let vname = attrs
+            .rename
+            .clone()
+            .unwrap_or_else(|| rename_variant(&v.ident.to_string(), container.rename_all.as_ref()));
+        let doc = doc_string(&v.attrs);
+        // the serialized content of the variant, if any
+        let content = match &v.fields {
+            Fields::Unit => None,
+            Fields::Unnamed(f) if f.unnamed.len() == 1 => {
+                let ty = &f.unnamed[0].ty;
+                Some(quote! { <#ty as crate::schema::JsonSchema>::schema(defs) })
+            }
+            Fields::Unnamed(_) => panic!("Tuple variants are not supported"),
+            Fields::Named(_) => Some(impl_object(&v.fields, &SerdeAttrs::default())),
+        };
+        let schema = match (&container.tag, &container.content, container.untagged, content) {
+            (_, _, true, None) => quote! { serde_json::json!({ "type": "null" }) },
+            (_, _, true, Some(c)) => c,
+            (Some(tag), Some(content_key), _, c) => {
+                let c = c.map(|c| quote! { Some(#c) }).unwrap_or_else(|| quote! { None });
+                quote! { crate::schema::adjacently_tagged(#tag, #vname, #content_key, #c) }
+            }
+            (Some(tag), None, _, c) => {
+                let c = c.map(|c| quote! { Some(#c) }).unwrap_or_else(|| quote! { None });
+                quote! { crate::schema::internally_tagged(#tag, #vname, #c) }
+            }
+            (None, _, false, None) => quote! { serde_json::json!({ "type": "string", "enum": [#vname] }) },
+            (None, _, false, Some(c)) => quote! { crate::schema::externally_tagged(#vname, #c) },
+        };
+        names.push(vname);
+        variant_tokens.extend(quote! {
+            variants.push(crate::schema::describe(#schema, #doc));
+        });
+    }
+    if all_unit && container.tag.is_none() && !container.untagged {
+        return quote! {
+            serde_json::json!({ "type": "string", "enum": [#(#names),*] })
+        };
+    }
+    let combinator = if container.untagged { "anyOf" } else { "oneOf" };
+    quote! {
+        {
+            let mut variants: Vec<serde_json::Value> = vec![];
+            #variant_tokens
+            serde_json::json!({ #combinator: variants })
+        }
+    }
+}
+
+/// The subset of serde attributes that affect the schema
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    from: Option<String>,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    default: bool,
+    flatten: bool,
+    skip_deserializing: bool,
+    deny_unknown_fields: bool,
+}
+
+impl SerdeAttrs {
+    fn from(attrs: &[Attribute]) -> Self {
+        let mut res = SerdeAttrs::default();
+        for attr in attrs {
+            let list = match attr.parse_meta() {
+                Ok(Meta::List(l)) if l.ident == "serde" => l,
+                _ => continue,
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::Word(w)) => match w.to_string().as_ref() {
+                        "untagged" => res.untagged = true,
+                        "default" => res.default = true,
+                        "flatten" => res.flatten = true,
+                        "skip" | "skip_deserializing" => res.skip_deserializing = true,
+                        "deny_unknown_fields" => res.deny_unknown_fields = true,
+                        _ => {}
+                    },
+                    NestedMeta::Meta(Meta::NameValue(nv)) => {
+                        let value = match nv.lit {
+                            Lit::Str(s) => s.value(),
+                            _ => continue,
+                        };
+                        match nv.ident.to_string().as_ref() {
+                            "rename" => res.rename = Some(value),
+                            "rename_all" => res.rename_all = Some(value),
+                            "from" => res.from = Some(value),
+                            "tag" => res.tag = Some(value),
+                            "content" => res.content = Some(value),
+                            "default" => res.default = true,
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Collect doc comments into a single description
+fn doc_string(attrs: &[Attribute]) -> String {
+    let mut lines = vec![];
+    for attr in attrs {
+        if let Ok(Meta::NameValue(nv)) = attr.parse_meta() {
+            if nv.ident == "doc" {
+                if let Lit::Str(s) = nv.lit {
+                    let line = s.value();
+                    // doc comments keep the space after `///`
+                    let line = line.strip_prefix(' ').map(String::from).unwrap_or(line);
+                    lines.push(line);
+                }
+            }
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Rename a snake_case field like serde's `rename_all`
+fn rename_field(field: &str, rule: Option<&String>) -> String {
+    match rule.map(String::as_str) {
+        Some("lowercase") | Some("snake_case") => field.to_string(),
+        Some("UPPERCASE") | Some("SCREAMING_SNAKE_CASE") => field.to_ascii_uppercase(),
+        Some("kebab-case") => field.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => field.replace('_', "-").to_ascii_uppercase(),
+        Some("camelCase") | Some("PascalCase") => {
+            let mut res = String::new();
+            let mut capitalize = rule.map(String::as_str) == Some("PascalCase");
+            for c in field.chars() {
+                if c == '_' {
+                    capitalize = true;
+                } else if capitalize {
+                    res.push(c.to_ascii_uppercase());
+                    capitalize = false;
+                } else {
+                    res.push(c);
+                }
+            }
+            res
+        }
+        _ => field.to_string(),
+    }
+}
+
+/// Rename a PascalCase variant like serde's `rename_all`
+fn rename_variant(variant: &str, rule: Option<&String>) -> String {
+    let snake = || {
+        let mut res = String::new();
+        for (i, c) in variant.char_indices() {
+            if c.is_uppercase() && i > 0 {
+                res.push('_');
+            }
+            res.push(c.to_ascii_lowercase());
+        }
+        res
+    };
+    match rule.map(String::as_str) {
+        Some("lowercase") => variant.to_ascii_lowercase(),
+        Some("UPPERCASE") => variant.to_ascii_uppercase(),
+        Some("camelCase") => variant[..1].to_ascii_lowercase() + &variant[1..],
+        Some("snake_case") => snake(),
+        Some("SCREAMING_SNAKE_CASE") => snake().to_ascii_uppercase(),
+        Some("kebab-case") => snake().replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => snake().replace('_', "-").to_ascii_uppercase(),
+        _ => variant.to_string(),
+    }
+}
@@ -148,9 +148,9 @@ async fn apply_kubectl(
     }
     let explicit_version = mfbase.version.clone().or(passed_version);
 
-    if !mfbase.regions.contains(&region.name) {
+    if !mfbase.is_deployable_in(&region.name) {
         bail!(
-            "Cannot deploy '{}' to a region it's not configured for in its manifest",
+            "Cannot deploy '{}' to a region it's not configured for or disabled in its manifest",
             svc
         );
     }
@@ -204,7 +204,7 @@ async fn apply_kubectl(
     region.versioningScheme.verify(&actual_version)?;
 
     // Complete and apply the CRD
-    let mfcrd = mfbase.version(actual_version.clone());
+    let mfcrd = mfbase.with_version(actual_version.clone());
     let crd_changed = s.apply(mfcrd.clone()).await?;
     // Cheap reconcile ends here if !changed && !force
     if crd_changed {
@@ -252,7 +252,7 @@ async fn apply_kubectl(
     // Create completed kubernetes yaml (via shipcat values | helm template)
     let tfile = format!("{}.kube.gen.yml", svc);
     let tpth = Path::new(".").join(tfile.clone());
-    if let Err(e) = helm::template(&mf, Some(tpth)).await {
+    if let Err(e) = helm::template(&mf, None, Some(tpth)).await {
         // Errors here are obscure, and should not happen, but pass them up anyway
         webhooks::apply_event(UpgradeState::Failed, &ui, &region, &conf).await;
         s.update_generate_false("ResolveFailure", e.description().to_string())
@@ -412,7 +412,7 @@ pub async fn restart(mf: &Manifest, wait: bool) -> Result<()> {
         trigger_rollout_restart(r).await?; // fire-and-forget for subresources
     }
     let main = Restartable {
-        name: mf.name.clone(),
+        name: mf.canonical_name().to_string(),
         namespace: mf.namespace.clone(),
         workload: mf.workload.clone(),
     };
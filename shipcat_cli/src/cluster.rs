@@ -96,7 +96,7 @@ async fn check_summary(svc: String, skipped: &[String], conf: &Config, reg: &Reg
     mf.uid = Some("FAKE-GUID".to_string());
 
     info!("verifying template for {}", mf.name);
-    let tpl = helm::template(&mf, None).await?;
+    let tpl = helm::template(&mf, None, None).await?;
     helm::template_check(&mf, reg, skipped, &tpl)?;
     Ok(mf.name)
 }
@@ -162,18 +162,8 @@ async fn crd_reconcile(
     // but when doing the actual upgrade we need a config + region with secrets.
     assert!(config_sec.has_secrets());
     assert!(!config_base.has_secrets());
-    let region_sec = config_sec
-        .get_regions()
-        .iter()
-        .find(|r| r.name == region)
-        .unwrap()
-        .clone();
-    let region_base = config_base
-        .get_regions()
-        .iter()
-        .find(|r| r.name == region)
-        .unwrap()
-        .clone();
+    let region_sec = config_sec.region(region)?.clone();
+    let region_base = config_base.region(region)?.clone();
 
     webhooks::reconcile_event(UpgradeState::Pending, &region_sec).await;
     // Always reconcile the CRDs (definitions themselves) first
@@ -1,7 +1,8 @@
 use super::{Config, Manifest, Region, Result};
-use crate::{git, helm, kubectl};
+use crate::{git, helm, kubeapi::ShipKube, kubectl};
 use regex::Regex;
-use shipcat_definitions::ShipcatManifest;
+use serde_json::Value;
+use shipcat_definitions::{canonical, ShipcatManifest};
 use std::process::Command;
 
 
@@ -69,6 +70,40 @@ pub async fn values_vs_region(
     shell_diff(&before_values, &after_values, &before_region, &after_region)
 }
 
+/// Placeholder for secret values in values compared against a baseline
+const MASKED_SECRET: &str = "************";
+
+/// Mask the secret values in helm values
+///
+/// Secret env vars and secret files are replaced with a placeholder, so that rotated
+/// secrets, and real vs. mocked secrets, all render the same.
+pub fn mask_secrets(values: &mut serde_yaml::Value) {
+    for key in &["secrets", "secretFiles"] {
+        if let Some(serde_yaml::Value::Mapping(m)) = values.get_mut(*key) {
+            for (_, v) in m.iter_mut() {
+                *v = serde_yaml::Value::String(MASKED_SECRET.into());
+            }
+        }
+    }
+}
+
+fn masked_yaml(mut values: serde_yaml::Value) -> Result<String> {
+    mask_secrets(&mut values);
+    Ok(canonical::to_yaml(&values)?)
+}
+
+/// Compare helm values against a committed baseline file
+///
+/// Secrets are masked on both sides, and secrets inlined elsewhere (e.g. in configs) are obfuscated.
+/// Prints a unified diff and returns whether the two are equal.
+pub fn values_vs_file(mf: &Manifest, baseline: &Path) -> Result<bool> {
+    let committed = fs::read_to_string(baseline)?;
+    let before = masked_yaml(serde_yaml::from_str(&committed)?)?;
+    let after = masked_yaml(serde_yaml::to_value(mf.redacted_clone())?)?;
+    let after = obfuscate_secrets(after, mf.get_secrets());
+    shell_diff(&before, &after, "baseline", "rendered")
+}
+
 /// Fast local git compare of shipcat template
 ///
 /// Because this uses the template in master against local state,
@@ -79,7 +114,7 @@ pub async fn template_vs_git(svc: &str, conf: &Config, region: &Region) -> Resul
         .await?
         .stub(region)
         .await?;
-    let _after = helm::template(&mf_after, Some(afterpth.clone())).await?;
+    let _after = helm::template(&mf_after, None, Some(afterpth.clone())).await?;
 
     // move git to get before state:
     let merge_base = git::merge_base()?;
@@ -96,7 +131,7 @@ pub async fn template_vs_git(svc: &str, conf: &Config, region: &Region) -> Resul
         .await?
         .stub(region)
         .await?;
-    let _before = helm::template(&mf_before, Some(beforepth.clone())).await?;
+    let _before = helm::template(&mf_before, None, Some(beforepth.clone())).await?;
 
     // move git back
     if needs_stash {
@@ -152,7 +187,7 @@ pub async fn template_vs_kubectl(mf: &Manifest) -> Result<Option<String>> {
     let tfile = format!("{}.shipcat.tpl.gen.yml", mf.name);
     let pth = Path::new(".").join(tfile);
 
-    let _tpl = helm::template(&mf, Some(pth.clone())).await?;
+    let _tpl = helm::template(&mf, None, Some(pth.clone())).await?;
 
     let (out, err, success) = kubectl::diff(pth.clone(), &mf.namespace).await?;
     // cleanup:
@@ -167,6 +202,77 @@ pub async fn template_vs_kubectl(mf: &Manifest) -> Result<Option<String>> {
     }
 }
 
+/// JSON Patch of the shipcatmanifest in kubernetes against local state
+///
+/// Prints an RFC 6902 patch consumable by `kubectl patch shipcatmanifest --type=json`.
+/// Returns whether the two are equal.
+pub async fn values_patch(svc: &str, conf: &Config, region: &Region) -> Result<bool> {
+    let mut mf = shipcat_filebacked::load_manifest(svc, conf, region).await?;
+    let crd = ShipKube::new(&mf).await?.get().await?;
+    // versions are set at deploy time, so keep whatever is running
+    mf.version = mf.version.or_else(|| crd.spec.version.clone());
+    mf.uid = crd.spec.uid.clone();
+    let ops = manifest_patch(&crd.spec, &mf)?;
+    println!("{}", serde_json::to_string_pretty(&ops)?);
+    Ok(ops.is_empty())
+}
+
+/// A single RFC 6902 JSON Patch operation
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+/// JSON Patch for the spec of a shipcatmanifest going from one manifest to another
+///
+/// Secret values are never included; secrets only show up as the names in `env.secrets`
+/// that the chart references from the service's `Secret`.
+pub fn manifest_patch(before: &Manifest, after: &Manifest) -> Result<Vec<PatchOp>> {
+    let mut before = serde_json::to_value(before)?;
+    let mut after = serde_json::to_value(after)?;
+    for v in &mut [&mut before, &mut after] {
+        if let Value::Object(o) = v {
+            o.remove("secrets");
+        }
+    }
+    let mut ops = vec![];
+    json_patch("/spec", &before, &after, &mut ops);
+    Ok(ops)
+}
+
+/// Compute the JSON Patch operations turning `before` into `after` at `path`
+///
+/// Objects are compared key by key, but arrays are replaced whole,
+/// because index paths into arrays are fragile when elements are inserted.
+pub fn json_patch(path: &str, before: &Value, after: &Value, ops: &mut Vec<PatchOp>) {
+    match (before, after) {
+        (Value::Object(b), Value::Object(a)) => {
+            // keys are escaped as JSON pointer tokens (RFC 6901)
+            let child = |k: &str| format!("{}/{}", path, k.replace('~', "~0").replace('/', "~1"));
+            for (k, bv) in b {
+                match a.get(k) {
+                    Some(av) => json_patch(&child(k), bv, av, ops),
+                    None => ops.push(PatchOp::Remove { path: child(k) }),
+                }
+            }
+            for (k, av) in a.iter().filter(|(k, _)| !b.contains_key(*k)) {
+                ops.push(PatchOp::Add {
+                    path: child(k),
+                    value: av.clone(),
+                });
+            }
+        }
+        (b, a) if b != a => ops.push(PatchOp::Replace {
+            path: path.to_string(),
+            value: a.clone(),
+        }),
+        _ => {}
+    }
+}
+
 // Compare using diff(1)
 // difference libraries all seemed to be lacking somewhat
 fn shell_diff(before: &str, after: &str, before_name: &str, after_name: &str) -> Result<bool> {
@@ -266,7 +372,63 @@ pub fn obfuscate_secrets(input: String, secrets: Vec<String>) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{infer_version_change, is_version_only, minify};
+    use super::{infer_version_change, is_version_only, json_patch, mask_secrets, minify, PatchOp};
+    use serde_json::json;
+
+    #[test]
+    fn mask_secrets_test() {
+        let mut values: serde_yaml::Value =
+            serde_yaml::from_str("name: x\nsecrets:\n  DB_PASS: hunter22\nsecretFiles:\n  cert: YWJjZA==\n")
+                .unwrap();
+        mask_secrets(&mut values);
+        let rotated: serde_yaml::Value = serde_yaml::from_str(
+            "name: x\nsecrets:\n  DB_PASS: '************'\nsecretFiles:\n  cert: '************'\n",
+        )
+        .unwrap();
+        assert_eq!(values, rotated);
+    }
+
+    #[test]
+    fn json_patch_test() {
+        let before = json!({
+            "version": "1.0.0",
+            "replicaCount": 2,
+            "env": { "plain": { "A": "1", "B": "2" } },
+            "labels": { "app/name": "x" },
+            "ports": [80],
+        });
+        let after = json!({
+            "version": "1.1.0",
+            "replicaCount": 2,
+            "env": { "plain": { "A": "1", "C": "3" } },
+            "labels": { "app/name": "x" },
+            "ports": [80, 443],
+        });
+        let mut ops = vec![];
+        json_patch("/spec", &before, &after, &mut ops);
+        assert_eq!(
+            ops,
+            vec![
+                PatchOp::Remove {
+                    path: "/spec/env/plain/B".into()
+                },
+                PatchOp::Add {
+                    path: "/spec/env/plain/C".into(),
+                    value: json!("3")
+                },
+                PatchOp::Replace {
+                    path: "/spec/ports".into(),
+                    value: json!([80, 443])
+                },
+                PatchOp::Replace {
+                    path: "/spec/version".into(),
+                    value: json!("1.1.0")
+                },
+            ]
+        );
+        let encoded = serde_json::to_string(&ops[0]).unwrap();
+        assert_eq!(encoded, r#"{"op":"remove","path":"/spec/env/plain/B"}"#);
+    }
 
     #[test]
     fn version_change_test() {
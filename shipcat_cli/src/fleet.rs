@@ -0,0 +1,230 @@
+use super::{Config, Manifest, Region, Result};
+use crate::top::{csv_row, Millicores, OutputFormat};
+use futures::stream::{self, StreamExt};
+use shipcat_definitions::structs::{Metadata, Resources};
+use std::collections::BTreeMap;
+
+use size_format::{PointSeparated, SizeFormatter, SizeFormatterBinary};
+
+/// Ownership and sizing of a single service in a region
+#[derive(Serialize, Clone, Debug)]
+pub struct ServiceSummary {
+    pub name: String,
+    /// Primary owning team
+    pub team: String,
+    /// All owning teams, including the primary team
+    pub teams: Vec<String>,
+    /// Cost center the service is billed to
+    pub cost_center: Option<String>,
+    /// Expected monthly spend
+    pub monthly_budget: Option<u32>,
+    /// Regions the service is deployed to
+    pub regions: Vec<String>,
+    /// Replicas of the main workload (minimum when autoscaling)
+    pub replicas: u32,
+    /// Requests of a single replica in cores and Bytes
+    pub requests: Resources<f64>,
+    /// Requests across all replicas, workers and sidecars in cores and Bytes
+    pub total_requests: Resources<f64>,
+}
+
+/// Requests summed up across a team's services
+#[derive(Serialize, Clone, Debug)]
+pub struct TeamSummary {
+    pub team: String,
+    pub services: usize,
+    /// Requests in cores and Bytes
+    pub total_requests: Resources<f64>,
+    /// Sum of the monthly budgets of the services that have one
+    pub monthly_budget: u32,
+}
+
+fn no_resources() -> Resources<f64> {
+    Resources {
+        cpu: 0.0,
+        memory: 0.0,
+    }
+}
+
+fn owning_teams(md: &Metadata) -> Vec<String> {
+    md.owning_teams().into_iter().map(String::from).collect()
+}
+
+fn summarize(mf: &Manifest) -> Result<ServiceSummary> {
+    let (team, teams) = match &mf.metadata {
+        Some(md) => (md.team.clone(), owning_teams(md)),
+        None => ("".into(), vec![]),
+    };
+    let replicas = mf
+        .autoScaling
+        .as_ref()
+        .map(|hpa| hpa.minReplicas)
+        .or(mf.replicaCount)
+        .unwrap_or(0);
+    let (requests, total_requests) = if let Some(res) = &mf.resources {
+        let total = mf.compute_resource_totals()?.base.requests;
+        (res.normalised()?.requests, total)
+    } else {
+        // verify makes resources mandatory, but do not fail the whole report over it
+        warn!("{} has no resources - reporting no requests", mf.name);
+        (no_resources(), no_resources())
+    };
+    let md = mf.metadata.as_ref();
+    Ok(ServiceSummary {
+        name: mf.name.clone(),
+        team,
+        teams,
+        cost_center: md.and_then(|md| md.costCenter.clone()),
+        monthly_budget: md.and_then(|md| md.monthlyBudget),
+        regions: mf.regions.clone(),
+        replicas,
+        requests,
+        total_requests,
+    })
+}
+
+async fn load_summary(svc: String, conf: &Config, reg: &Region) -> Result<ServiceSummary> {
+    // secrets and templates are irrelevant here, so the base manifest suffices
+    let mf = shipcat_filebacked::load_manifest(&svc, conf, reg).await?;
+    summarize(&mf)
+}
+
+/// Ownership and resource inventory of every service in a region
+///
+/// Like `top`, this works out requests from the manifests on disk.
+/// Services external to kubernetes are left out. Results are sorted by name.
+pub async fn fleet_report(conf: &Config, reg: &Region) -> Result<Vec<ServiceSummary>> {
+    let available = shipcat_filebacked::available(conf, reg).await?;
+    let mut buffered = stream::iter(available.into_iter().filter(|mf| !mf.external))
+        .map(move |mf| load_summary(mf.base.name, conf, reg))
+        .buffer_unordered(100);
+    let mut summaries = vec![];
+    while let Some(r) = buffered.next().await {
+        summaries.push(r?);
+    }
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(summaries)
+}
+
+/// Total requests per primary owning team
+///
+/// Co-owned services are only counted for their primary team to avoid double counting.
+pub fn team_totals(summaries: &[ServiceSummary]) -> Vec<TeamSummary> {
+    let mut teams = BTreeMap::<&str, TeamSummary>::new();
+    for s in summaries {
+        let t = teams.entry(s.team.as_str()).or_insert_with(|| TeamSummary {
+            team: s.team.clone(),
+            services: 0,
+            total_requests: no_resources(),
+            monthly_budget: 0,
+        });
+        t.services += 1;
+        t.monthly_budget += s.monthly_budget.unwrap_or(0);
+        t.total_requests.cpu += s.total_requests.cpu;
+        t.total_requests.memory += s.total_requests.memory;
+    }
+    teams.into_iter().map(|(_, t)| t).collect()
+}
+
+fn fmt_cpu(cores: f64) -> String {
+    let millis = (1000.0 * cores) as u64;
+    format!(
+        "{:.0}",
+        SizeFormatter::<u64, Millicores, PointSeparated>::new(millis)
+    )
+}
+
+fn fmt_memory(bytes: f64) -> String {
+    format!("{:.0}", SizeFormatterBinary::new(bytes as u64))
+}
+
+/// Render the report as a human readable table followed by team totals
+pub fn render_table(summaries: &[ServiceSummary], teams: &[TeamSummary]) -> String {
+    let mut lines = vec![format!(
+        "{0:<40} {1:<25} {2:<20} {3:<8} {4:<8} {5:<8} {6:<8} {7:<8} {8}",
+        "SERVICE", "TEAM", "COSTCENTER", "REPLICAS", "CPU", "MEMORY", "TOTALCPU", "TOTALMEM", "REGIONS"
+    )];
+    for s in summaries {
+        lines.push(format!(
+            "{0:<40} {1:<25} {2:<20} {3:<8} {4:<8} {5:<8} {6:<8} {7:<8} {8}",
+            s.name,
+            s.teams.join(","),
+            s.cost_center.clone().unwrap_or_else(|| "-".into()),
+            s.replicas,
+            fmt_cpu(s.requests.cpu),
+            fmt_memory(s.requests.memory),
+            fmt_cpu(s.total_requests.cpu),
+            fmt_memory(s.total_requests.memory),
+            s.regions.join(",")
+        ));
+    }
+    lines.push("".into());
+    lines.push(format!(
+        "{0:<40} {1:<8} {2:<8} {3:<8} {4}",
+        "TEAM", "SERVICES", "CPU", "MEMORY", "BUDGET"
+    ));
+    for t in teams {
+        lines.push(format!(
+            "{0:<40} {1:<8} {2:<8} {3:<8} {4}",
+            t.team,
+            t.services,
+            fmt_cpu(t.total_requests.cpu),
+            fmt_memory(t.total_requests.memory),
+            t.monthly_budget
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Render the report as csv with raw numbers in milli-cores and Bytes
+///
+/// Multiple teams and regions are separated by spaces within their column.
+pub fn render_csv(summaries: &[ServiceSummary]) -> String {
+    let mut lines = vec![
+        "service,team,teams,cost_center,monthly_budget,regions,replicas,cpu,memory,total_cpu,total_memory"
+            .to_string(),
+    ];
+    for s in summaries {
+        lines.push(csv_row(&[
+            s.name.clone(),
+            s.team.clone(),
+            s.teams.join(" "),
+            s.cost_center.clone().unwrap_or_default(),
+            s.monthly_budget.map(|b| b.to_string()).unwrap_or_default(),
+            s.regions.join(" "),
+            s.replicas.to_string(),
+            ((1000.0 * s.requests.cpu) as u64).to_string(),
+            (s.requests.memory as u64).to_string(),
+            ((1000.0 * s.total_requests.cpu) as u64).to_string(),
+            (s.total_requests.memory as u64).to_string(),
+        ]));
+    }
+    lines.join("\n")
+}
+
+/// Print the fleet report for a region
+pub async fn print_fleet_report(
+    fmt: OutputFormat,
+    conf: &Config,
+    reg: &Region,
+) -> Result<Vec<ServiceSummary>> {
+    let summaries = fleet_report(conf, reg).await?;
+    let teams = team_totals(&summaries);
+    match fmt {
+        OutputFormat::Table => println!("{}", render_table(&summaries, &teams)),
+        OutputFormat::Csv => println!("{}", render_csv(&summaries)),
+        OutputFormat::Yaml => {
+            #[derive(Serialize)]
+            struct YamlOutput<'a> {
+                services: &'a [ServiceSummary],
+                teams: &'a [TeamSummary],
+            }
+            let output = YamlOutput {
+                services: &summaries,
+                teams: &teams,
+            };
+            println!("{}", serde_yaml::to_string(&output)?);
+        }
+    }
+    Ok(summaries)
+}
@@ -2,6 +2,7 @@ use super::{Config, Region};
 use std::collections::BTreeMap;
 
 use super::{structs::security::DataHandling, Result};
+use shipcat_definitions::canonical;
 
 /// GdprOutput across manifests
 #[derive(Serialize)]
@@ -22,7 +23,7 @@ pub async fn show(svc: Option<String>, conf: &Config, region: &Region) -> Result
         } else {
             DataHandling::default()
         };
-        serde_yaml::to_string(&data)?
+        canonical::to_yaml(&data)?
     } else {
         let mut mappings = BTreeMap::new();
         let mut services = vec![];
@@ -34,7 +35,7 @@ pub async fn show(svc: Option<String>, conf: &Config, region: &Region) -> Result
             services.push(s.base.name);
         }
         let data = GdprOutput { mappings, services };
-        serde_yaml::to_string(&data)?
+        canonical::to_yaml(&data)?
     };
     println!("{}", out);
     Ok(())
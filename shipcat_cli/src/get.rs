@@ -49,25 +49,27 @@ pub async fn codeowners(conf: &Config) -> Result<Vec<String>> {
         let md = mf.metadata;
         let mut ghids = vec![];
 
-        if let Some(s) = conf.owners.squads.get(&md.team) {
-            if let Some(gha) = &s.github.admins {
-                ghids.push(format!("@{}/{}", org.to_lowercase(), gha));
-            }
-            // Add all squad members. Helpful because github codeowners are bad for teams
-            // (Teams need to be added explicitly to the repo...)
-            // Can perhaps be removed in the future
-            for o in &s.members {
-                if let Some(p) = conf.owners.people.get(o) {
-                    if let Some(gh) = &p.github {
-                        ghids.push(format!("@{}", gh));
+        for team in md.owning_teams() {
+            if let Some(s) = conf.owners.squads.get(team) {
+                if let Some(gha) = &s.github.admins {
+                    ghids.push(format!("@{}/{}", org.to_lowercase(), gha));
+                }
+                // Add all squad members. Helpful because github codeowners are bad for teams
+                // (Teams need to be added explicitly to the repo...)
+                // Can perhaps be removed in the future
+                for o in &s.members {
+                    if let Some(p) = conf.owners.people.get(o) {
+                        if let Some(gh) = &p.github {
+                            let id = format!("@{}", gh);
+                            if !ghids.contains(&id) {
+                                ghids.push(id);
+                            }
+                        }
                     }
                 }
+            } else {
+                warn!("No squad found for {} in teams.yml - ignoring {}", team, mf.name);
             }
-        } else {
-            warn!(
-                "No squad found for {} in teams.yml - ignoring {}",
-                md.team, mf.name
-            );
         }
 
         if !ghids.is_empty() {
@@ -322,3 +324,31 @@ pub async fn kafkausers(conf: &Config, reg: &Region) -> Result<()> {
     println!("{}", serde_json::to_string_pretty(&output)?);
     Ok(())
 }
+
+/// Generate PrometheusRules for all services in a region from their alerts
+pub async fn prometheusrules(conf: &Config, reg: &Region) -> Result<()> {
+    for svc in shipcat_filebacked::available(conf, reg).await? {
+        let mf = shipcat_filebacked::load_manifest(&svc.base.name, &conf, &reg).await?;
+        if let Some(rule) = mf.prometheus_rule() {
+            println!("{}", serde_yaml::to_string(&rule)?);
+        }
+    }
+    Ok(())
+}
+
+/// Generate NetworkPolicies for all services in a region from their dependencies
+///
+/// Only available in regions with `networkPolicies` enabled.
+pub async fn networkpolicies(conf: &Config, reg: &Region) -> Result<()> {
+    if !reg.networkPolicies {
+        bail!("networkPolicies is not enabled for region {}", reg.name);
+    }
+    let mut manifests = vec![];
+    for svc in shipcat_filebacked::available(conf, reg).await? {
+        manifests.push(shipcat_filebacked::load_manifest(&svc.base.name, &conf, &reg).await?);
+    }
+    for np in shipcat_definitions::netpol::network_policies(&manifests) {
+        println!("{}", serde_yaml::to_string(&np)?);
+    }
+    Ok(())
+}
@@ -1,8 +1,14 @@
 use petgraph::{
-    dot,
-    graph::{DiGraph, NodeIndex},
+    algo,
+    graph::{DiGraph, EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug},
+    fs,
+    path::Path,
 };
-use std::fmt::{self, Debug};
 
 use super::{
     structs::{Dependency, DependencyProtocol},
@@ -13,12 +19,16 @@ use super::{
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ManifestNode {
     pub name: String,
+    /// Whether the service lives outside kubernetes
+    #[serde(default)]
+    pub external: bool,
     // pub image: String,
 }
 impl ManifestNode {
     fn new(mf: &Manifest) -> Self {
         ManifestNode {
             name: mf.name.clone(),
+            external: mf.external,
             /* image would be nice, but requires env override atm - should be global
              * image: format!("{}", mf.image.clone().unwrap()), */
         }
@@ -104,8 +114,89 @@ fn recurse_manifest(
     Ok(())
 }
 
+/// Edges that are part of a dependency cycle
+///
+/// Both ends of such an edge are in the same strongly connected component.
+fn cyclic_edges(graph: &CatGraph) -> HashSet<EdgeIndex> {
+    let mut component = HashMap::new();
+    for (i, scc) in algo::tarjan_scc(graph).into_iter().enumerate() {
+        for idx in scc {
+            component.insert(idx, i);
+        }
+    }
+    graph
+        .edge_references()
+        .filter(|e| component[&e.source()] == component[&e.target()])
+        .map(|e| e.id())
+        .collect()
+}
+
+/// Render the graph in graphviz dot format
+///
+/// Edges are labelled with the api version depended on.
+/// External services are filled grey, and dependency cycles are drawn in red.
+pub fn to_dot(graph: &CatGraph) -> String {
+    let cyclic = cyclic_edges(graph);
+    let mut cyclic_nodes = HashSet::new();
+    for e in graph.edge_references().filter(|e| cyclic.contains(&e.id())) {
+        cyclic_nodes.insert(e.source());
+        cyclic_nodes.insert(e.target());
+    }
+
+    let mut lines = vec!["digraph {".to_string()];
+    for idx in graph.node_indices() {
+        let node = &graph[idx];
+        let mut attrs = vec![format!("label = {:?}", node.name)];
+        if node.external {
+            attrs.push("style = filled".into());
+            attrs.push("fillcolor = lightgrey".into());
+        }
+        if cyclic_nodes.contains(&idx) {
+            attrs.push("color = red".into());
+        }
+        lines.push(format!("    {} [ {} ]", idx.index(), attrs.join(", ")));
+    }
+    for e in graph.edge_references() {
+        let mut attrs = vec![format!("label = {:?}", e.weight().api)];
+        if cyclic.contains(&e.id()) {
+            attrs.push("color = red".into());
+            attrs.push("fontcolor = red".into());
+        }
+        lines.push(format!(
+            "    {} -> {} [ {} ]",
+            e.source().index(),
+            e.target().index(),
+            attrs.join(", ")
+        ));
+    }
+    lines.push("}".into());
+    lines.join("\n")
+}
+
+/// Print the graph as dot or yaml, or write it to a file if given
+fn output(graph: &CatGraph, dot: bool, file: Option<&Path>) -> Result<()> {
+    let out = if dot {
+        to_dot(graph)
+    } else {
+        serde_yaml::to_string(graph)?
+    };
+    if let Some(pth) = file {
+        fs::write(pth, out)?;
+        info!("Wrote graph to {}", pth.display());
+    } else {
+        println!("{}", out);
+    }
+    Ok(())
+}
+
 /// Generate dependency graph from an entry point via recursion
-pub async fn generate(service: &str, conf: &Config, reg: &Region, dot: bool) -> Result<CatGraph> {
+pub async fn generate(
+    service: &str,
+    conf: &Config,
+    reg: &Region,
+    dot: bool,
+    file: Option<&Path>,
+) -> Result<CatGraph> {
     let base = shipcat_filebacked::load_manifest(service, conf, reg).await?;
 
     let mut graph: CatGraph = DiGraph::<_, _>::new();
@@ -114,12 +205,7 @@ pub async fn generate(service: &str, conf: &Config, reg: &Region, dot: bool) ->
 
     recurse_manifest(baseidx, &base, conf, reg, &mut graph)?;
 
-    let out = if dot {
-        format!("{:?}", dot::Dot::with_config(&graph, &[dot::Config::EdgeNoLabel]))
-    } else {
-        serde_yaml::to_string(&graph)?
-    };
-    println!("{}", out);
+    output(&graph, dot, file)?;
     Ok(graph)
 }
 
@@ -129,14 +215,18 @@ pub async fn generate(service: &str, conf: &Config, reg: &Region, dot: bool) ->
 /// one or more services as we could also show grahps reaching into the ecosystem.
 ///
 /// But it would require: TODO: optionally filter edges around node(s)
-pub async fn full(dot: bool, conf: &Config, reg: &Region) -> Result<CatGraph> {
+pub async fn full(dot: bool, conf: &Config, reg: &Region, file: Option<&Path>) -> Result<CatGraph> {
     let mut graph: CatGraph = DiGraph::<_, _>::new();
     for svc in shipcat_filebacked::available(conf, reg).await? {
         debug!("Scanning service {:?}", svc);
 
         let mf = shipcat_filebacked::load_manifest(&svc.base.name, conf, reg).await?;
-        let node = ManifestNode::new(&mf);
-        let idx = graph.add_node(node);
+        // may already have been added as a dependency of another service
+        let idx = if let Some(id) = nodeidx_from_name(&mf.name, &graph) {
+            id
+        } else {
+            graph.add_node(ManifestNode::new(&mf))
+        };
 
         for dep in &mf.dependencies {
             let subidx = if let Some(id) = nodeidx_from_name(&dep.name, &graph) {
@@ -152,12 +242,7 @@ pub async fn full(dot: bool, conf: &Config, reg: &Region) -> Result<CatGraph> {
         }
     }
 
-    let out = if dot {
-        format!("{:?}", dot::Dot::with_config(&graph, &[dot::Config::EdgeNoLabel]))
-    } else {
-        serde_yaml::to_string(&graph)?
-    };
-    println!("{}", out);
+    output(&graph, dot, file)?;
     Ok(graph)
 }
 
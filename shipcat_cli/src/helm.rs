@@ -9,7 +9,7 @@ use tokio::{
 };
 
 use super::Result;
-use shipcat_definitions::{Manifest, ReconciliationMode, Region};
+use shipcat_definitions::{canonical, Config, Manifest, ReconciliationMode, Region};
 
 pub fn hexists() -> Result<()> {
     if which::which("helm").is_err() {
@@ -36,12 +36,26 @@ pub async fn hout(args: Vec<String>) -> Result<(String, String, bool)> {
     Ok((out, err, s.status.success()))
 }
 
+/// Path of a generated file, under an output directory if one is given
+///
+/// Creates the output directory if it is missing. Defaults to the current directory.
+pub async fn output_path(output_dir: Option<&Path>, file: &Path) -> Result<PathBuf> {
+    match output_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir).await?;
+            Ok(dir.join(file))
+        }
+        None => Ok(Path::new(".").join(file)),
+    }
+}
+
 /// Create helm values file for a service
 ///
-/// Requires a completed manifest (with inlined configs)
-pub async fn values(mf: &Manifest, output: &str) -> Result<()> {
-    let encoded = serde_yaml::to_string(&mf)?;
-    let pth = Path::new(".").join(output);
+/// Requires a completed manifest (with inlined configs).
+/// Returns the path of the values file.
+pub async fn values(mf: &Manifest, output_dir: Option<&Path>, output: &str) -> Result<PathBuf> {
+    let encoded = canonical::to_yaml(&mf)?;
+    let pth = output_path(output_dir, Path::new(output)).await?;
     debug!("Writing helm values for {} to {}", mf.name, pth.display());
     let mut f = File::create(&pth).await?;
     f.write_all(&encoded.as_bytes()).await?;
@@ -50,25 +64,46 @@ pub async fn values(mf: &Manifest, output: &str) -> Result<()> {
         "Wrote helm values for {} to {}: \n{}",
         mf.name,
         pth.display(),
-        encoded
+        canonical::to_yaml(&mf.redacted_clone())?
     );
-    Ok(())
+    Ok(pth)
+}
+
+/// Helm values for a service pinned to a given version
+///
+/// Used to regenerate the values of a known good version when rolling back,
+/// without editing the manifests. Secrets are mocked unless `secrets` is set.
+pub async fn rollback_values(
+    svc: &str,
+    version: &str,
+    conf: &Config,
+    reg: &Region,
+    secrets: bool,
+) -> Result<Manifest> {
+    let mf = shipcat_filebacked::load_manifest(svc, conf, reg).await?;
+    mf.verify_rollback_version(version, reg)?;
+    let mf = if secrets {
+        mf.complete(reg).await?
+    } else {
+        mf.stub(reg).await?
+    };
+    Ok(mf.with_version(version.to_string()))
 }
 
 
 /// Analogue of helm template
 ///
-/// Generates helm values to disk, then passes it to helm template
-pub async fn template(mf: &Manifest, output: Option<PathBuf>) -> Result<String> {
-    let hfile = format!("{}.helm.gen.yml", mf.name);
-    values(&mf, &hfile).await?;
+/// Generates helm values to disk, then passes it to helm template.
+/// Generated files are written under `output_dir` when given.
+pub async fn template(mf: &Manifest, output_dir: Option<&Path>, output: Option<PathBuf>) -> Result<String> {
+    let hfile = values(&mf, output_dir, &format!("{}.helm.gen.yml", mf.name)).await?;
 
     // helm template with correct params
     let tplvec = vec![
         "template".into(),
         format!("charts/{}", mf.chart.clone().unwrap()),
         "-f".into(),
-        hfile.clone(),
+        hfile.display().to_string(),
     ];
     // NB: this call does NOT need --tiller-namespace (offline call)
     let (tpl, tplerr, success) = hout(tplvec.clone()).await?;
@@ -77,7 +112,7 @@ pub async fn template(mf: &Manifest, output: Option<PathBuf>) -> Result<String>
         bail!("helm template failed");
     }
     if let Some(o) = &output {
-        let pth = Path::new(".").join(o);
+        let pth = output_path(output_dir, o).await?;
         debug!("Writing helm template for {} to {}", mf.name, pth.display());
         let mut f = File::create(&pth).await?;
         f.write_all(&tpl.as_bytes()).await?;
@@ -89,12 +124,74 @@ pub async fn template(mf: &Manifest, output: Option<PathBuf>) -> Result<String>
             tpl
         );
         if let Err(e) = fs::remove_file(&hfile).await {
-            warn!("Failed to delete file: {} {}", hfile, e);
+            warn!("Failed to delete file: {} {}", hfile.display(), e);
         }
     }
     Ok(tpl)
 }
 
+/// Render every template in the chart of a service
+///
+/// Returns the rendered objects keyed by the template file they came from,
+/// e.g. `templates/deployment.yaml`. Disabled services render nothing.
+pub async fn render_all(mf: &Manifest) -> Result<BTreeMap<String, String>> {
+    if mf.disabled || mf.external {
+        debug!("Not rendering templates for {}", mf.name);
+        return Ok(BTreeMap::new());
+    }
+    let tpl = template(mf, None, None).await?;
+    Ok(split_sources(&tpl))
+}
+
+/// Render a single template file for a service
+///
+/// `.j2` files are rendered with the context of config files. Anything else is taken to be
+/// a template of the service's chart, e.g. `charts/base/templates/deployment.yaml`,
+/// and only its output is returned. Chart templates must exist in the chart to be rendered.
+pub async fn template_file(mf: &Manifest, reg: &Region, file: &Path) -> Result<String> {
+    if file.extension().map_or(false, |e| e == "j2") {
+        let data = fs::read_to_string(file).await?;
+        return Ok(mf.render_template(reg, data)?);
+    }
+    let rendered = render_all(mf).await?;
+    match rendered.iter().find(|(k, _)| file.ends_with(k.as_str())) {
+        Some((_, tpl)) => Ok(tpl.clone()),
+        None => bail!(
+            "{} did not render anything for {} - rendered templates are: {}",
+            file.display(),
+            mf.name,
+            rendered.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+/// Split `helm template` output into its templates using the `# Source:` comments
+///
+/// The chart name is stripped from the path. Templates that render several
+/// objects keep them together, and templates that render nothing are left out.
+fn split_sources(tpl: &str) -> BTreeMap<String, String> {
+    let mut res: BTreeMap<String, String> = BTreeMap::new();
+    for doc in tpl.split("\n---") {
+        let mut lines = doc.trim_start_matches("---").trim_start().lines();
+        let source = match lines.next() {
+            Some(l) if l.starts_with("# Source: ") => l["# Source: ".len()..].trim(),
+            _ => continue,
+        };
+        let file = source.splitn(2, '/').nth(1).unwrap_or(source).to_string();
+        let body = lines.collect::<Vec<_>>().join("\n");
+        if body.trim().is_empty() {
+            continue;
+        }
+        let entry = res.entry(file).or_default();
+        if !entry.is_empty() {
+            entry.push_str("---\n");
+        }
+        entry.push_str(body.trim_end());
+        entry.push('\n');
+    }
+    res
+}
+
 /// Helper to validate the assumption of the charts
 ///
 /// This is an addon to checks done through `kubeval`.
@@ -272,3 +369,26 @@ fn check_no_tiller_refs(kind: &str, obj: &KubeObject) -> Result<bool> {
     }
     Ok(success)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::split_sources;
+
+    #[test]
+    fn split_template_sources() {
+        let tpl = "---\n# Source: base/templates/service.yaml\napiVersion: v1\nkind: Service\n\
+                   ---\n# Source: base/templates/hpa.yaml\n\
+                   ---\n# Source: base/templates/deployment.yaml\nkind: Deployment\n\
+                   ---\n# Source: base/templates/service.yaml\nkind: Service\n";
+        let files = split_sources(tpl);
+        assert_eq!(files.keys().collect::<Vec<_>>(), vec![
+            "templates/deployment.yaml",
+            "templates/service.yaml"
+        ]);
+        assert_eq!(files["templates/deployment.yaml"], "kind: Deployment\n");
+        assert_eq!(
+            files["templates/service.yaml"],
+            "apiVersion: v1\nkind: Service\n---\nkind: Service\n"
+        );
+    }
+}
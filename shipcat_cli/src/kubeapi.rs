@@ -1,6 +1,6 @@
 use crate::{ErrorKind, Manifest, Result};
 use k8s_openapi::api::{
-    apps::v1::{Deployment, ReplicaSet, StatefulSet},
+    apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
     core::v1::Pod,
 };
 use kube::{
@@ -38,7 +38,10 @@ pub struct ShipKube {
     client: APIClient,
     pub(crate) applier: Applier,
     api: Api<ShipcatManifest>,
+    /// Name of the shipcatmanifest
     name: String,
+    /// Name of the workload and its pods, the canonical name of the service
+    workload: String,
     namespace: String,
 }
 
@@ -52,6 +55,7 @@ impl ShipKube {
 
         Ok(Self {
             name: svc.to_string(),
+            workload: svc.to_string(),
             namespace: ns.to_string(),
             applier: Applier::infer(),
             api,
@@ -61,7 +65,9 @@ impl ShipKube {
     }
 
     pub async fn new(mf: &Manifest) -> Result<Self> {
-        Self::new_within(&mf.name, &mf.namespace).await
+        let mut s = Self::new_within(&mf.name, &mf.namespace).await?;
+        s.workload = mf.canonical_name().to_string();
+        Ok(s)
     }
 
     /// Apply a Manifest (e.g. it's CRD wrapper)
@@ -129,7 +135,7 @@ impl ShipKube {
     pub async fn get_pods(&self) -> Result<ObjectList<Pod>> {
         let api: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = ListParams {
-            label_selector: Some(format!("app={}", self.name)),
+            label_selector: Some(format!("app={}", self.workload)),
             ..Default::default()
         };
         let pods = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -140,7 +146,7 @@ impl ShipKube {
     pub async fn get_pods_by_template_hash(&self, hash: &str) -> Result<ObjectList<Pod>> {
         let api: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = ListParams {
-            label_selector: Some(format!("app={},pod-template-hash={}", self.name, hash)),
+            label_selector: Some(format!("app={},pod-template-hash={}", self.workload, hash)),
             ..Default::default()
         };
         let pods = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -152,7 +158,7 @@ impl ShipKube {
         let api: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = LogParams {
             tail_lines: Some(30),
-            container: Some(self.name.to_string()),
+            container: Some(self.workload.to_string()),
             ..Default::default()
         };
         let logs = api.logs(podname, &lp).await.map_err(ErrorKind::KubeError)?;
@@ -163,7 +169,7 @@ impl ShipKube {
     pub async fn get_rs(&self) -> Result<ObjectList<ReplicaSet>> {
         let api: Api<ReplicaSet> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = ListParams {
-            label_selector: Some(format!("app={}", self.name)),
+            label_selector: Some(format!("app={}", self.workload)),
             ..Default::default()
         };
         let rs = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -174,7 +180,7 @@ impl ShipKube {
     pub async fn get_rs_by_template_hash(&self, hash: &str) -> Result<Option<ReplicaSet>> {
         let api: Api<ReplicaSet> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = ListParams {
-            label_selector: Some(format!("app={},pod-template-hash={}", self.name, hash)),
+            label_selector: Some(format!("app={},pod-template-hash={}", self.workload, hash)),
             ..Default::default()
         };
         let rs = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -187,13 +193,13 @@ impl ShipKube {
         let replicasets: Api<ReplicaSet> = Api::namespaced(self.client.clone(), &self.namespace);
 
         // Get owning deployment and its revision annotation
-        let dep = deps.get(&self.name).await.map_err(ErrorKind::KubeError)?;
+        let dep = deps.get(&self.workload).await.map_err(ErrorKind::KubeError)?;
         let mut rev = None;
         if let Some(meta) = dep.metadata {
             if let Some(annot) = meta.annotations {
                 if let Some(r) = annot.get("deployment.kubernetes.io/revision") {
                     rev = Some(r.clone());
-                    debug!("Desired deployment revision for {} is {}", self.name, r);
+                    debug!("Desired deployment revision for {} is {}", self.workload, r);
                 }
             }
         }
@@ -202,7 +208,7 @@ impl ShipKube {
         if let Some(desired) = rev {
             // Find all replicasets with our app label
             let lp = ListParams {
-                label_selector: Some(format!("app={}", self.name)),
+                label_selector: Some(format!("app={}", self.workload)),
                 ..Default::default()
             };
             let rs = replicasets.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -215,7 +221,7 @@ impl ShipKube {
                         if let Some(annot) = &meta.annotations {
                             if let Some(found) = annot.get("deployment.kubernetes.io/revision") {
                                 if found == &desired {
-                                    debug!("Tracking replicaset revision {} for {}", found, self.name);
+                                    debug!("Tracking replicaset revision {} for {}", found, self.workload);
                                     return true;
                                 }
                             }
@@ -237,14 +243,21 @@ impl ShipKube {
     // helper to get deployment data
     pub async fn get_deploy(&self) -> Result<Deployment> {
         let api: Api<Deployment> = Api::namespaced(self.client.clone(), &self.namespace);
-        let deps = api.get(&self.name).await.map_err(ErrorKind::KubeError)?;
+        let deps = api.get(&self.workload).await.map_err(ErrorKind::KubeError)?;
         Ok(deps)
     }
 
     // helper to get statefulset data
     pub async fn get_statefulset(&self) -> Result<StatefulSet> {
         let api: Api<StatefulSet> = Api::namespaced(self.client.clone(), &self.namespace);
-        let ssets = api.get(&self.name).await.map_err(ErrorKind::KubeError)?;
+        let ssets = api.get(&self.workload).await.map_err(ErrorKind::KubeError)?;
         Ok(ssets)
     }
+
+    // helper to get daemonset data
+    pub async fn get_daemonset(&self) -> Result<DaemonSet> {
+        let api: Api<DaemonSet> = Api::namespaced(self.client.clone(), &self.namespace);
+        let dsets = api.get(&self.workload).await.map_err(ErrorKind::KubeError)?;
+        Ok(dsets)
+    }
 }
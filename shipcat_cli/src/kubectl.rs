@@ -145,7 +145,7 @@ pub async fn use_context(context: &str) -> Result<String> {
 pub async fn shell(mf: &Manifest, cmd: Option<Vec<&str>>) -> Result<()> {
     // TODO: kubectl auth can-i create pods/exec
 
-    let target = format!("{}/{}", mf.workload.to_string(), mf.name);
+    let target = format!("{}/{}", mf.workload.to_string(), mf.canonical_name());
     debug!("Shelling into {}", target);
 
     // kubectl exec -it deployment/$pod sh
@@ -233,7 +233,7 @@ pub async fn port_forward(mf: &Manifest) -> Result<()> {
     let mut pfargs = vec![
         format!("-n={}", mf.namespace),
         "port-forward".into(),
-        format!("{}/{}", mf.workload.to_string(), mf.name),
+        format!("{}/{}", mf.workload.to_string(), mf.canonical_name()),
     ];
 
     for (port, localport) in ports {
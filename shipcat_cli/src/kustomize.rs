@@ -0,0 +1,234 @@
+use serde_yaml::{Mapping, Value};
+use std::{collections::BTreeMap, path::Path};
+use tokio::fs;
+
+use super::{helm, Config, ConfigState, Result};
+
+/// Kubernetes objects of a service in a region, keyed by kind and name
+type Objects = BTreeMap<(String, String), Value>;
+
+/// A `kustomization.yaml`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Kustomization {
+    api_version: String,
+    kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    resources: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    patches_strategic_merge: Vec<String>,
+}
+
+impl Kustomization {
+    fn new(namespace: Option<String>, resources: Vec<String>, patches: Vec<String>) -> Self {
+        Kustomization {
+            api_version: "kustomize.config.k8s.io/v1beta1".into(),
+            kind: "Kustomization".into(),
+            namespace,
+            resources,
+            patches_strategic_merge: patches,
+        }
+    }
+}
+
+/// Generate a Kustomize base and overlays for a service
+///
+/// The objects the chart renders in the first region become `base/`, and every region
+/// gets an overlay in `overlays/<region>/` that patches the base with its differences.
+/// Regions default to those of the service.
+///
+/// Secrets are left out, as their values would end up in the files.
+/// Ownership references to the `ShipcatManifest` are also dropped, as Kustomize manages the objects.
+pub async fn generate(svc: &str, regions: Vec<String>, output_dir: &Path) -> Result<()> {
+    let regions = if regions.is_empty() {
+        shipcat_filebacked::load_base(svc, &Config::read().await?).await?.regions
+    } else {
+        regions
+    };
+    let mut rendered = vec![];
+    for r in &regions {
+        let (conf, reg) = Config::new(ConfigState::Base, r).await?;
+        let mut mf = shipcat_filebacked::load_manifest(svc, &conf, &reg)
+            .await?
+            .stub(&reg)
+            .await?;
+        // ensure valid chart
+        mf.uid = Some("FAKE-GUID".to_string());
+        mf.version = mf.version.or_else(|| Some("latest".to_string()));
+        let tpl = helm::template(&mf, None, None).await?;
+        rendered.push((r.clone(), reg.namespace.clone(), parse_objects(&tpl)?));
+    }
+    let base = match rendered.first() {
+        Some((_, _, objects)) => objects.clone(),
+        None => bail!("{} has no regions to generate overlays for", svc),
+    };
+
+    let base_dir = output_dir.join("base");
+    fs::create_dir_all(&base_dir).await?;
+    let mut resources = vec![];
+    for (key, obj) in &base {
+        let file = file_name(key);
+        fs::write(base_dir.join(&file), serde_yaml::to_string(obj)?).await?;
+        resources.push(file);
+    }
+    let kustomization = Kustomization::new(None, resources, vec![]);
+    fs::write(base_dir.join("kustomization.yaml"), serde_yaml::to_string(&kustomization)?).await?;
+
+    for (region, namespace, objects) in rendered {
+        let dir = output_dir.join("overlays").join(&region);
+        fs::create_dir_all(&dir).await?;
+        let mut resources = vec!["../../base".to_string()];
+        let mut patches = vec![];
+        for (key, obj) in &objects {
+            let file = file_name(key);
+            match base.get(key) {
+                None => {
+                    fs::write(dir.join(&file), serde_yaml::to_string(obj)?).await?;
+                    resources.push(file);
+                }
+                Some(b) => {
+                    if let Some(patch) = merge_patch(b, obj) {
+                        let patch = identify(key, b, patch);
+                        fs::write(dir.join(&file), serde_yaml::to_string(&patch)?).await?;
+                        patches.push(file);
+                    }
+                }
+            }
+        }
+        for (key, b) in base.iter().filter(|(k, _)| !objects.contains_key(*k)) {
+            let file = file_name(key);
+            let mut delete = Mapping::new();
+            delete.insert("$patch".into(), "delete".into());
+            let patch = identify(key, b, Value::Mapping(delete));
+            fs::write(dir.join(&file), serde_yaml::to_string(&patch)?).await?;
+            patches.push(file);
+        }
+        debug!("Writing {} overlay with {} patches", region, patches.len());
+        let kustomization = Kustomization::new(Some(namespace), resources, patches);
+        fs::write(dir.join("kustomization.yaml"), serde_yaml::to_string(&kustomization)?).await?;
+    }
+    Ok(())
+}
+
+/// Parse the output of `helm template` into objects
+fn parse_objects(tpl: &str) -> Result<Objects> {
+    let mut objects = BTreeMap::new();
+    for doc in tpl.split("\n---") {
+        let mut obj: Value = serde_yaml::from_str(doc.trim_start_matches("---"))?;
+        let kind = match obj.get("kind").and_then(Value::as_str) {
+            Some(k) => k.to_string(),
+            None => continue, // empty templates and comments
+        };
+        if kind == "Secret" {
+            warn!("Leaving out Secret objects - these need to be created separately");
+            continue;
+        }
+        let name = match obj.get("metadata").and_then(|m| m.get("name")).and_then(Value::as_str) {
+            Some(n) => n.to_string(),
+            None => bail!("{} object without a metadata.name", kind),
+        };
+        if let Some(Value::Mapping(meta)) = obj.get_mut("metadata") {
+            meta.remove(&"ownerReferences".into());
+        }
+        objects.insert((kind, name), obj);
+    }
+    Ok(objects)
+}
+
+/// File name for an object, e.g. `deployment-myservice.yaml`
+fn file_name((kind, name): &(String, String)) -> String {
+    format!("{}-{}.yaml", kind.to_lowercase(), name)
+}
+
+/// Add the fields kustomize finds the patched object by to a patch
+fn identify((kind, name): &(String, String), base: &Value, patch: Value) -> Value {
+    let mut res = match patch {
+        Value::Mapping(m) => m,
+        _ => Mapping::new(),
+    };
+    if let Some(v) = base.get("apiVersion") {
+        res.insert("apiVersion".into(), v.clone());
+    }
+    res.insert("kind".into(), kind.as_str().into());
+    let mut meta = match res.remove(&"metadata".into()) {
+        Some(Value::Mapping(m)) => m,
+        _ => Mapping::new(),
+    };
+    meta.insert("name".into(), name.as_str().into());
+    res.insert("metadata".into(), Value::Mapping(meta));
+    Value::Mapping(res)
+}
+
+/// Merge patch that turns `base` into `target`, or nothing if they are the same
+///
+/// Keys missing from `target` are removed with a `null`, as in RFC 7386.
+/// Lists are patched as a whole.
+fn merge_patch(base: &Value, target: &Value) -> Option<Value> {
+    match (base, target) {
+        (Value::Mapping(b), Value::Mapping(t)) => {
+            let mut patch = Mapping::new();
+            for (k, bv) in b {
+                match t.get(k) {
+                    None => {
+                        patch.insert(k.clone(), Value::Null);
+                    }
+                    Some(tv) => {
+                        if let Some(p) = merge_patch(bv, tv) {
+                            patch.insert(k.clone(), p);
+                        }
+                    }
+                }
+            }
+            for (k, tv) in t.iter().filter(|(k, _)| !b.contains_key(k)) {
+                patch.insert(k.clone(), tv.clone());
+            }
+            if patch.is_empty() {
+                None
+            } else {
+                Some(Value::Mapping(patch))
+            }
+        }
+        _ if base == target => None,
+        _ => Some(target.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{identify, merge_patch, parse_objects};
+    use serde_yaml::Value;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn merge_patches() {
+        let base = yaml("spec:\n  replicas: 1\n  paused: true\n  template:\n    image: a:1\n");
+        let target = yaml("spec:\n  replicas: 3\n  template:\n    image: a:1\n");
+        assert_eq!(merge_patch(&base, &base), None);
+        assert_eq!(
+            merge_patch(&base, &target),
+            Some(yaml("spec:\n  replicas: 3\n  paused: ~\n"))
+        );
+    }
+
+    #[test]
+    fn parse_and_identify() {
+        let tpl = "---\n# Source: base/templates/secrets.yaml\napiVersion: v1\nkind: Secret\nmetadata:\n  name: a\n\
+                   ---\n# Source: base/templates/deployment.yaml\napiVersion: apps/v1\nkind: Deployment\n\
+                   metadata:\n  name: a\n  ownerReferences:\n  - kind: ShipcatManifest\n";
+        let objects = parse_objects(tpl).unwrap();
+        assert_eq!(objects.len(), 1);
+        let key = ("Deployment".to_string(), "a".to_string());
+        let deploy = &objects[&key];
+        assert_eq!(deploy, &yaml("apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: a\n"));
+
+        let patch = identify(&key, deploy, yaml("spec:\n  replicas: 3\n"));
+        assert_eq!(
+            patch,
+            yaml("spec:\n  replicas: 3\napiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: a\n")
+        );
+    }
+}
@@ -86,8 +86,8 @@ error_chain! {
 
 pub use shipcat_definitions::{
     config::{self, Config, ConfigFallback},
-    region::{AuditWebhook, KongConfig, Region, VersionScheme, Webhook},
-    structs, ConfigState, Manifest,
+    region::{AuditWebhook, KongConfig, Region, ValidationWebhook, VersionScheme, Webhook},
+    structs, ConfigState, Manifest, PrimaryWorkload,
 };
 // pub use shipcat_definitions::Product;
 
@@ -140,10 +140,16 @@ pub mod get;
 pub mod top;
 pub use top::{OutputFormat, ResourceOrder};
 
+/// Ownership and resource inventory
+pub mod fleet;
+
 
 /// Diffing module for values
 pub mod diff;
 
+/// Kustomize layout generator
+pub mod kustomize;
+
 /// Git stuff
 pub mod git;
 
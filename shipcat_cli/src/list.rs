@@ -17,12 +17,12 @@ pub fn locations(conf: &Config) -> Result<()> {
     Ok(())
 }
 
-/// Print supported services in a region
-/// TODO: this one needs to do the guess outside in main!
+/// Print the services that deploy to a region
+///
+/// Excludes services that are disabled in the region or kube-external.
 pub async fn services(conf: &Config, region: &Region) -> Result<()> {
-    let services = shipcat_filebacked::available(conf, region).await?;
-    for svc in services {
-        println!("{}", &svc.base.name);
+    for svc in shipcat_filebacked::deployable(conf, region).await? {
+        println!("{}", svc);
     }
     Ok(())
 }
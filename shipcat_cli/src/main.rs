@@ -3,7 +3,11 @@
 
 use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
 use shipcat::{kubeapi::ShipKube, *};
-use std::{process, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
 
 fn print_error_debug(e: &Error) {
     use std::env;
@@ -103,7 +107,26 @@ fn build_cli() -> App<'static, 'static> {
                 .short("s")
                 .long("secrets")
                 .help("Verifies secrets exist everywhere"))
-              .about("Validate the shipcat manifest"))
+              .arg(Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .default_value("8")
+                .help("Number of services to validate concurrently"))
+              .arg(Arg::with_name("cluster")
+                .long("cluster")
+                .help("Also compare against the live Deployments in the cluster"))
+              .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .default_value("log")
+                .possible_values(&["log", "table"])
+                .help("Also summarise the result of every service in a table"))
+              .arg(Arg::with_name("print")
+                .long("print")
+                .help("Print the validated manifests, with secrets redacted"))
+              .about("Validate the shipcat manifest")
+              .after_help("Use `-r all` to validate in every region the services declare"))
 
         .subcommand(SubCommand::with_name("verify")
             .about("Verify all manifests of a region"))
@@ -148,6 +171,19 @@ fn build_cli() -> App<'static, 'static> {
                 .help("Reduce eventstreams info"))
               .subcommand(SubCommand::with_name("kafkausers")
                 .help("Reduce kafkauser info"))
+              .subcommand(SubCommand::with_name("networkpolicies")
+                .help("Generate NetworkPolicies from service dependencies"))
+              .subcommand(SubCommand::with_name("prometheusrules")
+                .help("Generate PrometheusRules from service alerts"))
+              .subcommand(SubCommand::with_name("fleet")
+                .arg(Arg::with_name("output")
+                  .takes_value(true)
+                  .default_value("table")
+                  .possible_values(&["table", "yaml", "csv"])
+                  .long("output")
+                  .short("o")
+                  .help("Output format to print. Yaml and csv contain machine parseable numbers."))
+                .help("Report ownership, replicas and resource requests of every service"))
               .subcommand(SubCommand::with_name("codeowners")
                 .help("Generate CODEOWNERS syntax for manifests based on team ownership"))
               .subcommand(SubCommand::with_name("vault-policy")
@@ -182,6 +218,11 @@ fn build_cli() -> App<'static, 'static> {
               .arg(Arg::with_name("reverse")
                 .long("reverse")
                 .help("Generate reverse dependencies for a service"))
+              .arg(Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .help("Write the graph to a file instead of stdout"))
               .about("Graph the dependencies of a service"))
         // cluster admin operations
         .subcommand(SubCommand::with_name("cluster")
@@ -223,6 +264,9 @@ fn build_cli() -> App<'static, 'static> {
         .subcommand(SubCommand::with_name("list-services")
             .setting(AppSettings::Hidden)
             .about("list supported services for a specified"))
+        .subcommand(SubCommand::with_name("list")
+            .about("List the services that deploy to a region")
+            .after_help("Services disabled in the region or kube-external are left out."))
 
         // new service subcommands (absorbing some service manifest responsibility from helm/validate cmds)
         .subcommand(SubCommand::with_name("status")
@@ -243,11 +287,67 @@ fn build_cli() -> App<'static, 'static> {
                 .help("Service to generate crd for"))
               .about("Generate the kube equivalent ShipcatManifest CRD"))
 
+        .subcommand(SubCommand::with_name("explain")
+              .arg(Arg::with_name("service")
+                .required(true)
+                .help("Service to explain the overrides of"))
+              .about("Show what each override file of a service changes in the region"))
+
+        .subcommand(SubCommand::with_name("schema")
+              .about("Generate a JSON Schema for shipcat.yml"))
+
+        .subcommand(SubCommand::with_name("kustomize")
+              .arg(Arg::with_name("service")
+                .required(true)
+                .help("Service to generate a kustomize layout for"))
+              .arg(Arg::with_name("output-dir")
+                .long("output-dir")
+                .short("o")
+                .takes_value(true)
+                .required(true)
+                .help("Directory to write base/ and overlays/ to"))
+              .arg(Arg::with_name("regions")
+                .long("regions")
+                .takes_value(true)
+                .multiple(true)
+                .help("Regions to generate overlays for (defaults to the regions of the service)"))
+              .about("Generate a Kustomize base and per region overlays from the chart"))
+
+        .subcommand(SubCommand::with_name("lock")
+              .arg(Arg::with_name("service")
+                .required(true)
+                .help("Service to pin the version of"))
+              .arg(Arg::with_name("version")
+                .required(true)
+                .help("Version to pin the service to in the region"))
+              .about("Pin the version of a service in a region with a lockfile"))
+
         .subcommand(SubCommand::with_name("values")
               .arg(Arg::with_name("secrets")
                 .short("s")
                 .long("secrets")
                 .help("Use actual secrets from vault"))
+              .arg(Arg::with_name("unredacted")
+                .long("unredacted")
+                .requires("secrets")
+                .help("Print the secrets from vault rather than redacting them"))
+              .arg(Arg::with_name("tag")
+                .long("tag")
+                .short("t")
+                .takes_value(true)
+                .help("Version to pin the values to (e.g. to roll back to a known good version)"))
+              .arg(Arg::with_name("set")
+                .long("set")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with("tag")
+                .help("Override a field, e.g. replicaCount=1 or env.LOG_LEVEL=debug"))
+              .arg(Arg::with_name("diff")
+                .long("diff")
+                .takes_value(true)
+                .conflicts_with("tag")
+                .help("Diff against committed values in this file instead of printing, with secrets masked"))
               .arg(Arg::with_name("service")
                 .required(true)
                 .help("Service to generate values for"))
@@ -265,6 +365,9 @@ fn build_cli() -> App<'static, 'static> {
                 .short("c")
                 .long("check")
                 .help("Check the validity of the template"))
+              .arg(Arg::with_name("debug")
+                .long("debug")
+                .help("Render the debugContainer of the service (never use this to deploy)"))
                .arg(Arg::with_name("skip-kinds")
                 .long("skip-kinds")
                 .takes_value(true)
@@ -275,6 +378,23 @@ fn build_cli() -> App<'static, 'static> {
                 .short("t")
                 .takes_value(true)
                 .help("Image version to override (useful when validating)"))
+              .arg(Arg::with_name("output-dir")
+                .long("output-dir")
+                .takes_value(true)
+                .conflicts_with("check")
+                .help("Directory to write the kube yaml to instead of printing it"))
+              .arg(Arg::with_name("file")
+                .long("file")
+                .short("f")
+                .takes_value(true)
+                .conflicts_with_all(&["check", "output-dir"])
+                .help("Only render this chart template, or a .j2 file with the context of config files"))
+              .arg(Arg::with_name("set")
+                .long("set")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Override a field, e.g. replicaCount=1 or env.LOG_LEVEL=debug"))
               .arg(Arg::with_name("service")
                 .required(true)
                 .help("Service to generate kube yaml for"))
@@ -344,6 +464,11 @@ fn build_cli() -> App<'static, 'static> {
               .arg(Arg::with_name("crd")
                 .long("crd")
                 .help("Compare the shipcatmanifest crd output instead of the full kube yaml"))
+              .arg(Arg::with_name("patch")
+                .long("patch")
+                .conflicts_with("git")
+                .conflicts_with("with-region")
+                .help("Output a JSON Patch for the shipcatmanifest crd in kubernetes"))
               .arg(Arg::with_name("mock")
                 .long("mock")
                 .help("Mock uids and versions rather than fetching from the kubernetes shipcatmanifest"))
@@ -390,10 +515,10 @@ fn build_cli() -> App<'static, 'static> {
             .arg(Arg::with_name("output")
                 .takes_value(true)
                 .default_value("table")
-                .possible_values(&["table", "yaml"])
+                .possible_values(&["table", "yaml", "csv"])
                 .long("output")
                 .short("o")
-                .help("Output format to print. Yaml contains machine parseable numbers."))
+                .help("Output format to print. Yaml and csv contain machine parseable numbers."))
             .arg(Arg::with_name("world")
                 .long("world")
                 .help("Show resource requests across all regions"))
@@ -405,6 +530,10 @@ fn build_cli() -> App<'static, 'static> {
                 .long("tribes")
                 .conflicts_with("squads")
                 .help("Aggregate services by tribe ownership"))
+            .arg(Arg::with_name("capacity")
+                .long("capacity")
+                .conflicts_with_all(&["world", "squads", "tribes"])
+                .help("Compare requests at full scale against the cluster capacity"))
             .arg(Arg::with_name("sort")
                 .takes_value(true)
                 .possible_values(&["cpu", "memory"])
@@ -531,6 +660,18 @@ async fn resolve_config(args: &ArgMatches<'_>, ct: ConfigState) -> Result<(Confi
     Ok((cfg, reg))
 }
 
+/// Apply `--set path=value` overrides to a completed manifest
+fn apply_sets(mf: &mut Manifest, args: &ArgMatches<'_>) -> Result<()> {
+    for set in args.values_of("set").into_iter().flatten() {
+        let mut kv = set.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some(path), Some(value)) => mf.set_override(path, value)?,
+            _ => return Err(format!("--set {} must be of the form path=value", set).into()),
+        }
+    }
+    Ok(())
+}
+
 fn void<T>(_x: T) {} // helper so that dispatch_commands can return Result<()>
 
 /// Dispatch clap arguments to shipcat handlers
@@ -539,14 +680,27 @@ fn void<T>(_x: T) {} // helper so that dispatch_commands can return Result<()>
 /// in the dispatched functions to catch the majority of errors herein.
 #[allow(clippy::cognitive_complexity)] // clap 3 will have typed subcmds..
 async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
-    // listers first
+    // standalone generators first
+    if args.subcommand_matches("schema").is_some() {
+        return shipcat::show::manifest_schema();
+    }
+    if let Some(a) = args.subcommand_matches("kustomize") {
+        let svc = a.value_of("service").unwrap();
+        let regions = a.values_of("regions").map(|v| v.map(String::from).collect()).unwrap_or_default();
+        let dir = Path::new(a.value_of("output-dir").unwrap());
+        return shipcat::kustomize::generate(svc, regions, dir).await;
+    }
+    // listers
     if let Some(_a) = args.subcommand_matches("list-regions") {
         let rawconf = Config::read().await?;
         return shipcat::list::regions(&rawconf);
     } else if args.subcommand_matches("list-locations").is_some() {
         let rawconf = Config::read().await?;
         return shipcat::list::locations(&rawconf);
-    } else if let Some(a) = args.subcommand_matches("list-services") {
+    } else if let Some(a) = args
+        .subcommand_matches("list-services")
+        .or_else(|| args.subcommand_matches("list"))
+    {
         let (conf, region) = resolve_config(a, ConfigState::Base).await?;
         return shipcat::list::services(&conf, &region).await;
     } else if let Some(a) = args.subcommand_matches("login") {
@@ -580,6 +734,10 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         if let Some(_) = a.subcommand_matches("images") {
             return shipcat::get::images(&conf, &region).await.map(void);
         }
+        if let Some(b) = a.subcommand_matches("fleet") {
+            let fmt = top::OutputFormat::from_str(b.value_of("output").unwrap())?;
+            return shipcat::fleet::print_fleet_report(fmt, &conf, &region).await.map(void);
+        }
         if let Some(_) = a.subcommand_matches("codeowners") {
             return shipcat::get::codeowners(&conf).await.map(void);
         }
@@ -596,6 +754,12 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         if let Some(_) = a.subcommand_matches("kafkausers") {
             return shipcat::get::kafkausers(&conf, &region).await;
         }
+        if let Some(_) = a.subcommand_matches("networkpolicies") {
+            return shipcat::get::networkpolicies(&conf, &region).await;
+        }
+        if let Some(_) = a.subcommand_matches("prometheusrules") {
+            return shipcat::get::prometheusrules(&conf, &region).await;
+        }
     } else if let Some(a) = args.subcommand_matches("top") {
         let sort = top::ResourceOrder::from_str(a.value_of("sort").unwrap())?;
         let fmt = top::OutputFormat::from_str(a.value_of("output").unwrap())?;
@@ -617,7 +781,11 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
             }
         } else {
             let (conf, region) = resolve_config(a, ConfigState::Base).await?;
-            if a.is_present("squads") {
+            if a.is_present("capacity") {
+                shipcat::top::region_utilization(10, fmt, &conf, &region)
+                    .await
+                    .map(void)
+            } else if a.is_present("squads") {
                 shipcat::top::region_squad_requests(sort, ub, fmt, &conf, &region)
                     .await
                     .map(void)
@@ -681,15 +849,18 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         return shipcat::status::show(&svc, &conf, &region).await;
     } else if let Some(a) = args.subcommand_matches("graph") {
         let dot = a.is_present("dot");
+        let file = a.value_of("output").map(Path::new);
         let (conf, region) = resolve_config(a, ConfigState::Base).await?;
         return if let Some(svc) = a.value_of("service") {
             if a.is_present("reverse") {
                 shipcat::graph::reverse(svc, &conf, &region).await.map(void)
             } else {
-                shipcat::graph::generate(svc, &conf, &region, dot).await.map(void)
+                shipcat::graph::generate(svc, &conf, &region, dot, file)
+                    .await
+                    .map(void)
             }
         } else {
-            shipcat::graph::full(dot, &conf, &region).await.map(void)
+            shipcat::graph::full(dot, &conf, &region, file).await.map(void)
         };
     } else if let Some(a) = args.subcommand_matches("validate") {
         let services = a
@@ -697,14 +868,30 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
             .unwrap()
             .map(String::from)
             .collect::<Vec<_>>();
+        let opts = shipcat::validate::ValidateOpts {
+            secrets: a.is_present("secrets"),
+            jobs: a.value_of("jobs").unwrap().parse()?,
+            format: shipcat::validate::ValidateFormat::from_str(a.value_of("format").unwrap())?,
+            print: a.is_present("print"),
+        };
+        if a.value_of("region") == Some("all") {
+            if a.is_present("cluster") {
+                return Err("--cluster needs a single region".into());
+            }
+            return shipcat::validate::manifest_all_regions(services, opts).await;
+        }
         // this only needs a kube context if you don't specify it
-        let ss = if a.is_present("secrets") {
+        let ss = if opts.secrets {
             ConfigState::Filtered
         } else {
             ConfigState::Base
         };
         let (conf, region) = resolve_config(a, ss).await?;
-        return shipcat::validate::manifest(services, &conf, &region, a.is_present("secrets")).await;
+        shipcat::validate::manifest(services.clone(), &conf, &region, opts).await?;
+        if a.is_present("cluster") {
+            return shipcat::validate::cluster_drift(services, &conf, &region).await;
+        }
+        return Ok(());
     } else if let Some(a) = args.subcommand_matches("verify") {
         return if a.value_of("region").is_some() {
             let (conf, region) = resolve_config(a, ConfigState::Base).await?;
@@ -712,6 +899,12 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         } else {
             shipcat::validate::all_manifests().await
         };
+    } else if let Some(a) = args.subcommand_matches("lock") {
+        let svc = a.value_of("service").unwrap();
+        let ver = a.value_of("version").unwrap();
+        let (_conf, region) = resolve_config(a, ConfigState::Base).await?;
+        shipcat_filebacked::lock_version(svc, &region, ver).await?;
+        return Ok(());
     } else if let Some(a) = args.subcommand_matches("values") {
         let svc = a.value_of("service").map(String::from).unwrap();
 
@@ -722,7 +915,13 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         };
         let (conf, region) = resolve_config(a, ss).await?;
 
-        let mf = if a.is_present("secrets") {
+        if let Some(ver) = a.value_of("tag") {
+            let mf =
+                shipcat::helm::rollback_values(&svc, ver, &conf, &region, a.is_present("secrets")).await?;
+            mf.print(a.is_present("unredacted"))?;
+            return Ok(());
+        }
+        let mut mf = if a.is_present("secrets") {
             shipcat_filebacked::load_manifest(&svc, &conf, &region)
                 .await?
                 .complete(&region)
@@ -733,7 +932,12 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
                 .stub(&region)
                 .await?
         };
-        mf.print()?;
+        apply_sets(&mut mf, a)?;
+        if let Some(baseline) = a.value_of("diff") {
+            let same = shipcat::diff::values_vs_file(&mf, Path::new(baseline))?;
+            process::exit(if same { 0 } else { 1 });
+        }
+        mf.print(a.is_present("unredacted"))?;
         return Ok(());
     } else if let Some(a) = args.subcommand_matches("template") {
         let svc = a.value_of("service").map(String::from).unwrap();
@@ -768,7 +972,20 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
             mf.uid = Some("FAKE-GUID".to_string());
             mf.version = mf.version.or(Some("latest".to_string()));
         }
-        let tpl = shipcat::helm::template(&mf, None).await?;
+        if a.is_present("debug") {
+            mf.enable_debug(&region)?;
+        }
+        apply_sets(&mut mf, a)?;
+        if let Some(file) = a.value_of("file") {
+            print!("{}", shipcat::helm::template_file(&mf, &region, Path::new(file)).await?);
+            return Ok(());
+        }
+        if let Some(dir) = a.value_of("output-dir") {
+            let output = PathBuf::from(format!("{}.kube.gen.yml", mf.name));
+            shipcat::helm::template(&mf, Some(Path::new(dir)), Some(output)).await?;
+            return Ok(());
+        }
+        let tpl = shipcat::helm::template(&mf, None, None).await?;
         if a.is_present("check") {
             let skipped = a
                 .value_of("skip-kinds")
@@ -787,6 +1004,10 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
 
         let (conf, region) = resolve_config(a, ConfigState::Base).await?;
         return shipcat::show::manifest_crd(&svc, &conf, &region).await;
+    } else if let Some(a) = args.subcommand_matches("explain") {
+        let svc = a.value_of("service").unwrap();
+        let (conf, region) = resolve_config(a, ConfigState::Base).await?;
+        return shipcat::show::overrides(svc, &conf, &region).await;
     } else if let Some(a) = args.subcommand_matches("env") {
         let svc = a.value_of("service").map(String::from).unwrap();
         let (conf, region) = resolve_config(a, ConfigState::Filtered).await?;
@@ -794,7 +1015,11 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         return shipcat::env::print_bash(&svc, &conf, &region, mock).await;
     } else if let Some(a) = args.subcommand_matches("diff") {
         let svc = a.value_of("service").map(String::from).unwrap();
-        let diff_exit = if a.is_present("crd") {
+        let diff_exit = if a.is_present("patch") {
+            // NB: no secrets in CRD
+            let (conf, region) = resolve_config(a, ConfigState::Base).await?;
+            shipcat::diff::values_patch(&svc, &conf, &region).await?
+        } else if a.is_present("crd") {
             // NB: no secrets in CRD
             let (conf, region) = resolve_config(a, ConfigState::Base).await?;
             if a.is_present("git") {
@@ -1,5 +1,5 @@
 use super::{Config, Region, Result};
-use shipcat_definitions::{ShipcatConfig, ShipcatManifest};
+use shipcat_definitions::{schema, Manifest, ShipcatConfig, ShipcatManifest};
 
 /// Print the config
 ///
@@ -28,3 +28,41 @@ pub async fn manifest_crd(svc: &str, conf: &Config, reg: &Region) -> Result<()>
     println!("{}", serde_yaml::to_string(&crd)?);
     Ok(())
 }
+
+/// Print the fields each override file of a service changes in a region
+///
+/// Overrides that set a field to the value it already had are flagged, as they are likely stale.
+pub async fn overrides(svc: &str, conf: &Config, reg: &Region) -> Result<()> {
+    let applied = shipcat_filebacked::explain_overrides(svc, conf, reg).await?;
+    let mut file = None;
+    for a in &applied {
+        if file != Some(&a.file) {
+            println!("{}", a.file);
+            file = Some(&a.file);
+        }
+        let old = match &a.old {
+            Some(v) => serde_json::to_string(v)?,
+            None => "unset".into(),
+        };
+        let new = serde_json::to_string(&a.new)?;
+        if a.is_noop() {
+            println!("  {}: {} (unchanged)", a.path, new);
+        } else {
+            println!("  {}: {} -> {}", a.path, old, new);
+        }
+    }
+    let stale = applied.iter().filter(|a| a.is_noop()).count();
+    if stale > 0 {
+        warn!("{} overrides of {} in {} change nothing", stale, svc, reg.name);
+    }
+    Ok(())
+}
+
+/// Print a JSON Schema for the manifest
+///
+/// Usable for editor validation of `shipcat.yml` files.
+pub fn manifest_schema() -> Result<()> {
+    let schema = schema::schema_for::<Manifest>("shipcat.yml");
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
@@ -258,7 +258,8 @@ fn create_github_compare_url(md: &Metadata, vers: (&str, &str)) -> SlackTextCont
 fn contacts_to_text_content(contacts: &[Contact]) -> Vec<SlackTextContent> {
     contacts
         .iter()
-        .map(|cc| User(SlackUserLink::new(&cc.slack)))
+        .filter_map(|cc| cc.slack.as_ref())
+        .map(|slack| User(SlackUserLink::new(slack)))
         .collect()
 }
 
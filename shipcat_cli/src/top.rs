@@ -1,13 +1,16 @@
 use super::{Config, Error, Manifest, Region, Result};
 use futures::stream::{self, StreamExt};
-use shipcat_definitions::{math::ResourceTotals, BaseManifest};
+use shipcat_definitions::{
+    math::{ResourceTotals, Utilization},
+    BaseManifest,
+};
 use std::{collections::BTreeMap, str::FromStr};
 
 use generic_array::{typenum::U4, GenericArray};
 use size_format::{PointSeparated, PrefixType, SizeFormatter, SizeFormatterBinary};
 
 // Nice size formatting of millicores.
-struct Millicores;
+pub(crate) struct Millicores;
 
 // milli is default unit, then stop sub-dividing.
 impl PrefixType for Millicores {
@@ -41,8 +44,7 @@ impl FromStr for ResourceOrder {
 async fn load_mf_req(svc: String, conf: &Config, reg: &Region) -> Result<(Manifest, ResourceTotals)> {
     let mf = shipcat_filebacked::load_manifest(&svc, &conf, &reg)
         .await?
-        .stub(&reg)
-        .await?;
+        .lazy(&reg)?;
     let res = mf.compute_resource_totals()?;
     Ok((mf, res))
 }
@@ -68,9 +70,8 @@ async fn load_mf_req_world(base: BaseManifest, conf: &Config) -> Result<Option<(
             trace!("valid region: {}", reg.name);
             let mf = shipcat_filebacked::load_manifest(&base.name, &conf, &reg)
                 .await?
-                .stub(&reg)
-                .await?;
-            if !mf.disabled && !mf.external {
+                .lazy(&reg)?;
+            if mf.is_deployable_in(&reg.name) && !mf.external {
                 let ResourceTotals { base: rb, extra: se } = mf.compute_resource_totals()?;
                 debug!(
                     "{} in {}: adding reqs: {} {}",
@@ -145,6 +146,8 @@ pub enum OutputFormat {
     Table,
     /// Yaml output with raw numbers in milli-cores and Bytes
     Yaml,
+    /// Csv output with raw numbers in milli-cores and Bytes
+    Csv,
 }
 
 impl FromStr for OutputFormat {
@@ -154,7 +157,8 @@ impl FromStr for OutputFormat {
         match input {
             "table" => Ok(Self::Table),
             "yaml" => Ok(Self::Yaml),
-            _ => bail!("Resource type must be table or yaml"),
+            "csv" => Ok(Self::Csv),
+            _ => bail!("Output format must be table, yaml or csv"),
         }
     }
 }
@@ -164,6 +168,21 @@ impl Default for OutputFormat {
     }
 }
 
+/// Format a line of csv, quoting fields where necessary
+pub(crate) fn csv_row(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            if f.contains(&[',', '"', '\n'][..]) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 fn sort_and_print_resources(
     mut mfs: Vec<(Manifest, ResourceTotals)>,
     order: ResourceOrder,
@@ -254,6 +273,14 @@ fn sort_and_print_resources(
         OutputFormat::Yaml => {
             println!("{}", serde_yaml::to_string(&output)?);
         }
+        OutputFormat::Csv => {
+            println!("service,cpu,memory,squad,tribe");
+            for o in output {
+                let tribe = o.tribe.unwrap_or_default();
+                let row = [o.name, o.cpu.to_string(), o.memory.to_string(), o.squad, tribe];
+                println!("{}", csv_row(&row));
+            }
+        }
     }
     Ok(mfs)
 }
@@ -449,6 +476,113 @@ fn sort_and_print_team_resources(
         OutputFormat::Yaml => {
             println!("{}", serde_yaml::to_string(&output)?);
         }
+        OutputFormat::Csv => {
+            println!("{},cpu,memory", team_type);
+            for o in output {
+                println!("{}", csv_row(&[o.team, o.cpu.to_string(), o.memory.to_string()]));
+            }
+        }
     }
     Ok(reqs)
 }
+
+fn fmt_cpu(cores: f64) -> String {
+    let millis = (1000.0 * cores) as u64;
+    format!(
+        "{:.0}",
+        SizeFormatter::<u64, Millicores, PointSeparated>::new(millis)
+    )
+}
+
+fn fmt_memory(bytes: f64) -> String {
+    format!("{:.0}", SizeFormatterBinary::new(bytes as u64))
+}
+
+/// Resource utilization of a region against the capacity of its cluster
+///
+/// Sums requests of every enabled service at full scale, and compares them with
+/// the `capacity` of the owning cluster in shipcat.conf.
+/// Like the other top commands, it does NOT talk to kubernetes.
+pub async fn region_utilization(
+    top: usize,
+    fmt: OutputFormat,
+    conf: &Config,
+    reg: &Region,
+) -> Result<Utilization> {
+    let capacity = conf.region_capacity(reg)?;
+    if capacity.is_none() {
+        warn!("No capacity set for the cluster serving {}", reg.name);
+    }
+    let available = shipcat_filebacked::available(conf, reg).await?;
+    let mut buffered = stream::iter(available.into_iter().filter(|mf| mf.enabled && !mf.external))
+        .map(move |mf| async move { shipcat_filebacked::load_manifest(&mf.base.name, conf, reg).await })
+        .buffer_unordered(100);
+    let mut mfs = vec![];
+    while let Some(r) = buffered.next().await {
+        mfs.push(r?);
+    }
+    let util = Utilization::compute(&mfs, capacity, top);
+
+    let percent = |p: Option<f64>| p.map(|p| format!("{:.0}%", p)).unwrap_or_else(|| "-".into());
+    let (cap_cpu, cap_memory) = match &util.capacity {
+        Some(c) => (fmt_cpu(c.cpu), fmt_memory(c.memory)),
+        None => ("-".into(), "-".into()),
+    };
+    match fmt {
+        OutputFormat::Table => {
+            println!(
+                "{0:<10} {1:<10} {2:<10} {3:<10}",
+                "RESOURCE", "REQUESTED", "CAPACITY", "USED"
+            );
+            println!(
+                "{0:<10} {1:<10} {2:<10} {3:<10}",
+                "cpu",
+                fmt_cpu(util.requests.cpu),
+                cap_cpu,
+                percent(util.cpu_percent)
+            );
+            println!(
+                "{0:<10} {1:<10} {2:<10} {3:<10}",
+                "memory",
+                fmt_memory(util.requests.memory),
+                cap_memory,
+                percent(util.memory_percent)
+            );
+            println!();
+            println!("{0:<50} {1:<8} {2:<8}", "TOP CONSUMERS", "CPU", "MEMORY");
+            for (name, r) in &util.top_consumers {
+                println!(
+                    "{0:<50} {1:<8} {2:<8}",
+                    name,
+                    fmt_cpu(r.cpu),
+                    fmt_memory(r.memory)
+                );
+            }
+            if util.is_overcommitted() {
+                println!();
+                println!("WARNING: {} requests more than its cluster capacity", reg.name);
+            }
+        }
+        OutputFormat::Yaml => {
+            println!("{}", serde_yaml::to_string(&util)?);
+        }
+        OutputFormat::Csv => {
+            println!("service,cpu,memory");
+            for (name, r) in &util.top_consumers {
+                let row = [
+                    name.clone(),
+                    ((1000.0 * r.cpu) as u64).to_string(),
+                    (r.memory as u64).to_string(),
+                ];
+                println!("{}", csv_row(&row));
+            }
+        }
+    }
+    if !util.skipped.is_empty() {
+        warn!(
+            "Left out services without computable resources: {}",
+            util.skipped.join(", ")
+        );
+    }
+    Ok(util)
+}
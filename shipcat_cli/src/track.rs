@@ -2,7 +2,7 @@
 use crate::{kubeapi::ShipKube, slack::short_ver, Result};
 use chrono::{Duration, Utc};
 use k8s_openapi::api::{
-    apps::v1::{Deployment, ReplicaSet, StatefulSet},
+    apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
     core::v1::Pod,
 };
 use kube::api::{Meta, ObjectList};
@@ -166,6 +166,7 @@ pub async fn debug(mf: &Manifest, kube: &ShipKube) -> Result<()> {
     match mf.workload {
         PrimaryWorkload::Deployment => debug_deployment(kube).await,
         PrimaryWorkload::Statefulset => debug_statefulset(kube).await,
+        PrimaryWorkload::Daemonset => debug_daemonset(kube).await,
     }
 }
 
@@ -198,6 +199,14 @@ async fn debug_statefulset(kube: &ShipKube) -> Result<()> {
     Ok(())
 }
 
+/// Debug a daemonset
+async fn debug_daemonset(kube: &ShipKube) -> Result<()> {
+    let pods = kube.get_pods().await?;
+    info!("Daemonset contains:");
+    debug_pods(pods, kube).await?;
+    Ok(())
+}
+
 async fn debug_pods(pods: ObjectList<Pod>, kube: &ShipKube) -> Result<()> {
     for pod in pods {
         let podstate = PodSummary::try_from(pod)?;
@@ -305,6 +314,31 @@ impl TryFrom<StatefulSet> for StatefulSummary {
     }
 }
 
+/// A summary of a Daemonset's status
+#[derive(Debug)]
+pub struct DaemonSummary {
+    pub desired: i32,
+    pub ready: i32,
+    pub updated: i32,
+}
+
+impl TryFrom<DaemonSet> for DaemonSummary {
+    type Error = crate::Error;
+
+    /// Helper to convert the openapi Daemonset to the useful info
+    fn try_from(d: DaemonSet) -> Result<DaemonSummary> {
+        if let Some(status) = d.status {
+            Ok(DaemonSummary {
+                desired: status.desired_number_scheduled,
+                ready: status.number_ready,
+                updated: status.updated_number_scheduled.unwrap_or(0),
+            })
+        } else {
+            bail!("Missing daemonset status object")
+        }
+    }
+}
+
 #[derive(Debug)]
 struct RolloutResult {
     progress: u32,
@@ -408,6 +442,29 @@ async fn rollout_status(mf: &Manifest, kube: &ShipKube, hash: &Option<String>) -
                 ok,
             })
         }
+        PrimaryWorkload::Daemonset => {
+            // One pod per node, so the expected count comes from the daemonset
+            let ds = kube.get_daemonset().await?;
+            let d = DaemonSummary::try_from(ds)?;
+            debug!("{}: {:?}", mf.name, d);
+
+            let ok = d.updated == d.desired && d.ready == d.desired;
+            let message = if ok {
+                None
+            } else {
+                Some("Daemonset update in progress".to_string())
+            };
+            Ok(RolloutResult {
+                progress: std::cmp::max(0, d.updated)
+                    .try_into()
+                    .expect("ds.updated_number_scheduled >= 0"),
+                expected: std::cmp::max(0, d.desired)
+                    .try_into()
+                    .expect("ds.desired_number_scheduled >= 0"),
+                message,
+                ok,
+            })
+        }
     }
 }
 
@@ -461,6 +518,8 @@ pub async fn workload_rollout(mf: &Manifest, kube: &ShipKube) -> Result<bool> {
                 hash = Some(ur);
             }
         }
+        // daemonsets are tracked by their number of updated pods
+        PrimaryWorkload::Daemonset => {}
     }
 
 
@@ -479,6 +538,9 @@ pub async fn workload_rollout(mf: &Manifest, kube: &ShipKube) -> Result<bool> {
             PrimaryWorkload::Statefulset => {
                 pb.set_prefix(h); // statefulset hash already prefixes name
             }
+            PrimaryWorkload::Daemonset => {
+                pb.set_prefix(&mf.name);
+            }
         }
     } else {
         pb.set_prefix(&mf.name);
@@ -1,32 +1,72 @@
-use super::{Config, Manifest, Region, Result};
-use crate::{error_chain::ChainedError, git};
+use super::{Config, Error, Manifest, Region, Result};
+use crate::{error_chain::ChainedError, git, webhooks};
 use futures::stream::{self, StreamExt};
+use shipcat_definitions::{Vault, Warning};
+use std::{
+    collections::BTreeMap,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
 
-async fn verify_manifest(svc: String, conf: &Config, reg: &Region) -> Result<Manifest> {
-    let mf = shipcat_filebacked::load_manifest(&svc, &conf, &reg)
-        .await?
-        .stub(&reg)
-        .await?;
-    mf.verify(&conf, &reg)?;
-    Ok(mf)
+/// Names of the dependencies of a manifest that are not deployed in the region
+async fn disabled_dependencies(mf: &Manifest, conf: &Config, reg: &Region) -> Result<Vec<String>> {
+    let mut disabled = vec![];
+    for d in &mf.dependencies {
+        let dep = shipcat_filebacked::load_metadata(&d.name, conf, reg).await?;
+        if !dep.enabled {
+            disabled.push(d.name.clone());
+        }
+    }
+    Ok(disabled)
+}
+
+async fn verify_manifest(svc: String, conf: &Config, reg: &Region) -> (String, Result<Manifest>) {
+    let res: Result<Manifest> = async {
+        let mf = shipcat_filebacked::load_manifest(&svc, &conf, &reg)
+            .await?
+            .stub(&reg)
+            .await?;
+        mf.verify(&conf, &reg)?;
+        let disabled = disabled_dependencies(&mf, conf, reg).await?;
+        mf.verify_dependencies_enabled(reg, &disabled)?;
+        Ok(mf)
+    }
+    .await;
+    (svc, res)
+}
+
+/// Outcome of validating all manifests in a region
+#[derive(Serialize, Clone, Debug)]
+pub struct ValidationSummary {
+    pub region: String,
+    pub passed: usize,
+    pub failed: usize,
+    /// Names of the services that failed validation, sorted
+    pub failed_services: Vec<String>,
 }
 
 /// Validate all manifests in a service directory for a region
 ///
 /// This is meant to replace `shipcat validate ..all_services`
 /// This does not check secrets.
+///
+/// A `ValidationSummary` is sent to the region's validation webhooks, if any.
 pub async fn regional_manifests(conf: &Config, reg: &Region) -> Result<()> {
     let available = shipcat_filebacked::available(conf, &reg).await?;
-
     let mut buffered = stream::iter(available)
         .map(move |mf| verify_manifest(mf.base.name, &conf, &reg))
         .buffer_unordered(16);
 
     let mut errs = vec![];
+    let mut failed_services = vec![];
+    let mut passed = 0;
     let mut used_stream_names = vec![];
-    while let Some(r) = buffered.next().await {
+    while let Some((svc, r)) = buffered.next().await {
         match r {
-            Err(e) => errs.push(e),
+            Err(e) => {
+                errs.push(e);
+                failed_services.push(svc);
+            }
             Ok(mf) => {
                 // uniqueness validation
                 for es in mf.eventStreams {
@@ -35,10 +75,20 @@ pub async fn regional_manifests(conf: &Config, reg: &Region) -> Result<()> {
                     }
                     used_stream_names.push(es.name.clone());
                 }
+                passed += 1;
             }
         }
     }
 
+    failed_services.sort();
+    let summary = ValidationSummary {
+        region: reg.name.clone(),
+        passed,
+        failed: failed_services.len(),
+        failed_services,
+    };
+    webhooks::validation_event(&summary, reg).await;
+
     if !errs.is_empty() {
         for e in &errs {
             error!("{}", e.display_chain());
@@ -82,33 +132,346 @@ pub async fn all_manifests() -> Result<()> {
     Ok(())
 }
 
+async fn validate_service(
+    svc: String,
+    conf: &Config,
+    reg: &Region,
+    secrets: bool,
+) -> (String, Result<(Manifest, Vec<Warning>)>) {
+    debug!("validating {} for {}", svc, reg.name);
+    let res: Result<(Manifest, Vec<Warning>)> = async {
+        let mf = shipcat_filebacked::load_manifest(&svc, conf, reg).await?;
+        let mf = if secrets {
+            mf.complete(reg).await?
+        } else {
+            mf.stub(reg).await?
+        };
+        let warnings = mf.verify_collecting(conf, reg)?;
+        let disabled = disabled_dependencies(&mf, conf, reg).await?;
+        mf.verify_dependencies_enabled(reg, &disabled)?;
+        Ok((mf, warnings))
+    }
+    .await;
+    (svc, res)
+}
+
+/// Validate services concurrently, with at most `jobs` in flight
+///
+/// Results are sorted by service name, and failures do not stop the other validations.
+async fn validate_services(
+    services: Vec<String>,
+    conf: &Config,
+    reg: &Region,
+    secrets: bool,
+    jobs: usize,
+) -> Vec<(String, Result<(Manifest, Vec<Warning>)>)> {
+    let mut results = stream::iter(services)
+        .map(|svc| validate_service(svc, conf, reg, secrets))
+        .buffer_unordered(jobs.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    results
+}
+
+/// How `shipcat validate` reports its results
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValidateFormat {
+    /// Only log failures
+    Log,
+    /// Summarise every service in a table after logging failures
+    Table,
+}
+
+impl FromStr for ValidateFormat {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "log" => Ok(Self::Log),
+            "table" => Ok(Self::Table),
+            _ => bail!("Validate format must be log or table"),
+        }
+    }
+}
+
+/// Options of `shipcat validate`
+#[derive(Clone, Copy, Debug)]
+pub struct ValidateOpts {
+    /// Verify that secrets exist in vault, which needs vault credentials
+    pub secrets: bool,
+    /// How many services to validate at the same time
+    pub jobs: usize,
+    /// How results are reported
+    pub format: ValidateFormat,
+    /// Print the validated manifests, with their secrets redacted
+    pub print: bool,
+}
+
+impl Default for ValidateOpts {
+    fn default() -> Self {
+        ValidateOpts {
+            secrets: false,
+            jobs: 1,
+            format: ValidateFormat::Log,
+            print: false,
+        }
+    }
+}
+
+/// Outcome of validating a service in one or more regions
+#[derive(Default, Clone, Debug)]
+pub struct ServiceValidation {
+    pub regions: Vec<String>,
+    pub warnings: usize,
+    pub failed: bool,
+}
+
+impl ServiceValidation {
+    /// Add the outcome in a region, with the number of warnings if it passed
+    fn add(&mut self, region: &str, warnings: Option<usize>) {
+        self.regions.push(region.to_string());
+        match warnings {
+            Some(n) => self.warnings += n,
+            None => self.failed = true,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        if self.failed {
+            "FAIL"
+        } else if self.warnings > 0 {
+            "WARN"
+        } else {
+            "PASS"
+        }
+    }
+}
+
+/// Render validation outcomes as a table, with colored results if `color` is set
+pub fn render_table(results: &BTreeMap<String, ServiceValidation>, color: bool) -> String {
+    let mut lines = vec![format!(
+        "{0:<40} {1:<6} {2:<8} {3}",
+        "SERVICE", "RESULT", "WARNINGS", "REGIONS"
+    )];
+    for (svc, v) in results {
+        // pad before coloring, as escape codes count towards the width
+        let mut status = format!("{:<6}", v.status());
+        if color {
+            let code = match v.status() {
+                "FAIL" => 31,
+                "WARN" => 33,
+                _ => 32,
+            };
+            status = format!("\x1b[{}m{}\x1b[0m", code, status);
+        }
+        lines.push(format!(
+            "{0:<40} {1} {2:<8} {3}",
+            svc,
+            status,
+            v.warnings,
+            v.regions.join(",")
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Whether stdout is a terminal that can show colors
+fn stdout_is_tty() -> bool {
+    io::stdout().is_terminal()
+}
+
+/// Number of warnings of a passed validation, printing the manifest if requested
+fn passed(mf: Manifest, warnings: Vec<Warning>, print: bool) -> Result<usize> {
+    if print {
+        mf.print(false)?;
+    }
+    Ok(warnings.len())
+}
+
 /// Validate the manifest of a service in the services directory
 ///
 /// This will populate the manifest for all supported environments,
 /// and `verify` their parameters.
 /// Optionally, it will also verify that all secrets are found in the corresponding
 /// vault locations serverside (which require vault credentials).
-pub async fn manifest(services: Vec<String>, conf: &Config, reg: &Region, secrets: bool) -> Result<()> {
+///
+/// Up to `opts.jobs` services are validated at the same time, and all failures are reported.
+pub async fn manifest(services: Vec<String>, conf: &Config, reg: &Region, opts: ValidateOpts) -> Result<()> {
     conf.verify()?; // this should work even with a limited config!
+    let mut errs = 0;
+    let mut summary: BTreeMap<String, ServiceValidation> = BTreeMap::new();
+    for (svc, res) in validate_services(services, conf, reg, opts.secrets, opts.jobs).await {
+        let res = res.and_then(|(mf, warnings)| passed(mf, warnings, opts.print));
+        if let Err(e) = &res {
+            error!("{} in {}: {}", svc, reg.name, e.display_chain());
+            errs += 1;
+        }
+        summary.entry(svc).or_default().add(&reg.name, res.ok());
+    }
+    if opts.secrets {
+        let (hits, misses) = Vault::cache_stats();
+        debug!("Read {} secrets from vault, {} more from the cache", misses, hits);
+    }
+    if opts.format == ValidateFormat::Table {
+        println!("{}", render_table(&summary, stdout_is_tty()));
+    }
+    if errs > 0 {
+        bail!("Invalid shipcat data for {} services in {}", errs, reg.name);
+    }
+    Ok(())
+}
+
+/// Compare the manifests of services against their live Deployments
+///
+/// Used by `shipcat validate --cluster` to find drift in replicas, images and resources.
+/// This needs access to the cluster of the region, and only covers Deployments.
+pub async fn cluster_drift(services: Vec<String>, conf: &Config, reg: &Region) -> Result<()> {
+    use crate::{kubeapi::ShipKube, PrimaryWorkload};
+    let mut drifted = 0;
     for svc in services {
-        debug!("validating {} for {}", svc, reg.name);
-        let mf = if secrets {
-            shipcat_filebacked::load_manifest(&svc, conf, reg)
-                .await?
-                .complete(reg)
-                .await?
-        } else {
-            shipcat_filebacked::load_manifest(&svc, conf, reg)
-                .await?
-                .stub(reg)
-                .await?
+        let mf = shipcat_filebacked::load_manifest(&svc, conf, reg)
+            .await?
+            .lazy(reg)?;
+        match mf.workload {
+            PrimaryWorkload::Deployment => {}
+            _ => {
+                warn!("Skipping drift check of {} {}", mf.workload.to_string(), svc);
+                continue;
+            }
+        }
+        let live = ShipKube::new(&mf).await?.get_deploy().await?;
+        let drift = mf.verify_against_cluster(&live)?;
+        for d in &drift {
+            warn!(
+                "{} in {} has drifted: {} is {} but declared as {}",
+                svc, reg.name, d.field, d.live, d.declared
+            );
+        }
+        if !drift.is_empty() {
+            drifted += 1;
+        }
+    }
+    if drifted > 0 {
+        bail!(
+            "{} services in {} have drifted from their manifests",
+            drifted,
+            reg.name
+        );
+    }
+    Ok(())
+}
+
+/// Validate the manifests of services in every region they declare
+///
+/// Used by `shipcat validate -r all`, to catch override files that are only broken in one region.
+/// Every service and region pair is validated, and all failures are reported at the end.
+pub async fn manifest_all_regions(services: Vec<String>, opts: ValidateOpts) -> Result<()> {
+    use crate::ConfigState;
+    let state = if opts.secrets {
+        ConfigState::Filtered
+    } else {
+        ConfigState::Base
+    };
+    let base = Config::read().await?;
+
+    let mut errs: Vec<(String, String, Error)> = vec![];
+    let mut summary: BTreeMap<String, ServiceValidation> = BTreeMap::new();
+    let mut by_region: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    // manifests in regions of an environment tier, to compare sizing across them
+    let mut tiered: BTreeMap<String, Vec<Manifest>> = BTreeMap::new();
+    for svc in services {
+        match shipcat_filebacked::load_base(&svc, &base).await {
+            Ok(mf) => {
+                for r in mf.regions {
+                    by_region.entry(r).or_default().push(svc.clone());
+                }
+            }
+            Err(e) => {
+                summary.entry(svc.clone()).or_default().failed = true;
+                errs.push((svc, "any region".to_string(), e.into()));
+            }
+        }
+    }
+    for (r, svcs) in by_region {
+        let (conf, reg) = match Config::new(state.clone(), &r).await {
+            Ok(cr) => cr,
+            Err(e) => {
+                for svc in svcs {
+                    summary.entry(svc.clone()).or_default().add(&r, None);
+                    errs.push((svc, r.clone(), e.to_string().into()));
+                }
+                continue;
+            }
         };
-        mf.verify(conf, reg)?;
-        debug!("validated {} for {}", svc, reg.name);
+        if let Err(e) = conf.verify() {
+            for svc in svcs {
+                summary.entry(svc).or_default().add(&r, None);
+            }
+            errs.push(("config".into(), r.clone(), e.into()));
+            continue;
+        }
+        for (svc, res) in validate_services(svcs, &conf, &reg, opts.secrets, opts.jobs).await {
+            let res = res.and_then(|(mf, warnings)| {
+                if base.environment_tier(&mf.environment).is_some() {
+                    tiered.entry(svc.clone()).or_default().push(mf.clone());
+                }
+                passed(mf, warnings, opts.print)
+            });
+            match res {
+                Ok(n) => {
+                    info!("validated {} for {}", svc, r);
+                    summary.entry(svc).or_default().add(&r, Some(n));
+                }
+                Err(e) => {
+                    summary.entry(svc.clone()).or_default().add(&r, None);
+                    errs.push((svc, r.clone(), e));
+                }
+            }
+        }
+    }
+
+    for (svc, mfs) in tiered {
+        match tier_sizing(&base, &mfs) {
+            Ok(warnings) => summary.entry(svc).or_default().warnings += warnings.len(),
+            Err(e) => errs.push((svc, "all regions".to_string(), e)),
+        }
+    }
+
+    if !errs.is_empty() {
+        errs.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        for (svc, r, e) in &errs {
+            error!("{} in {}: {}", svc, r, e.display_chain());
+        }
+    }
+    if opts.format == ValidateFormat::Table {
+        println!("{}", render_table(&summary, stdout_is_tty()));
+    }
+    if !errs.is_empty() {
+        bail!("Invalid shipcat data for {} service and region pairs", errs.len());
     }
     Ok(())
 }
 
+/// Compare the sizing of a service between regions in different `environmentTiers`
+fn tier_sizing(conf: &Config, mfs: &[Manifest]) -> Result<Vec<Warning>> {
+    let mut warnings = vec![];
+    for higher in mfs {
+        for lower in mfs {
+            let tiers = (
+                conf.environment_tier(&lower.environment),
+                conf.environment_tier(&higher.environment),
+            );
+            if let (Some(l), Some(h)) = tiers {
+                if l < h {
+                    warnings.extend(higher.verify_sizing_against(lower)?);
+                }
+            }
+        }
+    }
+    Ok(warnings)
+}
+
 /// Validate the secrets exists in all regions
 ///
 /// This is one of very few functions not validating a single kube context,
@@ -121,7 +484,7 @@ pub async fn secret_presence_full(conf: &Config, regions: Vec<String>) -> Result
         for svc in shipcat_filebacked::available(conf, &reg).await? {
             let mf = shipcat_filebacked::load_manifest(&svc.base.name, conf, &reg).await?;
             debug!("validating secrets for {} in {}", &svc.base.name, r);
-            mf.verify_secrets_exist(&reg.vault).await?;
+            mf.verify_secrets_exist(&reg).await?;
         }
     }
     Ok(())
@@ -139,12 +502,12 @@ pub async fn secret_presence_explicit(svcs: Vec<String>, conf: &Config, regions:
         for svc in &svcs {
             debug!("Validating {}", svc);
             if let Ok(mf) = shipcat_filebacked::load_manifest(&svc, conf, &reg).await {
-                if !mf.regions.contains(&r) {
+                if !mf.is_deployable_in(&r) {
                     debug!("ignoring {} for {} (not deployed there)", svc, r);
                     continue;
                 }
                 debug!("validating secrets for {} in {}", &svc, r);
-                mf.verify_secrets_exist(&reg.vault).await?;
+                mf.verify_secrets_exist(&reg).await?;
             }
         }
     }
@@ -174,12 +537,12 @@ pub async fn secret_presence_git(conf: &Config, regions: Vec<String>) -> Result<
         };
         for svc in svcs {
             if let Ok(mf) = shipcat_filebacked::load_manifest(&svc, conf, &reg).await {
-                if !mf.regions.contains(&r) {
+                if !mf.is_deployable_in(&r) {
                     debug!("ignoring {} for {} (not deployed there)", svc, r);
                     continue;
                 }
                 debug!("validating secrets for {} in {}", &svc, r);
-                mf.verify_secrets_exist(&reg.vault).await?;
+                mf.verify_secrets_exist(&reg).await?;
             }
         }
     }
@@ -214,3 +577,45 @@ fn git_diff_changes() -> Result<Vec<String>> {
     }
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{render_table, ServiceValidation};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn validation_table() {
+        let mut results = BTreeMap::new();
+        let mut ok = ServiceValidation::default();
+        ok.add("dev-uk", Some(0));
+        ok.add("prod-uk", Some(0));
+        results.insert("fake-ask".to_string(), ok);
+        let mut warned = ServiceValidation::default();
+        warned.add("dev-uk", Some(2));
+        results.insert("fake-storage".to_string(), warned);
+        let mut failed = ServiceValidation::default();
+        failed.add("dev-uk", Some(1));
+        failed.add("prod-uk", None);
+        results.insert("fake-web".to_string(), failed);
+
+        let table = render_table(&results, false);
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("SERVICE"));
+        assert_eq!(
+            lines[1].split_whitespace().collect::<Vec<_>>(),
+            vec!["fake-ask", "PASS", "0", "dev-uk,prod-uk"]
+        );
+        assert_eq!(
+            lines[2].split_whitespace().collect::<Vec<_>>(),
+            vec!["fake-storage", "WARN", "2", "dev-uk"]
+        );
+        assert_eq!(
+            lines[3].split_whitespace().collect::<Vec<_>>(),
+            vec!["fake-web", "FAIL", "1", "dev-uk,prod-uk"]
+        );
+
+        let colored = render_table(&results, true);
+        assert!(colored.contains("\x1b[31mFAIL  \x1b[0m"));
+    }
+}
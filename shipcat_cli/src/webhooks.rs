@@ -1,5 +1,6 @@
-use super::{Config, Region, Webhook};
-use crate::{apply::UpgradeInfo, audit, slack, Result};
+use super::{Config, Region, ValidationWebhook, Webhook};
+use crate::{apply::UpgradeInfo, audit, slack, validate::ValidationSummary, ErrorKind, Result, ResultExt};
+use futures::future::BoxFuture;
 
 /// The different states an upgrade can be in
 #[derive(Serialize, PartialEq, Clone)]
@@ -32,6 +33,7 @@ pub async fn reconcile_event(us: UpgradeState, reg: &Region) {
         if let Ok(whc) = wh.get_configuration() {
             let res = match wh {
                 Webhook::Audit(h) => audit::reconciliation(&us, &reg.name, &h, whc).await,
+                Webhook::Validation(_) => Ok(()),
             };
             if let Err(e) = res {
                 warn!("Failed to notify about reconciliation event: {}", e)
@@ -56,6 +58,7 @@ pub async fn apply_event(us: UpgradeState, info: &UpgradeInfo, reg: &Region, con
                         _ => Ok(()), // audit only sends Started / Failed / Completed
                     }
                 }
+                Webhook::Validation(_) => Ok(()),
             };
             if let Err(e) = res {
                 warn!("Failed to notify about apply event: {}", e)
@@ -106,6 +109,7 @@ pub async fn delete_event(us: &UpgradeState, info: &UpgradeInfo, reg: &Region, c
         if let Ok(whc) = wh.get_configuration() {
             let res = match wh {
                 Webhook::Audit(h) => audit::deletion(&us, &info, &h, whc).await,
+                Webhook::Validation(_) => Ok(()),
             };
             if let Err(e) = res {
                 warn!("Failed to notify about delete event: {}", e)
@@ -134,3 +138,49 @@ pub async fn delete_event(us: &UpgradeState, info: &UpgradeInfo, reg: &Region, c
         _ => {}
     };
 }
+
+/// Something that can be told about the outcome of a region wide validation
+pub trait Notifier {
+    fn notify<'a>(&'a self, summary: &'a ValidationSummary) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Notifier that POSTs the summary as json to a configured url
+pub struct WebhookNotifier<'a> {
+    cfg: &'a ValidationWebhook,
+}
+
+impl<'a> WebhookNotifier<'a> {
+    pub fn new(cfg: &'a ValidationWebhook) -> Self {
+        WebhookNotifier { cfg }
+    }
+}
+
+impl Notifier for WebhookNotifier<'_> {
+    fn notify<'a>(&'a self, summary: &'a ValidationSummary) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let endpoint = &self.cfg.url;
+            debug!("Validation summary for {} to {}", summary.region, endpoint);
+            reqwest::Client::new()
+                .post(endpoint.clone())
+                .json(summary)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .chain_err(|| ErrorKind::Url(endpoint.clone()))?;
+            Ok(())
+        })
+    }
+}
+
+/// Send a validation summary to configured notifiers - warning on delivery errors
+///
+/// Notifications are best effort and SHOULD NOT fail the validation.
+pub async fn validation_event(summary: &ValidationSummary, reg: &Region) {
+    for wh in &reg.webhooks {
+        if let Webhook::Validation(h) = wh {
+            if let Err(e) = WebhookNotifier::new(h).notify(summary).await {
+                warn!("Failed to notify about validation: {}", e)
+            }
+        }
+    }
+}
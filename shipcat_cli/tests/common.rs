@@ -208,7 +208,8 @@ async fn templating_test() {
     assert_eq!(&sec["FAKE_NUMBER"], "-2"); // NB: ACTUALLY IN_VAULT
 
     let configs = mf.configs.clone().unwrap();
-    let configini = configs.files[0].clone();
+    assert_eq!(configs[0].name, Some("fake-ask-config".to_string()));
+    let configini = configs[0].files[0].clone();
     let cfgtpl = configini.value.unwrap();
     print!("{:?}", cfgtpl);
     assert!(cfgtpl.contains("CORE=https://woot.com/somesvc"));
@@ -0,0 +1,27 @@
+mod common;
+use crate::common::setup;
+use shipcat::fleet::{fleet_report, render_csv, team_totals};
+use shipcat_definitions::{Config, ConfigState};
+
+#[tokio::test]
+async fn fleet_inventory() {
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let report = fleet_report(&conf, &reg).await.unwrap();
+    let names = report.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+    assert!(names.contains(&"fake-ask"));
+    assert!(!names.contains(&"external"));
+
+    let ask = report.iter().find(|s| s.name == "fake-ask").unwrap();
+    assert!(ask.regions.contains(&"dev-uk".to_string()));
+    assert!(ask.total_requests.cpu >= ask.requests.cpu);
+
+    // team totals add up to the fleet total
+    let teams = team_totals(&report);
+    let fleet_cpu: f64 = report.iter().map(|s| s.total_requests.cpu).sum();
+    let team_cpu: f64 = teams.iter().map(|t| t.total_requests.cpu).sum();
+    assert!((fleet_cpu - team_cpu).abs() < 1e-9);
+
+    let csv = render_csv(&report);
+    assert_eq!(csv.lines().count(), report.len() + 1);
+}
@@ -1,13 +1,14 @@
 mod common;
 use crate::common::setup;
-use shipcat::graph::{generate, nodeidx_from_name};
+use shipcat::graph::{generate, nodeidx_from_name, to_dot, CatGraph, DepEdge, ManifestNode};
+use shipcat::structs::DependencyProtocol;
 use shipcat_definitions::{Config, ConfigState};
 
 #[tokio::test]
 async fn graph_generate() {
     setup();
     let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
-    let graph = generate("fake-ask", &conf, &reg, true).await.unwrap();
+    let graph = generate("fake-ask", &conf, &reg, true, None).await.unwrap();
     assert!(graph.edge_count() > 0);
     print!("got struct: \n{:?}\n", serde_yaml::to_string(&graph));
     let askidx = nodeidx_from_name("fake-ask", &graph).unwrap();
@@ -20,3 +21,38 @@ async fn graph_generate() {
     println!("edge: {:?}", edge);
     assert_eq!(edge.intent, Some("testing graph module".into()));
 }
+
+fn node(name: &str, external: bool) -> ManifestNode {
+    ManifestNode {
+        name: name.into(),
+        external,
+    }
+}
+
+fn edge(api: &str) -> DepEdge {
+    DepEdge {
+        api: api.into(),
+        contract: None,
+        protocol: DependencyProtocol::Http,
+        intent: None,
+    }
+}
+
+#[test]
+fn graph_dot() {
+    let mut graph = CatGraph::new();
+    let ask = graph.add_node(node("fake-ask", false));
+    let strg = graph.add_node(node("fake-storage", false));
+    let ext = graph.add_node(node("fake-external", true));
+    graph.add_edge(ask, strg, edge("v1"));
+    graph.add_edge(strg, ask, edge("v2"));
+    graph.add_edge(ask, ext, edge("v1"));
+
+    let dot = to_dot(&graph);
+    assert!(dot.starts_with("digraph {"));
+    assert!(dot.contains("0 [ label = \"fake-ask\", color = red ]"));
+    assert!(dot.contains("2 [ label = \"fake-external\", style = filled, fillcolor = lightgrey ]"));
+    assert!(dot.contains("0 -> 1 [ label = \"v1\", color = red, fontcolor = red ]"));
+    assert!(dot.contains("1 -> 0 [ label = \"v2\", color = red, fontcolor = red ]"));
+    assert!(dot.contains("0 -> 2 [ label = \"v1\" ]"));
+}
@@ -13,7 +13,7 @@ async fn helm_template() -> Result<()> {
         .stub(&reg)
         .await?;
 
-    let res = helm::template(&mf, None).await?;
+    let res = helm::template(&mf, None, None).await?;
 
     // verify we have deferred to helm for templating
     assert!(res.contains("image: \"quay.io/babylonhealth/fake-ask:1.6.0\""));
@@ -1,15 +1,63 @@
 mod common;
 use crate::common::setup;
 
-use shipcat::validate::manifest as validate;
-use shipcat_definitions::{Config, ConfigState};
+use shipcat::validate::{manifest as validate, ValidateFormat, ValidateOpts};
+use shipcat_definitions::{Config, ConfigState, Vault, WarningCode};
 
 #[tokio::test]
 async fn validate_test() {
     setup();
     let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
-    let res = validate(vec!["fake-ask".into()], &conf, &reg, true).await;
+    let opts = ValidateOpts {
+        secrets: true,
+        ..Default::default()
+    };
+    let res = validate(vec!["fake-ask".into()], &conf, &reg, opts).await;
     assert!(res.is_ok());
-    let res2 = validate(vec!["fake-storage".into(), "fake-ask".into()], &conf, &reg, false).await;
+    let svcs = vec!["fake-storage".into(), "fake-ask".into()];
+    let opts = ValidateOpts {
+        jobs: 2,
+        format: ValidateFormat::Table,
+        ..Default::default()
+    };
+    let res2 = validate(svcs, &conf, &reg, opts).await;
     assert!(res2.is_ok())
 }
+
+#[tokio::test]
+async fn lazy_secrets() {
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let mut mf = shipcat_filebacked::load_manifest("fake-ask", &conf, &reg)
+        .await
+        .unwrap()
+        .lazy(&reg)
+        .unwrap();
+    // placeholders are left for later, but the manifest is still verifiable
+    assert_eq!(mf.env.plain["FAKE_SECRET"], "IN_VAULT");
+    assert!(mf.secrets.is_empty());
+    assert!(mf.verify(&conf, &reg).is_ok());
+
+    let vault = Vault::mocked(&reg.vault).unwrap();
+    mf.resolve_secrets(&vault, &reg).await.unwrap();
+    assert!(mf.env.secrets.contains("FAKE_SECRET"));
+    assert!(mf.secrets.contains_key("FAKE_SECRET"));
+    // only lazy manifests can be resolved
+    assert!(mf.resolve_secrets(&vault, &reg).await.is_err());
+}
+
+#[tokio::test]
+async fn collect_warnings() {
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let mf = shipcat_filebacked::load_manifest("external", &conf, &reg)
+        .await
+        .unwrap()
+        .stub(&reg)
+        .await
+        .unwrap();
+    let warnings = mf.verify_collecting(&conf, &reg).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, WarningCode::ExternalService);
+    assert!(mf.verify(&conf, &reg).is_ok());
+}
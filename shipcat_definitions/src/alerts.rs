@@ -0,0 +1,114 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use std::collections::BTreeMap;
+
+use super::{structs::AlertRule, Manifest};
+
+/// A prometheus-operator `PrometheusRule`
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PrometheusRule {
+    pub api_version: String,
+    pub kind: String,
+    pub metadata: ObjectMeta,
+    pub spec: PrometheusRuleSpec,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PrometheusRuleSpec {
+    pub groups: Vec<RuleGroup>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RuleGroup {
+    pub name: String,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Rule {
+    pub alert: String,
+    pub expr: String,
+    #[serde(rename = "for", skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Rule {
+    fn new(svc: &str, team: &str, a: &AlertRule) -> Self {
+        let mut labels = BTreeMap::new();
+        labels.insert("service".to_string(), svc.to_string());
+        labels.insert("team".to_string(), team.to_string());
+        labels.insert(
+            "severity".to_string(),
+            serde_json::to_value(a.severity).unwrap().as_str().unwrap().to_string(),
+        );
+        Rule {
+            alert: a.name.clone(),
+            expr: a.expr.clone(),
+            duration: a.duration.clone(),
+            labels,
+            annotations: a.annotations.clone(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Generate the `PrometheusRule` holding the `alerts` of the service
+    ///
+    /// Rules are labelled with the service, its team and their severity for routing in alertmanager.
+    /// Services without alerts get no `PrometheusRule`.
+    pub fn prometheus_rule(&self) -> Option<PrometheusRule> {
+        if self.alerts.is_empty() {
+            return None;
+        }
+        let name = self.canonical_name().to_string();
+        let team = self.metadata.as_ref().map(|md| md.team.clone()).unwrap_or_default();
+        let mut labels = BTreeMap::new();
+        labels.insert("app".to_string(), name.clone());
+        Some(PrometheusRule {
+            api_version: "monitoring.coreos.com/v1".into(),
+            kind: "PrometheusRule".into(),
+            metadata: ObjectMeta {
+                name: Some(name.clone()),
+                namespace: Some(self.namespace.clone()),
+                labels: Some(labels),
+                ..Default::default()
+            },
+            spec: PrometheusRuleSpec {
+                groups: vec![RuleGroup {
+                    name,
+                    rules: self.alerts.iter().map(|a| Rule::new(&self.name, &team, a)).collect(),
+                }],
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Manifest;
+
+    #[test]
+    fn prometheus_rule() {
+        let mut mf = Manifest::test("fake-svc");
+        assert!(mf.prometheus_rule().is_none());
+
+        mf.alerts = vec![serde_yaml::from_str(
+            "name: HighErrorRate\nexpr: rate(errors_total[5m]) > 1\nfor: 10m\nseverity: critical\n\
+             annotations:\n  summary: errors",
+        )
+        .unwrap()];
+        let rule = serde_yaml::to_value(mf.prometheus_rule().unwrap()).unwrap();
+        assert_eq!(rule["kind"], "PrometheusRule");
+        assert_eq!(rule["metadata"]["name"], "fake-svc");
+        assert_eq!(rule["metadata"]["namespace"], "apps");
+        let alert = &rule["spec"]["groups"][0]["rules"][0];
+        assert_eq!(alert["alert"], "HighErrorRate");
+        assert_eq!(alert["for"], "10m");
+        assert_eq!(alert["labels"]["severity"], "critical");
+        assert_eq!(alert["labels"]["team"], "doves");
+        assert_eq!(alert["annotations"]["summary"], "errors");
+    }
+}
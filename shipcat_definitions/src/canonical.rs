@@ -0,0 +1,101 @@
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+use super::Result;
+
+/// Serialize to yaml with all mapping keys sorted
+///
+/// Struct fields otherwise serialize in declaration order, so reordering fields
+/// would churn rendered output that is committed to git.
+/// This makes the same logical value always serialize byte-identically.
+pub fn to_yaml<T: Serialize>(data: &T) -> Result<String> {
+    let value = serde_yaml::to_value(data)?;
+    Ok(serde_yaml::to_string(&canonicalize(value))?)
+}
+
+/// Recursively sort the keys of all mappings in a yaml value
+pub fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Mapping(m) => {
+            let mut entries = m
+                .into_iter()
+                .map(|(k, v)| (sort_key(&k), k, canonicalize(v)))
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut sorted = Mapping::new();
+            for (_, k, v) in entries {
+                sorted.insert(k, v);
+            }
+            Value::Mapping(sorted)
+        }
+        Value::Sequence(s) => Value::Sequence(s.into_iter().map(canonicalize).collect()),
+        v => v,
+    }
+}
+
+fn sort_key(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        // non-string keys are rare, but still need a stable position
+        k => serde_yaml::to_string(k).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_yaml;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Unsorted {
+        zebra: u32,
+        apple: Vec<Nested>,
+    }
+
+    #[derive(Serialize)]
+    struct Nested {
+        name: String,
+        extra: BTreeMap<String, String>,
+        age: u32,
+    }
+
+    #[derive(Serialize)]
+    struct Reordered {
+        apple: Vec<ReorderedNested>,
+        zebra: u32,
+    }
+
+    #[derive(Serialize)]
+    struct ReorderedNested {
+        age: u32,
+        extra: BTreeMap<String, String>,
+        name: String,
+    }
+
+    #[test]
+    fn sorted_keys() {
+        let mut extra = BTreeMap::new();
+        extra.insert("b".to_string(), "2".to_string());
+        extra.insert("a".to_string(), "1".to_string());
+        let unsorted = Unsorted {
+            zebra: 1,
+            apple: vec![Nested {
+                name: "x".into(),
+                extra: extra.clone(),
+                age: 3,
+            }],
+        };
+        let reordered = Reordered {
+            apple: vec![ReorderedNested {
+                age: 3,
+                extra,
+                name: "x".into(),
+            }],
+            zebra: 1,
+        };
+        let out = to_yaml(&unsorted).unwrap();
+        assert_eq!(out, to_yaml(&reordered).unwrap());
+        assert!(out.find("apple").unwrap() < out.find("zebra").unwrap());
+        assert!(out.find("age").unwrap() < out.find("name").unwrap());
+    }
+}
@@ -1,13 +1,17 @@
 #![allow(non_snake_case)]
 
 use kube_derive::CustomResource;
+use regex::Regex;
 use semver::Version;
 use std::collections::{BTreeMap, BTreeSet};
 
-use crate::teams;
+use crate::{
+    structs::{ResourceRequirements, Resources},
+    teams,
+};
 #[allow(unused_imports)] use std::path::{Path, PathBuf};
 
-#[allow(unused_imports)] use super::{Error, Result};
+#[allow(unused_imports)] use super::{Error, ErrorKind, Result};
 use crate::{
     region::{Environment, Region},
     states::ConfigState,
@@ -24,6 +28,20 @@ pub struct ManifestDefaults {
     pub chart: String,
     /// Default replication counts
     pub replicaCount: u32,
+    /// Named resource presets that manifests can pick via `resourceTier`
+    ///
+    /// ```yaml
+    /// resourceTiers:
+    ///   small:
+    ///     requests:
+    ///       cpu: 100m
+    ///       memory: 256Mi
+    ///     limits:
+    ///       cpu: 500m
+    ///       memory: 512Mi
+    /// ```
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub resourceTiers: BTreeMap<String, ResourceRequirements<String>>,
 }
 
 // Allow smaller base configs
@@ -33,6 +51,7 @@ impl Default for ManifestDefaults {
             chart: "base".into(),
             replicaCount: 1,
             imagePrefix: "".into(),
+            resourceTiers: BTreeMap::new(),
         }
     }
 }
@@ -50,6 +69,11 @@ pub struct Cluster {
     pub teleport: Option<String>,
     /// What regions this cluster control (perhaps not exclusively)
     pub regions: Vec<String>,
+    /// Allocatable resources across the nodes of the cluster
+    ///
+    /// Used to detect overcommitted regions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<Resources<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -81,6 +105,57 @@ pub struct SlackParameters {
 }
 
 
+/// Ownership metadata that services must declare
+///
+/// Every service needs a `team`. The rest is opt-in, and can be changed per team:
+///
+/// ```yaml
+/// metadataRequirements:
+///   repo: true
+///   contact: true
+///   teams:
+///     platform:
+///       repo: false
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct MetadataRequirements {
+    /// Require a repo url
+    #[serde(default)]
+    pub repo: bool,
+    /// Require a contact, a maintainer, or a slack channel
+    #[serde(default)]
+    pub contact: bool,
+    /// Overrides for specific teams
+    #[serde(default)]
+    pub teams: BTreeMap<String, TeamMetadataRequirements>,
+}
+
+/// Per-team override of `MetadataRequirements`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct TeamMetadataRequirements {
+    #[serde(default)]
+    pub repo: Option<bool>,
+    #[serde(default)]
+    pub contact: Option<bool>,
+}
+
+impl MetadataRequirements {
+    /// Whether a team must declare a repo
+    pub fn repo_required(&self, team: &str) -> bool {
+        self.teams.get(team).and_then(|t| t.repo).unwrap_or(self.repo)
+    }
+
+    /// Whether a team must declare a way to contact it
+    pub fn contact_required(&self, team: &str) -> bool {
+        self.teams
+            .get(team)
+            .and_then(|t| t.contact)
+            .unwrap_or(self.contact)
+    }
+}
+
 // ----------------------------------------------------------------------------------
 
 
@@ -129,6 +204,30 @@ pub struct Config {
     #[serde(default)]
     pub allowedCustomMetadata: BTreeSet<String>,
 
+    /// Cost centers services can be attributed to in `metadata.costCenter`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub costCenters: BTreeSet<String>,
+
+    /// Whether unknown keys in a service's `manifest.yml` are errors
+    ///
+    /// Otherwise they are ignored, which allows keys from newer shipcat versions.
+    #[serde(default)]
+    pub strictManifests: bool,
+
+    /// Environments from lowest to highest tier, e.g. `[dev, staging, prod]`
+    ///
+    /// Services in a higher tier are expected to be at least as large as in lower ones.
+    #[serde(default)]
+    pub environmentTiers: Vec<Environment>,
+
+    /// Feature flags each chart supports, keyed by chart name
+    #[serde(default)]
+    pub chartFeatures: BTreeMap<String, BTreeSet<String>>,
+
+    /// Ownership metadata services must declare
+    #[serde(default)]
+    pub metadataRequirements: MetadataRequirements,
+
     /// Shipcat version pins
     pub versions: BTreeMap<Environment, Version>,
 
@@ -144,31 +243,68 @@ pub struct Config {
 }
 
 impl Config {
+    /// Verify the whole config
+    ///
+    /// Collects every problem found rather than stopping at the first one,
+    /// so a broken `shipcat.conf` can be fixed in one go.
     pub fn verify(&self) -> Result<()> {
+        self.verify_in(Path::new("."))
+    }
+
+    /// Verify the config with charts looked up relative to `root`
+    fn verify_in(&self, root: &Path) -> Result<()> {
+        let problems = self.find_problems(root);
+        if !problems.is_empty() {
+            bail!(ErrorKind::InvalidConfig(problems));
+        }
+        Ok(())
+    }
+
+    fn find_problems(&self, root: &Path) -> Vec<String> {
+        let mut problems = vec![];
         let defs = &self.defaults;
         // verify default chart exists
         if cfg!(feature = "filesystem") {
-            let chart = Path::new(".").join("charts").join(&defs.chart).join("Chart.yaml");
+            let chart = root.join("charts").join(&defs.chart).join("Chart.yaml");
             if !chart.is_file() {
-                bail!("Default chart {} does not exist", self.defaults.chart);
+                problems.push(format!("Default chart {} does not exist", defs.chart));
             }
         }
+        if defs.chart == "" {
+            problems.push("Default chart must be set".into());
+        }
+        if defs.imagePrefix == "" {
+            problems.push("Default imagePrefix must be set".into());
+        }
         if defs.imagePrefix.ends_with('/') {
-            bail!("image prefix must not end with a slash");
+            problems.push("image prefix must not end with a slash".into());
+        }
+        if defs.replicaCount == 0 {
+            problems.push("Default replicaCount must be at least 1".into());
+        }
+        for (name, tier) in &defs.resourceTiers {
+            if let Err(e) = tier.verify() {
+                problems.push(format!("resource tier {} is invalid: {}", name, e));
+            }
         }
 
         for (cname, clst) in &self.clusters {
             if cname != &clst.name {
-                bail!(
+                problems.push(format!(
                     "clust '{}' must have a '.name' equal to its key in clusters",
                     cname
-                );
+                ));
+            }
+            if let Some(cap) = &clst.capacity {
+                if let Err(e) = cap.normalised() {
+                    problems.push(format!("cluster {} has invalid capacity: {}", cname, e));
+                }
             }
             // can't actually verify this in a smaller manifest..
             #[cfg(feature = "filesystem")]
             for r in &clst.regions {
                 if !self.has_region(r) && self.state == ConfigState::File {
-                    bail!("cluster {} defines undefined region {}", cname, r);
+                    problems.push(format!("cluster {} defines undefined region {}", cname, r));
                 }
             }
         }
@@ -176,40 +312,76 @@ impl Config {
         for (k, v) in &self.contextAliases {
             // all contextAlias values must exist as defined regions
             if !self.has_region(v) {
-                bail!("context alias {} points to undefined region {}", k, v);
+                problems.push(format!("context alias {} points to undefined region {}", k, v));
             }
             // cannot alias something that exists!
             if self.has_region(k) {
-                bail!("cannot self-alias region {}", k);
+                problems.push(format!("cannot self-alias region {}", k));
             }
         }
 
+        let env_key = Regex::new(r"^[A-Z_][A-Z0-9_]*$").unwrap();
         let mut used_kong_urls = vec![];
         for r in &self.regions {
             if r.namespace == "" {
-                bail!("Need to set `namespace` in {}", r.name);
+                problems.push(format!("Need to set `namespace` in {}", r.name));
             }
             if r.cluster == "" {
-                bail!("Need to set the serving `cluster` of {}", r.name);
+                problems.push(format!("Need to set the serving `cluster` of {}", r.name));
+            } else if !self.clusters.keys().any(|c| c == &r.cluster) {
+                problems.push(format!(
+                    "Region {} served by missing cluster '{}'",
+                    r.name, r.cluster
+                ));
+            }
+            if let Some(prefix) = &r.defaults.imagePrefix {
+                if prefix == "" || prefix.ends_with('/') {
+                    problems.push(format!(
+                        "Region {} imagePrefix must be non-empty and not end with a slash",
+                        r.name
+                    ));
+                }
+            }
+            if let Err(e) = r.vault.verify(&r.name) {
+                problems.push(e.to_string());
             }
-            if !self.clusters.keys().any(|c| c == &r.cluster) {
-                bail!("Region {} served by missing cluster '{}'", r.name, r.cluster);
+            for (name, vc) in &r.vaultRegions {
+                if let Err(e) = vc.verify(&format!("vault region {} in {}", name, r.name)) {
+                    problems.push(e.to_string());
+                }
+            }
+            for k in r.env.keys() {
+                if !env_key.is_match(k) {
+                    problems.push(format!(
+                        "Region {} env var {} must be uppercase alphanumeric with underscores",
+                        r.name, k
+                    ));
+                }
             }
-            r.vault.verify(&r.name)?;
             for v in r.base_urls.values() {
                 if v.ends_with('/') {
-                    bail!("A base_url must not end with a slash");
+                    problems.push(format!("A base_url must not end with a slash (found {})", v));
                 }
             }
             if let Some(kong) = &r.kong {
-                kong.verify()?;
+                if let Err(e) = kong.verify() {
+                    problems.push(e.to_string());
+                }
                 if used_kong_urls.contains(&kong.config_url) {
-                    bail!("Cannot reuse kong config urls for {} across regions", r.name);
+                    problems.push(format!(
+                        "Cannot reuse kong config urls for {} across regions",
+                        r.name
+                    ));
                 }
                 used_kong_urls.push(kong.config_url.clone());
             }
         }
-        Ok(())
+        problems
+    }
+
+    /// Position of an environment in `environmentTiers`, if it is in there
+    pub fn environment_tier(&self, env: &str) -> Option<usize> {
+        self.environmentTiers.iter().position(|e| e.to_string() == env)
     }
 
     #[cfg(feature = "filesystem")]
@@ -266,7 +438,7 @@ impl Config {
 
     /// Print Config to stdout
     pub fn print(&self) -> Result<()> {
-        println!("{}", serde_yaml::to_string(self)?);
+        println!("{}", crate::canonical::to_yaml(self)?);
         Ok(())
     }
 
@@ -275,6 +447,28 @@ impl Config {
         self.regions.iter().map(|r| r.name.clone()).collect()
     }
 
+    /// Iterate over the regions in the config
+    pub fn regions(&self) -> impl Iterator<Item = &Region> {
+        self.regions.iter()
+    }
+
+    /// Names of the regions in the config, sorted
+    pub fn region_names(&self) -> Vec<String> {
+        let mut names = self.list_regions();
+        names.sort();
+        names
+    }
+
+    /// Region accessor by exact name
+    ///
+    /// Unlike `get_region` this does not resolve context aliases.
+    pub fn region(&self, name: &str) -> Result<&Region> {
+        match self.regions.iter().find(|r| r.name == name) {
+            Some(r) => Ok(r),
+            None => bail!(ErrorKind::MissingRegion(name.to_string())),
+        }
+    }
+
     /// Fill secrets from vault on a Base config for a known to exist region
     ///
     /// This will use the HTTP api of Vault using the configuration parameters.
@@ -355,7 +549,7 @@ impl Config {
     /// Useful for small helper subcommands that do validation later.
     pub fn get_region(&self, ctx: &str) -> Result<Region> {
         if let Some(region) = self.resolve_context(ctx.to_string()) {
-            return Ok(self.regions.iter().find(|r| r.name == region).unwrap().clone());
+            return Ok(self.region(&region)?.clone());
         }
         bail!(
             "You need to define your kube context '{}' in shipcat.conf regions first",
@@ -368,6 +562,14 @@ impl Config {
         self.regions.clone()
     }
 
+    /// Capacity of the cluster serving a region, if known
+    pub fn region_capacity(&self, region: &Region) -> Result<Option<Resources<f64>>> {
+        match self.find_owning_cluster(region).and_then(|c| c.capacity) {
+            Some(cap) => Ok(Some(cap.normalised()?)),
+            None => Ok(None),
+        }
+    }
+
     /// Find the Cluster struct that owns this Region
     pub fn find_owning_cluster(&self, region: &Region) -> Option<Cluster> {
         for c in self.clusters.values() {
@@ -384,6 +586,17 @@ impl Config {
 }
 
 
+/// Path to the config file
+///
+/// Can be overridden with `SHIPCAT_CONFIG` to run shipcat against a config anywhere on disk.
+/// Defaults to `shipcat.conf` in pwd.
+pub fn config_path() -> PathBuf {
+    match std::env::var("SHIPCAT_CONFIG") {
+        Ok(pth) => PathBuf::from(pth),
+        Err(_) => Path::new(".").join("shipcat.conf"),
+    }
+}
+
 /// Simplified config with version information only
 ///
 /// The part of shipcat.conf you never get to break the format of.
@@ -396,8 +609,7 @@ impl ConfigFallback {
     /// Read the fallback version of the Config to decide if upgrade needed
     fn read() -> Result<ConfigFallback> {
         use std::fs;
-        let pwd = Path::new(".");
-        let mpath = pwd.join("shipcat.conf");
+        let mpath = config_path();
         let data = fs::read_to_string(&mpath)?;
         let vc: ConfigFallback = serde_yaml::from_str(&data)?;
         Ok(vc)
@@ -431,6 +643,88 @@ impl ConfigFallback {
     }
 }
 
+/// Resolve the regions of a raw config that `extends` another region
+///
+/// The extending region starts from the values of its base, and overrides the keys it sets.
+/// Maps are merged key by key, while lists and other values are replaced as a whole.
+/// Bases can extend other regions in turn.
+///
+/// ```yaml
+/// regions:
+/// - name: prod-eu
+///   extends: prod-uk
+///   namespace: eu
+/// ```
+pub fn resolve_region_extends(raw: &mut serde_yaml::Value) -> Result<()> {
+    use serde_yaml::Value;
+    let regions = match raw.get_mut("regions") {
+        Some(Value::Sequence(rs)) => rs,
+        _ => return Ok(()),
+    };
+    let by_name = regions
+        .iter()
+        .filter_map(|r| Some((r.get("name")?.as_str()?.to_string(), r.clone())))
+        .collect::<BTreeMap<_, _>>();
+    for r in regions.iter_mut() {
+        if r.get("extends").is_none() {
+            continue;
+        }
+        let name = match r.get("name").and_then(Value::as_str) {
+            Some(n) => n.to_string(),
+            None => bail!("Regions using extends need a name"),
+        };
+        *r = resolve_region(&name, &by_name, &mut vec![])?;
+    }
+    Ok(())
+}
+
+fn resolve_region(
+    name: &str,
+    regions: &BTreeMap<String, serde_yaml::Value>,
+    chain: &mut Vec<String>,
+) -> Result<serde_yaml::Value> {
+    use serde_yaml::Value;
+    chain.push(name.to_string());
+    let mut region = regions[name].clone();
+    let base = match &mut region {
+        Value::Mapping(m) => m.remove(&"extends".into()),
+        _ => None,
+    };
+    match base {
+        None => Ok(region),
+        Some(Value::String(base)) => {
+            if chain.contains(&base) {
+                bail!("Region {} extends itself via {} -> {}", base, chain.join(" -> "), base);
+            }
+            if !regions.contains_key(&base) {
+                bail!("Region {} extends {}, which is not a region", name, base);
+            }
+            let mut resolved = resolve_region(&base, regions, chain)?;
+            merge_value(&mut resolved, region);
+            Ok(resolved)
+        }
+        Some(_) => bail!("Region {} must extend the name of another region", name),
+    }
+}
+
+/// Deep merge `over` into `base`
+fn merge_value(base: &mut serde_yaml::Value, over: serde_yaml::Value) {
+    use serde_yaml::Value;
+    match (base, over) {
+        (Value::Mapping(b), Value::Mapping(o)) => {
+            for (k, v) in o {
+                match b.get_mut(&k) {
+                    Some(bv) => merge_value(bv, v),
+                    None => {
+                        b.insert(k, v);
+                    }
+                }
+            }
+        }
+        (b, o) => *b = o,
+    }
+}
+
 /// Filesystem accessors for Config
 ///
 /// These must live in here because they use private methods herein.
@@ -465,26 +759,38 @@ impl Config {
     }
 
     /// Read a config file in an arbitrary path
-    async fn read_from(pwd: &PathBuf) -> Result<Config> {
+    async fn read_file(mpath: &Path) -> Result<Config> {
         use tokio::fs;
-        let mpath = pwd.join("shipcat.conf");
         trace!("Using config in {}", mpath.display());
         if !mpath.exists() {
             bail!("Config file {} does not exist", mpath.display())
         }
         let data = fs::read_to_string(&mpath).await?;
-        let res = serde_yaml::from_str(&data)?;
+        let mut raw = serde_yaml::from_str(&data)?;
+        resolve_region_extends(&mut raw)?;
+        let res = serde_yaml::from_value(raw)?;
         Ok(res)
     }
 
-    /// Read a config in pwd and leave placeholders
-    pub async fn read() -> Result<Config> {
-        let pwd = Path::new(".");
-        let mut conf = Config::read_from(&pwd.to_path_buf()).await?;
-        conf.owners = teams::Owners::read()?;
+    /// Read a config at a given path, verify it, and leave placeholders
+    ///
+    /// Teams and charts are read from the directory of the config.
+    pub async fn read_from(mpath: &Path) -> Result<Config> {
+        let mut conf = Config::read_file(mpath).await?;
+        let dir = match mpath.parent() {
+            Some(d) if d != Path::new("") => d,
+            _ => Path::new("."),
+        };
+        conf.owners = teams::Owners::read_from(&dir.to_path_buf())?;
+        conf.verify_in(dir)?;
         Ok(conf)
     }
 
+    /// Read the config in `SHIPCAT_CONFIG`, or in pwd, verify it, and leave placeholders
+    pub async fn read() -> Result<Config> {
+        Config::read_from(&config_path()).await
+    }
+
     pub fn has_all_regions(&self) -> bool {
         self.state == ConfigState::File
     }
@@ -529,7 +835,8 @@ impl Config {
 
 #[cfg(test)]
 mod tests {
-    use crate::region::VersionScheme;
+    use super::{resolve_region_extends, Config};
+    use crate::{region::VersionScheme, ErrorKind};
     #[test]
     fn version_validate_test() {
         let scheme = VersionScheme::GitShaOrSemver;
@@ -546,4 +853,181 @@ mod tests {
             .verify("e7c1e5dd5de74b2b5da5eef76eb5bf12bdc2ac19")
             .is_err());
     }
+
+    #[test]
+    fn verify_reports_all_problems() {
+        let conf: Config = serde_yaml::from_str(
+            r#"
+defaults:
+  imagePrefix: ""
+  chart: base
+  replicaCount: 0
+clusters:
+  kops-uk:
+    name: kops-uk
+    api: https://api.kube.uk.some.domain
+    regions:
+    - dev-uk
+regions:
+- name: dev-uk
+  namespace: ""
+  environment: dev
+  cluster: kops-uk
+  versioningScheme: Semver
+  env:
+    GOOD_EVAR: indeed
+    bad-evar: nope
+  vault:
+    url: http://localhost:8200
+    folder: dev-uk
+slack:
+  team: T1234ABCD
+github:
+  organisation: babylonhealth
+versions:
+  dev: 0.1.0
+"#,
+        )
+        .unwrap();
+        let err = conf.verify().unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidConfig(problems) => {
+                // the chart is only looked up with the filesystem feature
+                let problems = problems
+                    .iter()
+                    .filter(|p| !p.starts_with("Default chart"))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                assert_eq!(problems, vec![
+                    "Default imagePrefix must be set".to_string(),
+                    "Default replicaCount must be at least 1".to_string(),
+                    "Need to set `namespace` in dev-uk".to_string(),
+                    "Region dev-uk env var bad-evar must be uppercase alphanumeric with underscores"
+                        .to_string(),
+                ]);
+            }
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+        let msg = err.to_string();
+        assert!(msg.contains("\n - Default replicaCount must be at least 1"));
+    }
+
+    #[test]
+    fn region_accessors() {
+        let conf: Config = serde_yaml::from_str(
+            r#"
+defaults:
+  imagePrefix: ""
+  chart: base
+  replicaCount: 1
+clusters: {}
+regions:
+- name: staging-uk
+  namespace: apps
+  environment: staging
+  cluster: kops-uk
+  versioningScheme: Semver
+  vault:
+    url: http://localhost:8200
+    folder: staging-uk
+- name: dev-uk
+  namespace: apps
+  environment: dev
+  cluster: kops-uk
+  versioningScheme: Semver
+  vault:
+    url: http://localhost:8200
+    folder: dev-uk
+slack:
+  team: T1234ABCD
+github:
+  organisation: babylonhealth
+versions:
+  dev: 0.1.0
+"#,
+        )
+        .unwrap();
+        assert_eq!(conf.region_names(), vec!["dev-uk", "staging-uk"]);
+        assert_eq!(conf.regions().count(), 2);
+        assert_eq!(conf.region("dev-uk").unwrap().environment.to_string(), "dev");
+        let err = conf.region("prod-uk").unwrap_err();
+        assert_eq!(err.to_string(), "Region prod-uk is not defined in shipcat.conf");
+        assert!(conf.get_region("prod-uk").is_err());
+    }
+
+    #[test]
+    fn region_extends() {
+        let mut raw: serde_yaml::Value = serde_yaml::from_str(
+            "regions:
+- name: prod-uk
+  namespace: apps
+  env:
+    A: a
+    B: b
+  kong:
+    base_url: uk.example.com
+- name: prod-eu
+  extends: prod-uk
+  env:
+    B: eu
+- name: prod-eu-blue
+  extends: prod-eu
+  kong: ~
+",
+        )
+        .unwrap();
+        resolve_region_extends(&mut raw).unwrap();
+        let expected: serde_yaml::Value = serde_yaml::from_str(
+            "regions:
+- name: prod-uk
+  namespace: apps
+  env:
+    A: a
+    B: b
+  kong:
+    base_url: uk.example.com
+- name: prod-eu
+  namespace: apps
+  env:
+    A: a
+    B: eu
+  kong:
+    base_url: uk.example.com
+- name: prod-eu-blue
+  namespace: apps
+  env:
+    A: a
+    B: eu
+  kong: ~
+",
+        )
+        .unwrap();
+        assert_eq!(raw, expected);
+
+        let mut missing: serde_yaml::Value =
+            serde_yaml::from_str("regions:\n- name: prod-eu\n  extends: prod-us\n").unwrap();
+        let err = resolve_region_extends(&mut missing).unwrap_err();
+        assert_eq!(err.to_string(), "Region prod-eu extends prod-us, which is not a region");
+
+        let mut cycle: serde_yaml::Value = serde_yaml::from_str(
+            "regions:\n- name: a\n  extends: b\n- name: b\n  extends: c\n- name: c\n  extends: a\n",
+        )
+        .unwrap();
+        let err = resolve_region_extends(&mut cycle).unwrap_err();
+        assert_eq!(err.to_string(), "Region a extends itself via a -> b -> c -> a");
+    }
+
+    #[cfg(feature = "filesystem")]
+    #[tokio::test]
+    async fn read_from_path() {
+        use std::path::Path;
+        let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("tests");
+
+        let conf = Config::read_from(&tests.join("shipcat.conf")).await.unwrap();
+        assert!(conf.get_region("dev-uk").is_ok());
+        assert!(!conf.owners.squads.is_empty());
+
+        let err = Config::read_from(&tests.join("missing.conf")).await.unwrap_err();
+        assert!(err.to_string().contains("missing.conf"));
+    }
 }
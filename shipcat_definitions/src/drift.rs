@@ -0,0 +1,184 @@
+use k8s_openapi::{api::apps::v1::Deployment, apimachinery::pkg::api::resource::Quantity};
+use std::collections::BTreeMap;
+
+use crate::{
+    structs::{parse_cpu, parse_memory, Resources},
+    Manifest, Result,
+};
+
+/// A property of a manifest that differs from its live Deployment
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Drift {
+    /// The drifting property, e.g. `resources.requests.cpu`
+    pub field: String,
+    /// Value in the manifest
+    pub declared: String,
+    /// Value in the cluster
+    pub live: String,
+}
+
+impl Drift {
+    fn new(field: &str, declared: String, live: String) -> Drift {
+        Drift {
+            field: field.into(),
+            declared,
+            live,
+        }
+    }
+}
+
+impl Manifest {
+    /// Compare a completed manifest against its live Deployment
+    ///
+    /// Covers replicas, as well as the image and resources of the main container.
+    /// With autoScaling, replicas only drift when outside of `minReplicas` and `maxReplicas`.
+    pub fn verify_against_cluster(&self, live: &Deployment) -> Result<Vec<Drift>> {
+        let mut drift = vec![];
+        let spec = match &live.spec {
+            Some(s) => s,
+            None => bail!("Deployment {} has no spec", self.name),
+        };
+        let replicas = spec.replicas.unwrap_or(1) as u32;
+        if let Some(hpa) = &self.autoScaling {
+            if replicas < hpa.minReplicas || replicas > hpa.maxReplicas {
+                let declared = format!("{}-{}", hpa.minReplicas, hpa.maxReplicas);
+                drift.push(Drift::new("replicas", declared, replicas.to_string()));
+            }
+        } else if let Some(declared) = self.replicaCount {
+            if declared != replicas {
+                drift.push(Drift::new("replicas", declared.to_string(), replicas.to_string()));
+            }
+        }
+
+        let containers = match &spec.template.spec {
+            Some(s) => &s.containers[..],
+            None => &[],
+        };
+        // the main container is named after the service
+        let main = containers.iter().find(|c| c.name == self.name);
+        let container = match main.or_else(|| containers.first()) {
+            Some(c) => c,
+            None => bail!("Deployment {} has no containers", self.name),
+        };
+        if let (Some(image), Some(version)) = (&self.image, &self.version) {
+            let declared = format!("{}:{}", image, version);
+            let live_image = container.image.clone().unwrap_or_default();
+            if declared != live_image {
+                drift.push(Drift::new("image", declared, live_image));
+            }
+        }
+        if let Some(res) = &self.resources {
+            let live_res = container.resources.clone().unwrap_or_default();
+            drift.extend(compare_resources(
+                "resources.requests",
+                Some(&res.requests),
+                live_res.requests.as_ref(),
+            )?);
+            drift.extend(compare_resources(
+                "resources.limits",
+                res.limits.as_ref(),
+                live_res.limits.as_ref(),
+            )?);
+        }
+        Ok(drift)
+    }
+}
+
+fn compare_resources(
+    prefix: &str,
+    declared: Option<&Resources<String>>,
+    live: Option<&BTreeMap<String, Quantity>>,
+) -> Result<Vec<Drift>> {
+    let mut drift = vec![];
+    let parsers: [(&str, fn(&str) -> Result<f64>); 2] = [("cpu", parse_cpu), ("memory", parse_memory)];
+    for (key, parse) in parsers.iter() {
+        let declared = declared.map(|d| if *key == "cpu" { &d.cpu } else { &d.memory });
+        let live = live.and_then(|l| l.get(*key)).map(|q| &q.0);
+        let differs = match (declared, live) {
+            (Some(d), Some(l)) => (parse(d)? - parse(l)?).abs() > 1e-6,
+            (None, None) => false,
+            _ => true,
+        };
+        if differs {
+            let none = String::from("none");
+            drift.push(Drift::new(
+                &format!("{}.{}", prefix, key),
+                declared.unwrap_or(&none).clone(),
+                live.unwrap_or(&none).clone(),
+            ));
+        }
+    }
+    Ok(drift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Drift;
+    use crate::{structs::autoscaling::AutoScaling, Manifest};
+    use k8s_openapi::api::apps::v1::Deployment;
+    use serde_json::json;
+
+    fn deployment(replicas: u32, image: &str, cpu: &str) -> Deployment {
+        serde_json::from_value(json!({
+            "spec": {
+                "replicas": replicas,
+                "selector": {},
+                "template": {
+                    "spec": {
+                        "containers": [{
+                            "name": "fake-svc",
+                            "image": image,
+                            "resources": {
+                                "requests": { "cpu": cpu, "memory": "1Gi" },
+                            }
+                        }]
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn drift() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.replicaCount = Some(2);
+        mf.image = Some("quay.io/babylon/fake-svc".into());
+        mf.version = Some("1.0.0".into());
+        let res = "requests: { cpu: 500m, memory: 1024Mi }\nlimits: { cpu: 1, memory: 1Gi }";
+        mf.resources = Some(serde_yaml::from_str(res).unwrap());
+
+        let live = deployment(2, "quay.io/babylon/fake-svc:1.0.0", "0.5");
+        let drift = mf.verify_against_cluster(&live).unwrap();
+        // only the limits are missing in the cluster
+        assert_eq!(drift.len(), 2);
+        assert_eq!(
+            drift[0],
+            Drift {
+                field: "resources.limits.cpu".into(),
+                declared: "1".into(),
+                live: "none".into(),
+            }
+        );
+        mf.resources.as_mut().unwrap().limits = None;
+        assert!(mf.verify_against_cluster(&live).unwrap().is_empty());
+
+        let live = deployment(5, "quay.io/babylon/fake-svc:1.1.0", "1");
+        let fields = mf
+            .verify_against_cluster(&live)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.field)
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec!["replicas", "image", "resources.requests.cpu"]);
+
+        // autoscaled services may run any number of replicas within bounds
+        mf.autoScaling = Some(AutoScaling {
+            minReplicas: 2,
+            maxReplicas: 6,
+            metrics: vec![],
+        });
+        let live = deployment(5, "quay.io/babylon/fake-svc:1.0.0", "500m");
+        assert!(mf.verify_against_cluster(&live).unwrap().is_empty());
+    }
+}
@@ -6,6 +6,8 @@
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate log;
 #[macro_use] extern crate maplit;
+#[macro_use] extern crate lazy_static;
+#[macro_use] extern crate schema_derive;
 
 #[macro_use] extern crate error_chain; // bail and error_chain macro
 error_chain! {
@@ -35,6 +37,30 @@ error_chain! {
             description("VAULT_TOKEN not specified")
             display("VAULT_TOKEN not specified")
         }
+        MissingVaultAppRole {
+            description("VAULT_ROLE_ID or VAULT_SECRET_ID not specified")
+            display("VAULT_ROLE_ID and VAULT_SECRET_ID must be specified for approle auth")
+        }
+        MissingVaultKubernetesRole {
+            description("VAULT_KUBERNETES_ROLE not specified")
+            display("VAULT_KUBERNETES_ROLE must be specified for kubernetes auth")
+        }
+        VaultKubernetesRole(role: String) {
+            description("vault kubernetes role rejected the service account")
+            display("Vault role {} does not exist or is not bound to this service account", &role)
+        }
+        VaultLogin(url: reqwest::Url) {
+            description("vault login failed")
+            display("could not log in to vault at '{}'", &url)
+        }
+        VaultTokenExpired {
+            description("vault token expired")
+            display("Vault token has expired and is not renewable - please log in to vault again")
+        }
+        VaultTokenRenewal {
+            description("vault token renewal failed")
+            display("Vault token is about to expire and could not be renewed - please log in to vault again")
+        }
         UnexpectedHttpStatus(status: reqwest::StatusCode) {
             description("unexpected HTTP status")
             display("unexpected HTTP status: {}", &status)
@@ -56,14 +82,26 @@ error_chain! {
             description("invalid template")
             display("template '{}' is invalid", tpl)
         }
+        TemplateRender(tpl: String) {
+            description("template failed to render")
+            display("template '{}' is invalid", tpl)
+        }
         InvalidManifest(svc: String) {
             description("manifest does not validate")
             display("manifest for {} does not validate", &svc)
         }
+        ServiceNameCollision(first: String, second: String) {
+            description("service names collide")
+            display("Services in {} and {} have colliding names", &first, &second)
+        }
         InvalidSecretForm(key: String) {
             description("secret is of incorrect form")
             display("secret '{}' not have the 'value' key", &key)
         }
+        VaultRead(url: reqwest::Url) {
+            description("could not read from vault")
+            display("could not access URL '{}'", &url)
+        }
         SecretNotAccessible(key: String) {
             description("secret could not be reached or accessed")
             display("secret '{}'", &key)
@@ -72,6 +110,126 @@ error_chain! {
             description("failed to build manifest")
             display("failed to build manifest for {} in {}", &service_name, &region_name)
         }
+        MissingRegion(region: String) {
+            description("region not defined")
+            display("Region {} is not defined in shipcat.conf", &region)
+        }
+        UnknownRegion(region: String, svc: String) {
+            description("service is not deployed in region")
+            display("Unsupported region {} for service {}", &region, &svc)
+        }
+        InvalidName(svc: String, reason: &'static str) {
+            description("invalid service name")
+            display("{}", reason)
+        }
+        MissingMetadata(svc: String) {
+            description("missing metadata")
+            display("Missing metadata for {}", &svc)
+        }
+        MissingResources(svc: String) {
+            description("missing resources")
+            display("Resources is mandatory")
+        }
+        IncompleteManifest(svc: String, reason: String) {
+            description("manifest is missing values set when it is completed")
+            display("{}", reason)
+        }
+        MissingRegions(svc: String) {
+            description("no regions specified")
+            display("No regions specified for {}", &svc)
+        }
+        MissingDestinationRuleHostRegex(region: String) {
+            description("region has no destinationRuleHostRegex")
+            display("Cannot use `destinationRules` in a region without a `destinationRuleHostRegex`")
+        }
+        InvalidGate(svc: String, reason: &'static str) {
+            description("invalid gate configuration")
+            display("{}", reason)
+        }
+        InvalidLabel(svc: String, label: String) {
+            description("label not allowed by config")
+            display("Service: {} using label {} not defined in config", &svc, &label)
+        }
+        InvalidReplicaCount(svc: String) {
+            description("invalid replica count")
+            display("Need replicaCount to be at least 1")
+        }
+        SecretTemplateConflict(key: String) {
+            description("secret templated with different values")
+            display("Secret {} can not be used in multiple templates with different values", &key)
+        }
+        SecretSourceConflict(key: String) {
+            description("secret both templated and in vault")
+            display("Secret {} can not be both templated and fetched from vault", &key)
+        }
+        SecretNotBase64(key: String) {
+            description("secret file is not base64 encoded")
+            display("Secret {} is not base64 encoded", &key)
+        }
+        MissingSecretFolder(path: String, expected: ::std::collections::BTreeSet<String>, cause: String) {
+            description("secret folder could not be listed")
+            display("Missing secret folder {} expected to contain {:?}: {}", &path, &expected, &cause)
+        }
+        MissingSecrets(path: String, missing: Vec<String>) {
+            description("secrets missing from vault")
+            display("Missing secrets: {:?} not found in vault {}", &missing, &path)
+        }
+        InvalidConfig(problems: Vec<String>) {
+            description("config does not validate")
+            display("shipcat.conf is invalid:\n - {}", problems.join("\n - "))
+        }
+        UnknownVaultRegion(name: String, key: String) {
+            description("secret refers to an undefined vault region")
+            display("Secret {} uses vault region {} which is not defined in vaultRegions", &key, &name)
+        }
+        DuplicateEnvKey(svc: String, first: String, second: String) {
+            description("env vars differ only by case")
+            display("Env vars {} and {} in {} differ only by case", &first, &second, &svc)
+        }
+        PortCollision(svc: String, port: u32, first: String, second: String) {
+            description("port used twice in a pod")
+            display("Port {} is claimed by both {} and {} in {}", port, &first, &second, &svc)
+        }
+        MountCollision(svc: String, collisions: String) {
+            description("mount paths overlap")
+            display("Mount paths collide in {}: {}", &svc, &collisions)
+        }
+        SecurityPolicyViolation(svc: String, reason: String) {
+            description("security context violates region policy")
+            display("{} violates the region security policy: {}", &svc, &reason)
+        }
+        UnknownResourceTier(svc: String, tier: String) {
+            description("resource tier not defined in config")
+            display("{} uses resource tier '{}' which is not in the config defaults", &svc, &tier)
+        }
+        InvalidResources(svc: String, reason: String) {
+            description("resources violate region policy")
+            display("{} has invalid resources: {}", &svc, &reason)
+        }
+        IncompleteOwnership(problems: Vec<String>) {
+            description("ownership metadata is incomplete")
+            display("metadata is missing ownership information:\n - {}", problems.join("\n - "))
+        }
+        InvalidPort(svc: String, claimant: String, port: u32) {
+            description("port out of range")
+            display("Port {} of {} in {} is outside the valid range 1-65535", port, &claimant, &svc)
+        }
+        EnvTooLarge(svc: String, size: usize, max: usize) {
+            description("env vars too large")
+            display("{} has {} bytes of env vars, more than the limit of {} - move large values into a ConfigMap or volume", &svc, size, max)
+        }
+        PolicyViolations(svc: String, reasons: Vec<String>) {
+            description("manifest violates organisational policies")
+            display("{} violates policies:\n - {}", &svc, reasons.join("\n - "))
+        }
+        DisabledDependency(svc: String, dependency: String, region: String) {
+            description("dependency not deployed in the region")
+            display("{} depends on {} which is disabled in {}", &svc, &dependency, &region)
+        }
+        ExternalWorkload(svc: String, fields: Vec<String>) {
+            description("external service declares workload fields")
+            display("{} is external but declares workload fields that have no effect: {}", &svc, fields.join(", "))
+        }
     }
 }
 
@@ -80,7 +238,10 @@ pub mod region;
 pub use crate::region::{Environment, KongConfig, ReconciliationMode, Region, VaultConfig, VersionScheme};
 /// Master config with cross-region data
 pub mod config;
-pub use crate::config::{Cluster, Config, ConfigFallback, ManifestDefaults, ShipcatConfig};
+pub use crate::config::{
+    Cluster, Config, ConfigFallback, ManifestDefaults, MetadataRequirements, ShipcatConfig,
+    TeamMetadataRequirements,
+};
 
 
 /// Structs for the manifest
@@ -99,6 +260,12 @@ pub mod teams;
 mod crds;
 pub use crate::crds::gen_all_crds;
 
+/// NetworkPolicy generation from the dependency graph
+pub mod netpol;
+
+/// PrometheusRule generation from service alerts
+pub mod alerts;
+
 /// Status objects
 pub mod status;
 pub use status::ManifestStatus;
@@ -110,6 +277,17 @@ pub use crate::states::{ConfigState, PrimaryWorkload};
 /// Computational helpers
 pub mod math;
 
+/// Differences between manifests and what runs in the cluster
+pub mod drift;
+
+/// Pluggable organisational policies checked during verification
+pub mod policy;
+pub use crate::policy::{Policy, Violation};
+
+/// Soft issues found during verification
+pub mod warnings;
+pub use crate::warnings::{Warning, WarningCode};
+
 /// A renderer of `tera` templates (jinja style)
 ///
 /// Used for small app configs that are inlined in the completed manifests.
@@ -120,3 +298,9 @@ pub mod vault;
 pub use crate::vault::Vault;
 
 pub mod deserializers;
+
+/// Deterministic yaml output
+pub mod canonical;
+
+/// JSON Schema generation for manifests
+pub mod schema;
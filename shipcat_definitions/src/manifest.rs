@@ -1,12 +1,16 @@
 use crate::vault::Vault;
 use kube_derive::CustomResource;
 use regex::Regex;
-use std::collections::{BTreeMap, BTreeSet};
+use semver::Version;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
 
-use super::Result;
+use super::{Error, ErrorKind, Result, Warning, WarningCode};
 use crate::{
     config::Config,
-    region::{Region, VaultConfig},
+    region::{Environment, Region, VaultConfig},
     states::{ManifestState, PrimaryWorkload},
     ManifestStatus,
 };
@@ -14,18 +18,22 @@ use crate::{
 // All structs come from the structs directory
 use super::structs::{
     autoscaling::AutoScaling,
+    features, hostnetwork,
     newrelic::Newrelic,
+    port::PortProtocol,
     security::DataHandling,
     sentry::Sentry,
     tolerations::Tolerations,
-    volume::{Volume, VolumeMount},
-    ConfigMap, Container, CronJob, Dependency, DestinationRule, EnvVars, EventStream, Gate, HealthCheck,
-    HostAlias, Kafka, Kong, LifeCycle, Metadata, NotificationMode, PersistentVolume, Port, Probe, Rbac,
-    ResourceRequirements, RollingUpdate, SecurityContext, VaultOpts, Worker,
+    volume::{self, Volume, VolumeMount},
+    AlertRule, ConfigMaps, Container, ContainerSecurityContext, CronJob, Dependency, DeploymentStrategy,
+    DestinationRule, EnvVars, EventStream, Gate, GracefulShutdown, HealthCheck, HostAlias, Kafka, Kong,
+    LifeCycle, Metadata, NotificationMode, PersistentVolume, Port, Probe, Rbac, ResourcePolicy,
+    ResourceRequirements, RollingUpdate, SecurityContext, ServiceAccount, StatefulSetOptions,
+    TopologySpreadConstraint, VaultOpts, Worker,
 };
 
 /// Main manifest, serializable from manifest.yml or the shipcat CRD.
-#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[kube(
     group = "babylontech.co.uk",
     kind = "ShipcatManifest",
@@ -73,6 +81,7 @@ pub struct Manifest {
     /// Service is external
     ///
     /// This cancels all validation and marks the manifest as a non-kube reference only.
+    /// Workload fields like `resources` or `volumes` are rejected, as they would have no effect.
     ///
     /// ```yaml
     /// external: true
@@ -195,6 +204,37 @@ pub struct Manifest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub securityContext: Option<SecurityContext>,
 
+    /// Security context for the main container
+    ///
+    /// Takes precedence over `securityContext` for the fields they share.
+    /// Regions with a `securityPolicy` may require some of these.
+    ///
+    /// ```yaml
+    /// containerSecurityContext:
+    ///   runAsNonRoot: true
+    ///   runAsUser: 1000
+    ///   readOnlyRootFilesystem: true
+    ///   capabilities:
+    ///     drop: ["ALL"]
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub containerSecurityContext: Option<ContainerSecurityContext>,
+
+    /// ServiceAccount the pods run as
+    ///
+    /// Needed to bind pods to a cloud identity, e.g. via IRSA or workload identity.
+    /// Without this, a ServiceAccount named after the service is used.
+    /// Regions can set a default in `defaults.serviceAccount`.
+    ///
+    /// ```yaml
+    /// serviceAccount:
+    ///   name: webapp
+    ///   annotations:
+    ///     eks.amazonaws.com/role-arn: arn:aws:iam::123456789012:role/webapp
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serviceAccount: Option<ServiceAccount>,
+
     /// Data sources and handling strategies
     ///
     /// An experimental abstraction around GDPR
@@ -231,6 +271,17 @@ pub struct Manifest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub resources: Option<ResourceRequirements<String>>,
 
+    /// Resource tier to use when `resources` are not set
+    ///
+    /// Refers to one of the `resourceTiers` in the config defaults.
+    /// Setting it in an environment or region file replaces the `resources` of the manifest.
+    ///
+    /// ```yaml
+    /// resourceTier: small
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resourceTier: Option<String>,
+
     /// Kubernetes replication count
     ///
     /// This is set on the `Deployment` object in kubernetes.
@@ -242,10 +293,24 @@ pub struct Manifest {
     #[serde(default)]
     pub replicaCount: Option<u32>,
 
+    /// Pause rollouts of the service
+    ///
+    /// The manifest is still rendered and validated, but the `Deployment` is
+    /// [paused](https://kubernetes.io/docs/concepts/workloads/controllers/deployment/#pausing-and-resuming-a-deployment)
+    /// so that changes to its pods are not rolled out. Unlike `disabled`, nothing is removed.
+    /// Usually set in a region override during maintenance.
+    ///
+    /// ```yaml
+    /// paused: true
+    /// ```
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub paused: bool,
+
     /// Environment variables to inject
     ///
     /// These have a few special convenience behaviours:
-    /// "IN_VAULT" values is replaced with value from vault/secret/folder/service/KEY
+    /// "IN_VAULT" values is replaced with value from vault/mount/folder/service/KEY
+    /// "IN_VAULT@name" values are read from the region's named vault `vaultRegions.name`
     /// One off `tera` templates are calculated with a limited template context
     ///
     /// IN_VAULT secrets will all be put in a single kubernetes `Secret` object.
@@ -274,7 +339,7 @@ pub struct Manifest {
     /// Kubernetes Secret Files to inject
     ///
     /// These have the same special "IN_VAULT" behavior as `Manifest::env`:
-    /// "IN_VAULT" values is replaced with value from vault/secret/folder/service/key
+    /// "IN_VAULT" values is replaced with value from vault/mount/folder/service/key
     ///
     /// Note the lowercase restriction on keys.
     /// All `secretFiles` are expected to be base64 in vault, and are placed into a
@@ -288,7 +353,7 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub secretFiles: BTreeMap<String, String>,
 
-    /// Config files to inline in a kubernetes `ConfigMap`
+    /// Config files to inline in kubernetes `ConfigMap`s
     ///
     /// These are read and templated by `tera` before they are passed to helm.
     /// A full `tera` context from `Manifest::make_template_context` is used.
@@ -301,8 +366,10 @@ pub struct Manifest {
     ///     dest: webhooks.json
     ///   - name: newrelic-java.yml.j2
     /// ```
+    ///
+    /// A list of ConfigMaps with distinct mount paths is also accepted.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub configs: Option<ConfigMap>,
+    pub configs: Option<ConfigMaps>,
 
     /// Vault options
     ///
@@ -332,7 +399,13 @@ pub struct Manifest {
     ///  - port: 6122
     ///    name: rpc
     ///  - port: 6125
+    ///  - port: 8125
+    ///    name: statsd
+    ///    protocol: UDP
     /// ```
+    ///
+    /// Names must be unique, and can not be `http` or `health-http` which the chart uses
+    /// for `httpPort` and `health.port`. A `hostPort` can also be set to expose a port on the node.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ports: Vec<Port>,
 
@@ -356,6 +429,17 @@ pub struct Manifest {
     ///   uri: /health
     ///   wait: 15
     /// ```
+    ///
+    /// Slow booting services should add a startup probe rather than a long `wait`:
+    ///
+    /// ```yaml
+    /// health:
+    ///   uri: /health
+    ///   startup:
+    ///     path: /health
+    ///     failureThreshold: 30
+    ///     periodSeconds: 10
+    /// ```
     #[serde(skip_serializing_if = "Option::is_none")]
     pub health: Option<HealthCheck>,
 
@@ -370,7 +454,10 @@ pub struct Manifest {
     /// - name: ask2
     /// - name: chatbot-reporting
     /// - name: clinical-knowledge
+    ///   port: 8080
     /// ```
+    ///
+    /// Generated network policies only allow egress to the `port` of a dependency when it is given.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub dependencies: Vec<Dependency>,
 
@@ -434,6 +521,27 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub sidecars: Vec<Container>,
 
+    /// Container with debugging tools for on-call use
+    ///
+    /// Only rendered next to the main container in debug mode,
+    /// i.e. `shipcat template --debug`, so it never ends up in normal deploys.
+    ///
+    /// ```yaml
+    /// debugContainer:
+    ///   name: debug
+    ///   image: nicolaka/netshoot
+    ///   version: v0.1
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debugContainer: Option<Container>,
+
+    /// Whether the manifest is rendered in debug mode
+    ///
+    /// This is an internal property that is exposed as an output only.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[cfg_attr(feature = "filesystem", serde(skip_deserializing))]
+    pub debug: bool,
+
     /// `readinessProbe` for kubernetes
     ///
     /// This configures the service's health check, which is used to gate rolling upgrades.
@@ -474,6 +582,26 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub lifecycle: Option<LifeCycle>,
 
+    /// Graceful deregistration from service discovery on shutdown
+    ///
+    /// Generates a `preStop` lifecycle hook that optionally hits an endpoint on the `httpPort`,
+    /// then sleeps for the delay, and sets `terminationGracePeriodSeconds`.
+    /// Cannot be combined with an explicit `lifecycle`.
+    ///
+    /// ```yaml
+    /// gracefulShutdown:
+    ///   deregisterDelaySeconds: 15
+    ///   deregisterEndpoint: /deregister
+    ///   terminationGracePeriodSeconds: 45
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gracefulShutdown: Option<GracefulShutdown>,
+
+    /// Termination grace period for the pods, from `gracefulShutdown`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "filesystem", serde(skip_deserializing))]
+    pub terminationGracePeriodSeconds: Option<u32>,
+
     /// Rolling update Deployment parameters
     ///
     /// These tweak the speed and care kubernetes uses when doing a rolling update.
@@ -488,6 +616,18 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub rollingUpdate: Option<RollingUpdate>,
 
+    /// Strategy used to replace old pods with new ones
+    ///
+    /// Either `RollingUpdate` (the kubernetes default) or `Recreate`.
+    /// Services with `ReadWriteOnce` persistent volumes or created claims must use `Recreate`,
+    /// as a new pod cannot mount the volume while an old pod holds it.
+    ///
+    /// ```yaml
+    /// strategy: Recreate
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<DeploymentStrategy>,
+
     /// `HorizontalPodAutoScaler` parameters for kubernetes
     ///
     /// Passed all parameters directly onto the `spec` of a kube HPA.
@@ -521,6 +661,22 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tolerations: Vec<Tolerations>,
 
+    /// Constraints for spreading `Pod`s evenly across topology domains
+    ///
+    /// Straight from [kubernetes topology spread constraints](https://kubernetes.io/docs/concepts/workloads/pods/pod-topology-spread-constraints/).
+    ///
+    /// ```yaml
+    /// topologySpread:
+    /// - maxSkew: 1
+    ///   topologyKey: topology.kubernetes.io/zone
+    ///   whenUnsatisfiable: ScheduleAnyway
+    ///   labelSelector:
+    ///     matchLabels:
+    ///       app: webapp
+    /// ```
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub topologySpread: Vec<TopologySpreadConstraint>,
+
     /// Host aliases to inject in /etc/hosts in every kubernetes `Pod`
     ///
     /// Straight from [kubernetes host aliases](https://kubernetes.io/docs/concepts/services-networking/add-entries-to-pod-etc-hosts-with-host-aliases/).
@@ -534,6 +690,48 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub hostAliases: Vec<HostAlias>,
 
+    /// Run the `Pod` in the network namespace of its node
+    ///
+    /// Only for low-level networking services. Ports are bound on the node itself,
+    /// so they can conflict with other pods, and the pod can see all node traffic.
+    /// Requires `dnsPolicy: ClusterFirstWithHostNet` (or `Default`).
+    ///
+    /// ```yaml
+    /// hostNetwork: true
+    /// dnsPolicy: ClusterFirstWithHostNet
+    /// ```
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hostNetwork: bool,
+
+    /// Acknowledge that the service intentionally works on its own
+    ///
+    /// Services that expose no port, and have no dependencies, cron jobs or sidecars
+    /// are warned about, as these are often left half-configured. This silences the warning.
+    ///
+    /// ```yaml
+    /// standalone: true
+    /// ```
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub standalone: bool,
+
+    /// [DNS policy](https://kubernetes.io/docs/concepts/services-networking/dns-pod-service/#pod-s-dns-policy) of the `Pod`
+    ///
+    /// One of `ClusterFirst`, `ClusterFirstWithHostNet`, or `Default`. Left to kubernetes if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnsPolicy: Option<String>,
+
+    /// Pod conditions that must be true before a `Pod` is considered ready
+    ///
+    /// For conditions set by custom controllers, e.g. when a warm-up has completed.
+    /// Rendered as [kubernetes readiness gates](https://kubernetes.io/docs/concepts/workloads/pods/pod-lifecycle/#pod-readiness-gate).
+    ///
+    /// ```yaml
+    /// readinessGates:
+    /// - example.com/warm-up-complete
+    /// ```
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub readinessGates: Vec<String>,
+
     /// `initContainer` list for every kubernetes `Pod`
     ///
     /// Allows database connectivity checks to be done as pre-boot init-step.
@@ -551,6 +749,7 @@ pub struct Manifest {
     /// Volumes that can be mounted in every kubernetes `Pod`
     ///
     /// Supports our subset of [kubernetes volumes](https://kubernetes.io/docs/concepts/storage/volumes/)
+    /// A `persistentVolumeClaim` with a `size` creates the claim as well.
     ///
     /// ```yaml
     /// volumes:
@@ -620,6 +819,14 @@ pub struct Manifest {
     ///   svc.k8s.io/aws-load-balancer-ssl-negotiation-policy: ELBSecurityPolicy-TLS-1-2-2018-01
     ///   helm.sh/resource-policy: keep
     /// ```
+    ///
+    /// Values are templated like `env`, so they can differ between regions:
+    ///
+    /// ```yaml
+    /// serviceAnnotations:
+    ///   svc.k8s.io/aws-load-balancer-ssl-cert: "{{ env.SSL_CERT_ARN }}"
+    ///   external-dns.alpha.kubernetes.io/hostname: "{{ service }}.{{ region }}.example.com"
+    /// ```
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub serviceAnnotations: BTreeMap<String, String>,
 
@@ -646,6 +853,19 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub labels: BTreeMap<String, String>,
 
+    /// Optional chart behaviour to turn on or off
+    ///
+    /// Available as `features` in templates. Keys must be listed for the chart
+    /// in the `chartFeatures` of `shipcat.conf`.
+    ///
+    /// ```yaml
+    /// features:
+    ///   ingress: true
+    ///   pdb: false
+    /// ```
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub features: BTreeMap<String, bool>,
+
     /// Kong config
     ///
     /// A mostly straight from API configuration struct for Kong
@@ -761,6 +981,22 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub sentry: Option<Sentry>,
 
+    /// Prometheus alerting rules for the service
+    ///
+    /// Generated into a `PrometheusRule` for the prometheus-operator.
+    ///
+    /// ```yaml
+    /// alerts:
+    /// - name: HighErrorRate
+    ///   expr: sum(rate(http_requests_total{app="webapp",code=~"5.."}[5m])) > 1
+    ///   for: 10m
+    ///   severity: critical
+    ///   annotations:
+    ///     summary: webapp returns errors
+    /// ```
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<AlertRule>,
+
     /// Slack upgrade notification settings
     ///
     /// ```yaml
@@ -797,6 +1033,28 @@ pub struct Manifest {
     #[cfg_attr(feature = "filesystem", serde(skip_deserializing))]
     pub namespace: String,
 
+    /// Name of the service's kubernetes resources in the region
+    ///
+    /// The `name` transformed by the region's `serviceNaming`.
+    /// Exposed from shipcat, but not overrideable.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    #[cfg_attr(feature = "filesystem", serde(skip_deserializing))]
+    pub canonicalName: String,
+
+    /// Whether the version is pinned by a region lockfile
+    ///
+    /// Exposed from shipcat, but not overrideable.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[cfg_attr(feature = "filesystem", serde(skip_deserializing))]
+    pub versionLocked: bool,
+
+    /// Env vars that were `IN_VAULT` in one manifest source, but set literally in an override
+    ///
+    /// Exposed from shipcat, but not overrideable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(feature = "filesystem", serde(skip_deserializing))]
+    pub plaintextSecrets: Vec<String>,
+
     /// Uid from the CRD injected into the helm chart
     ///
     /// This is required to inject into the charts due to
@@ -829,39 +1087,153 @@ pub struct Manifest {
 
     /// The default workload associated with a Manifest
     ///
-    /// Defaults to Deployment
+    /// Defaults to Deployment. Can also be a Statefulset or a Daemonset.
     ///
     /// ```yaml
     /// workload: Statefulset
     /// ```
     #[serde(default)]
     pub workload: PrimaryWorkload,
+
+    /// Settings for a Statefulset workload
+    ///
+    /// ```yaml
+    /// workload: Statefulset
+    /// statefulSet:
+    ///   podManagementPolicy: Parallel
+    ///   volumeClaimTemplates:
+    ///   - name: data
+    ///     mountPath: /var/lib/data
+    ///     size: 10Gi
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statefulSet: Option<StatefulSetOptions>,
 }
 
 impl Manifest {
     /// Set the version field
-    pub fn version(mut self, version: String) -> Self {
+    ///
+    /// Overrides any version pinned in the manifests.
+    pub fn with_version(mut self, version: String) -> Self {
         self.version = Some(version);
         self
     }
 
+    /// Override a single field, like `helm --set`
+    ///
+    /// For one-off experiments on generated values, without editing the manifests.
+    /// Supports `version`, `image`, `replicaCount` and `env.KEY` for env vars that are
+    /// already set, including secrets.
+    pub fn set_override(&mut self, path: &str, value: &str) -> Result<()> {
+        match path {
+            "version" => self.version = Some(value.to_string()),
+            "image" => self.image = Some(value.to_string()),
+            "replicaCount" => match value.parse() {
+                Ok(n) => self.replicaCount = Some(n),
+                Err(_) => bail!("replicaCount must be a number, got '{}'", value),
+            },
+            p if p.starts_with("env.") => {
+                let key = &p["env.".len()..];
+                if let Some(v) = self.env.plain.get_mut(key) {
+                    *v = value.to_string();
+                } else if self.env.secrets.contains(key) {
+                    self.secrets.insert(key.to_string(), value.to_string());
+                } else {
+                    bail!("Cannot override unset env var {} in {}", key, self.name);
+                }
+            }
+            _ => bail!(
+                "Cannot override {} - expected one of version, image, replicaCount or env.KEY",
+                path
+            ),
+        }
+        Ok(())
+    }
+
+    /// Verify that a version is a valid target for a rollback
+    ///
+    /// The version must follow the versioning scheme of the region,
+    /// and cannot be newer than a semver version pinned in the manifests.
+    pub fn verify_rollback_version(&self, version: &str, region: &Region) -> Result<()> {
+        region.versioningScheme.verify(version)?;
+        if let Some(current) = &self.version {
+            if let (Ok(cur), Ok(target)) = (Version::parse(current), Version::parse(version)) {
+                if target > cur {
+                    bail!(
+                        "Cannot roll back {} to {} which is newer than the pinned version {}",
+                        self.name,
+                        version,
+                        current
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Print manifest to stdout
-    pub fn print(&self) -> Result<()> {
-        println!("{}", serde_yaml::to_string(self)?);
+    ///
+    /// Secrets are redacted unless `unredacted` is set.
+    pub fn print(&self, unredacted: bool) -> Result<()> {
+        let out = if unredacted {
+            crate::canonical::to_yaml(self)?
+        } else {
+            crate::canonical::to_yaml(&self.redacted_clone())?
+        };
+        println!("{}", out);
         Ok(())
     }
 
+    /// Copy of the manifest with all secret values replaced by `***`
+    ///
+    /// Use this whenever a manifest is serialized for display or logging.
+    /// Only the rendering passed on to helm for deploys should need real secrets.
+    pub fn redacted_clone(&self) -> Manifest {
+        let mut mf = self.clone();
+        for v in mf.secrets.values_mut().chain(mf.secretFiles.values_mut()) {
+            *v = "***".to_string();
+        }
+        mf
+    }
+
     /// Verify the region for this manifest is one of its declared ones
     ///
     /// Assumes the manifest has been populated with `implicits`
     pub fn verify_region(&self) -> Result<&Self> {
         assert!(self.region != ""); // needs to have been set by implicits!
         if !self.regions.contains(&self.region.to_string()) {
-            bail!("Unsupported region {} for service {}", self.region, self.name);
+            bail!(ErrorKind::UnknownRegion(self.region.clone(), self.name.clone()));
         };
         Ok(self)
     }
 
+    /// Whether the service should be deployed in a region
+    ///
+    /// Requires the region to be listed in `regions`, and the service not to be `disabled`.
+    pub fn is_deployable_in(&self, region: &str) -> bool {
+        !self.disabled && self.regions.iter().any(|r| r == region)
+    }
+
+    /// Name of the service's kubernetes resources
+    ///
+    /// This is the `canonicalName` when it is set, and the `name` otherwise.
+    pub fn canonical_name(&self) -> &str {
+        if self.canonicalName.is_empty() {
+            &self.name
+        } else {
+            &self.canonicalName
+        }
+    }
+
+    /// Verify the service name, and the canonical name, are usable in kube dns
+    pub fn verify_name(&self) -> Result<()> {
+        verify_dns_name(&self.name)?;
+        if self.canonical_name() != self.name {
+            verify_dns_name(self.canonical_name())?;
+        }
+        Ok(())
+    }
+
     /// Verifies the "destinationRules" manifest entries if they are configured
     ///
     /// It is erroneous to define destination rules without configuring the corresponding region's
@@ -873,7 +1245,7 @@ impl Manifest {
                     dr.verify(destinationRuleHostRegex)?;
                 }
             } else {
-                bail!("Cannot use `destinationRules` in a region without a `destinationRuleHostRegex`")
+                bail!(ErrorKind::MissingDestinationRuleHostRegex(region.name.clone()))
             }
         }
         Ok(())
@@ -881,18 +1253,20 @@ impl Manifest {
 
     /// Verify assumptions about manifest
     ///
-    /// Assumes the manifest has been populated with `implicits`
+    /// Assumes the manifest has been populated with `implicits`.
+    /// Soft issues are only logged, use `verify_collecting` to get them back.
     pub fn verify(&self, conf: &Config, region: &Region) -> Result<()> {
+        self.verify_collecting(conf, region).map(|_| ())
+    }
+
+    /// Verify assumptions about manifest, and collect soft issues
+    ///
+    /// Errors fail the verification straight away, whereas warnings are logged
+    /// and returned so callers can surface them.
+    pub fn verify_collecting(&self, conf: &Config, region: &Region) -> Result<Vec<Warning>> {
+        let mut warnings = vec![];
         self.verify_region()?;
-        // limit to 50 characters, alphanumeric, dashes for sanity.
-        // 63 is kube dns limit (13 char suffix buffer)
-        let re = Regex::new(r"^[0-9a-z\-]{1,50}$").unwrap();
-        if !re.is_match(&self.name) {
-            bail!("Please use a short, lower case service names with dashes");
-        }
-        if self.name.ends_with('-') || self.name.starts_with('-') {
-            bail!("Please use dashes to separate words only");
-        }
+        self.verify_name()?;
 
         self.verify_destination_rules(region)?;
 
@@ -902,14 +1276,23 @@ impl Manifest {
         }
 
         if let Some(ref md) = self.metadata {
-            md.verify(&conf.owners, &conf.allowedCustomMetadata)?;
+            md.verify(
+                &conf.owners,
+                &conf.allowedCustomMetadata,
+                &conf.metadataRequirements,
+            )?;
+            md.verify_cost_center(&conf.costCenters)?;
         } else {
-            bail!("Missing metadata for {}", self.name);
+            bail!(ErrorKind::MissingMetadata(self.name.clone()));
         }
 
         if self.external {
-            warn!("Ignoring most validation for kube-external service {}", self.name);
-            return Ok(());
+            self.verify_external(conf.defaults.replicaCount)?;
+            warnings.push(Warning::new(
+                WarningCode::ExternalService,
+                format!("Ignoring most validation for kube-external service {}", self.name),
+            ));
+            return Ok(warnings);
         }
 
         if let Some(v) = &self.version {
@@ -919,20 +1302,33 @@ impl Manifest {
         // TODO [DIP-499]: Separate gate/kong params + adjust the checks
         if let Some(g) = &self.gate {
             if self.kongApis.is_empty() {
-                bail!("Can't have a `gate` configuration without a `kong` one");
+                bail!(ErrorKind::InvalidGate(
+                    self.name.clone(),
+                    "Can't have a `gate` configuration without a `kong` one"
+                ));
             }
             if g.public != self.publiclyAccessible {
-                bail!("[Migration plan] `publiclyAccessible` and `gate.public` must be equal");
+                bail!(ErrorKind::InvalidGate(
+                    self.name.clone(),
+                    "[Migration plan] `publiclyAccessible` and `gate.public` must be equal"
+                ));
             }
         }
 
         // run the `Verify` trait on all imported structs
         // mandatory structs first
+        self.verify_resource_tier(&conf.defaults.resourceTiers)?;
         if let Some(ref r) = self.resources {
             r.verify()?;
         } else {
-            bail!("Resources is mandatory");
+            bail!(ErrorKind::MissingResources(self.name.clone()));
         }
+        warnings.extend(self.verify_resources(&region.resourcePolicy)?);
+        warnings.extend(
+            region
+                .envPolicy
+                .verify(&self.name, self.env_size(), self.env.count())?,
+        );
 
         // optional/vectorised entries
         for d in &self.dependencies {
@@ -942,73 +1338,544 @@ impl Manifest {
         for ha in &self.hostAliases {
             ha.verify()?;
         }
+        let allowed_plugins = region.kong.as_ref().and_then(|k| k.allowed_plugins.as_ref());
+        for k in &self.kongApis {
+            k.verify(allowed_plugins)?;
+        }
+        self.verify_readiness_gates()?;
+        if let Some(gs) = &self.gracefulShutdown {
+            gs.verify(self.httpPort)?;
+        }
         for tl in &self.tolerations {
             tl.verify()?;
         }
+        for tsc in &self.topologySpread {
+            tsc.verify()?;
+        }
         for r in &self.rbac {
             r.verify()?;
         }
         for pv in &self.persistentVolumes {
             pv.verify()?;
         }
-        if let Some(ref cmap) = self.configs {
-            cmap.verify()?;
+        for v in &self.volumes {
+            v.verify()?;
         }
-        for k in self.labels.keys() {
-            if !conf.allowedLabels.contains(k) {
-                bail!("Service: {} using label {} not defined in config", self.name, k)
-            }
+        if let Some(ref cmaps) = self.configs {
+            cmaps.verify()?;
+        }
+        if let Some(ref sa) = self.serviceAccount {
+            sa.verify()?;
         }
+        self.verify_labels(&conf.allowedLabels)?;
+        let chart = self.chart.clone().unwrap_or_default();
+        features::verify(&self.name, &chart, &self.features, &conf.chartFeatures)?;
         for es in &self.eventStreams {
             es.verify()?;
         }
+        AlertRule::verify_all(&self.name, &self.alerts)?;
+        CronJob::verify_names(&self.name, &self.cronJobs)?;
         // misc minor properties
         if self.replicaCount.unwrap() == 0 {
-            bail!("Need replicaCount to be at least 1");
+            bail!(ErrorKind::InvalidReplicaCount(self.name.clone()));
         }
         if let Some(ref ru) = &self.rollingUpdate {
             ru.verify(self.replicaCount.unwrap())?;
         }
-
+        self.strategy.clone().unwrap_or_default().verify(
+            &self.name,
+            self.rollingUpdate.as_ref(),
+            &self.persistentVolumes,
+            &self.volumes,
+        )?;
+        StatefulSetOptions::verify_workload(
+            &self.name,
+            &self.workload,
+            self.statefulSet.as_ref(),
+            self.autoScaling.is_some(),
+        )?;
+
+        self.verify_env_key_case()?;
+        warnings.extend(self.verify_ports()?);
+        volume::verify_mount_paths(&self.name, &self.mount_paths())?;
+        region.securityPolicy.verify(
+            &self.name,
+            self.securityContext.as_ref(),
+            self.containerSecurityContext.as_ref(),
+        )?;
+        region.securityPolicy.verify_pinned_images(
+            &self.name,
+            self.version.as_ref().map(String::as_str),
+            &self.initContainers,
+            &self.sidecars,
+        )?;
         self.env.verify()?;
+        warnings.extend(
+            region
+                .securityPolicy
+                .verify_plaintext_secrets(&self.name, &self.plaintextSecrets)?,
+        );
+        if let Some(ref detect) = region.envPolicy.detectSecrets {
+            let containers = self.sidecars.iter().chain(&self.initContainers);
+            let envs = std::iter::once(&self.env)
+                .chain(containers.map(|c| &c.env))
+                .chain(self.workers.iter().map(|w| &w.container.env))
+                .chain(self.cronJobs.iter().map(|c| &c.container.env));
+            for env in envs {
+                warnings.extend(detect.verify(&self.name, &env.plain)?);
+            }
+        }
+        self.verify_vault_regions(region)?;
 
         // internal errors - implicits set these!
+        let incomplete = |reason: String| ErrorKind::IncompleteManifest(self.name.clone(), reason);
         if self.image.is_none() {
-            bail!("Image should be set at this point")
+            bail!(incomplete("Image should be set at this point".into()))
         }
         if self.imageSize.is_none() {
-            bail!("imageSize must be set at this point");
+            bail!(incomplete("imageSize must be set at this point".into()));
         }
         if self.chart.is_none() {
-            bail!("chart must be set at this point");
+            bail!(incomplete("chart must be set at this point".into()));
         }
         if self.namespace == "" {
-            bail!("namespace must be set at this point");
+            bail!(incomplete("namespace must be set at this point".into()));
         }
         if self.regions.is_empty() {
-            bail!("No regions specified for {}", self.name);
+            bail!(ErrorKind::MissingRegions(self.name.clone()));
         }
         if self.environment == "" {
-            bail!("Service {} ended up with an empty environment", self.name);
-        }
-        if self.namespace == "" {
-            bail!("Service {} ended up with an empty namespace", self.name);
+            bail!(incomplete(format!("Service {} ended up with an empty environment", self.name)));
         }
 
         // health check
         if self.health.is_none() && self.readinessProbe.is_none() {
-            warn!("{} does not set a health check", self.name)
+            warnings.push(Warning::new(
+                WarningCode::MissingHealthCheck,
+                format!("{} does not set a health check", self.name),
+            ));
+        }
+        if region.requireVersionLock && !self.versionLocked {
+            warnings.push(Warning::new(
+                WarningCode::UnlockedVersion,
+                format!("{} has no version lockfile for {}", self.name, region.name),
+            ));
+        }
+        if self.paused {
+            warnings.push(Warning::new(
+                WarningCode::Paused,
+                format!(
+                    "{} is PAUSED in {} - changes will not be rolled out",
+                    self.name, region.name
+                ),
+            ));
         }
+        warnings.extend(hostnetwork::verify(
+            &self.name,
+            self.hostNetwork,
+            self.dnsPolicy.as_ref().map(String::as_str),
+        )?);
+        if let Some(hc) = &self.health {
+            hc.verify()?;
+            if hc.needs_startup_probe() {
+                warnings.push(Warning::new(
+                    WarningCode::SlowStartup,
+                    format!(
+                        "{} waits {}s before its health check - consider using health.startup instead",
+                        self.name, hc.wait
+                    ),
+                ));
+            }
+        }
+        if self.httpPort.is_none() && !self.kongApis.is_empty() {
+            warnings.push(Warning::new(
+                WarningCode::MissingHttpPort,
+                format!("{} is exposed through kong without an httpPort", self.name),
+            ));
+        }
+        warnings.extend(self.verify_standalone());
+
+        // organisational policies last
+        crate::policy::check_registered(self, conf)?;
+
+        Ok(warnings)
+    }
 
+    /// Total size in bytes of the environment of the main container
+    ///
+    /// Counts every `KEY=value` pair with its separator and terminator, including secrets.
+    pub fn env_size(&self) -> usize {
+        let plain = self.env.plain.iter();
+        let secrets = self
+            .env
+            .secrets
+            .iter()
+            .filter_map(|k| self.secrets.get(k).map(|v| (k, v)));
+        plain.chain(secrets).map(|(k, v)| k.len() + v.len() + 2).sum()
+    }
+
+    /// Verify that no two env keys differ only by case
+    ///
+    /// Considers the merged plain env, the vault backed and external env names and resolved secrets.
+    /// Container runtimes and some shells treat these case-insensitively.
+    pub fn verify_env_key_case(&self) -> Result<()> {
+        let keys = self
+            .env
+            .plain
+            .keys()
+            .chain(self.env.secrets.iter())
+            .chain(self.env.external.iter())
+            .chain(self.secrets.keys())
+            .collect::<BTreeSet<_>>();
+        let mut seen: BTreeMap<String, &String> = BTreeMap::new();
+        for k in keys {
+            if let Some(prev) = seen.insert(k.to_uppercase(), k) {
+                bail!(ErrorKind::DuplicateEnvKey(
+                    self.name.clone(),
+                    prev.clone(),
+                    k.clone()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Warn about services that seemingly do nothing useful
+    ///
+    /// A service without ports, dependencies, cron jobs or sidecars is usually half-configured,
+    /// unless it is acknowledged as `standalone`.
+    fn verify_standalone(&self) -> Option<Warning> {
+        let isolated = self.httpPort.is_none()
+            && self.ports.is_empty()
+            && self.dependencies.is_empty()
+            && self.cronJobs.is_empty()
+            && self.sidecars.is_empty();
+        if !isolated || self.standalone {
+            return None;
+        }
+        Some(Warning::new(
+            WarningCode::Standalone,
+            format!(
+                "{} exposes no port and has no dependencies, cron jobs or sidecars - set standalone: true if this is intended",
+                self.name
+            ),
+        ))
+    }
+
+    /// Verify that the ports opened in the main pod are valid and distinct
+    ///
+    /// Covers `httpPort`, a separate health check port, `ports` and the ports of all `sidecars`.
+    /// Ports below 1024 only produce a warning, as most images need extra capabilities to bind them.
+    pub fn verify_ports(&self) -> Result<Vec<Warning>> {
+        let mut warnings = vec![];
+        let mut claims = vec![];
+        // names of the main container's ports, including those the chart adds
+        let mut names = vec![];
+        if let Some(p) = self.httpPort {
+            claims.push(("httpPort".to_string(), p, PortProtocol::Tcp));
+            names.push("http");
+        }
+        if let Some(p) = self.health.as_ref().and_then(|h| h.port) {
+            // the chart reuses the http port when these are equal
+            if Some(p) != self.httpPort {
+                claims.push(("health.port".to_string(), p, PortProtocol::Tcp));
+                names.push("health-http");
+            }
+        }
+        for p in &self.ports {
+            if names.contains(&p.name.as_str()) {
+                bail!("Port name {} is used more than once in {}", p.name, self.name);
+            }
+            names.push(&p.name);
+            claims.push((format!("port {}", p.name), p.port, p.protocol));
+            if let Some(hp) = p.host_port {
+                claims.push((format!("port {} hostPort", p.name), hp, p.protocol));
+            }
+        }
+        for s in &self.sidecars {
+            for p in &s.ports {
+                claims.push((format!("sidecar {} port {}", s.name, p.name), p.port, p.protocol));
+            }
+        }
+        // the same port number can be used once per protocol
+        let mut seen: BTreeMap<(u32, PortProtocol), String> = BTreeMap::new();
+        for (claimant, port, protocol) in claims {
+            if port == 0 || port > 65535 {
+                bail!(ErrorKind::InvalidPort(self.name.clone(), claimant, port));
+            }
+            if port < 1024 {
+                warnings.push(Warning::new(
+                    WarningCode::PrivilegedPort,
+                    format!("{} uses privileged port {} for {}", self.name, port, claimant),
+                ));
+            }
+            if let Some(prev) = seen.get(&(port, protocol)) {
+                bail!(ErrorKind::PortCollision(
+                    self.name.clone(),
+                    port,
+                    prev.clone(),
+                    claimant
+                ));
+            }
+            seen.insert((port, protocol), claimant);
+        }
+        Ok(warnings)
+    }
+
+    /// Verify that none of the dependencies are disabled in the region
+    ///
+    /// Takes the names of the dependencies that are not deployable in the region,
+    /// as this requires their manifests.
+    /// Fails if the region sets `forbidDisabledDependencies`, and warns otherwise.
+    pub fn verify_dependencies_enabled(&self, region: &Region, disabled: &[String]) -> Result<Vec<Warning>> {
+        let mut warnings = vec![];
+        for d in self.dependencies.iter().filter(|d| disabled.contains(&d.name)) {
+            let err = ErrorKind::DisabledDependency(self.name.clone(), d.name.clone(), region.name.clone());
+            if region.forbidDisabledDependencies {
+                bail!(err);
+            }
+            warnings.push(Warning::new(WarningCode::DisabledDependency, err.to_string()));
+        }
+        Ok(warnings)
+    }
+
+    /// Every path mounted in the main container, with where it comes from
+    ///
+    /// Config files are mounted individually under `configs.mount`.
+    pub fn mount_paths(&self) -> Vec<(String, String)> {
+        let mut paths = vec![];
+        if let Some(cmaps) = &self.configs {
+            for cmap in cmaps.iter() {
+                let mount = cmap.mount.trim_end_matches('/');
+                for f in &cmap.files {
+                    paths.push((format!("config {}", f.name), format!("{}/{}", mount, f.dest)));
+                }
+            }
+        }
+        for vm in &self.volumeMounts {
+            paths.push((format!("volumeMount {}", vm.name), vm.mountPath.clone()));
+        }
+        for pv in &self.persistentVolumes {
+            paths.push((format!("persistentVolume {}", pv.name), pv.mountPath.clone()));
+        }
+        if let Some(ss) = &self.statefulSet {
+            for vct in &ss.volumeClaimTemplates {
+                paths.push((format!("volumeClaimTemplate {}", vct.name), vct.mountPath.clone()));
+            }
+        }
+        paths
+    }
+
+    /// Verify that readiness gates are valid pod condition types
+    ///
+    /// Condition types are qualified names, optionally prefixed by a DNS subdomain.
+    pub fn verify_readiness_gates(&self) -> Result<()> {
+        for gate in &self.readinessGates {
+            if !is_qualified_name(gate) {
+                bail!(
+                    "readinessGate '{}' in {} is not a valid pod condition type",
+                    gate,
+                    self.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Labels recommended by kubernetes, derived from the manifest
+    ///
+    /// See [common labels](https://kubernetes.io/docs/concepts/overview/working-with-objects/common-labels/).
+    /// The version is left out if it is not a valid label value.
+    /// The cost center is added as well, for cost allocation tags.
+    pub fn recommended_labels(&self) -> BTreeMap<String, String> {
+        let mut labels = BTreeMap::new();
+        labels.insert(format!("{}name", RECOMMENDED_LABEL_PREFIX), self.name.clone());
+        labels.insert(
+            format!("{}managed-by", RECOMMENDED_LABEL_PREFIX),
+            "shipcat".into(),
+        );
+        if let Some(v) = self.version.as_ref().filter(|v| is_label_value(v)) {
+            labels.insert(format!("{}version", RECOMMENDED_LABEL_PREFIX), v.clone());
+        }
+        if let Some(ctx) = self.metadata.as_ref().and_then(|md| md.context.as_ref()) {
+            labels.insert(format!("{}part-of", RECOMMENDED_LABEL_PREFIX), ctx.name.clone());
+        }
+        if let Some(cc) = self.metadata.as_ref().and_then(|md| md.costCenter.as_ref()) {
+            labels.insert(COST_CENTER_LABEL.into(), cc.clone());
+        }
+        labels
+    }
+
+    /// Add the recommended labels, keeping any that were set explicitly
+    pub fn add_recommended_labels(&mut self) {
+        let mut labels = self.recommended_labels();
+        labels.append(&mut self.labels);
+        self.labels = labels;
+    }
+
+    /// Verify labels are allowed by the config and valid in kubernetes
+    ///
+    /// Recommended `app.kubernetes.io/` labels and the cost center label are always allowed.
+    pub fn verify_labels(&self, allowed: &[String]) -> Result<()> {
+        for (k, v) in &self.labels {
+            if !k.starts_with(RECOMMENDED_LABEL_PREFIX) && k != COST_CENTER_LABEL && !allowed.contains(k) {
+                bail!(ErrorKind::InvalidLabel(self.name.clone(), k.clone()))
+            }
+            if !is_qualified_name(k) {
+                bail!(
+                    "Label key '{}' in {} is not a valid kubernetes label",
+                    k,
+                    self.name
+                );
+            }
+            if !is_label_value(v) {
+                bail!(
+                    "Label {} in {} has invalid value '{}' - use at most 63 alphanumerics, dashes, underscores and dots",
+                    k,
+                    self.name,
+                    v
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the debug container in templates
+    ///
+    /// Debug tooling should not normally run in prod, so this warns when used there.
+    pub fn enable_debug(&mut self, region: &Region) -> Result<()> {
+        match &self.debugContainer {
+            None => bail!("{} has no debugContainer to render", self.name),
+            Some(c) if c.image.is_none() => bail!("debugContainer of {} needs an image", self.name),
+            Some(_) => {}
+        }
+        if region.environment == Environment::Prod {
+            warn!("!!! Rendering the debug container of {} in prod region {} !!!", self.name, region.name);
+        }
+        self.debug = true;
+        Ok(())
+    }
+
+    /// Compare the size of the service against itself in a region of a lower tier
+    ///
+    /// Fewer replicas or smaller resource requests than in e.g. staging
+    /// usually mean a promotion was missed. Replicas are not compared with autoscaling.
+    pub fn verify_sizing_against(&self, lower: &Manifest) -> Result<Vec<Warning>> {
+        let mut smaller = vec![];
+        if self.autoScaling.is_none() && lower.autoScaling.is_none() {
+            let (ours, theirs) = (self.replicaCount.unwrap_or(1), lower.replicaCount.unwrap_or(1));
+            if ours < theirs {
+                smaller.push(("replicaCount", ours.to_string(), theirs.to_string()));
+            }
+        }
+        if let (Some(ours), Some(theirs)) = (&self.resources, &lower.resources) {
+            smaller.extend(ours.smaller_requests(theirs)?);
+        }
+        Ok(smaller
+            .into_iter()
+            .map(|(field, ours, theirs)| {
+                Warning::new(
+                    WarningCode::SmallerThanLowerTier,
+                    format!(
+                        "{} has {} {} in {}, less than {} in {}",
+                        self.name, field, ours, self.region, theirs, lower.region
+                    ),
+                )
+            })
+            .collect())
+    }
+
+    /// Verify that an external service does not declare workload fields
+    ///
+    /// Nothing is deployed for external services, so these are usually left over from
+    /// a copy-paste, or a real workload was marked `external` by mistake.
+    /// The `replicaCount` every service gets from the config defaults is allowed.
+    pub fn verify_external(&self, default_replicas: u32) -> Result<()> {
+        let mut fields = vec![];
+        let mut check = |set: bool, name: &str| {
+            if set {
+                fields.push(name.to_string());
+            }
+        };
+        check(self.resources.is_some(), "resources");
+        check(self.resourceTier.is_some(), "resourceTier");
+        check(self.replicaCount.map_or(false, |r| r != default_replicas), "replicaCount");
+        check(self.autoScaling.is_some(), "autoScaling");
+        check(!self.command.is_empty(), "command");
+        check(self.health.is_some(), "health");
+        check(self.readinessProbe.is_some(), "readinessProbe");
+        check(self.livenessProbe.is_some(), "livenessProbe");
+        check(self.lifecycle.is_some(), "lifecycle");
+        check(self.rollingUpdate.is_some(), "rollingUpdate");
+        check(!self.volumes.is_empty(), "volumes");
+        check(!self.volumeMounts.is_empty(), "volumeMounts");
+        check(!self.persistentVolumes.is_empty(), "persistentVolumes");
+        check(!self.sidecars.is_empty(), "sidecars");
+        check(!self.initContainers.is_empty(), "initContainers");
+        check(!self.workers.is_empty(), "workers");
+        check(!self.cronJobs.is_empty(), "cronJobs");
+        if !fields.is_empty() {
+            bail!(ErrorKind::ExternalWorkload(self.name.clone(), fields));
+        }
+        Ok(())
+    }
+
+    /// Verify that a referenced resource tier is defined in the config
+    pub fn verify_resource_tier(&self, tiers: &BTreeMap<String, ResourceRequirements<String>>) -> Result<()> {
+        if let Some(tier) = &self.resourceTier {
+            if !tiers.contains_key(tier) {
+                bail!(ErrorKind::UnknownResourceTier(self.name.clone(), tier.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify resources of all containers against the region's resource policy
+    pub fn verify_resources(&self, policy: &ResourcePolicy) -> Result<Vec<Warning>> {
+        let mut warnings = vec![];
+        if let Some(r) = &self.resources {
+            warnings.extend(policy.verify(&self.name, &self.name, r)?);
+        }
+        let containers = self
+            .sidecars
+            .iter()
+            .chain(self.workers.iter().map(|w| &w.container))
+            .chain(self.cronJobs.iter().map(|c| &c.container));
+        for c in containers {
+            if let Some(r) = &c.resources {
+                warnings.extend(policy.verify(&self.name, &c.name, r)?);
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Verify that `IN_VAULT@name` placeholders refer to vaults defined in the region
+    pub fn verify_vault_regions(&self, region: &Region) -> Result<()> {
+        let mut envs = vec![&self.env];
+        envs.extend(self.sidecars.iter().map(|s| &s.env));
+        envs.extend(self.workers.iter().map(|w| &w.container.env));
+        envs.extend(self.cronJobs.iter().map(|c| &c.container.env));
+        envs.extend(self.initContainers.iter().map(|i| &i.env));
+        let placeholders = envs
+            .into_iter()
+            .flat_map(|e| e.plain.iter())
+            .chain(self.secretFiles.iter());
+        for (k, v) in placeholders {
+            if let Some(Some(name)) = EnvVars::vault_placeholder(v) {
+                if !region.vaultRegions.contains_key(&name) {
+                    bail!(ErrorKind::UnknownVaultRegion(name, k.clone()));
+                }
+            }
+        }
         Ok(())
     }
 
     fn get_vault_path(&self, vc: &VaultConfig) -> String {
         // some services use keys from other services
         let (svc, reg) = if let Some(ref vopts) = self.vault {
-            (vopts.name.clone(), vc.folder.clone())
+            (vopts.name.clone(), vc.path())
         } else {
-            (self.name.clone(), vc.folder.clone())
+            (self.name.clone(), vc.path())
         };
         format!("{}/{}", reg, svc)
     }
@@ -1035,55 +1902,118 @@ impl Manifest {
     /// Populate placeholder fields with secrets from vault
     ///
     /// This will use the HTTP api of Vault using the configuration parameters
-    /// in the `Config`.
-    pub async fn secrets(&mut self, client: &Vault, vc: &VaultConfig) -> Result<()> {
-        let pth = self.get_vault_path(vc);
+    /// in the `Config`. Placeholders of the form `IN_VAULT@name` are read from
+    /// the named vault in `Region::vaultRegions` instead of the region's own vault.
+    pub async fn secrets(&mut self, client: &Vault, reg: &Region) -> Result<()> {
+        let pth = self.get_vault_path(&reg.vault);
         debug!("Injecting secrets from vault {} ({:?})", pth, client.mode());
 
-        let mut vault_secrets = BTreeSet::new();
+        let mut vault_secrets = BTreeMap::new();
         let mut template_secrets = BTreeMap::new();
         for e in &mut self.get_env_vars() {
-            for k in e.vault_secrets() {
-                vault_secrets.insert(k.to_string());
+            for (k, vault_region) in e.vault_secrets() {
+                vault_secrets.insert(k, vault_region);
             }
             for (k, v) in e.template_secrets() {
                 let original = template_secrets.insert(k.to_string(), v.to_string());
                 if original.iter().any(|x| x == &v) {
-                    bail!(
-                        "Secret {} can not be used in multiple templates with different values",
-                        k
-                    );
+                    bail!(ErrorKind::SecretTemplateConflict(k.to_string()));
                 }
             }
         }
 
-        let template_keys = template_secrets.keys().map(|x| x.to_string()).collect();
-        if let Some(k) = vault_secrets.intersection(&template_keys).next() {
-            bail!("Secret {} can not be both templated and fetched from vault", k);
+        if let Some(k) = vault_secrets.keys().find(|k| template_secrets.contains_key(*k)) {
+            bail!(ErrorKind::SecretSourceConflict(k.to_string()));
         }
 
-        // Lookup values for each secret in vault.
-        for k in vault_secrets {
-            let vkey = format!("{}/{}", pth, k);
-            self.secrets.insert(k.to_string(), client.read(&vkey).await?);
+        // Lookup values for each secret in vault, with one client per named vault
+        let mut clients = BTreeMap::new();
+        for (k, vault_region) in vault_secrets {
+            let (v, vkey) = self.resolve_vault_key(client, &mut clients, reg, &k, vault_region)?;
+            self.secrets.insert(k.to_string(), v.read(&vkey).await?);
         }
 
         self.secrets.append(&mut template_secrets);
 
         // do the same for secret secrets
-        for (k, v) in &mut self.secretFiles {
-            if v == "IN_VAULT" {
-                let vkey = format!("{}/{}", pth, k);
+        let mut secret_files = std::mem::replace(&mut self.secretFiles, BTreeMap::new());
+        for (k, v) in &mut secret_files {
+            if let Some(vault_region) = EnvVars::vault_placeholder(v) {
+                let (client, vkey) = self.resolve_vault_key(client, &mut clients, reg, k, vault_region)?;
                 *v = client.read(&vkey).await?;
             }
             // sanity check; secretFiles are assumed base64 verify we can decode
             if base64::decode(v).is_err() {
-                bail!("Secret {} is not base64 encoded", k);
+                bail!(ErrorKind::SecretNotBase64(k.to_string()));
             }
         }
+        self.secretFiles = secret_files;
         Ok(())
     }
 
+    /// The fully resolved env of the main container in a region
+    ///
+    /// Templates evars and reads secrets like a completed manifest would,
+    /// but skips the remaining work of templating configs.
+    /// With `mask`, secret values are replaced by asterisks, e.g. for documentation.
+    pub async fn env_for_region(
+        mut self,
+        reg: &Region,
+        vault: &Vault,
+        mask: bool,
+    ) -> Result<BTreeMap<String, String>> {
+        if !self.is_base() {
+            bail!(
+                "Can only resolve env for a Base manifest, {} is {:?}",
+                self.name,
+                self.state
+            );
+        }
+        self.template_evars(reg)?;
+        self.secrets(vault, reg).await?;
+        let mut env = self.env.plain.clone();
+        for k in &self.env.secrets {
+            let v = if mask {
+                "************".to_string()
+            } else {
+                self.secrets[k].clone()
+            };
+            env.insert(k.clone(), v);
+        }
+        Ok(env)
+    }
+
+    /// Find the vault client and key to read a secret from
+    ///
+    /// Secrets in the region's own vault use `client`. Clients for named vaults
+    /// are created once and kept in `clients`, so their token is only looked up once.
+    fn resolve_vault_key(
+        &self,
+        client: &Vault,
+        clients: &mut BTreeMap<String, Vault>,
+        reg: &Region,
+        key: &str,
+        vault_region: Option<String>,
+    ) -> Result<(Vault, String)> {
+        let name = match vault_region {
+            None => return Ok((client.clone(), format!("{}/{}", self.get_vault_path(&reg.vault), key))),
+            Some(name) => name,
+        };
+        let vc = match reg.vaultRegions.get(&name) {
+            Some(vc) => vc,
+            None => bail!(ErrorKind::UnknownVaultRegion(name, key.to_string())),
+        };
+        let v = match clients.get(&vc.url) {
+            Some(v) => v.clone(),
+            None => {
+                let v = client.retarget(vc)?;
+                clients.insert(vc.url.clone(), v.clone());
+                v
+            }
+        };
+        Ok((v, format!("{}/{}", self.get_vault_path(vc), key)))
+    }
+
     /// Get a list of raw secrets (without associated keys)
     ///
     /// Useful for obfuscation mechanisms so it knows what to obfuscate.
@@ -1096,55 +2026,116 @@ impl Manifest {
         secrets
     }
 
-    pub async fn verify_secrets_exist(&self, vc: &VaultConfig) -> Result<()> {
+    pub async fn verify_secrets_exist(&self, reg: &Region) -> Result<()> {
         use std::collections::HashSet;
-        // what are we requesting
+        // what are we requesting, grouped by the named vault region they live in
         // TODO: Use envvars directly
-        let keys = self
-            .env
-            .plain
-            .clone()
-            .into_iter()
-            .filter(|(_, v)| v == "IN_VAULT")
-            .map(|(k, _)| k)
-            .collect::<HashSet<_>>();
-        let files = self
-            .secretFiles
-            .clone()
-            .into_iter()
-            .filter(|(_, v)| v == "IN_VAULT")
-            .map(|(k, _)| k)
-            .collect::<HashSet<_>>();
-        let expected = keys.union(&files).cloned().collect::<HashSet<_>>();
-        if expected.is_empty() {
-            return Ok(()); // no point trying to cross reference
-        }
-
-        // what we have
-        let v = Vault::regional(vc)?;
-        let secpth = self.get_vault_path(vc);
-
-        // list secrets; fail immediately if folder is empty
-        let found = match v.list(&secpth).await {
-            Ok(lst) => lst.into_iter().collect::<HashSet<_>>(),
-            Err(e) => bail!(
-                "Missing secret folder {} expected to contain {:?}: {}",
-                secpth,
-                expected,
-                e
-            ),
-        };
-        debug!("Found secrets {:?} for {}", found, self.name);
+        let mut expected: BTreeMap<Option<String>, HashSet<String>> = BTreeMap::new();
+        for (k, v) in self.env.plain.iter().chain(self.secretFiles.iter()) {
+            if let Some(vault_region) = EnvVars::vault_placeholder(v) {
+                expected.entry(vault_region).or_default().insert(k.clone());
+            }
+        }
 
-        // compare sets
-        let missing = expected.difference(&found).collect::<Vec<_>>();
-        if !missing.is_empty() {
-            bail!("Missing secrets: {:?} not found in vault {}", missing, secpth);
+        for (vault_region, expected) in expected {
+            let vc = match &vault_region {
+                None => &reg.vault,
+                Some(name) => match reg.vaultRegions.get(name) {
+                    Some(vc) => vc,
+                    None => {
+                        let key = expected.into_iter().next().unwrap_or_default();
+                        bail!(ErrorKind::UnknownVaultRegion(name.clone(), key));
+                    }
+                },
+            };
+
+            // what we have
+            let v = Vault::regional(vc).await?;
+            let secpth = self.get_vault_path(vc);
+
+            // list secrets; fail immediately if folder is empty
+            let found = match v.list(&secpth).await {
+                Ok(lst) => lst.into_iter().collect::<HashSet<_>>(),
+                Err(e) => bail!(ErrorKind::MissingSecretFolder(
+                    secpth,
+                    expected.into_iter().collect(),
+                    e.to_string()
+                )),
+            };
+            debug!("Found secrets {:?} for {}", found, self.name);
+
+            // compare sets
+            let missing = expected.difference(&found).cloned().collect::<Vec<_>>();
+            if !missing.is_empty() {
+                bail!(ErrorKind::MissingSecrets(secpth, missing));
+            }
         }
         Ok(())
     }
 }
 
+const RECOMMENDED_LABEL_PREFIX: &str = "app.kubernetes.io/";
+/// Label for the cost center in the metadata
+const COST_CENTER_LABEL: &str = "cost-center";
+
+/// Whether a string is a kubernetes qualified name, like label keys and condition types
+///
+/// That is a name of at most 63 characters, optionally prefixed by a DNS subdomain and a slash.
+fn is_qualified_name(s: &str) -> bool {
+    let name_re = Regex::new(r"^[A-Za-z0-9]([-A-Za-z0-9_.]{0,61}[A-Za-z0-9])?$").unwrap();
+    let prefix_re = Regex::new(r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?(\.[a-z0-9]([-a-z0-9]*[a-z0-9])?)*$").unwrap();
+    let (prefix, name) = match s.rfind('/') {
+        Some(i) => (Some(&s[..i]), &s[i + 1..]),
+        None => (None, s),
+    };
+    prefix.map_or(true, |p| p.len() <= 253 && prefix_re.is_match(p)) && name_re.is_match(name)
+}
+
+/// Whether a string is a valid kubernetes label value
+fn is_label_value(s: &str) -> bool {
+    let re = Regex::new(r"^(([A-Za-z0-9][-A-Za-z0-9_.]{0,61})?[A-Za-z0-9])?$").unwrap();
+    re.is_match(s)
+}
+
+fn verify_dns_name(name: &str) -> Result<()> {
+    // limit to 50 characters, alphanumeric, dashes for sanity.
+    // 63 is kube dns limit (13 char suffix buffer)
+    let re = Regex::new(r"^[0-9a-z\-]{1,50}$").unwrap();
+    if !re.is_match(name) {
+        bail!(ErrorKind::InvalidName(
+            name.to_string(),
+            "Please use a short, lower case service names with dashes"
+        ));
+    }
+    if name.ends_with('-') || name.starts_with('-') {
+        bail!(ErrorKind::InvalidName(
+            name.to_string(),
+            "Please use dashes to separate words only"
+        ));
+    }
+    Ok(())
+}
+
+/// Parse a manifest from yaml without touching the filesystem
+///
+/// Runs the name and region validation that does not depend on a `Config`.
+/// The `region` is only checked against `regions` when it is set in the yaml.
+impl FromStr for Manifest {
+    type Err = Error;
+
+    fn from_str(yaml: &str) -> Result<Manifest> {
+        let mf: Manifest = serde_yaml::from_str(yaml)?;
+        mf.verify_name()?;
+        if mf.regions.is_empty() {
+            bail!(ErrorKind::MissingRegions(mf.name.clone()));
+        }
+        if mf.region != "" {
+            mf.verify_region()?;
+        }
+        Ok(mf)
+    }
+}
+
 // Cross-crate test manifest creator
 impl Manifest {
     pub fn test(name: &str) -> Manifest {
@@ -1169,3 +2160,520 @@ impl Manifest {
         mf
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Container, Manifest};
+    use crate::{
+        region::{Environment, Region, VaultConfig},
+        states::PrimaryWorkload,
+        structs::{EnvVars, ResourceRequirements, Resources},
+        ErrorKind, WarningCode,
+    };
+    use std::{collections::BTreeMap, str::FromStr};
+
+    #[test]
+    fn verify_region_error_kind() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.region = "prod-uk".into();
+        let err = mf.verify_region().unwrap_err();
+        match err.kind() {
+            ErrorKind::UnknownRegion(r, svc) => {
+                assert_eq!(r, "prod-uk");
+                assert_eq!(svc, "fake-svc");
+            }
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+        assert_eq!(err.to_string(), "Unsupported region prod-uk for service fake-svc");
+    }
+
+    #[test]
+    fn verify_ports() {
+        use crate::structs::{port::PortProtocol, Container, HealthCheck, Port};
+        let mut mf = Manifest::test("fake-svc");
+        mf.httpPort = Some(8080);
+        mf.health = Some(HealthCheck {
+            port: Some(8080),
+            ..Default::default()
+        });
+        mf.ports.push(Port {
+            name: "grpc".into(),
+            port: 9000,
+            ..Default::default()
+        });
+        mf.sidecars.push(Container {
+            name: "redis".into(),
+            ports: vec![Port {
+                name: "redis".into(),
+                port: 6379,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        assert!(mf.verify_ports().unwrap().is_empty());
+
+        // privileged ports are only warned about
+        mf.ports[0].port = 443;
+        let warnings = mf.verify_ports().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, WarningCode::PrivilegedPort);
+        assert!(warnings[0].message.contains("443"));
+        mf.ports[0].port = 9000;
+
+        mf.sidecars[0].ports[0].port = 9000;
+        let err = mf.verify_ports().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Port 9000 is claimed by both port grpc and sidecar redis port redis in fake-svc"
+        );
+
+        // ports can be reused with another protocol
+        mf.sidecars[0].ports[0].protocol = PortProtocol::Udp;
+        assert!(mf.verify_ports().is_ok());
+
+        mf.sidecars.clear();
+        mf.ports.push(Port {
+            name: "http".into(),
+            port: 9001,
+            ..Default::default()
+        });
+        let err = mf.verify_ports().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Port name http is used more than once in fake-svc"
+        );
+        mf.ports.pop();
+
+        mf.ports[0].port = 70000;
+        let err = mf.verify_ports().unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidPort(_, claimant, port) => {
+                assert_eq!(claimant, "port grpc");
+                assert_eq!(*port, 70000);
+            }
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+    }
+
+    #[test]
+    fn mount_paths() {
+        use crate::structs::{ConfigMap, ConfigMappedFile, ConfigMaps, VolumeMount};
+        let mut mf = Manifest::test("fake-svc");
+        mf.configs = Some(ConfigMaps(vec![ConfigMap {
+            mount: "/app/config/".into(),
+            files: vec![ConfigMappedFile {
+                name: "settings.yml.j2".into(),
+                dest: "settings.yml".into(),
+                value: None,
+            }],
+            ..Default::default()
+        }]));
+        mf.volumeMounts.push(VolumeMount {
+            name: "data".into(),
+            mountPath: "/app/data".into(),
+            subPath: None,
+            readOnly: false,
+        });
+        mf.persistentVolumes = vec![serde_yaml::from_str("name: store\nmountPath: /app\nsize: 1Gi").unwrap()];
+        let path = |src: &str, p: &str| (src.to_string(), p.to_string());
+        assert_eq!(
+            mf.mount_paths(),
+            vec![
+                path("config settings.yml.j2", "/app/config/settings.yml"),
+                path("volumeMount data", "/app/data"),
+                path("persistentVolume store", "/app"),
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_dependencies_enabled() {
+        use crate::structs::Dependency;
+        let mut mf = Manifest::test("fake-svc");
+        mf.dependencies = vec![Dependency {
+            name: "fake-storage".into(),
+            ..Dependency::default()
+        }];
+        let mut reg = Region::default();
+        reg.name = "prod-uk".into();
+        let disabled = vec!["fake-storage".to_string()];
+
+        assert!(mf.verify_dependencies_enabled(&reg, &[]).unwrap().is_empty());
+        let warnings = mf.verify_dependencies_enabled(&reg, &disabled).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, WarningCode::DisabledDependency);
+
+        reg.forbidDisabledDependencies = true;
+        let err = mf.verify_dependencies_enabled(&reg, &disabled).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "fake-svc depends on fake-storage which is disabled in prod-uk"
+        );
+    }
+
+    #[test]
+    fn canonical_name() {
+        let mut mf = Manifest::test("fake-svc");
+        assert_eq!(mf.canonical_name(), "fake-svc");
+        mf.canonicalName = "fake-svc-eu".into();
+        assert_eq!(mf.canonical_name(), "fake-svc-eu");
+        assert!(mf.verify_name().is_ok());
+
+        mf.canonicalName = "fake-svc-".into();
+        let err = mf.verify_name().unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidName(name, _) => assert_eq!(name, "fake-svc-"),
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+    }
+
+    #[test]
+    fn env_size() {
+        use crate::structs::EnvPolicy;
+        let mut mf = Manifest::test("fake-svc");
+        mf.env.plain.insert("A".into(), "1".into());
+        mf.env.secrets.insert("SECRET".into());
+        mf.secrets.insert("SECRET".into(), "x".repeat(100));
+        // A=1\0 and SECRET=xx..\0
+        assert_eq!(mf.env_size(), 4 + 108);
+
+        let mut policy = EnvPolicy {
+            warnBytes: 100,
+            maxBytes: 200,
+            warnCount: 2,
+            detectSecrets: None,
+        };
+        assert_eq!(mf.env.count(), 2);
+        let warnings = policy.verify(&mf.name, mf.env_size(), mf.env.count()).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, WarningCode::LargeEnv);
+
+        policy.warnCount = 1;
+        let warnings = policy.verify(&mf.name, mf.env_size(), mf.env.count()).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1].code, WarningCode::ManyEnvVars);
+
+        mf.secrets.insert("SECRET".into(), "x".repeat(200));
+        let err = policy.verify(&mf.name, mf.env_size(), mf.env.count()).unwrap_err();
+        match err.kind() {
+            ErrorKind::EnvTooLarge(svc, size, max) => {
+                assert_eq!(svc, "fake-svc");
+                assert_eq!(*size, 212);
+                assert_eq!(*max, 200);
+            }
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+    }
+
+    #[test]
+    fn template_annotations() {
+        let mut mf = Manifest::test("fake-svc");
+        let mut reg = Region::default();
+        reg.name = "dev-uk".into();
+        mf.env.plain.insert("CERT".into(), "arn:aws:acm:cert".into());
+        let anns = &mut mf.serviceAnnotations;
+        anns.insert("host".into(), "{{ service }}.{{ region }}".into());
+        anns.insert("cert".into(), "{{ env.CERT }}".into());
+        anns.insert("static".into(), "keep".into());
+        mf.template_annotations(&reg).unwrap();
+        assert_eq!(mf.serviceAnnotations["host"], "fake-svc.dev-uk");
+        assert_eq!(mf.serviceAnnotations["cert"], "arn:aws:acm:cert");
+        assert_eq!(mf.serviceAnnotations["static"], "keep");
+
+        let anns = &mut mf.serviceAnnotations;
+        anns.insert("missing".into(), "{{ env.CERT_ARN }}".into());
+        assert!(mf.template_annotations(&reg).is_err());
+    }
+
+    #[test]
+    fn set_override() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.env.plain.insert("RUST_LOG".into(), "info".into());
+        mf.env.secrets.insert("API_KEY".into());
+        mf.secrets.insert("API_KEY".into(), "secret".into());
+
+        mf.set_override("version", "debug").unwrap();
+        mf.set_override("replicaCount", "3").unwrap();
+        mf.set_override("env.RUST_LOG", "debug").unwrap();
+        mf.set_override("env.API_KEY", "other").unwrap();
+        assert_eq!(mf.version, Some("debug".into()));
+        assert_eq!(mf.replicaCount, Some(3));
+        assert_eq!(mf.env.plain["RUST_LOG"], "debug");
+        assert_eq!(mf.secrets["API_KEY"], "other");
+
+        assert!(mf.set_override("replicaCount", "many").is_err());
+        assert!(mf.set_override("env.MISSING", "x").is_err());
+        assert!(mf.set_override("chart", "other").is_err());
+    }
+
+    #[test]
+    fn is_deployable_in() {
+        let mut mf = Manifest::test("fake-svc");
+        assert!(mf.is_deployable_in("dev-uk"));
+        assert!(!mf.is_deployable_in("prod-uk"));
+        mf.disabled = true;
+        assert!(!mf.is_deployable_in("dev-uk"));
+    }
+
+    #[test]
+    fn verify_readiness_gates() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.readinessGates = vec!["WarmedUp".into(), "example.com/warm-up-complete".into()];
+        assert!(mf.verify_readiness_gates().is_ok());
+        for bad in &["", "example.com/", "/warm", "Example.com/warm", "warm up"] {
+            mf.readinessGates = vec![bad.to_string()];
+            assert!(mf.verify_readiness_gates().is_err(), "{} is invalid", bad);
+        }
+    }
+
+    #[test]
+    fn recommended_labels() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.labels
+            .insert("app.kubernetes.io/part-of".into(), "platform".into());
+        mf.add_recommended_labels();
+        assert_eq!(mf.labels["app.kubernetes.io/name"], "fake-svc");
+        assert_eq!(mf.labels["app.kubernetes.io/version"], "1.0.0");
+        assert_eq!(mf.labels["app.kubernetes.io/managed-by"], "shipcat");
+        // explicit labels win
+        assert_eq!(mf.labels["app.kubernetes.io/part-of"], "platform");
+
+        assert!(mf.verify_labels(&[]).is_ok());
+        mf.labels.insert("team".into(), "devops".into());
+        assert!(mf.verify_labels(&[]).is_err());
+        assert!(mf.verify_labels(&["team".into()]).is_ok());
+        mf.labels
+            .insert("app.kubernetes.io/component".into(), "not valid!".into());
+        assert!(mf.verify_labels(&["team".into()]).is_err());
+
+        // semver build metadata is not a valid label value
+        mf.version = Some("1.0.0+build".into());
+        assert!(!mf.recommended_labels().contains_key("app.kubernetes.io/version"));
+
+        mf.metadata.as_mut().unwrap().costCenter = Some("platform-eng".into());
+        assert_eq!(mf.recommended_labels()["cost-center"], "platform-eng");
+    }
+
+    #[test]
+    fn enable_debug() {
+        let mut mf = Manifest::test("fake-svc");
+        let mut reg = Region::default();
+        assert!(mf.enable_debug(&reg).is_err());
+        mf.debugContainer = Some(Container {
+            name: "debug".into(),
+            ..Container::default()
+        });
+        assert!(mf.enable_debug(&reg).is_err());
+
+        mf.debugContainer.as_mut().unwrap().image = Some("nicolaka/netshoot".into());
+        reg.environment = Environment::Prod;
+        mf.enable_debug(&reg).unwrap();
+        assert!(mf.debug);
+    }
+
+    #[test]
+    fn verify_sizing_against() {
+        let mut staging = Manifest::test("fake-svc");
+        staging.region = "staging-uk".into();
+        staging.replicaCount = Some(2);
+        staging.resources = Some(ResourceRequirements {
+            requests: Resources {
+                cpu: "500m".into(),
+                memory: "1Gi".into(),
+            },
+            limits: None,
+        });
+        let mut prod = staging.clone();
+        prod.region = "prod-uk".into();
+        assert!(prod.verify_sizing_against(&staging).unwrap().is_empty());
+
+        prod.replicaCount = Some(1);
+        prod.resources.as_mut().unwrap().requests.cpu = "0.25".into();
+        prod.resources.as_mut().unwrap().requests.memory = "2Gi".into();
+        let warnings = prod.verify_sizing_against(&staging).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].code, WarningCode::SmallerThanLowerTier);
+        assert_eq!(
+            warnings[1].message,
+            "fake-svc has requests.cpu 0.25 in prod-uk, less than 500m in staging-uk"
+        );
+    }
+
+    #[test]
+    fn verify_external() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.external = true;
+        mf.replicaCount = Some(2);
+        assert!(mf.verify_external(2).is_ok());
+
+        mf.replicaCount = Some(3);
+        mf.volumes = vec![serde_yaml::from_str("name: data\nemptyDir: {}").unwrap()];
+        let err = mf.verify_external(2).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "fake-svc is external but declares workload fields that have no effect: replicaCount, volumes"
+        );
+    }
+
+    #[test]
+    fn verify_env_key_case() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.env.plain.insert("PATH".into(), "/usr/bin".into());
+        mf.env.plain.insert("RUST_LOG".into(), "info".into());
+        assert!(mf.verify_env_key_case().is_ok());
+
+        mf.env.plain.insert("Path".into(), "/bin".into());
+        let err = mf.verify_env_key_case().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Env vars PATH and Path in fake-svc differ only by case"
+        );
+
+        // secrets count towards collisions as well
+        let mut mf = Manifest::test("fake-svc");
+        mf.env.plain.insert("DATABASE_URL".into(), "postgres://".into());
+        mf.secrets.insert("database_url".into(), "hunter2".into());
+        assert!(mf.verify_env_key_case().is_err());
+    }
+
+    #[test]
+    fn from_str_minimal() {
+        let mf = Manifest::from_str(
+            "name: fake-svc\nregions: [dev-uk]\nmetadata:\n  team: doves\n  repo: https://github.com/babylonhealth/shipcat\n",
+        )
+        .unwrap();
+        assert_eq!(mf.name, "fake-svc");
+        assert_eq!(mf.regions, vec!["dev-uk".to_string()]);
+        // defaults applied
+        match mf.workload {
+            PrimaryWorkload::Deployment => {}
+            _ => panic!("default workload should be a deployment"),
+        }
+        assert!(!mf.external);
+        assert!(!mf.disabled);
+        assert!(mf.env.plain.is_empty());
+        assert!(mf.configs.is_none());
+        assert_eq!(mf.region, "");
+    }
+
+    #[test]
+    fn from_str_validates() {
+        let err = Manifest::from_str("name: Fake_Svc\nregions: [dev-uk]\n").unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidName(svc, _) => assert_eq!(svc, "Fake_Svc"),
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+        let err = Manifest::from_str("name: fake-svc\n").unwrap_err();
+        match err.kind() {
+            ErrorKind::MissingRegions(svc) => assert_eq!(svc, "fake-svc"),
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+        // region is only deserialized from the CRD form
+        if cfg!(not(feature = "filesystem")) {
+            let err = Manifest::from_str("name: fake-svc\nregions: [dev-uk]\nregion: prod-uk\n").unwrap_err();
+            match err.kind() {
+                ErrorKind::UnknownRegion(r, _) => assert_eq!(r, "prod-uk"),
+                _ => panic!("unexpected error kind {:?}", err.kind()),
+            }
+        }
+    }
+
+    #[test]
+    fn vault_placeholders() {
+        assert_eq!(EnvVars::vault_placeholder("IN_VAULT"), Some(None));
+        assert_eq!(
+            EnvVars::vault_placeholder("IN_VAULT@central"),
+            Some(Some("central".to_string()))
+        );
+        assert_eq!(EnvVars::vault_placeholder("IN_VAULT@"), None);
+        assert_eq!(EnvVars::vault_placeholder("plain"), None);
+    }
+
+    #[test]
+    fn verify_vault_regions() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.env.plain.insert("LOCAL_SECRET".into(), "IN_VAULT".into());
+        mf.env
+            .plain
+            .insert("SHARED_SECRET".into(), "IN_VAULT@central".into());
+        let mut reg = Region::default();
+        let err = mf.verify_vault_regions(&reg).unwrap_err();
+        match err.kind() {
+            ErrorKind::UnknownVaultRegion(name, key) => {
+                assert_eq!(name, "central");
+                assert_eq!(key, "SHARED_SECRET");
+            }
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+        reg.vaultRegions.insert(
+            "central".into(),
+            VaultConfig {
+                url: "https://vault.central.example.com:8200".into(),
+                folder: "central".into(),
+                mount: "secret".into(),
+            },
+        );
+        assert!(mf.verify_vault_regions(&reg).is_ok());
+
+        // secret files can use named vaults as well
+        mf.secretFiles
+            .insert("shared-keystore".into(), "IN_VAULT@elsewhere".into());
+        assert!(mf.verify_vault_regions(&reg).is_err());
+    }
+
+    #[test]
+    fn verify_rollback_version() {
+        let mf = Manifest::test("fake-svc");
+        let reg = Region::default();
+        assert!(mf.verify_rollback_version("0.9.0", &reg).is_ok());
+        assert!(mf.verify_rollback_version("1.0.0", &reg).is_ok());
+        // newer than the pinned version
+        assert!(mf.verify_rollback_version("1.0.1", &reg).is_err());
+        // not semver in a semver region
+        assert!(mf.verify_rollback_version("latest", &reg).is_err());
+        let mf = mf.with_version("0.9.0".into());
+        assert_eq!(mf.version, Some("0.9.0".into()));
+    }
+
+    #[test]
+    fn verify_resource_tier() {
+        let mut mf = Manifest::test("fake-svc");
+        let mut tiers = BTreeMap::new();
+        assert!(mf.verify_resource_tier(&tiers).is_ok());
+        mf.resourceTier = Some("small".into());
+        let err = mf.verify_resource_tier(&tiers).unwrap_err();
+        match err.kind() {
+            ErrorKind::UnknownResourceTier(svc, tier) => {
+                assert_eq!(svc, "fake-svc");
+                assert_eq!(tier, "small");
+            }
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+        let small = serde_yaml::from_str("requests: {cpu: 100m, memory: 256Mi}").unwrap();
+        tiers.insert("small".into(), small);
+        assert!(mf.verify_resource_tier(&tiers).is_ok());
+    }
+
+    #[test]
+    fn redacted_clone() {
+        let mut mf = Manifest::test("fake-svc");
+        mf.secrets.insert("DB_PASSWORD".into(), "hunter2".into());
+        mf.secretFiles.insert("ssl-key".into(), "aHVudGVyMg==".into());
+        let redacted = mf.redacted_clone();
+        assert_eq!(redacted.secrets["DB_PASSWORD"], "***");
+        assert_eq!(redacted.secretFiles["ssl-key"], "***");
+        assert_eq!(mf.secrets["DB_PASSWORD"], "hunter2");
+    }
+
+    #[test]
+    fn verify_standalone() {
+        let mut mf = Manifest::test("fake-svc");
+        let warning = mf.verify_standalone().unwrap();
+        assert_eq!(warning.code, WarningCode::Standalone);
+        mf.standalone = true;
+        assert!(mf.verify_standalone().is_none());
+        mf.standalone = false;
+        mf.httpPort = Some(8080);
+        assert!(mf.verify_standalone().is_none());
+    }
+}
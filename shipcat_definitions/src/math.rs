@@ -1,5 +1,5 @@
 use super::{
-    structs::{rollingupdate::RollingUpdate, ResourceRequirements},
+    structs::{rollingupdate::RollingUpdate, ResourceRequirements, Resources},
     Manifest, Result,
 };
 
@@ -90,7 +90,8 @@ impl Manifest {
 
             // how long each iteration needs to wait due to readinessProbe params.
             let delayTimeSecs = if let Some(ref hc) = self.health {
-                hc.wait
+                // a startup probe holds off the health check until the service boots
+                hc.wait + hc.startup.as_ref().map_or(0, |sp| sp.max_startup_time())
             } else if let Some(ref rp) = self.readinessProbe {
                 rp.initialDelaySeconds
             } else {
@@ -115,7 +116,10 @@ impl Manifest {
     pub fn compute_resource_totals(&self) -> Result<ResourceTotals> {
         let mut base: ResourceRequirements<f64> = ResourceRequirements::default();
         let mut extra: ResourceRequirements<f64> = ResourceRequirements::default(); // autoscaling limits
-        let res = self.resources.clone().unwrap().normalised()?; // exists by verify
+        let res = match &self.resources {
+            Some(r) => r.normalised()?,
+            None => bail!("{} does not have resources", self.name), // exists by verify
+        };
         if let Some(ref ascale) = self.autoScaling {
             base += res.clone() * ascale.minReplicas;
             extra += res * (ascale.maxReplicas - ascale.minReplicas);
@@ -151,11 +155,99 @@ impl Manifest {
     }
 }
 
+/// Requested resources in a region compared to the capacity of its cluster
+#[derive(Serialize, Debug)]
+pub struct Utilization {
+    /// Requests at full scale in cores and Bytes
+    pub requests: Resources<f64>,
+    /// Cluster capacity in cores and Bytes, if known
+    pub capacity: Option<Resources<f64>>,
+    /// Percentage of the cpu capacity requested
+    pub cpu_percent: Option<f64>,
+    /// Percentage of the memory capacity requested
+    pub memory_percent: Option<f64>,
+    /// Services with the largest share of the requests, largest first
+    pub top_consumers: Vec<(String, Resources<f64>)>,
+    /// Services whose requests could not be worked out
+    pub skipped: Vec<String>,
+}
+
+impl Utilization {
+    /// Sum up requests across manifests and compare against a capacity
+    ///
+    /// Services are counted at full scale, i.e. `maxReplicas` when autoscaling.
+    /// Services without resources are skipped rather than failing the whole region.
+    pub fn compute(mfs: &[Manifest], capacity: Option<Resources<f64>>, top: usize) -> Self {
+        let mut requests = Resources {
+            cpu: 0.0,
+            memory: 0.0,
+        };
+        let mut consumers = vec![];
+        let mut skipped = vec![];
+        for mf in mfs {
+            match mf.compute_resource_totals() {
+                Ok(ResourceTotals { base, extra }) => {
+                    let req = (base + extra).requests;
+                    requests.cpu += req.cpu;
+                    requests.memory += req.memory;
+                    consumers.push((mf.name.clone(), req));
+                }
+                Err(e) => {
+                    warn!("Skipping {} in resource totals: {}", mf.name, e);
+                    skipped.push(mf.name.clone());
+                }
+            }
+        }
+        // rank by the largest share of either resource
+        let share = |r: &Resources<f64>| {
+            let cpu = if requests.cpu > 0.0 {
+                r.cpu / requests.cpu
+            } else {
+                0.0
+            };
+            let memory = if requests.memory > 0.0 {
+                r.memory / requests.memory
+            } else {
+                0.0
+            };
+            cpu.max(memory)
+        };
+        consumers.sort_by(|(_, a), (_, b)| share(b).partial_cmp(&share(a)).unwrap());
+        consumers.truncate(top);
+
+        let percent = |req: f64, cap: f64| if cap > 0.0 { Some(100.0 * req / cap) } else { None };
+        let cpu_percent = capacity.as_ref().and_then(|c| percent(requests.cpu, c.cpu));
+        let memory_percent = capacity.as_ref().and_then(|c| percent(requests.memory, c.memory));
+        let res = Utilization {
+            requests,
+            capacity,
+            cpu_percent,
+            memory_percent,
+            top_consumers: consumers,
+            skipped,
+        };
+        if res.is_overcommitted() {
+            warn!(
+                "Requests exceed capacity: {:.0}% of cpu and {:.0}% of memory",
+                res.cpu_percent.unwrap_or_default(),
+                res.memory_percent.unwrap_or_default()
+            );
+        }
+        res
+    }
+
+    /// Whether more cpu or memory is requested than the capacity
+    pub fn is_overcommitted(&self) -> bool {
+        let over = |p: Option<f64>| matches!(p, Some(p) if p > 100.0);
+        over(self.cpu_percent) || over(self.memory_percent)
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
-    use super::Manifest;
-    use crate::structs::HealthCheck;
+    use super::{Manifest, Utilization};
+    use crate::structs::{HealthCheck, ResourceRequirements, Resources, StartupProbe};
 
     #[test]
     fn mf_wait_time_check() {
@@ -193,5 +285,71 @@ mod tests {
         });
         mf.replicaCount = Some(1);
         assert_eq!(mf.estimate_wait_time(), 990); // lots of leeway here just in case
+        assert!(mf.health.as_ref().unwrap().needs_startup_probe());
+
+        // slow boot covered by a startup probe
+        mf.health = Some(HealthCheck {
+            uri: "/".into(),
+            wait: 10,
+            startup: Some(StartupProbe {
+                path: "/".into(),
+                failureThreshold: 12,
+                periodSeconds: 10,
+            }),
+            ..Default::default()
+        });
+        assert!(!mf.health.as_ref().unwrap().needs_startup_probe());
+        assert_eq!(mf.estimate_wait_time(), 285); // (10 + 120)*1.5 + 90s
+    }
+
+    #[test]
+    fn utilization() {
+        let resources = |cpu: &str, memory: &str| ResourceRequirements {
+            requests: Resources {
+                cpu: cpu.into(),
+                memory: memory.into(),
+            },
+            limits: Some(Resources {
+                cpu: cpu.into(),
+                memory: memory.into(),
+            }),
+        };
+        let small = Manifest {
+            name: "small".into(),
+            replicaCount: Some(2),
+            resources: Some(resources("500m", "1Gi")),
+            ..Default::default()
+        };
+        let big = Manifest {
+            name: "big".into(),
+            replicaCount: Some(1),
+            resources: Some(resources("1", "4Gi")),
+            // counted at maxReplicas
+            autoScaling: serde_yaml::from_str("minReplicas: 1\nmaxReplicas: 3\nmetrics: []").unwrap(),
+            ..Default::default()
+        };
+        let broken = Manifest {
+            name: "broken".into(),
+            replicaCount: Some(1),
+            ..Default::default()
+        };
+
+        let gi = 1024.0 * 1024.0 * 1024.0;
+        let capacity = Resources {
+            cpu: 8.0,
+            memory: 12.0 * gi,
+        };
+        let util = Utilization::compute(&[small, big, broken], Some(capacity), 1);
+        assert_eq!(util.requests.cpu, 4.0);
+        assert_eq!(util.requests.memory, 14.0 * gi);
+        assert_eq!(util.cpu_percent, Some(50.0));
+        assert!(util.is_overcommitted());
+        assert_eq!(util.top_consumers.len(), 1);
+        assert_eq!(util.top_consumers[0].0, "big");
+        assert_eq!(util.skipped, vec!["broken".to_string()]);
+
+        let util = Utilization::compute(&[], None, 10);
+        assert_eq!(util.cpu_percent, None);
+        assert!(!util.is_overcommitted());
     }
 }
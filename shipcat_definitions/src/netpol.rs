@@ -0,0 +1,229 @@
+use k8s_openapi::{
+    api::networking::v1::{
+        NetworkPolicy, NetworkPolicyEgressRule, NetworkPolicyIngressRule, NetworkPolicyPeer,
+        NetworkPolicyPort, NetworkPolicySpec,
+    },
+    apimachinery::pkg::{
+        apis::meta::v1::{LabelSelector, ObjectMeta},
+        util::intstr::IntOrString,
+    },
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+    structs::{port::PortProtocol, Dependency},
+    Manifest,
+};
+
+/// Selector for the pods of a service, as labelled by the charts
+fn app_selector(name: &str) -> LabelSelector {
+    let mut labels = BTreeMap::new();
+    labels.insert("app".to_string(), name.to_string());
+    LabelSelector {
+        match_labels: Some(labels),
+        ..Default::default()
+    }
+}
+
+fn peers(names: &BTreeSet<&str>) -> Vec<NetworkPolicyPeer> {
+    names
+        .iter()
+        .map(|n| NetworkPolicyPeer {
+            pod_selector: Some(app_selector(n)),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Ports a dependency is called on, or `None` for all ports
+fn dependency_ports(mf: &Manifest, dep: &Dependency) -> Option<Vec<NetworkPolicyPort>> {
+    let port = match dep.port {
+        Some(p) => p,
+        None => {
+            warn!(
+                "{} does not declare the port of its dependency {} - allowing all ports",
+                mf.name, dep.name
+            );
+            return None;
+        }
+    };
+    let protocol = match dep.portProtocol.unwrap_or_default() {
+        PortProtocol::Tcp => "TCP",
+        PortProtocol::Udp => "UDP",
+        PortProtocol::Sctp => "SCTP",
+    };
+    Some(vec![NetworkPolicyPort {
+        port: Some(IntOrString::Int(port as i32)),
+        protocol: Some(protocol.into()),
+    }])
+}
+
+/// Generate a `NetworkPolicy` for every service from the dependency graph
+///
+/// Each service may send traffic to its `dependencies` and receive traffic from
+/// services that depend on it. Both directions are restricted, so anything else
+/// (DNS, ingress controllers, external hosts) needs separate policies.
+///
+/// Egress is limited to the `port` of each dependency when it is declared, and
+/// allowed on all ports of the dependency otherwise.
+/// Dependencies on services that are external or not part of `manifests` are left out.
+/// Policies and pods are named by the canonical names of the services.
+pub fn network_policies(manifests: &[Manifest]) -> Vec<NetworkPolicy> {
+    let internal = manifests
+        .iter()
+        .filter(|mf| !mf.external)
+        .map(|mf| (mf.name.as_str(), mf.canonical_name()))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut egress: BTreeMap<&str, Vec<NetworkPolicyEgressRule>> = BTreeMap::new();
+    let mut ingress: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for mf in manifests.iter().filter(|mf| !mf.external) {
+        for dep in &mf.dependencies {
+            if let Some(dep_canonical) = internal.get(dep.name.as_str()) {
+                egress.entry(&mf.name).or_default().push(NetworkPolicyEgressRule {
+                    to: Some(peers(&btreeset![*dep_canonical])),
+                    ports: dependency_ports(mf, dep),
+                });
+                ingress.entry(&dep.name).or_default().insert(mf.canonical_name());
+            }
+        }
+    }
+
+    let mut res = vec![];
+    for mf in manifests.iter().filter(|mf| !mf.external) {
+        let to = egress.remove(mf.name.as_str()).unwrap_or_default();
+        let from = ingress.remove(mf.name.as_str()).unwrap_or_default();
+        let name = mf.canonical_name().to_string();
+        let mut labels = BTreeMap::new();
+        labels.insert("app".to_string(), name.clone());
+        res.push(NetworkPolicy {
+            metadata: Some(ObjectMeta {
+                name: Some(name),
+                namespace: Some(mf.namespace.clone()),
+                labels: Some(labels),
+                ..Default::default()
+            }),
+            spec: Some(NetworkPolicySpec {
+                pod_selector: app_selector(mf.canonical_name()),
+                policy_types: Some(vec!["Ingress".into(), "Egress".into()]),
+                // an empty list of rules denies all traffic in that direction
+                egress: Some(to),
+                ingress: Some(if from.is_empty() {
+                    vec![]
+                } else {
+                    vec![NetworkPolicyIngressRule {
+                        from: Some(peers(&from)),
+                        ports: None,
+                    }]
+                }),
+            }),
+        });
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::network_policies;
+    use crate::{
+        structs::{port::PortProtocol, Dependency},
+        Manifest,
+    };
+
+    fn depends_on(name: &str, deps: &[&str]) -> Manifest {
+        let mut mf = Manifest::test(name);
+        mf.namespace = "apps".into();
+        mf.dependencies = deps
+            .iter()
+            .map(|d| Dependency {
+                name: d.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        mf
+    }
+
+    #[test]
+    fn policies_from_dependencies() {
+        let mut ext = depends_on("ext", &[]);
+        ext.external = true;
+        let mfs = vec![
+            depends_on("web", &["api", "ext", "unknown"]),
+            depends_on("api", &["db"]),
+            depends_on("db", &[]),
+            ext,
+        ];
+        let pols = network_policies(&mfs);
+        assert_eq!(pols.len(), 3);
+
+        let yaml = serde_yaml::to_value(&pols[0]).unwrap();
+        assert_eq!(yaml["kind"], "NetworkPolicy");
+        assert_eq!(yaml["metadata"]["name"], "web");
+        assert_eq!(yaml["metadata"]["namespace"], "apps");
+        let egress = &yaml["spec"]["egress"][0]["to"];
+        assert_eq!(egress.as_sequence().unwrap().len(), 1);
+        assert_eq!(egress[0]["podSelector"]["matchLabels"]["app"], "api");
+        assert!(yaml["spec"]["ingress"].as_sequence().unwrap().is_empty());
+
+        let api = serde_yaml::to_value(&pols[1]).unwrap();
+        assert_eq!(
+            api["spec"]["egress"][0]["to"][0]["podSelector"]["matchLabels"]["app"],
+            "db"
+        );
+        assert_eq!(
+            api["spec"]["ingress"][0]["from"][0]["podSelector"]["matchLabels"]["app"],
+            "web"
+        );
+
+        let db = serde_yaml::to_value(&pols[2]).unwrap();
+        assert!(db["spec"]["egress"].as_sequence().unwrap().is_empty());
+        assert_eq!(
+            db["spec"]["ingress"][0]["from"][0]["podSelector"]["matchLabels"]["app"],
+            "api"
+        );
+    }
+
+    #[test]
+    fn policies_with_dependency_ports() {
+        let mut web = depends_on("web", &["api", "db"]);
+        web.dependencies[0].port = Some(8080);
+        web.dependencies[1].port = Some(5432);
+        web.dependencies[1].portProtocol = Some(PortProtocol::Tcp);
+        let pols = network_policies(&[web, depends_on("api", &[]), depends_on("db", &[])]);
+
+        let web = serde_yaml::to_value(&pols[0]).unwrap();
+        let egress = web["spec"]["egress"].as_sequence().unwrap();
+        assert_eq!(egress.len(), 2);
+        assert_eq!(egress[0]["to"][0]["podSelector"]["matchLabels"]["app"], "api");
+        assert_eq!(egress[0]["ports"][0]["port"], 8080);
+        assert_eq!(egress[0]["ports"][0]["protocol"], "TCP");
+        assert_eq!(egress[1]["ports"][0]["port"], 5432);
+
+        // without a port everything on the dependency is allowed
+        let pols = network_policies(&[depends_on("web", &["api"]), depends_on("api", &[])]);
+        let web = serde_yaml::to_value(&pols[0]).unwrap();
+        assert!(web["spec"]["egress"][0].get("ports").is_none());
+    }
+
+    #[test]
+    fn policies_use_canonical_names() {
+        let mut web = depends_on("web", &["api"]);
+        web.canonicalName = "web-eu".into();
+        let mut api = depends_on("api", &[]);
+        api.canonicalName = "api-eu".into();
+        let pols = network_policies(&[web, api]);
+
+        let web = serde_yaml::to_value(&pols[0]).unwrap();
+        assert_eq!(web["metadata"]["name"], "web-eu");
+        assert_eq!(web["spec"]["podSelector"]["matchLabels"]["app"], "web-eu");
+        assert_eq!(
+            web["spec"]["egress"][0]["to"][0]["podSelector"]["matchLabels"]["app"],
+            "api-eu"
+        );
+        let api = serde_yaml::to_value(&pols[1]).unwrap();
+        assert_eq!(
+            api["spec"]["ingress"][0]["from"][0]["podSelector"]["matchLabels"]["app"],
+            "web-eu"
+        );
+    }
+}
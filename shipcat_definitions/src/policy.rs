@@ -0,0 +1,156 @@
+use std::sync::RwLock;
+
+use super::{Config, ErrorKind, Manifest, Result};
+
+/// A breach of an organisational policy by a manifest
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// Name of the policy that was breached
+    pub policy: String,
+    pub message: String,
+}
+
+/// An organisational rule that manifests must follow
+///
+/// Policies are checked by `Manifest::verify` after the built-in checks.
+/// Downstream crates add their own with `register`.
+pub trait Policy: Send + Sync {
+    /// Short name used in violations
+    fn name(&self) -> &str;
+
+    /// Find all the ways a manifest breaks the policy
+    ///
+    /// Errors are reserved for failures to evaluate the policy.
+    fn check(&self, mf: &Manifest, conf: &Config) -> Result<Vec<Violation>>;
+
+    /// Helper for creating a violation of this policy
+    fn violation(&self, message: String) -> Violation {
+        Violation {
+            policy: self.name().to_string(),
+            message,
+        }
+    }
+}
+
+lazy_static! {
+    static ref POLICIES: RwLock<Vec<Box<dyn Policy>>> = RwLock::new(vec![]);
+}
+
+/// Register a policy to be checked when verifying manifests
+pub fn register(policy: Box<dyn Policy>) {
+    POLICIES.write().expect("policy registry lock").push(policy);
+}
+
+/// Check a manifest against a set of policies, failing on any violations
+pub fn check_policies(policies: &[Box<dyn Policy>], mf: &Manifest, conf: &Config) -> Result<()> {
+    let mut violations = vec![];
+    for p in policies {
+        violations.extend(p.check(mf, conf)?);
+    }
+    if !violations.is_empty() {
+        let reasons = violations
+            .into_iter()
+            .map(|v| format!("{}: {}", v.policy, v.message))
+            .collect();
+        bail!(ErrorKind::PolicyViolations(mf.name.clone(), reasons));
+    }
+    Ok(())
+}
+
+/// Check a manifest against all registered policies
+pub fn check_registered(mf: &Manifest, conf: &Config) -> Result<()> {
+    let policies = POLICIES.read().expect("policy registry lock");
+    check_policies(&policies, mf, conf)
+}
+
+/// Policy requiring a label to be set, e.g. for cost allocation
+pub struct RequiredLabel {
+    pub label: String,
+}
+
+impl Policy for RequiredLabel {
+    fn name(&self) -> &str {
+        "required-label"
+    }
+
+    fn check(&self, mf: &Manifest, _: &Config) -> Result<Vec<Violation>> {
+        if mf.labels.contains_key(&self.label) {
+            return Ok(vec![]);
+        }
+        Ok(vec![self.violation(format!("missing label {}", self.label))])
+    }
+}
+
+/// Policy restricting services to a set of charts, e.g. to retire deprecated ones
+pub struct ChartAllowlist {
+    pub charts: Vec<String>,
+}
+
+impl Policy for ChartAllowlist {
+    fn name(&self) -> &str {
+        "chart-allowlist"
+    }
+
+    fn check(&self, mf: &Manifest, _: &Config) -> Result<Vec<Violation>> {
+        match &mf.chart {
+            Some(c) if !self.charts.contains(c) => Ok(vec![self.violation(format!(
+                "chart {} is not one of {}",
+                c,
+                self.charts.join(", ")
+            ))]),
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_policies, ChartAllowlist, Policy, RequiredLabel};
+    use crate::{Config, ErrorKind, Manifest};
+
+    #[test]
+    fn example_policies() {
+        let conf: Config = serde_yaml::from_str(
+            r#"
+defaults:
+  imagePrefix: quay.io/babylonhealth
+  chart: base
+  replicaCount: 1
+clusters: {}
+regions: []
+slack:
+  team: T1234ABCD
+github:
+  organisation: babylonhealth
+versions:
+  dev: 0.1.0
+"#,
+        )
+        .unwrap();
+        let policies: Vec<Box<dyn Policy>> = vec![
+            Box::new(RequiredLabel {
+                label: "cost-center".into(),
+            }),
+            Box::new(ChartAllowlist {
+                charts: vec!["base".into()],
+            }),
+        ];
+        let mut mf = Manifest::test("fake-svc");
+        mf.chart = Some("legacy".into());
+        let err = check_policies(&policies, &mf, &conf).unwrap_err();
+        match err.kind() {
+            ErrorKind::PolicyViolations(svc, reasons) => {
+                assert_eq!(svc, "fake-svc");
+                assert_eq!(reasons, &vec![
+                    "required-label: missing label cost-center".to_string(),
+                    "chart-allowlist: chart legacy is not one of base".to_string(),
+                ]);
+            }
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+
+        mf.chart = Some("base".into());
+        mf.labels.insert("cost-center".into(), "1234".into());
+        assert!(check_policies(&policies, &mf, &conf).is_ok());
+    }
+}
@@ -1,5 +1,8 @@
-use crate::structs::kong::Kong;
-use std::{collections::BTreeMap, env};
+use crate::structs::kong::{Kong, RateLimit};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+};
 
 use regex::Regex;
 
@@ -10,7 +13,7 @@ use uuid::Uuid;
 
 #[allow(unused_imports)] use super::{BaseManifest, ConfigState, Result, Vault};
 
-use super::structs::Authorization;
+use super::structs::{Authorization, EnvPolicy, ResourcePolicy, SecurityPolicy, ServiceAccount};
 
 /// Versioning Scheme used in region
 ///
@@ -64,10 +67,17 @@ impl VersionScheme {
 pub struct VaultConfig {
     /// Vault url up to and including port
     pub url: String,
-    /// Root folder under secret/
+    /// Root folder under the mount
     ///
     /// Typically, the name of the region to disambiguate.
     pub folder: String,
+    /// Name of the KV secrets engine mount, `secret` by default
+    #[serde(default = "default_vault_mount")]
+    pub mount: String,
+}
+
+fn default_vault_mount() -> String {
+    "secret".into()
 }
 
 impl VaultConfig {
@@ -85,9 +95,21 @@ impl VaultConfig {
                 self.url
             );
         }
+        if self.mount == "" || self.mount.starts_with('/') || self.mount.ends_with('/') {
+            bail!(
+                "vault config mount '{}' (under {}) must be set without leading or trailing slashes",
+                self.mount,
+                self.url
+            );
+        }
         Ok(())
     }
 
+    /// Path of the region folder in vault, including the mount
+    pub fn path(&self) -> String {
+        format!("{}/{}", self.mount, self.folder)
+    }
+
     /// Make vault a vault policy for a team based on team ownership
     ///
     /// Returns plaintext hcl
@@ -95,7 +117,7 @@ impl VaultConfig {
     pub async fn make_policy(&self, mfs: Vec<BaseManifest>, team: &str, env: Environment) -> Result<String> {
         let mut owned_manifests = vec![];
         for mf in mfs {
-            if mf.metadata.team == team {
+            if mf.metadata.owned_by(team) {
                 owned_manifests.push(mf.name);
             }
         }
@@ -139,6 +161,8 @@ pub struct KafkaConfig {
 pub enum Webhook {
     /// Audit webhook details
     Audit(AuditWebhook),
+    /// Validation summary webhook details
+    Validation(ValidationWebhook),
 }
 
 /// Where / how to send audited events
@@ -151,6 +175,14 @@ pub struct AuditWebhook {
     pub token: String,
 }
 
+/// Where to send the summary of a region wide validation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct ValidationWebhook {
+    /// Endpoint
+    pub url: Url,
+}
+
 /// Configure how CRs will be deployed on a region
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
@@ -179,6 +211,11 @@ pub struct KongConfig {
     pub internal_ips_whitelist: Vec<String>,
     #[serde(default, skip_serializing)]
     pub extra_apis: BTreeMap<String, Kong>,
+    /// Kong plugins services may enable, e.g. `cors` or `response-transformer`
+    ///
+    /// All plugins shipcat supports are allowed when this is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_plugins: Option<BTreeSet<String>>,
 }
 
 /// StatusCake configuration for a region
@@ -256,6 +293,29 @@ impl KongConfig {
     }
 }
 
+/// Prefix and suffix added to service names in a region
+///
+/// Lets the same service be deployed under different names to a shared namespace.
+/// The manifest `name` is unaffected, and the result is the `canonicalName`.
+///
+/// ```yaml
+/// serviceNaming:
+///   suffix: -eu
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ServiceNaming {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl ServiceNaming {
+    /// Transform a service name
+    pub fn apply(&self, name: &str) -> String {
+        format!("{}{}{}", self.prefix, name, self.suffix)
+    }
+}
+
 /// Defaults for services in this region
 // TODO: This should be ManifestDefaults from shipcat_filebacked
 #[derive(Deserialize, Clone, Debug, Default)]
@@ -263,6 +323,10 @@ impl KongConfig {
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct DefaultConfig {
     pub kong: DefaultKongConfig,
+    /// ServiceAccount for services that do not set their own
+    pub serviceAccount: Option<ServiceAccount>,
+    /// Image prefix overriding the global one, e.g. for a region-local registry mirror
+    pub imagePrefix: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Debug, Default)]
@@ -272,27 +336,31 @@ pub struct DefaultKongConfig {
     pub authorization: Option<Authorization>,
     // HACK: Authorization doesn't have an enabled property, so this allows authorization to be enabled/disabled on a per region basis until we can use AuthorizationSource.
     pub authorizationEnabled: bool,
+    /// Rate limit for Kong APIs that do not set their own
+    pub rate_limit: Option<RateLimit>,
 }
 
 impl Webhook {
-    async fn secrets(&mut self, vault: &Vault, region: &str) -> Result<()> {
+    async fn secrets(&mut self, vault: &Vault, prefix: &str) -> Result<()> {
         match self {
             Webhook::Audit(h) => {
                 if h.token == "IN_VAULT" {
-                    let vkey = format!("{}/shipcat/WEBHOOK_AUDIT_TOKEN", region);
+                    let vkey = format!("{}/shipcat/WEBHOOK_AUDIT_TOKEN", prefix);
                     h.token = vault.read(&vkey).await?;
                 }
             }
+            Webhook::Validation(_) => {}
         }
         Ok(())
     }
 
-    async fn verify_secrets_exist(&self, vault: &Vault, region: &str) -> Result<()> {
+    async fn verify_secrets_exist(&self, vault: &Vault, prefix: &str) -> Result<()> {
         match self {
             Webhook::Audit(_h) => {
-                let vkey = format!("{}/shipcat/WEBHOOK_AUDIT_TOKEN", region);
+                let vkey = format!("{}/shipcat/WEBHOOK_AUDIT_TOKEN", prefix);
                 vault.read(&vkey).await?;
             }
+            Webhook::Validation(_) => {}
         }
         // TODO: when more secrets, build up a list and do a LIST on shipcat folder
         Ok(())
@@ -332,6 +400,7 @@ impl Webhook {
 
                 debug!("Audit webhook config {:?}", whc);
             }
+            Webhook::Validation(_) => {}
         }
 
         // TODO: when slack webhook is cfged, require this:
@@ -497,6 +566,18 @@ pub struct Region {
     pub kafka: KafkaConfig,
     /// Vault configuration for the region
     pub vault: VaultConfig,
+    /// Named vaults that secrets can be read from regardless of the region
+    ///
+    /// Referenced per key with an `IN_VAULT@name` placeholder:
+    ///
+    /// ```yaml
+    /// vaultRegions:
+    ///   central:
+    ///     url: https://vault.central.example.com:8200
+    ///     folder: central
+    /// ```
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vaultRegions: BTreeMap<String, VaultConfig>,
     /// Logz.io configuration for the region
     pub logzio: Option<LogzIoConfig>,
     /// Grafana details for the region
@@ -517,31 +598,64 @@ pub struct Region {
     /// The regular expression used to verify destination rules' regions
     #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_regex")]
     pub destinationRuleHostRegex: Option<Regex>,
+    /// Security context requirements for services in the region
+    #[serde(default)]
+    pub securityPolicy: SecurityPolicy,
+    /// Sanity requirements on resource requests and limits of services in the region
+    #[serde(default)]
+    pub resourcePolicy: ResourcePolicy,
+    /// Limits on the total size of env vars of services in the region
+    #[serde(default)]
+    pub envPolicy: EnvPolicy,
+    /// Whether to generate NetworkPolicies from service dependencies
+    ///
+    /// For clusters that deny pod traffic by default.
+    #[serde(default)]
+    pub networkPolicies: bool,
+    /// Whether depending on a service that is disabled in the region fails validation
+    ///
+    /// Otherwise only a warning is given.
+    #[serde(default)]
+    pub forbidDisabledDependencies: bool,
+    /// Whether services should have their version pinned in a `<region>.lock` file
+    ///
+    /// Services without a lockfile get a warning.
+    #[serde(default)]
+    pub requireVersionLock: bool,
+    /// Transform of service names for their kubernetes resources
+    #[serde(default)]
+    pub serviceNaming: ServiceNaming,
 }
 
 impl Region {
     // Internal secret populator for Config::new
     pub async fn secrets(&mut self) -> Result<()> {
-        let v = Vault::regional(&self.vault)?;
+        let v = Vault::regional(&self.vault).await?;
+        let prefix = self.webhook_vault_prefix();
         for wh in self.webhooks.iter_mut() {
-            wh.secrets(&v, &self.name).await?;
+            wh.secrets(&v, &prefix).await?;
         }
         Ok(())
     }
 
     // Entry point for region verifier
     pub async fn verify_secrets_exist(&self) -> Result<()> {
-        let v = Vault::regional(&self.vault)?;
+        let v = Vault::regional(&self.vault).await?;
         for wh in &self.webhooks {
-            wh.verify_secrets_exist(&v, &self.name).await?;
+            wh.verify_secrets_exist(&v, &self.webhook_vault_prefix()).await?;
         }
         Ok(())
     }
 
+    // Vault path for region level secrets used by webhooks
+    fn webhook_vault_prefix(&self) -> String {
+        format!("{}/{}", self.vault.mount, self.name)
+    }
+
     // Get the Vault URL for a given service in this region
     pub fn vault_url(&self, app: &str) -> String {
         let vault_url = self.vault.url.clone();
-        let path = "/ui/vault/secrets/secret/list/";
+        let path = format!("/ui/vault/secrets/{}/list/", self.vault.mount);
         format!(
             "{vault_url}/{path}/{env}/{app}/",
             vault_url = vault_url.trim_matches('/'),
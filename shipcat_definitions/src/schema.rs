@@ -0,0 +1,472 @@
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Named schemas referenced from `#/definitions/`
+pub type Definitions = Map<String, Value>;
+
+/// A type that can describe itself as a JSON Schema
+///
+/// Derived for our structs with `#[derive(JsonSchema)]` from `schema_derive`,
+/// which reads serde attributes and doc comments.
+pub trait JsonSchema {
+    /// Schema to use when this type is embedded in another
+    ///
+    /// Derived types register themselves in `defs` and return a `$ref`.
+    fn schema(defs: &mut Definitions) -> Value;
+
+    /// Full schema of this type, without indirection
+    fn inline(defs: &mut Definitions) -> Value {
+        Self::schema(defs)
+    }
+
+    /// Whether this type can be left out of its parent object
+    fn optional() -> bool {
+        false
+    }
+}
+
+/// Generate a standalone JSON Schema document for a type
+pub fn schema_for<T: JsonSchema>(title: &str) -> Value {
+    let mut defs = Definitions::new();
+    let mut root = T::inline(&mut defs);
+    if let Value::Object(o) = &mut root {
+        o.insert("$schema".into(), "http://json-schema.org/draft-07/schema#".into());
+        o.insert("title".into(), title.into());
+        o.insert("definitions".into(), Value::Object(defs));
+    }
+    root
+}
+
+// helpers used by the derived implementations
+
+pub fn reference<T: JsonSchema>(name: &str, defs: &mut Definitions) -> Value {
+    if !defs.contains_key(name) {
+        // placeholder guards against recursive types
+        defs.insert(name.into(), Value::Null);
+        let schema = T::inline(defs);
+        defs.insert(name.into(), schema);
+    }
+    json!({ "$ref": format!("#/definitions/{}", name) })
+}
+
+pub fn describe(mut schema: Value, description: &str) -> Value {
+    if description.is_empty() {
+        return schema;
+    }
+    // siblings of $ref are ignored by validators
+    if schema.get("$ref").is_some() {
+        schema = json!({ "allOf": [schema] });
+    }
+    if let Value::Object(o) = &mut schema {
+        o.insert("description".into(), description.into());
+    }
+    schema
+}
+
+pub fn add_property(
+    props: &mut Map<String, Value>,
+    required: &mut Vec<Value>,
+    key: &str,
+    schema: Value,
+    req: bool,
+) {
+    props.insert(key.into(), schema);
+    if req {
+        required.push(key.into());
+    }
+}
+
+pub fn flatten_into(props: &mut Map<String, Value>, required: &mut Vec<Value>, inner: Value) {
+    if let Some(Value::Object(p)) = inner.get("properties") {
+        props.extend(p.clone());
+    }
+    if let Some(Value::Array(r)) = inner.get("required") {
+        required.extend(r.clone());
+    }
+}
+
+pub fn object(props: Map<String, Value>, required: Vec<Value>, deny_unknown: bool) -> Value {
+    let mut res = json!({ "type": "object", "properties": props });
+    if !required.is_empty() {
+        res["required"] = required.into();
+    }
+    if deny_unknown {
+        res["additionalProperties"] = false.into();
+    }
+    res
+}
+
+pub fn externally_tagged(variant: &str, content: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": { variant: content },
+        "required": [variant],
+        "additionalProperties": false,
+    })
+}
+
+pub fn internally_tagged(tag: &str, variant: &str, content: Option<Value>) -> Value {
+    let tagged = json!({
+        "type": "object",
+        "properties": { tag: { "enum": [variant] } },
+        "required": [tag],
+    });
+    match content {
+        Some(c) => json!({ "allOf": [tagged, c] }),
+        None => tagged,
+    }
+}
+
+pub fn adjacently_tagged(tag: &str, variant: &str, content_key: &str, content: Option<Value>) -> Value {
+    let mut res = internally_tagged(tag, variant, None);
+    if let Some(c) = content {
+        res["properties"][content_key] = c;
+    }
+    res
+}
+
+// implementations for std and foreign types
+
+impl JsonSchema for String {
+    fn schema(_: &mut Definitions) -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl JsonSchema for bool {
+    fn schema(_: &mut Definitions) -> Value {
+        json!({ "type": "boolean" })
+    }
+}
+
+macro_rules! unsigned_schema {
+    ($($t:ty),*) => {
+        $(impl JsonSchema for $t {
+            fn schema(_: &mut Definitions) -> Value {
+                json!({ "type": "integer", "minimum": 0 })
+            }
+        })*
+    };
+}
+unsigned_schema!(u8, u16, u32, u64, usize);
+
+macro_rules! signed_schema {
+    ($($t:ty),*) => {
+        $(impl JsonSchema for $t {
+            fn schema(_: &mut Definitions) -> Value {
+                json!({ "type": "integer" })
+            }
+        })*
+    };
+}
+signed_schema!(i8, i16, i32, i64, isize);
+
+impl JsonSchema for f64 {
+    fn schema(_: &mut Definitions) -> Value {
+        json!({ "type": "number" })
+    }
+}
+
+impl JsonSchema for Value {
+    fn schema(_: &mut Definitions) -> Value {
+        json!({})
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Option<T> {
+    fn schema(defs: &mut Definitions) -> Value {
+        T::schema(defs)
+    }
+
+    fn inline(defs: &mut Definitions) -> Value {
+        T::inline(defs)
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Box<T> {
+    fn schema(defs: &mut Definitions) -> Value {
+        T::schema(defs)
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Vec<T> {
+    fn schema(defs: &mut Definitions) -> Value {
+        json!({ "type": "array", "items": T::schema(defs) })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for BTreeSet<T> {
+    fn schema(defs: &mut Definitions) -> Value {
+        json!({ "type": "array", "items": T::schema(defs), "uniqueItems": true })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for BTreeMap<String, T> {
+    fn schema(defs: &mut Definitions) -> Value {
+        json!({ "type": "object", "additionalProperties": T::schema(defs) })
+    }
+}
+
+impl JsonSchema for k8s_openapi::api::autoscaling::v2beta2::MetricSpec {
+    fn schema(_: &mut Definitions) -> Value {
+        json!({
+            "type": "object",
+            "description": "Kubernetes autoscaling/v2beta2 MetricSpec",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{schema_for, JsonSchema};
+    use crate::Manifest;
+    use serde_json::json;
+
+    /// A documented struct
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase", deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct Fields {
+        /// Kept as is
+        plain_field: String,
+        #[serde(rename = "other")]
+        renamed: u32,
+        #[serde(default)]
+        defaulted: bool,
+        #[serde(default = "default_port")]
+        port: u16,
+        optional: Option<String>,
+        #[serde(skip)]
+        skipped: String,
+        #[serde(skip_deserializing)]
+        internal: String,
+        #[serde(flatten)]
+        inner: Inner,
+        nested: Option<Inner>,
+    }
+
+    fn default_port() -> u16 {
+        80
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Inner {
+        inner_key: String,
+        #[serde(default)]
+        inner_flag: bool,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Port(u16);
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    #[allow(dead_code)]
+    enum Unit {
+        FirstOne,
+        #[serde(rename = "2nd")]
+        Second,
+        #[serde(skip_deserializing)]
+        Hidden,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "lowercase")]
+    #[allow(dead_code)]
+    enum External {
+        Plain,
+        /// A string payload
+        Value(String),
+        Object { key: String },
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(tag = "kind")]
+    #[allow(dead_code)]
+    enum Internal {
+        A,
+        B { x: u32 },
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(tag = "t", content = "c")]
+    #[allow(dead_code)]
+    enum Adjacent {
+        A(String),
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(untagged)]
+    #[allow(dead_code)]
+    enum Untagged {
+        Nothing,
+        Number(u32),
+    }
+
+    /// Converted from an untagged enum
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(from = "Untagged")]
+    #[allow(dead_code)]
+    struct Converted(u32);
+
+    impl From<Untagged> for Converted {
+        fn from(u: Untagged) -> Self {
+            match u {
+                Untagged::Nothing => Converted(0),
+                Untagged::Number(n) => Converted(n),
+            }
+        }
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Renames {
+        kebab: Kebab,
+        screaming: Screaming,
+        pascal: Pascal,
+        variants: Variants,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "kebab-case")]
+    #[allow(dead_code)]
+    struct Kebab {
+        two_words: bool,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    #[allow(dead_code)]
+    struct Screaming {
+        two_words: bool,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "PascalCase")]
+    #[allow(dead_code)]
+    struct Pascal {
+        two_words: bool,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    #[allow(dead_code)]
+    enum Variants {
+        TwoWords,
+        #[serde(rename = "SHOUT")]
+        Shout,
+    }
+
+    #[test]
+    fn manifest_schema() {
+        let schema = schema_for::<Manifest>("shipcat.yml");
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["title"], "shipcat.yml");
+        let props = &schema["properties"];
+        // doc comments become descriptions
+        let desc = props["name"]["description"].as_str().unwrap();
+        assert!(desc.starts_with("Name of the service"));
+        // nested structs are referenced
+        assert_eq!(props["metadata"]["allOf"][0]["$ref"], "#/definitions/Metadata");
+        assert!(schema["definitions"]["Metadata"]["properties"]["team"].is_object());
+        // enum constraints
+        let workload = &schema["definitions"]["PrimaryWorkload"];
+        assert_eq!(workload["enum"], serde_json::json!(["Deployment", "Statefulset", "Daemonset"]));
+        // types deserialized from another type use its schema
+        let sa = &schema["definitions"]["ServiceAccount"];
+        assert_eq!(sa["anyOf"].as_array().map(Vec::len), Some(2));
+        assert!(schema["definitions"].get("ServiceAccountSource").is_none());
+        // internal fields are not part of the input
+        assert!(props.get("secrets").is_none());
+        assert!(props.get("state").is_none());
+        // every reference resolves
+        for (name, def) in schema["definitions"].as_object().unwrap() {
+            assert!(def.is_object(), "definition {} is complete", name);
+        }
+        assert!(<Option<String> as JsonSchema>::optional());
+    }
+
+    #[test]
+    fn derive_struct() {
+        let schema = schema_for::<Fields>("fields");
+        assert_eq!(schema["description"], "A documented struct");
+        assert_eq!(schema["additionalProperties"], false);
+        let props = schema["properties"].as_object().unwrap();
+        let mut keys: Vec<_> = props.keys().map(String::as_str).collect();
+        keys.sort();
+        let expected = [
+            "defaulted",
+            "inner_flag",
+            "inner_key",
+            "nested",
+            "optional",
+            "other",
+            "plainField",
+            "port",
+        ];
+        assert_eq!(keys, expected);
+        assert_eq!(props["plainField"], json!({ "type": "string", "description": "Kept as is" }));
+        assert_eq!(props["port"], json!({ "type": "integer", "minimum": 0 }));
+        assert_eq!(props["nested"], json!({ "$ref": "#/definitions/Inner" }));
+        // defaults and options are not required, flattened requirements are kept
+        assert_eq!(schema["required"], json!(["plainField", "other", "inner_key"]));
+        let inner = &schema["definitions"]["Inner"];
+        assert_eq!(inner["required"], json!(["inner_key"]));
+        assert!(inner.get("additionalProperties").is_none());
+
+        assert_eq!(schema_for::<Port>("port")["minimum"], 0);
+    }
+
+    #[test]
+    fn derive_enums() {
+        let unit = schema_for::<Unit>("unit");
+        assert_eq!(unit["enum"], json!(["first_one", "2nd"]));
+
+        let external = schema_for::<External>("external");
+        let variants = &external["oneOf"];
+        assert_eq!(variants[0], json!({ "type": "string", "enum": ["plain"] }));
+        assert_eq!(variants[1]["properties"]["value"], json!({ "type": "string" }));
+        assert_eq!(variants[1]["required"], json!(["value"]));
+        assert_eq!(variants[1]["description"], "A string payload");
+        assert_eq!(variants[2]["properties"]["object"]["required"], json!(["key"]));
+
+        let internal = schema_for::<Internal>("internal");
+        let variants = &internal["oneOf"];
+        assert_eq!(variants[0]["properties"]["kind"]["enum"], json!(["A"]));
+        assert_eq!(variants[0]["required"], json!(["kind"]));
+        assert_eq!(variants[1]["allOf"][0]["properties"]["kind"]["enum"], json!(["B"]));
+        assert_eq!(variants[1]["allOf"][1]["required"], json!(["x"]));
+
+        let adjacent = schema_for::<Adjacent>("adjacent");
+        let a = &adjacent["oneOf"][0];
+        assert_eq!(a["properties"]["t"]["enum"], json!(["A"]));
+        assert_eq!(a["properties"]["c"], json!({ "type": "string" }));
+
+        let untagged = schema_for::<Untagged>("untagged");
+        assert_eq!(untagged["anyOf"], json!([{ "type": "null" }, { "type": "integer", "minimum": 0 }]));
+    }
+
+    #[test]
+    fn derive_from() {
+        let schema = schema_for::<Converted>("converted");
+        assert_eq!(schema["anyOf"].as_array().map(Vec::len), Some(2));
+        assert_eq!(schema["description"], "Converted from an untagged enum");
+        assert!(schema["definitions"].get("Untagged").is_none());
+    }
+
+    #[test]
+    fn derive_renames() {
+        let schema = schema_for::<Renames>("renames");
+        let defs = &schema["definitions"];
+        assert!(defs["Kebab"]["properties"]["two-words"].is_object());
+        assert!(defs["Screaming"]["properties"]["TWO_WORDS"].is_object());
+        assert!(defs["Pascal"]["properties"]["TwoWords"].is_object());
+        assert_eq!(defs["Variants"]["enum"], json!(["twoWords", "SHOUT"]));
+    }
+}
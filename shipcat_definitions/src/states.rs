@@ -1,10 +1,13 @@
 use super::{vault::Vault, Manifest, Region, Result};
 
 /// Type of primary workload that is associated with the Manifest
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub enum PrimaryWorkload {
     Deployment,
+    /// Pods with stable names and storage, see `statefulSet`
     Statefulset,
+    /// One pod on every node
+    Daemonset,
 }
 
 impl ToString for PrimaryWorkload {
@@ -50,6 +53,16 @@ pub enum ManifestState {
     /// This is the `shipcat values` equivalent of a manifest.
     Stubbed,
 
+    /// A lazily filled manifest
+    ///
+    /// Like a `Completed` manifest, except that secrets are deferred:
+    /// - evars templated, but vault placeholders left intact
+    /// - configs left in template form (they may use secret values)
+    ///
+    /// Meant for structural operations across many manifests.
+    /// Becomes `Completed` or `Stubbed` through `Manifest::resolve_secrets`.
+    Lazy,
+
     /// The Base manifest
     ///
     /// A state that is upgradeable to a completed one, contains all the pieces,
@@ -82,24 +95,71 @@ impl Default for ManifestState {
 /// - creating a base manifest from its backing
 impl Manifest {
     /// Upgrade a `Base` manifest to either a Complete or a Stubbed one
-    async fn upgrade(mut self, reg: &Region, state: ManifestState) -> Result<Self> {
-        assert_eq!(self.state, ManifestState::Base); // sanity
+    async fn upgrade(self, reg: &Region, state: ManifestState) -> Result<Self> {
         let v = match state {
-            ManifestState::Completed => Vault::regional(&reg.vault)?,
+            ManifestState::Completed => Vault::regional(&reg.vault).await?,
             ManifestState::Stubbed => Vault::mocked(&reg.vault)?,
             _ => bail!("Can only upgrade a Base manifest to Completed or Stubbed"),
         };
+        Manifest::fill_from(self, reg, &v).await
+    }
+
+    /// Fill in an already parsed `Base` manifest using a given vault client
+    ///
+    /// The manifest can come from anywhere (disk, a CRD, a PR diff),
+    /// as nothing is read from the services directory here.
+    /// A mocked vault results in a Stubbed manifest, otherwise it is Completed.
+    pub async fn fill_from(raw: Manifest, reg: &Region, vault: &Vault) -> Result<Manifest> {
+        let mut mf = Manifest::fill_lazy(raw, reg)?;
+        mf.resolve_secrets(vault, reg).await?;
+        Ok(mf)
+    }
+
+    /// Fill in an already parsed `Base` manifest without resolving secrets
+    ///
+    /// Vault placeholders are left intact until `resolve_secrets` is called,
+    /// so that tooling which never needs the values skips the vault round-trips.
+    pub fn fill_lazy(mut raw: Manifest, reg: &Region) -> Result<Manifest> {
+        if !raw.is_base() {
+            bail!("Can only fill a Base manifest, {} is {:?}", raw.name, raw.state);
+        }
         // replace one-off templates in evar strings with values
         // note that this happens before secrets because:
         // secrets may be injected at this step from the Region
-        self.template_evars(reg)?;
-        // secrets before configs (.j2 template files use raw secret values)
-        self.secrets(&v, &reg.vault).await?;
+        raw.template_evars(reg)?;
+        raw.template_annotations(reg)?;
+        // after any version change, as these include the version
+        raw.add_recommended_labels();
+        raw.state = ManifestState::Lazy;
+        Ok(raw)
+    }
 
+    /// Resolve the deferred secrets of a `Lazy` manifest
+    ///
+    /// Configs are templated here, as `.j2` template files use raw secret values.
+    /// A mocked vault results in a Stubbed manifest, otherwise it is Completed.
+    pub async fn resolve_secrets(&mut self, vault: &Vault, reg: &Region) -> Result<()> {
+        if self.state != ManifestState::Lazy {
+            bail!(
+                "Can only resolve secrets of a Lazy manifest, {} is {:?}",
+                self.name,
+                self.state
+            );
+        }
+        self.secrets(vault, reg).await?;
         // templates last
         self.template_configs(reg)?;
-        self.state = state;
-        Ok(self)
+        self.state = if vault.is_mocked() {
+            ManifestState::Stubbed
+        } else {
+            ManifestState::Completed
+        };
+        Ok(())
+    }
+
+    /// Fill in a Base manifest, deferring secrets until `resolve_secrets`
+    pub fn lazy(self, reg: &Region) -> Result<Self> {
+        Manifest::fill_lazy(self, reg)
     }
 
     /// Complete a Base manifest with stub secrets
@@ -0,0 +1,108 @@
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::Result;
+
+/// Severity of a Prometheus alert
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Critical,
+    Warning,
+    Info,
+}
+
+impl Default for AlertSeverity {
+    fn default() -> Self {
+        AlertSeverity::Warning
+    }
+}
+
+/// A Prometheus alerting rule for a service
+///
+/// ```yaml
+/// alerts:
+/// - name: HighErrorRate
+///   expr: sum(rate(http_requests_total{app="webapp",code=~"5.."}[5m])) > 1
+///   for: 10m
+///   severity: critical
+///   annotations:
+///     summary: webapp returns errors
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct AlertRule {
+    /// Name of the alert, e.g. `HighErrorRate`
+    pub name: String,
+    /// PromQL expression that fires the alert when it returns results
+    pub expr: String,
+    /// How long `expr` must return results before the alert fires, e.g. `5m`
+    #[serde(rename = "for", default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    /// Severity label of the alert
+    #[serde(default)]
+    pub severity: AlertSeverity,
+    /// Annotations of the alert, e.g. `summary` or `runbook_url`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl AlertRule {
+    pub fn verify(&self) -> Result<()> {
+        let name_re = Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+        if !name_re.is_match(&self.name) {
+            bail!(
+                "Alert name '{}' must be alphanumeric and start with a letter",
+                self.name
+            );
+        }
+        if self.expr.trim().is_empty() {
+            bail!("Alert {} needs an expr", self.name);
+        }
+        if let Some(d) = &self.duration {
+            // prometheus durations, e.g. 90s or 1h30m
+            let duration_re = Regex::new(r"^([0-9]+(ms|s|m|h|d|w|y))+$").unwrap();
+            if !duration_re.is_match(d) {
+                bail!("Alert {} has an invalid for duration '{}'", self.name, d);
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify alerting rules of a service, and that their names are distinct
+    pub fn verify_all(svc: &str, alerts: &[AlertRule]) -> Result<()> {
+        let mut names = BTreeSet::new();
+        for a in alerts {
+            a.verify()?;
+            if !names.insert(&a.name) {
+                bail!("{} has more than one alert named {}", svc, a.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AlertRule;
+
+    #[test]
+    fn verify_alerts() {
+        let mut alert = AlertRule {
+            name: "HighLatency".into(),
+            expr: "histogram_quantile(0.99, rate(latency_bucket[5m])) > 0.5".into(),
+            duration: Some("1h30m".into()),
+            ..Default::default()
+        };
+        assert!(alert.verify().is_ok());
+        assert!(AlertRule::verify_all("fake-svc", &[alert.clone(), alert.clone()]).is_err());
+        alert.duration = Some("10 minutes".into());
+        assert!(alert.verify().is_err());
+        alert.duration = None;
+        alert.expr = " ".into();
+        assert!(alert.verify().is_err());
+        alert.expr = "up == 0".into();
+        alert.name = "high latency".into();
+        assert!(alert.verify().is_err());
+    }
+}
@@ -6,7 +6,7 @@ use super::Result;
 use k8s_openapi::api::autoscaling::v2beta2::MetricSpec;
 
 /// Configuration parameters for HorizontalPodAutoScaler
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct AutoScaling {
     pub minReplicas: u32,
     pub maxReplicas: u32,
@@ -1,24 +1,38 @@
+use serde::{Serialize, Serializer};
+use std::ops::{Deref, DerefMut};
+
 use super::Result;
 
 /// ConfigMap
 ///
 /// A special abstraction that is used to create a kubernetes ConfigMap
 /// Deals with automatic mounting into the pods.
-///
-/// Only one of these is supported.
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct ConfigMap {
+    /// Name of the kubernetes ConfigMap
+    ///
+    /// Defaults to `{service}-config` for the first ConfigMap of a service,
+    /// and `{service}-config-{mount directory}` for the others.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     /// Container-local directory path where configs are available
     pub mount: String,
     /// Files from the config map to mount at this mountpath
+    #[serde(default)]
     pub files: Vec<ConfigMappedFile>,
+    /// Directory (relative to the service folder) whose files are all templated
+    ///
+    /// Files matching patterns in a `.shipcatignore` inside this directory are excluded.
+    /// Remaining files are added to `files` and mounted without their `.j2` suffix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
 }
 
 /// ConfigMapped File
 ///
 /// Files that are mounted under the parent `mount` path.
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct ConfigMappedFile {
     /// Name of file to template (from service repo paths)
@@ -32,26 +46,218 @@ pub struct ConfigMappedFile {
     pub value: Option<String>,
 }
 
-
 impl ConfigMap {
+    /// Generated name of the ConfigMap at position `index` for a service
+    pub fn implicit_name(&self, svc: &str, index: usize) -> String {
+        if index == 0 {
+            return format!("{}-config", svc);
+        }
+        let dir = self
+            .mount
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+        format!("{}-config-{}", svc, dir.trim_matches('-'))
+    }
+
     pub fn verify(&self) -> Result<()> {
-        // mount paths can't be empty string
-        if self.mount == "" || self.mount.starts_with('~') {
-            bail!("Invalid mountpath '{}'", self.mount)
+        // mount paths must be absolute, so that file destinations are too
+        if !self.mount.starts_with('/') {
+            bail!("Invalid mountpath '{}' - must be an absolute path", self.mount)
         }
         // and must end in a slash to have a standard
         if !self.mount.ends_with('/') {
             bail!("Mount path '{}' must end with a slash", self.mount);
         }
+        if let Some(dir) = &self.directory {
+            if dir == "" || dir.starts_with('/') || dir.split('/').any(|p| p == "..") {
+                bail!(
+                    "Config directory '{}' must be a path inside the service folder",
+                    dir
+                );
+            }
+        }
+        let mut dests: Vec<(String, &str)> = vec![];
         for f in &self.files {
             if !f.name.ends_with(".j2") {
                 bail!("Only supporting templated config files atm")
             }
+            if f.name.starts_with('/') || f.name.split('/').any(|p| p == "..") {
+                bail!(
+                    "Config file '{}' must be a path inside the service folder",
+                    f.name
+                );
+            }
             if f.dest == "" {
                 bail!("Empty mount destination for {}", f.name);
             }
+            // dest is relative to the mount, and must stay inside it
+            if f.dest.starts_with('/') || f.dest.split('/').any(|p| p == "..") {
+                bail!(
+                    "Mount destination '{}' for {} must be a path inside {}",
+                    f.dest,
+                    f.name,
+                    self.mount
+                );
+            }
+            let path = format!("{}{}", self.mount, f.dest.trim_start_matches("./"));
+            if let Some((_, other)) = dests.iter().find(|(p, _)| *p == path) {
+                bail!(
+                    "Config files {} and {} are both mounted at {}",
+                    other,
+                    f.name,
+                    path
+                );
+            }
+            dests.push((path, &f.name));
         }
         // TODO: verify file exists? done later anyway
         Ok(())
     }
 }
+
+/// One or more ConfigMaps
+///
+/// Either a single ConfigMap:
+///
+/// ```yaml
+/// configs:
+///   mount: /config/
+///   files:
+///   - name: webhooks.json.j2
+///     dest: webhooks.json
+/// ```
+///
+/// or a list of them, mounted at different paths:
+///
+/// ```yaml
+/// configs:
+/// - mount: /config/
+///   files:
+///   - name: webhooks.json.j2
+///     dest: webhooks.json
+/// - mount: /certs/
+///   files:
+///   - name: ca.pem.j2
+///     dest: ca.pem
+/// ```
+///
+/// A single ConfigMap serializes back to a single object, so charts expecting one keep working.
+#[derive(Deserialize, Debug, Clone, Default, JsonSchema)]
+#[serde(from = "ConfigMapsSource")]
+pub struct ConfigMaps(pub Vec<ConfigMap>);
+
+/// The accepted input forms of `ConfigMaps`
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ConfigMapsSource {
+    /// A single ConfigMap
+    Single(ConfigMap),
+    /// Multiple ConfigMaps
+    Multiple(Vec<ConfigMap>),
+}
+
+impl From<ConfigMapsSource> for ConfigMaps {
+    fn from(src: ConfigMapsSource) -> Self {
+        match src {
+            ConfigMapsSource::Single(c) => ConfigMaps(vec![c]),
+            ConfigMapsSource::Multiple(cs) => ConfigMaps(cs),
+        }
+    }
+}
+
+impl Serialize for ConfigMaps {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.0.as_slice() {
+            [single] => single.serialize(serializer),
+            all => all.serialize(serializer),
+        }
+    }
+}
+
+impl Deref for ConfigMaps {
+    type Target = Vec<ConfigMap>;
+
+    fn deref(&self) -> &Vec<ConfigMap> {
+        &self.0
+    }
+}
+
+impl DerefMut for ConfigMaps {
+    fn deref_mut(&mut self) -> &mut Vec<ConfigMap> {
+        &mut self.0
+    }
+}
+
+impl ConfigMaps {
+    /// Verify all ConfigMaps, and that their names and mount paths are distinct
+    pub fn verify(&self) -> Result<()> {
+        let mut names = vec![];
+        let mut mounts = vec![];
+        for c in self.iter() {
+            c.verify()?;
+            if let Some(n) = &c.name {
+                if names.contains(&n) {
+                    bail!("ConfigMap name {} is used more than once", n);
+                }
+                names.push(n);
+            }
+            if mounts.contains(&&c.mount) {
+                bail!("More than one ConfigMap is mounted at {}", c.mount);
+            }
+            mounts.push(&c.mount);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigMaps;
+
+    #[test]
+    fn config_map_forms() {
+        let single: ConfigMaps = serde_yaml::from_str("mount: /config/").unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].implicit_name("webapp", 0), "webapp-config");
+        // a single ConfigMap is not turned into a list
+        let out = serde_yaml::to_string(&single).unwrap();
+        assert!(out.contains("\nmount: /config/"));
+
+        let yaml = "- mount: /config/\n- mount: /etc/TLS_certs/";
+        let multiple: ConfigMaps = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(multiple.len(), 2);
+        assert_eq!(multiple[1].implicit_name("webapp", 1), "webapp-config-tls-certs");
+        let out = serde_yaml::to_string(&multiple).unwrap();
+        assert!(out.contains("- mount: /config/"));
+        multiple.verify().unwrap();
+
+        let yaml = "- mount: /config/\n- mount: /config/";
+        let clashing: ConfigMaps = serde_yaml::from_str(yaml).unwrap();
+        assert!(clashing.verify().is_err());
+    }
+
+    #[test]
+    fn config_file_paths() {
+        let mk = |files: &str| -> ConfigMaps {
+            serde_yaml::from_str(&format!("mount: /config/\nfiles:\n{}", files)).unwrap()
+        };
+        let ok = mk("- name: a.json.j2\n  dest: a.json\n- name: certs/b.pem.j2\n  dest: certs/b.pem");
+        ok.verify().unwrap();
+
+        let clobbered = mk("- name: a.json.j2\n  dest: a.json\n- name: b.json.j2\n  dest: ./a.json");
+        let err = clobbered.verify().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Config files a.json.j2 and b.json.j2 are both mounted at /config/a.json"
+        );
+        assert!(mk("- name: a.json.j2\n  dest: ../etc/passwd").verify().is_err());
+        assert!(mk("- name: ../other/a.json.j2\n  dest: a.json").verify().is_err());
+
+        let relative: ConfigMaps = serde_yaml::from_str("mount: config/").unwrap();
+        assert!(relative.verify().is_err());
+    }
+}
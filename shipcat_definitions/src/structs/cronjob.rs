@@ -1,7 +1,10 @@
-use super::Container;
-use std::collections::BTreeMap;
+use super::{Container, Result};
+use std::collections::{BTreeMap, BTreeSet};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Longest `CronJob` name that leaves room for the suffix of its `Job`s
+const CRONJOB_NAME_LIMIT: usize = 52;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct JobVolumeClaim {
     /// The cron job name
@@ -10,7 +13,7 @@ pub struct JobVolumeClaim {
 }
 
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct CronJob {
     /// Common properties for all types of container
     #[serde(flatten)]
@@ -45,3 +48,63 @@ pub struct CronJob {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub podAnnotations: BTreeMap<String, String>,
 }
+
+impl CronJob {
+    /// Verify cron jobs have distinct names that fit in kubernetes
+    ///
+    /// The chart names each `CronJob` after the job, and kubernetes appends an 11 character
+    /// suffix to the names of the `Job`s it creates.
+    pub fn verify_names(svc: &str, jobs: &[CronJob]) -> Result<()> {
+        let mut seen = BTreeSet::new();
+        for cj in jobs {
+            let name = &cj.container.name;
+            if name == svc {
+                bail!("cronJob {} has the same name as its service", name);
+            }
+            if name.len() > CRONJOB_NAME_LIMIT {
+                bail!(
+                    "cronJob {} in {} is longer than {} characters",
+                    name,
+                    svc,
+                    CRONJOB_NAME_LIMIT
+                );
+            }
+            if !seen.insert(name) {
+                bail!("cronJob {} is defined more than once in {}", name, svc);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CronJob;
+
+    #[test]
+    fn verify_names() {
+        let job = |name: &str| {
+            let mut cj = CronJob::default();
+            cj.container.name = name.into();
+            cj.schedule = "0 * * * *".into();
+            cj
+        };
+        let mut jobs = vec![job("fake-svc-cleanup"), job("fake-svc-report")];
+        assert!(CronJob::verify_names("fake-svc", &jobs).is_ok());
+
+        jobs.push(job("fake-svc-cleanup"));
+        let err = CronJob::verify_names("fake-svc", &jobs).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cronJob fake-svc-cleanup is defined more than once in fake-svc"
+        );
+
+        let err = CronJob::verify_names("fake-svc", &[job("fake-svc")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cronJob fake-svc has the same name as its service"
+        );
+
+        assert!(CronJob::verify_names("fake-svc", &[job(&"a".repeat(53))]).is_err());
+    }
+}
@@ -1,10 +1,10 @@
-use super::Result;
+use super::{port::PortProtocol, Result};
 use std::path::Path;
 
 /// Supported dependency protocols
 ///
 /// Forces lowercase values of this enum to be used
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum DependencyProtocol {
     /// HTTP REST dependency
@@ -25,7 +25,7 @@ impl Default for DependencyProtocol {
 }
 
 /// Dependency of a service
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Dependency {
     /// Name of service relied upon (used to goto dependent manifest)
@@ -40,6 +40,14 @@ pub struct Dependency {
     pub protocol: DependencyProtocol,
     /// Intent behind dependency - for manifest level descriptiveness
     pub intent: Option<String>,
+    /// Port the dependency is called on
+    ///
+    /// Network policies allow traffic to all ports of the dependency when this is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u32>,
+    /// Transport protocol of `port`, TCP by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub portProtocol: Option<PortProtocol>,
 }
 
 fn default_api_version() -> String {
@@ -63,6 +71,17 @@ impl Dependency {
                 ver
             );
         }
+        match self.port {
+            Some(p) if p == 0 || p > 65535 => bail!(
+                "Dependency {} has port {} outside the valid range 1-65535",
+                self.name,
+                p
+            ),
+            None if self.portProtocol.is_some() => {
+                bail!("Dependency {} sets a portProtocol without a port", self.name)
+            }
+            _ => {}
+        }
         Ok(())
     }
 }
@@ -0,0 +1,50 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::Result;
+
+/// Verify that feature flags are supported by the chart
+///
+/// `allowed` is the `chartFeatures` of the config, keyed by chart name.
+pub fn verify(
+    svc: &str,
+    chart: &str,
+    features: &BTreeMap<String, bool>,
+    allowed: &BTreeMap<String, BTreeSet<String>>,
+) -> Result<()> {
+    let supported = allowed.get(chart);
+    for k in features.keys() {
+        if !supported.map_or(false, |s| s.contains(k)) {
+            bail!(
+                "Feature {} in {} is not supported by chart {} - add it to chartFeatures",
+                k,
+                svc,
+                chart
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn verify_features() {
+        let mut features = BTreeMap::new();
+        let mut allowed = BTreeMap::new();
+        assert!(verify("fake-svc", "base", &features, &allowed).is_ok());
+
+        features.insert("ingres".to_string(), true);
+        assert!(verify("fake-svc", "base", &features, &allowed).is_err());
+        allowed.insert("base".to_string(), btreeset!["ingress".to_string(), "pdb".to_string()]);
+        assert!(verify("fake-svc", "base", &features, &allowed).is_err());
+
+        features.remove("ingres");
+        features.insert("ingress".into(), true);
+        features.insert("pdb".into(), false);
+        assert!(verify("fake-svc", "base", &features, &allowed).is_ok());
+        assert!(verify("fake-svc", "other", &features, &allowed).is_err());
+    }
+}
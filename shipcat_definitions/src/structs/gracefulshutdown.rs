@@ -0,0 +1,78 @@
+use super::{LifeCycle, Result};
+
+/// Graceful deregistration from service discovery before shutdown
+///
+/// Translated into a `preStop` lifecycle hook and `terminationGracePeriodSeconds`.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct GracefulShutdown {
+    /// Seconds to keep serving after deregistering, while discovery catches up
+    pub deregisterDelaySeconds: u32,
+    /// Path on the `httpPort` to `POST` to before waiting, e.g. `/deregister`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deregisterEndpoint: Option<String>,
+    /// Total seconds kubernetes waits for the pod to terminate
+    #[serde(default = "default_termination_grace_period")]
+    pub terminationGracePeriodSeconds: u32,
+}
+
+fn default_termination_grace_period() -> u32 {
+    30
+}
+
+impl GracefulShutdown {
+    pub fn verify(&self, http_port: Option<u32>) -> Result<()> {
+        if self.deregisterDelaySeconds >= self.terminationGracePeriodSeconds {
+            bail!(
+                "gracefulShutdown.deregisterDelaySeconds ({}) must be less than terminationGracePeriodSeconds ({})",
+                self.deregisterDelaySeconds,
+                self.terminationGracePeriodSeconds
+            );
+        }
+        if let Some(ep) = &self.deregisterEndpoint {
+            if !ep.starts_with('/') {
+                bail!("gracefulShutdown.deregisterEndpoint must be a path starting with /");
+            }
+            if ep.contains(|c: char| c.is_whitespace() || c == '\'') {
+                bail!("gracefulShutdown.deregisterEndpoint cannot contain whitespace or quotes");
+            }
+            if http_port.is_none() {
+                bail!("gracefulShutdown.deregisterEndpoint needs an httpPort");
+            }
+        }
+        Ok(())
+    }
+
+    /// The `preStop` hook that deregisters and then waits out the delay
+    pub fn lifecycle(&self, http_port: Option<u32>) -> LifeCycle {
+        let sleep = format!("sleep {}", self.deregisterDelaySeconds);
+        let script = match (&self.deregisterEndpoint, http_port) {
+            (Some(ep), Some(port)) => format!(
+                "curl -fsS -X POST 'http://localhost:{}{}' || true; {}",
+                port, ep, sleep
+            ),
+            _ => sleep,
+        };
+        LifeCycle::pre_stop(vec!["/bin/sh".into(), "-c".into(), script])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GracefulShutdown;
+
+    #[test]
+    fn verify() {
+        let gs: GracefulShutdown =
+            serde_yaml::from_str("deregisterDelaySeconds: 10\nderegisterEndpoint: /deregister").unwrap();
+        assert_eq!(gs.terminationGracePeriodSeconds, 30);
+        assert!(gs.verify(Some(8080)).is_ok());
+        assert!(gs.verify(None).is_err());
+        let hook = serde_yaml::to_string(&gs.lifecycle(Some(8080))).unwrap();
+        assert!(hook.contains("curl -fsS -X POST 'http://localhost:8080/deregister' || true; sleep 10"));
+
+        let slow: GracefulShutdown =
+            serde_yaml::from_str("deregisterDelaySeconds: 30\nterminationGracePeriodSeconds: 30").unwrap();
+        assert!(slow.verify(None).is_err());
+    }
+}
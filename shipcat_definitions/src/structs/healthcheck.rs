@@ -1,3 +1,5 @@
+use super::Result;
+
 /// HealthCheck
 ///
 /// Designed for HTTP services for now
@@ -5,7 +7,7 @@
 ///
 /// If we need complete control over these, consider writing a probes struct
 /// and making it only allowed if this is not present.
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct HealthCheck {
     /// Where the health check is located
@@ -17,6 +19,9 @@ pub struct HealthCheck {
     /// Health check port (if different from main httpPort)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub port: Option<u32>,
+    /// Startup probe to hold off the health check until the service has booted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub startup: Option<StartupProbe>,
 }
 fn health_check_url_default() -> String {
     "/health".into()
@@ -24,3 +29,54 @@ fn health_check_url_default() -> String {
 fn health_check_wait_time_default() -> u32 {
     30
 }
+
+/// Waits longer than this are better served by a startup probe
+const SLOW_STARTUP_WAIT: u32 = 60;
+
+/// Startup probe for slow booting services
+///
+/// Used to generate a `startupProbe` in kubernetes.
+/// Liveness and readiness checks only start once this succeeds,
+/// so the service gets up to `failureThreshold * periodSeconds` to boot.
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct StartupProbe {
+    /// Where the startup check is located
+    #[serde(default = "health_check_url_default")]
+    pub path: String,
+    /// How many failed checks to allow before restarting the container
+    #[serde(default = "startup_failure_threshold_default")]
+    pub failureThreshold: u32,
+    /// How often to check in seconds
+    #[serde(default = "startup_period_seconds_default")]
+    pub periodSeconds: u32,
+}
+fn startup_failure_threshold_default() -> u32 {
+    30
+}
+fn startup_period_seconds_default() -> u32 {
+    10
+}
+
+impl StartupProbe {
+    /// Longest time in seconds the service is given to boot
+    pub fn max_startup_time(&self) -> u32 {
+        self.failureThreshold * self.periodSeconds
+    }
+}
+
+impl HealthCheck {
+    pub fn verify(&self) -> Result<()> {
+        if let Some(sp) = &self.startup {
+            if sp.failureThreshold == 0 || sp.periodSeconds == 0 {
+                bail!("health.startup needs a non-zero failureThreshold and periodSeconds");
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the `wait` is long enough that a startup probe should be used instead
+    pub fn needs_startup_probe(&self) -> bool {
+        self.startup.is_none() && self.wait > SLOW_STARTUP_WAIT
+    }
+}
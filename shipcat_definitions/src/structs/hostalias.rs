@@ -1,9 +1,10 @@
 use super::Result;
 use regex::Regex;
+use std::net::IpAddr;
 
 // HostAlias support for all pods regardless of network configuration.
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
 pub struct HostAlias {
     /// ip address string
     pub ip: String,
@@ -13,22 +14,58 @@ pub struct HostAlias {
 
 impl HostAlias {
     /// Verify syntax
+    ///
+    /// The ip can be IPv4 or IPv6, and hostnames must be valid DNS names.
     pub fn verify(&self) -> Result<()> {
-        // Commonly accepted hostname regex from https://stackoverflow.com/questions/106179/regular-expression-to-match-dns-hostname-or-ip-address
-        let ip_re = Regex::new(r"^(([0-9]|[1-9][0-9]|1[0-9]{2}|2[0-4][0-9]|25[0-5])\.){3}([0-9]|[1-9][0-9]|1[0-9]{2}|2[0-4][0-9]|25[0-5])$").unwrap();
-        if self.ip == "" || !ip_re.is_match(&self.ip) {
-            bail!("The ip address for the host alias is incorrect");
+        if self.ip.parse::<IpAddr>().is_err() {
+            bail!("The ip address '{}' for the host alias is incorrect", self.ip);
         }
         if self.hostnames.is_empty() {
             bail!("At least one hostname must be specified for the host alias");
         }
+        // labels of letters, digits and dashes, not starting or ending with a dash
+        let label_re = Regex::new(r"^([a-zA-Z0-9]|[a-zA-Z0-9][a-zA-Z0-9\-]*[a-zA-Z0-9])$").unwrap();
         for hostname in &self.hostnames {
-            // Commonly accepted ip address regex from https://stackoverflow.com/questions/106179/regular-expression-to-match-dns-hostname-or-ip-address
-            let host_re = Regex::new(r"^(([a-zA-Z0-9]|[a-zA-Z0-9][a-zA-Z0-9\-]*[a-zA-Z0-9])\.)*([A-Za-z0-9]|[A-Za-z0-9][A-Za-z0-9\-]*[A-Za-z0-9])$").unwrap();
-            if !host_re.is_match(&hostname) {
-                bail!("The hostname {} is incorrect for {}", hostname, self.ip);
+            let valid = hostname.len() <= 253
+                && hostname
+                    .split('.')
+                    .all(|label| label.len() <= 63 && label_re.is_match(label));
+            if !valid {
+                bail!("The hostname '{}' is incorrect for {}", hostname, self.ip);
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::HostAlias;
+
+    #[test]
+    fn verify_host_alias() {
+        let mut ha = HostAlias {
+            ip: "10.0.0.1".into(),
+            hostnames: vec!["db.internal".into(), "db".into()],
+        };
+        assert!(ha.verify().is_ok());
+        ha.ip = "fd00::1".into();
+        assert!(ha.verify().is_ok());
+
+        ha.ip = "10.0.0.256".into();
+        let err = ha.verify().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The ip address '10.0.0.256' for the host alias is incorrect"
+        );
+        ha.ip = "10.0.0.1".into();
+
+        ha.hostnames.push("db..internal".into());
+        let err = ha.verify().unwrap_err();
+        assert_eq!(err.to_string(), "The hostname 'db..internal' is incorrect for 10.0.0.1");
+        ha.hostnames[2] = "-db.internal".into();
+        assert!(ha.verify().is_err());
+        ha.hostnames[2] = format!("{}.internal", "a".repeat(64));
+        assert!(ha.verify().is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use super::Result;
+use crate::{Warning, WarningCode};
+
+/// Values kubernetes accepts for a pod's `dnsPolicy`
+const DNS_POLICIES: [&str; 3] = ["ClusterFirst", "ClusterFirstWithHostNet", "Default"];
+
+/// Verify the dns policy works with the network mode
+///
+/// Pods on the host network cannot resolve cluster names with the default policy.
+/// Host networking is always warned about, as it exposes the node.
+pub fn verify(svc: &str, host_network: bool, dns_policy: Option<&str>) -> Result<Vec<Warning>> {
+    if let Some(dp) = dns_policy {
+        if !DNS_POLICIES.contains(&dp) {
+            bail!(
+                "dnsPolicy {} for {} must be one of {}",
+                dp,
+                svc,
+                DNS_POLICIES.join(", ")
+            );
+        }
+    }
+    if !host_network {
+        return Ok(vec![]);
+    }
+    match dns_policy {
+        Some("ClusterFirstWithHostNet") | Some("Default") => {}
+        _ => bail!(
+            "{} uses hostNetwork and needs dnsPolicy ClusterFirstWithHostNet or Default",
+            svc
+        ),
+    }
+    Ok(vec![Warning::new(
+        WarningCode::HostNetwork,
+        format!(
+            "{} uses HOST NETWORKING - its ports are bound on the node and it can see all node traffic",
+            svc
+        ),
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify;
+    use crate::WarningCode;
+
+    #[test]
+    fn verify_host_network() {
+        assert!(verify("fake-svc", false, None).unwrap().is_empty());
+        assert!(verify("fake-svc", false, Some("ClusterLast")).is_err());
+        let err = verify("fake-svc", true, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "fake-svc uses hostNetwork and needs dnsPolicy ClusterFirstWithHostNet or Default"
+        );
+        let warnings = verify("fake-svc", true, Some("ClusterFirstWithHostNet")).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, WarningCode::HostNetwork);
+    }
+}
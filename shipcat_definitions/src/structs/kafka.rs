@@ -1,7 +1,7 @@
 use crate::region::Region;
 use std::collections::BTreeMap;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Kafka {
     #[serde(default)]
     pub mountPodIP: bool,
@@ -1,10 +1,13 @@
-use std::{collections::BTreeMap, ops::Not};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Not,
+};
 
-use super::Authorization;
+use super::{Authorization, Result};
 use crate::deserializers::comma_separated_string;
 
 /// Kong setup for a service
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[serde(default)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Kong {
@@ -77,6 +80,12 @@ pub struct Kong {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cors: Option<Cors>,
 
+    /// Configuration parameters for the rate limiting plugin
+    ///
+    /// Defaults to `kong.rate_limit` in the region defaults.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+
     /// When internal is set to true, also add allow these ips through
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub additional_internal_ips: Vec<String>,
@@ -123,8 +132,81 @@ fn preserve_host_default() -> bool {
     true
 }
 
+impl Kong {
+    /// Names of the Kong plugins this API enables
+    ///
+    /// Plugins added to every API (`correlation-id`, `tcp-log`) are not included.
+    pub fn plugins(&self) -> Vec<&'static str> {
+        let mut plugins = vec![];
+        if let Some(a) = &self.authorization {
+            plugins.push("jwt");
+            plugins.push("jwt-validator");
+            if a.allow_cookies {
+                plugins.push("json-cookies-to-headers");
+                plugins.push("json-cookies-csrf");
+            }
+        } else if self.auth == Some(Authentication::Jwt) {
+            plugins.push("jwt");
+        }
+        if self.babylon_auth_header.is_some() {
+            plugins.push("babylon-auth-header");
+        }
+        if self.cors.is_some() {
+            plugins.push("cors");
+        }
+        if self.rate_limit.is_some() {
+            plugins.push("rate-limiting");
+        }
+        if !self.add_headers.is_empty() {
+            plugins.push("response-transformer");
+        }
+        if self.upstream_service.is_some() {
+            plugins.push("request-transformer");
+        }
+        if self.pii_region_header.is_some() {
+            plugins.push("pii-region-header");
+        }
+        plugins
+    }
+
+    /// Verify the routing and plugins of an API
+    ///
+    /// Plugins are only restricted when the region sets `kong.allowed_plugins`.
+    pub fn verify(&self, allowed_plugins: Option<&BTreeSet<String>>) -> Result<()> {
+        if self.uris.is_none() && self.hosts.is_empty() {
+            bail!("Kong API {} needs at least one of uris or hosts", self.name);
+        }
+        if let Err(e) = url::Url::parse(&self.upstream_url) {
+            bail!(
+                "Kong API {} has an invalid upstream_url '{}': {}",
+                self.name,
+                self.upstream_url,
+                e
+            );
+        }
+        if let Some(rl) = &self.rate_limit {
+            rl.verify()?;
+        }
+        if let Some(allowed) = allowed_plugins {
+            let denied = self
+                .plugins()
+                .into_iter()
+                .filter(|p| !allowed.contains(*p))
+                .collect::<Vec<_>>();
+            if !denied.is_empty() {
+                bail!(
+                    "Kong API {} uses plugins not allowed in this region: {}",
+                    self.name,
+                    denied.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Cors plugin data
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Cors {
     pub credentials: bool,
@@ -137,8 +219,136 @@ pub struct Cors {
     pub preflight_continue: bool,
 }
 
+/// Simplified Cors plugin setup
+///
+/// Expanded into `cors` when the manifest is loaded.
+///
+/// ```yaml
+/// cors_policy:
+///   origins:
+///   - https://app.example.com
+///   methods: [GET, POST]
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct CorsPolicy {
+    /// Origins allowed to call the API, e.g. `https://app.example.com` or `*`
+    pub origins: Vec<String>,
+    /// HTTP methods allowed from these origins
+    #[serde(default = "cors_methods_default")]
+    pub methods: Vec<String>,
+    /// Request headers allowed from these origins
+    #[serde(default = "cors_headers_default")]
+    pub headers: Vec<String>,
+    /// Whether cookies and authorization headers can be sent
+    #[serde(default)]
+    pub credentials: bool,
+    /// Seconds browsers may cache the preflight response for
+    #[serde(default = "cors_max_age_default")]
+    pub max_age: u32,
+}
+
+fn cors_methods_default() -> Vec<String> {
+    vec!["GET".into(), "HEAD".into(), "OPTIONS".into()]
+}
+fn cors_headers_default() -> Vec<String> {
+    vec!["Accept".into(), "Authorization".into(), "Content-Type".into()]
+}
+fn cors_max_age_default() -> u32 {
+    3600
+}
+
+const CORS_METHODS: &[&str] = &["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+impl CorsPolicy {
+    pub fn verify(&self) -> Result<()> {
+        if self.origins.is_empty() {
+            bail!("cors_policy needs at least one origin");
+        }
+        for o in &self.origins {
+            if o == "*" {
+                if self.credentials {
+                    bail!("cors_policy can not allow credentials from any origin");
+                }
+                continue;
+            }
+            let url = url::Url::parse(o).map_err(|e| format!("Invalid cors origin '{}': {}", o, e))?;
+            if (url.scheme() != "https" && url.scheme() != "http") || url.host_str().is_none() {
+                bail!("Cors origin '{}' must be an http(s) scheme and host", o);
+            }
+            // origins are compared verbatim, so trailing slashes or paths never match
+            if url.origin().ascii_serialization() != *o {
+                bail!("Cors origin '{}' must only contain a scheme, host and port", o);
+            }
+        }
+        for m in &self.methods {
+            if !CORS_METHODS.contains(&m.as_str()) {
+                bail!("Unknown cors method '{}' - expected one of {}", m, CORS_METHODS.join(", "));
+            }
+        }
+        if self.max_age == 0 {
+            bail!("cors_policy max_age must be positive");
+        }
+        Ok(())
+    }
+
+    /// The plugin data this expands into
+    pub fn expand(self) -> Cors {
+        Cors {
+            credentials: self.credentials,
+            enabled: true,
+            exposed_headers: "".into(),
+            headers: self.headers.join(","),
+            max_age: self.max_age.to_string(),
+            methods: self.methods.join(","),
+            origin: self.origins.join(","),
+            preflight_continue: false,
+        }
+    }
+}
+
+/// Rate limiting plugin data
+///
+/// Limits every consumer to a number of requests per window.
+///
+/// ```yaml
+/// rate_limit:
+///   requests: 100
+///   window: minute
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct RateLimit {
+    /// Requests allowed in every window
+    pub requests: u32,
+    /// Length of the window
+    pub window: RateLimitWindow,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitWindow {
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl RateLimit {
+    pub fn verify(&self) -> Result<()> {
+        if self.requests == 0 {
+            bail!("rate_limit requests must be positive - leave rate_limit out to disable it");
+        }
+        // kong counts in memory per node, so anything larger is not enforced reliably
+        if self.requests > 1_000_000 {
+            bail!("rate_limit of {} requests is too large", self.requests);
+        }
+        Ok(())
+    }
+}
+
 /// Babylon Auth Header plugin data
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct BabylonAuthHeader {
     pub auth_service: String,
@@ -148,7 +358,7 @@ pub struct BabylonAuthHeader {
 }
 
 /// Babylon Auth Header plugin data
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct PiiRegionHeader {
     pub region_service_uri: String,
@@ -156,9 +366,71 @@ pub struct PiiRegionHeader {
 }
 
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum Authentication {
     None,
     Jwt,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Cors, CorsPolicy, Kong};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn verify_kong() {
+        let mut kong = Kong {
+            name: "fake-ask".into(),
+            upstream_url: "http://fake-ask.apps.svc.cluster.local".into(),
+            uris: Some("/fake-ask".into()),
+            ..Default::default()
+        };
+        assert!(kong.verify(None).is_ok());
+
+        kong.upstream_url = "fake-ask.apps".into();
+        assert!(kong.verify(None).is_err());
+        kong.upstream_url = "http://fake-ask.apps.svc.cluster.local".into();
+
+        kong.uris = None;
+        assert!(kong.verify(None).is_err());
+        kong.hosts = vec!["fake-ask.example.com".into()];
+        assert!(kong.verify(None).is_ok());
+
+        kong.cors = Some(Cors::default());
+        kong.upstream_service = Some("fake-ask".into());
+        assert_eq!(kong.plugins(), vec!["cors", "request-transformer"]);
+        let mut allowed = BTreeSet::new();
+        allowed.insert("request-transformer".to_string());
+        let err = kong.verify(Some(&allowed)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Kong API fake-ask uses plugins not allowed in this region: cors"
+        );
+        allowed.insert("cors".to_string());
+        assert!(kong.verify(Some(&allowed)).is_ok());
+    }
+
+    #[test]
+    fn cors_policy() {
+        let mut cp: CorsPolicy = serde_yaml::from_str("origins: [https://app.example.com]").unwrap();
+        assert!(cp.verify().is_ok());
+        let cors = cp.clone().expand();
+        assert_eq!(cors.origin, "https://app.example.com");
+        assert_eq!(cors.methods, "GET,HEAD,OPTIONS");
+        assert_eq!(cors.max_age, "3600");
+
+        cp.origins.push("https://app.example.com/".into());
+        assert!(cp.verify().is_err());
+        cp.origins = vec!["app.example.com".into()];
+        assert!(cp.verify().is_err());
+        cp.origins = vec!["*".into()];
+        assert!(cp.verify().is_ok());
+        cp.credentials = true;
+        assert!(cp.verify().is_err());
+        cp.origins = vec!["http://localhost:8080".into()];
+        assert!(cp.verify().is_ok());
+        cp.methods.push("get".into());
+        assert!(cp.verify().is_err());
+    }
+}
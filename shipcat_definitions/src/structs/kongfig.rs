@@ -1,7 +1,7 @@
 // use super::traits::Verify;
 use crate::{
     region::KongConfig,
-    structs::{Authentication, BabylonAuthHeader, Cors, Kong},
+    structs::{Authentication, BabylonAuthHeader, Cors, Kong, RateLimit, RateLimitWindow},
     Region,
 };
 use serde::ser::{Serialize, SerializeMap, Serializer};
@@ -61,6 +61,40 @@ impl CorsPluginConfig {
     }
 }
 
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RateLimitingPluginConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub second: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minute: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hour: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub day: Option<u32>,
+    pub limit_by: String,
+    pub policy: String,
+    pub fault_tolerant: bool,
+}
+
+impl RateLimitingPluginConfig {
+    fn new(rl: RateLimit) -> Self {
+        let mut config = RateLimitingPluginConfig {
+            limit_by: "consumer".into(),
+            policy: "local".into(),
+            fault_tolerant: true,
+            ..Default::default()
+        };
+        let requests = Some(rl.requests);
+        match rl.window {
+            RateLimitWindow::Second => config.second = requests,
+            RateLimitWindow::Minute => config.minute = requests,
+            RateLimitWindow::Hour => config.hour = requests,
+            RateLimitWindow::Day => config.day = requests,
+        }
+        config
+    }
+}
+
 /// Serialise nil as brackets, a strange kongfig idiom
 fn none_as_brackets<S, T>(t: &Option<T>, s: S) -> Result<S::Ok, S::Error>
 where
@@ -296,6 +330,7 @@ pub enum ApiPlugin {
     Jwt(PluginBase<JwtPluginConfig>),
     JwtValidator(PluginBase<JwtValidatorPluginConfig>),
     Cors(PluginBase<CorsPluginConfig>),
+    RateLimiting(PluginBase<RateLimitingPluginConfig>),
     CorrelationId(PluginBase<CorrelationIdPluginConfig>),
     BabylonAuthHeader(PluginBase<BabylonAuthHeaderPluginConfig>),
     PiiRegionHeader(PluginBase<PiiRegionHeaderPluginConfig>),
@@ -429,6 +464,11 @@ pub fn kongfig_apis(from: BTreeMap<String, Kong>, config: KongConfig, region: &R
             })));
         }
 
+        // If set: rate limiting per consumer
+        if let Some(rl) = v.rate_limit {
+            plugins.push(ApiPlugin::RateLimiting(PluginBase::new(RateLimitingPluginConfig::new(rl))));
+        }
+
         // If enabled: ResponseTransformer to add headers
         if !v.add_headers.is_empty() {
             plugins.push(ApiPlugin::ResponseTransformer(PluginBase::new(
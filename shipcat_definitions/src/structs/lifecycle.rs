@@ -3,7 +3,7 @@ use super::Result;
 /// A straight port of Kubernetes Container Lifecycle Events
 ///
 /// From https://kubernetes.io/docs/tasks/configure-pod-container/attach-handler-lifecycle-event/
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct LifeCycle {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -12,13 +12,13 @@ pub struct LifeCycle {
     pub preStop: Option<LifeCycleHandler>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct LifeCycleHandler {
     pub exec: ExecAction,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct ExecAction {
     command: Vec<String>,
@@ -27,6 +27,16 @@ pub struct ExecAction {
 // TODO: support HttpGetAction + TcpSocketAction
 
 impl LifeCycle {
+    /// A lifecycle running a single command before stopping
+    pub fn pre_stop(command: Vec<String>) -> LifeCycle {
+        LifeCycle {
+            postStart: None,
+            preStop: Some(LifeCycleHandler {
+                exec: ExecAction { command },
+            }),
+        }
+    }
+
     pub fn verify(&self) -> Result<()> {
         if self.postStart.is_none() && self.preStop.is_none() {
             bail!("Need to set one of postStart or preStop in lifecycle");
@@ -5,18 +5,50 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use super::Result;
-use crate::config::SlackParameters;
+use super::{ErrorKind, Result};
+use crate::config::{MetadataRequirements, SlackParameters};
 
-/// Legacy contact data
+/// What a contact is for during incidents
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContactRole {
+    /// First point of contact
+    Primary,
+    /// Contacted when the primary contact does not respond
+    Escalation,
+}
+
+impl Default for ContactRole {
+    fn default() -> Self {
+        ContactRole::Primary
+    }
+}
+
+/// Contact for incident routing
 ///
-/// This property is being phased out in favour of .maintainer
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// ```yaml
+/// contacts:
+/// - name: "Eirik"
+///   slack: "@U82SKDQD9"
+/// - name: "Platform on-call"
+///   role: escalation
+///   pagerduty: PXYZ123
+/// ```
+///
+/// People without incident duties should be listed in .maintainers instead.
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct Contact {
     /// Free text name
     pub name: String,
+    /// Role in incident routing
+    #[serde(default)]
+    pub role: ContactRole,
     /// Slack handle
-    pub slack: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slack: Option<String>,
+    /// PagerDuty user or escalation policy id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pagerduty: Option<String>,
     /// Email address
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
@@ -29,14 +61,28 @@ impl Contact {
         if self.name.is_empty() {
             bail!("Contact name cannot be empty")
         }
-        if !self.slack.starts_with('@') {
-            bail!(
-                "Contact slack handle needs to start with the slack guid '@U...' - got {}",
-                self.slack
-            )
+        if self.slack.is_none() && self.pagerduty.is_none() {
+            bail!("Contact {} needs a slack or pagerduty handle", self.name)
+        }
+        if let Some(slack) = &self.slack {
+            if !slack.starts_with('@') {
+                bail!(
+                    "Contact slack handle needs to start with the slack guid '@U...' - got {}",
+                    slack
+                )
+            }
+            if slack.contains('|') {
+                bail!("Contact slack user id invalid - got {}", slack)
+            }
         }
-        if self.slack.contains('|') {
-            bail!("Contact slack user id invalid - got {}", self.slack)
+        if let Some(pd) = &self.pagerduty {
+            let re = Regex::new(r"^P[A-Z0-9]+$").unwrap();
+            if !re.is_match(pd) {
+                bail!(
+                    "Contact pagerduty handle must be a PagerDuty id like PXYZ123 - got {}",
+                    pd
+                )
+            }
         }
         if let Some(ref gh) = &self.github {
             if gh.starts_with('@') || gh.contains('/') {
@@ -49,7 +95,7 @@ impl Contact {
 }
 
 /// Slack channel verifier
-#[derive(Serialize, Deserialize, PartialEq, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Default, Debug, JsonSchema)]
 pub struct SlackChannel(String);
 impl SlackChannel {
     pub fn new(chan: &str) -> Self {
@@ -90,7 +136,7 @@ impl DerefMut for SlackChannel {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Language {
     Rust,
@@ -122,7 +168,7 @@ pub enum Language {
 /// context:
 ///   name: consultations
 /// ```
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Context {
     /// name of parent context
@@ -143,13 +189,22 @@ impl Context {
 }
 
 /// Metadata for a service
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(test, derive(Default))]
 pub struct Metadata {
     /// Git repository
+    ///
+    /// Mandatory unless `metadataRequirements` in the config says otherwise.
+    #[serde(default)]
     pub repo: String,
     /// Owning squad
+    ///
+    /// Shorthand for a single entry in `teams`, and the primary team when built.
+    #[serde(default)]
     pub team: String,
+    /// Owning squads, for services with shared ownership
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<String>,
 
     /// Context this resource belongs to
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -172,7 +227,9 @@ pub struct Metadata {
     #[serde(default = "default_format_string")]
     pub gitTagTemplate: String,
 
-    /// Contact person (legacy)
+    /// Incident contacts
+    ///
+    /// Exactly one must be the primary contact if any are given.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub contacts: Vec<Contact>,
 
@@ -193,6 +250,15 @@ pub struct Metadata {
     /// Canoncal documentation link
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub docs: Option<String>,
+
+    /// Cost center the service is billed to
+    ///
+    /// Must be one of the `costCenters` in the config, and is added as a `cost-center` label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub costCenter: Option<String>,
+    /// Expected monthly cloud spend, in the currency finance reports in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthlyBudget: Option<u32>,
     // TODO: generate swagger docs url from region and service name
     /// Custom metadata, keys defined in the Config
     #[serde(flatten)]
@@ -203,6 +269,38 @@ fn default_format_string() -> String {
 }
 
 impl Metadata {
+    /// All owning teams, with the primary team first
+    pub fn owning_teams(&self) -> Vec<&str> {
+        let mut res = vec![];
+        if !self.team.is_empty() {
+            res.push(self.team.as_str());
+        }
+        for t in &self.teams {
+            if !res.contains(&t.as_str()) {
+                res.push(t.as_str());
+            }
+        }
+        res
+    }
+
+    /// Whether a team is one of the owners
+    pub fn owned_by(&self, team: &str) -> bool {
+        self.owning_teams().contains(&team)
+    }
+
+    /// Fill in both `team` and `teams` from whichever was given
+    pub fn populate_teams(&mut self) {
+        let teams = self
+            .owning_teams()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if let Some(primary) = teams.first() {
+            self.team = primary.clone();
+        }
+        self.teams = teams;
+    }
+
     pub fn version_template(&self, ver: &str) -> Result<String> {
         use tera::{Context, Tera};
         let mut ctx = Context::new();
@@ -229,13 +327,29 @@ impl Metadata {
 }
 
 impl Metadata {
-    pub fn verify(&self, owners: &Owners, allowedCustomMetadata: &BTreeSet<String>) -> Result<()> {
-        if !owners.squads.contains_key(&self.team) {
-            bail!("Team name {} does not match a squad in teams.yml", self.team);
+    pub fn verify(
+        &self,
+        owners: &Owners,
+        allowedCustomMetadata: &BTreeSet<String>,
+        reqs: &MetadataRequirements,
+    ) -> Result<()> {
+        self.verify_ownership(reqs)?;
+        for team in self.owning_teams() {
+            if !owners.squads.contains_key(team) {
+                bail!("Team name {} does not match a squad in teams.yml", team);
+            }
         }
         for cc in &self.contacts {
             cc.verify()?;
         }
+        let primaries = self
+            .contacts
+            .iter()
+            .filter(|cc| cc.role == ContactRole::Primary)
+            .count();
+        if !self.contacts.is_empty() && primaries != 1 {
+            bail!("Need exactly one primary contact - found {}", primaries);
+        }
         if let Some(context) = &self.context {
             context.verify()?;
         }
@@ -275,9 +389,73 @@ impl Metadata {
     }
 }
 
+impl Metadata {
+    /// Verify the cost attribution against the cost centers in the config
+    pub fn verify_cost_center(&self, allowed: &BTreeSet<String>) -> Result<()> {
+        if let Some(cc) = &self.costCenter {
+            if !allowed.contains(cc) {
+                bail!("Cost center {} is not one of the costCenters in the config", cc);
+            }
+        }
+        if self.monthlyBudget == Some(0) {
+            bail!("monthlyBudget must be positive - leave it out if there is no budget");
+        }
+        Ok(())
+    }
+
+    /// Verify we know who owns the service and how to reach them
+    ///
+    /// Reports every missing or malformed field at once.
+    pub fn verify_ownership(&self, reqs: &MetadataRequirements) -> Result<()> {
+        let mut problems = vec![];
+        let teams = self.owning_teams();
+        let primary = teams.first().cloned().unwrap_or_default();
+        if primary.trim().is_empty() {
+            problems.push("team must be set".to_string());
+        }
+        if self.repo.is_empty() {
+            if reqs.repo_required(primary) {
+                problems.push("repo must be set".into());
+            }
+        } else if let Err(e) = verify_link(&self.repo) {
+            problems.push(format!("repo {}", e));
+        }
+        if let Some(docs) = &self.docs {
+            if let Err(e) = verify_link(docs) {
+                problems.push(format!("docs {}", e));
+            }
+        }
+        let contactable = !self.contacts.is_empty()
+            || !self.maintainers.is_empty()
+            || self.support.is_some()
+            || self.notifications.is_some();
+        if !contactable && reqs.contact_required(primary) {
+            problems.push("one of contacts, maintainers, support or notifications must be set".into());
+        }
+        if !problems.is_empty() {
+            bail!(ErrorKind::IncompleteOwnership(problems));
+        }
+        Ok(())
+    }
+}
+
+/// Check that a link is an absolute http(s) url
+fn verify_link(link: &str) -> std::result::Result<(), String> {
+    let url = url::Url::parse(link).map_err(|e| format!("'{}' is not a valid url: {}", link, e))?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err(format!("'{}' must be a http or https url", link));
+    }
+    if url.host_str().is_none() {
+        return Err(format!("'{}' has no host", link));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{default_format_string, Metadata, SlackChannel};
+    use super::{default_format_string, Contact, ContactRole, Metadata, SlackChannel};
+    use crate::config::{MetadataRequirements, TeamMetadataRequirements};
+    use crate::teams::{Owners, Squad};
 
     #[test]
     fn version_tpl() {
@@ -311,4 +489,113 @@ mod tests {
         let valid = sc.verify();
         assert!(valid.is_err());
     }
+
+    #[test]
+    fn ownership_requirements() {
+        let mut md = Metadata {
+            team: "platform".into(),
+            ..Default::default()
+        };
+        let mut reqs = MetadataRequirements::default();
+        assert!(md.verify_ownership(&reqs).is_ok());
+
+        reqs.repo = true;
+        reqs.contact = true;
+        md.docs = Some("not a url".into());
+        let err = md.verify_ownership(&reqs).unwrap_err().to_string();
+        // all problems reported together
+        assert!(err.contains("repo must be set"), "{}", err);
+        assert!(err.contains("docs 'not a url'"), "{}", err);
+        assert!(err.contains("support"), "{}", err);
+
+        md.repo = "ftp://github.com/babylonhealth/shipcat".into();
+        md.docs = None;
+        md.support = Some(SlackChannel::new("#platform"));
+        assert!(md.verify_ownership(&reqs).is_err());
+        md.repo = "https://github.com/babylonhealth/shipcat".into();
+        assert!(md.verify_ownership(&reqs).is_ok());
+
+        // teams can opt out
+        md.repo = "".into();
+        reqs.teams.insert(
+            "platform".into(),
+            TeamMetadataRequirements {
+                repo: Some(false),
+                contact: None,
+            },
+        );
+        assert!(md.verify_ownership(&reqs).is_ok());
+        md.team = "".into();
+        assert!(md.verify_ownership(&reqs).is_err());
+    }
+
+    #[test]
+    fn cost_center() {
+        let mut md = Metadata::default();
+        let allowed = vec!["platform-eng".to_string()].into_iter().collect();
+        assert!(md.verify_cost_center(&allowed).is_ok());
+
+        md.costCenter = Some("marketing".into());
+        assert!(md.verify_cost_center(&allowed).is_err());
+        md.costCenter = Some("platform-eng".into());
+        md.monthlyBudget = Some(0);
+        assert!(md.verify_cost_center(&allowed).is_err());
+        md.monthlyBudget = Some(2000);
+        assert!(md.verify_cost_center(&allowed).is_ok());
+    }
+
+    #[test]
+    fn shared_ownership() {
+        let mut md = Metadata {
+            team: "platform".into(),
+            teams: vec!["devops".into(), "platform".into()],
+            gitTagTemplate: default_format_string(),
+            ..Default::default()
+        };
+        assert_eq!(md.owning_teams(), vec!["platform", "devops"]);
+        md.populate_teams();
+        assert_eq!(md.team, "platform");
+        assert_eq!(md.teams, vec!["platform", "devops"]);
+        assert!(md.owned_by("devops"));
+
+        let squad = |name: &str| -> Squad {
+            let yaml = format!(
+                "name: {}\nmembers: []\ngithub: {{team: {}}}\nslack: {{}}",
+                name, name
+            );
+            serde_yaml::from_str(&yaml).unwrap()
+        };
+        let mut owners = Owners::default();
+        owners.squads.insert("platform".into(), squad("platform"));
+        let reqs = Default::default();
+        // every team must exist
+        assert!(md.verify(&owners, &Default::default(), &reqs).is_err());
+        owners.squads.insert("devops".into(), squad("devops"));
+        assert!(md.verify(&owners, &Default::default(), &reqs).is_ok());
+
+        let contact = |role, slack: Option<&str>, pd: Option<&str>| Contact {
+            name: "on-call".into(),
+            role,
+            slack: slack.map(String::from),
+            pagerduty: pd.map(String::from),
+            email: None,
+            github: None,
+        };
+        md.contacts = vec![
+            contact(ContactRole::Escalation, None, Some("PXYZ123")),
+            contact(ContactRole::Escalation, Some("@U82SKDQD9"), None),
+        ];
+        assert!(md.verify(&owners, &Default::default(), &reqs).is_err());
+        md.contacts[1].role = ContactRole::Primary;
+        assert!(md.verify(&owners, &Default::default(), &reqs).is_ok());
+        md.contacts
+            .push(contact(ContactRole::Primary, Some("@U82SKDQD9"), None));
+        assert!(md.verify(&owners, &Default::default(), &reqs).is_err());
+        md.contacts.pop();
+        // handles are required and checked
+        md.contacts[0].pagerduty = None;
+        assert!(md.verify(&owners, &Default::default(), &reqs).is_err());
+        md.contacts[0].pagerduty = Some("on-call".into());
+        assert!(md.verify(&owners, &Default::default(), &reqs).is_err());
+    }
 }
@@ -21,7 +21,9 @@ pub use self::worker::Worker;
 
 /// Kong configs
 pub mod kong;
-pub use self::kong::{Authentication, BabylonAuthHeader, Cors, Kong, PiiRegionHeader};
+pub use self::kong::{
+    Authentication, BabylonAuthHeader, Cors, CorsPolicy, Kong, PiiRegionHeader, RateLimit, RateLimitWindow,
+};
 
 pub mod authorization;
 pub use self::authorization::Authorization;
@@ -43,46 +45,62 @@ pub use self::kafka::Kafka;
 // abstractions - these have special handling
 /// Templated configmap abstractions
 mod configmap;
-pub use self::configmap::{ConfigMap, ConfigMappedFile};
+pub use self::configmap::{ConfigMap, ConfigMappedFile, ConfigMaps};
 /// Healthcheck abstraction
 mod healthcheck;
-pub use self::healthcheck::HealthCheck;
+pub use self::healthcheck::{HealthCheck, StartupProbe};
 
 mod env;
-pub use self::env::EnvVars;
+pub use self::env::{
+    EnvPolicy, EnvValueFrom, EnvVars, KeySelector, ObjectFieldSelector, ResourceFieldSelector,
+    SecretDetection,
+};
 
 // translations - these are typically inlined in templates as yaml
 /// Kubernetes resource structs
 pub mod resources;
-pub use self::resources::{parse_memory, ResourceRequirements};
+pub use self::resources::{parse_cpu, parse_memory, ResourcePolicy, ResourceRequirements, Resources};
 /// Kubernetes volumes
 pub mod volume;
-pub use self::volume::{Volume, VolumeMount};
+pub use self::volume::{PvcSource, Volume, VolumeMount};
 /// Kubernetes host aliases
 mod hostalias;
 pub use self::hostalias::HostAlias;
+/// Kubernetes host networking and dns policies
+pub mod hostnetwork;
+/// Chart feature flags
+pub mod features;
 /// Kubernetes health check probes
 mod probes;
 pub use self::probes::Probe;
 /// Kubernetes rolling-update settings
 pub mod rollingupdate;
-pub use self::rollingupdate::RollingUpdate;
+pub use self::rollingupdate::{DeploymentStrategy, RollingUpdate};
 /// Kubernetes horizontal pod autoscaler
 pub mod autoscaling;
 /// Kubernetes container lifecycle events
 mod lifecycle;
 /// Kuberneter tolerations
 pub mod tolerations;
+/// Kubernetes topology spread constraints
+mod topologyspread;
+pub use self::topologyspread::{TopologySpreadConstraint, WhenUnsatisfiable};
 pub use self::lifecycle::{LifeCycle, LifeCycleHandler};
+/// Graceful shutdown through lifecycle hooks
+mod gracefulshutdown;
+pub use self::gracefulshutdown::GracefulShutdown;
 
 pub mod metadata;
-pub use self::metadata::{Contact, Metadata, SlackChannel};
+pub use self::metadata::{Contact, ContactRole, Metadata, SlackChannel};
 
 /// Security related structs
 pub mod security;
 
 mod securitycontext;
-pub use securitycontext::SecurityContext;
+pub use securitycontext::{Capabilities, ContainerSecurityContext, SecurityContext, SecurityPolicy};
+
+mod serviceaccount;
+pub use self::serviceaccount::ServiceAccount;
 
 mod vault;
 pub use self::vault::VaultOpts;
@@ -104,12 +122,20 @@ pub use self::rbac::Rbac;
 
 // PersistentVolume
 mod persistentvolume;
-pub use self::persistentvolume::PersistentVolume;
+pub use self::persistentvolume::{PersistentVolume, VolumeAccessMode};
+
+// StatefulSet specific settings
+mod statefulset;
+pub use self::statefulset::{PodManagementPolicy, StatefulSetOptions};
 
 pub mod newrelic;
 
 pub mod sentry;
 
+/// Prometheus alerting rules
+mod alert;
+pub use self::alert::{AlertRule, AlertSeverity};
+
 mod notifications;
 pub use notifications::NotificationMode;
 
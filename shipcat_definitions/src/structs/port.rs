@@ -1,4 +1,4 @@
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum PortProtocol {
     Tcp,
@@ -13,7 +13,7 @@ impl Default for PortProtocol {
 }
 
 /// Port to open on a container
-#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug, JsonSchema)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Port {
     /// Name of the port
@@ -25,4 +25,7 @@ pub struct Port {
     /// Port protocol
     #[serde(default)]
     pub protocol: PortProtocol,
+    /// Port to expose on the node
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_port: Option<u32>,
 }
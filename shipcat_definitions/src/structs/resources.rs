@@ -1,4 +1,5 @@
 use super::Result;
+use crate::{ErrorKind, Warning, WarningCode};
 use std::ops::{Add, AddAssign, Mul};
 
 // Kubernetes resouce structs
@@ -10,7 +11,7 @@ use std::ops::{Add, AddAssign, Mul};
 // implemented to be a bit more useful, as well as some to convert between them.
 
 /// Kubernetes resource requests or limit
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Resources<T> {
     /// CPU request string
@@ -23,42 +24,72 @@ pub struct Resources<T> {
 /// Kubernetes resources
 ///
 /// This can be inlined straight into a container spec at the moment
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct ResourceRequirements<T> {
     /// Resource requests for k8s
     pub requests: Resources<T>,
     /// Resource limits for k8s
-    pub limits: Resources<T>,
+    ///
+    /// Mandatory unless the region's `resourcePolicy` says otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<Resources<T>>,
+}
+
+impl Resources<String> {
+    /// Convert shorthand strings to raw number of cores and Bytes of memory
+    pub fn normalised(&self) -> Result<Resources<f64>> {
+        Ok(Resources {
+            memory: parse_memory(&self.memory)?,
+            cpu: parse_cpu(&self.cpu)?,
+        })
+    }
 }
 
 impl ResourceRequirements<String> {
     /// Convert shorthand strings to raw number of cores and Bytes of memory
     pub fn normalised(&self) -> Result<ResourceRequirements<f64>> {
-        let requests = Resources {
-            memory: parse_memory(&self.requests.memory.to_string())?,
-            cpu: parse_cpu(&self.requests.cpu.to_string())?,
-        };
-        let limits = Resources {
-            memory: parse_memory(&self.limits.memory.to_string())?,
-            cpu: parse_cpu(&self.limits.cpu.to_string())?,
+        let requests = self.requests.normalised()?;
+        let limits = match &self.limits {
+            Some(l) => Some(l.normalised()?),
+            None => None,
         };
         Ok(ResourceRequirements { requests, limits })
     }
 }
 
 // For aggregation of resource use, implement addition on normalised versions
+impl Add for Resources<f64> {
+    type Output = Resources<f64>;
+
+    fn add(self, rhs: Resources<f64>) -> Resources<f64> {
+        Resources {
+            memory: self.memory + rhs.memory,
+            cpu: self.cpu + rhs.cpu,
+        }
+    }
+}
+
+impl Mul<u32> for Resources<f64> {
+    type Output = Resources<f64>;
+
+    fn mul(self, scalar: u32) -> Resources<f64> {
+        Resources {
+            memory: self.memory * f64::from(scalar),
+            cpu: self.cpu * f64::from(scalar),
+        }
+    }
+}
+
+/// Sums of limits only include the containers that set limits
 impl Add for ResourceRequirements<f64> {
     type Output = ResourceRequirements<f64>;
 
     fn add(self, rhs: ResourceRequirements<f64>) -> ResourceRequirements<f64> {
-        let requests = Resources {
-            memory: self.requests.memory + rhs.requests.memory,
-            cpu: self.requests.cpu + rhs.requests.cpu,
-        };
-        let limits = Resources {
-            memory: self.limits.memory + rhs.limits.memory,
-            cpu: self.limits.cpu + rhs.limits.cpu,
+        let requests = self.requests + rhs.requests;
+        let limits = match (self.limits, rhs.limits) {
+            (Some(l), Some(r)) => Some(l + r),
+            (l, r) => l.or(r),
         };
         ResourceRequirements { requests, limits }
     }
@@ -73,14 +104,8 @@ impl Mul<u32> for ResourceRequirements<f64> {
     type Output = ResourceRequirements<f64>;
 
     fn mul(self, scalar: u32) -> ResourceRequirements<f64> {
-        let requests = Resources {
-            memory: self.requests.memory * f64::from(scalar),
-            cpu: self.requests.cpu * f64::from(scalar),
-        };
-        let limits = Resources {
-            memory: self.limits.memory * f64::from(scalar),
-            cpu: self.limits.cpu * f64::from(scalar),
-        };
+        let requests = self.requests * scalar;
+        let limits = self.limits.map(|l| l * scalar);
         ResourceRequirements { requests, limits }
     }
 }
@@ -97,117 +122,288 @@ impl Default for ResourceRequirements<f64> {
             memory: 0.0,
             cpu: 0.0,
         };
-        ResourceRequirements { requests, limits }
+        ResourceRequirements {
+            requests,
+            limits: Some(limits),
+        }
     }
 }
 
 impl ResourceRequirements<f64> {
     /// Convert to gigabytes and round to two decimals
     pub fn round(&mut self) {
-        self.limits.memory = (self.limits.memory * 100.0 / (1024.0 * 1024.0 * 1024.0)).round() / 100.0;
+        if let Some(limits) = &mut self.limits {
+            limits.memory = (limits.memory * 100.0 / (1024.0 * 1024.0 * 1024.0)).round() / 100.0;
+            limits.cpu = (limits.cpu * 100.0).round() / 100.0;
+        }
         self.requests.memory = (self.requests.memory * 100.0 / (1024.0 * 1024.0 * 1024.0)).round() / 100.0;
-        self.limits.cpu = (self.limits.cpu * 100.0).round() / 100.0;
         self.requests.cpu = (self.requests.cpu * 100.0).round() / 100.0;
     }
 }
 
 impl ResourceRequirements<String> {
-    // TODO: look at config for limits?
+    /// Sanity check the magnitude of the quantities
+    ///
+    /// Comparisons between requests and limits are left to the region's `ResourcePolicy`.
     pub fn verify(&self) -> Result<()> {
         // (We can unwrap all the values as we assume implicit called!)
         let n = self.normalised()?;
         let req = &n.requests;
-        let lim = &n.limits;
 
-        // 1.1 limits >= requests
-        if req.cpu > lim.cpu {
-            bail!("Requested more CPU than what was limited");
-        }
-        if req.memory > lim.memory {
-            bail!("Requested more memory than what was limited");
-        }
-        // 1.2 sanity numbers (based on c5.9xlarge)
+        // sanity numbers (based on c5.9xlarge)
         if req.cpu > 36.0 {
             bail!("Requested more than 36 cores");
         }
         if req.memory > 72.0 * 1024.0 * 1024.0 * 1024.0 {
             bail!("Requested more than 72 GB of memory");
         }
-        if lim.cpu > 36.0 {
-            bail!("CPU limit set to more than 36 cores");
-        }
-        if lim.memory > 72.0 * 1024.0 * 1024.0 * 1024.0 {
-            bail!("Memory limit set to more than 72 GB of memory");
+        if let Some(lim) = &n.limits {
+            if lim.cpu > 36.0 {
+                bail!("CPU limit set to more than 36 cores");
+            }
+            if lim.memory > 72.0 * 1024.0 * 1024.0 * 1024.0 {
+                bail!("Memory limit set to more than 72 GB of memory");
+            }
         }
         Ok(())
     }
+
+    /// Requests that are smaller than the requests of `lower`
+    ///
+    /// Each is given as the field with our and their unnormalised value.
+    pub fn smaller_requests(&self, lower: &Self) -> Result<Vec<(&'static str, String, String)>> {
+        let (ours, theirs) = (self.normalised()?.requests, lower.normalised()?.requests);
+        let mut smaller = vec![];
+        if ours.cpu < theirs.cpu {
+            smaller.push(("requests.cpu", self.requests.cpu.clone(), lower.requests.cpu.clone()));
+        }
+        if ours.memory < theirs.memory {
+            smaller.push((
+                "requests.memory",
+                self.requests.memory.clone(),
+                lower.requests.memory.clone(),
+            ));
+        }
+        Ok(smaller)
+    }
 }
 
+/// Requirements on resources of services in a region
+///
+/// ```yaml
+/// resourcePolicy:
+///   requireLimits: true
+///   maxLimitRatio: 10
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ResourcePolicy {
+    /// Require every container to set limits
+    pub requireLimits: bool,
+    /// Warn when a limit is more than this many times its request
+    pub maxLimitRatio: Option<f64>,
+}
 
-/// Parse normal k8s memory/disk resource value into floats
+impl Default for ResourcePolicy {
+    fn default() -> Self {
+        ResourcePolicy {
+            requireLimits: true,
+            maxLimitRatio: Some(10.0),
+        }
+    }
+}
+
+impl ResourcePolicy {
+    /// Verify the resources of a container in a service
+    ///
+    /// Limits far above their requests are returned as warnings.
+    pub fn verify(
+        &self,
+        svc: &str,
+        container: &str,
+        res: &ResourceRequirements<String>,
+    ) -> Result<Vec<Warning>> {
+        let (raw, lim) = match &res.limits {
+            Some(l) => (l, l.normalised()?),
+            None if self.requireLimits => bail!(ErrorKind::InvalidResources(
+                svc.into(),
+                format!("limits must be set on {}", container)
+            )),
+            None => return Ok(vec![]),
+        };
+        let req = res.requests.normalised()?;
+        let mut warnings = vec![];
+        let quantities = [
+            ("cpu", req.cpu, lim.cpu, &res.requests.cpu, &raw.cpu),
+            (
+                "memory",
+                req.memory,
+                lim.memory,
+                &res.requests.memory,
+                &raw.memory,
+            ),
+        ];
+        for (kind, req, lim, raw_req, raw_lim) in quantities.iter() {
+            if lim < req {
+                bail!(ErrorKind::InvalidResources(
+                    svc.into(),
+                    format!(
+                        "{} {} limit {} is below its request {}",
+                        container, kind, raw_lim, raw_req
+                    )
+                ));
+            }
+            if let Some(max) = self.maxLimitRatio {
+                if *req > 0.0 && lim / req > max {
+                    warnings.push(Warning::new(
+                        WarningCode::HighLimitRatio,
+                        format!(
+                            "{}: {} {} limit {} is more than {} times its request {}",
+                            svc, container, kind, raw_lim, max, raw_req
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+/// Parse a kubernetes quantity into a float
 ///
-/// Note that kubernetes insists on using upper case K for kilo against SI conventions:
-/// > You can express memory as a plain integer or as a fixed-point integer using one of these suffixes: E, P, T, G, M, K. You can also use the power-of-two equivalents: Ei, Pi, Ti, Gi, Mi, Ki.
-/// https://kubernetes.io/docs/concepts/configuration/manage-compute-resources-container/#meaning-of-memory
-pub fn parse_memory(s: &str) -> Result<f64> {
+/// Handles decimal (`k`, `M`, .., `E`, `m`, `u`, `n`) and binary (`Ki`, .., `Ei`) suffixes,
+/// as well as exponents like `1e3`.
+/// See [quantities](https://kubernetes.io/docs/reference/kubernetes-api/common-definitions/quantity/).
+fn parse_quantity(s: &str) -> Result<f64> {
     let digits = s
         .chars()
         .take_while(|ch| ch.is_digit(10) || *ch == '.')
         .collect::<String>();
-    let unit = s
-        .chars()
-        .skip_while(|ch| ch.is_digit(10) || *ch == '.')
-        .collect::<String>();
-    let mut res: f64 = digits.parse()?;
+    let unit = &s[digits.len()..];
+    let res: f64 = digits.parse()?;
     trace!("Parsed {} ({})", digits, unit);
-    if unit == "Ki" {
-        res *= 1024.0;
-    } else if unit == "Mi" {
-        res *= 1024.0 * 1024.0;
-    } else if unit == "Gi" {
-        res *= 1024.0 * 1024.0 * 1024.0;
-    } else if unit == "Ti" {
-        res *= 1024.0 * 1024.0 * 1024.0 * 1024.0;
-    } else if unit == "Pi" {
-        res *= 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0;
-    } else if unit == "K" {
-        res *= 1000.0;
-    } else if unit == "M" {
-        res *= 1000.0 * 1000.0;
-    } else if unit == "G" {
-        res *= 1000.0 * 1000.0 * 1000.0;
-    } else if unit == "T" {
-        res *= 1000.0 * 1000.0 * 1000.0 * 1000.0;
-    } else if unit == "P" {
-        res *= 1000.0 * 1000.0 * 1000.0 * 1000.0 * 1000.0;
-    } else if unit != "" {
-        bail!("Unknown unit {}", unit);
-    }
+    let multiplier = match unit {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        // kubernetes only accepts lower case k, but we have always allowed K
+        "k" | "K" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        exp if exp.starts_with(&['e', 'E'][..]) => match exp[1..].parse::<i32>() {
+            Ok(e) => 10f64.powi(e),
+            Err(_) => bail!("Unknown unit {}", unit),
+        },
+        _ => bail!("Unknown unit {}", unit),
+    };
+    Ok(res * multiplier)
+}
+
+/// Parse normal k8s memory/disk resource value into floats
+///
+/// Note that kubernetes insists on using upper case K for kilo against SI conventions:
+/// > You can express memory as a plain integer or as a fixed-point integer using one of these suffixes: E, P, T, G, M, K. You can also use the power-of-two equivalents: Ei, Pi, Ti, Gi, Mi, Ki.
+/// https://kubernetes.io/docs/concepts/configuration/manage-compute-resources-container/#meaning-of-memory
+pub fn parse_memory(s: &str) -> Result<f64> {
+    let res = parse_quantity(s)?;
     trace!("Returned {} bytes", res);
     Ok(res)
 }
 
-// Parse normal k8s cpu resource values into floats
-// We don't allow power of two variants here
-fn parse_cpu(s: &str) -> Result<f64> {
-    let digits = s
-        .chars()
-        .take_while(|ch| ch.is_digit(10) || *ch == '.')
-        .collect::<String>();
-    let unit = s
-        .chars()
-        .skip_while(|ch| ch.is_digit(10) || *ch == '.')
-        .collect::<String>();
-    let mut res: f64 = digits.parse()?;
-
-    trace!("Parsed {} ({})", digits, unit);
-    if unit == "m" {
-        res /= 1000.0;
-    } else if unit == "k" {
-        res *= 1000.0;
-    } else if unit != "" {
-        bail!("Unknown unit {}", unit);
+/// Parse normal k8s cpu resource values into floats
+///
+/// Power of two variants are not allowed here.
+pub fn parse_cpu(s: &str) -> Result<f64> {
+    if s.ends_with('i') {
+        bail!("Unknown unit {} for cpu", s);
     }
+    let res = parse_quantity(s)?;
     trace!("Returned {} cores", res);
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cpu, parse_memory, ResourcePolicy, ResourceRequirements, Resources};
+    use crate::WarningCode;
+
+    #[test]
+    fn quantities() {
+        assert_eq!(parse_memory("128Mi").unwrap(), 128.0 * 1024.0 * 1024.0);
+        assert_eq!(parse_memory("1Ei").unwrap(), 1024f64.powi(6));
+        assert_eq!(parse_memory("2G").unwrap(), 2e9);
+        assert_eq!(parse_memory("129e6").unwrap(), 129e6);
+        assert_eq!(parse_memory("1.5Gi").unwrap(), 1.5 * 1024.0 * 1024.0 * 1024.0);
+        assert_eq!(parse_cpu("250m").unwrap(), 0.25);
+        assert_eq!(parse_cpu("2").unwrap(), 2.0);
+        assert_eq!(parse_cpu("500000u").unwrap(), 0.5);
+        assert!(parse_cpu("1Gi").is_err());
+        assert!(parse_memory("1Gb").is_err());
+        assert!(parse_memory("Gi").is_err());
+    }
+
+    #[test]
+    fn smaller_requests() {
+        let res = |cpu: &str, memory: &str| ResourceRequirements {
+            requests: Resources {
+                cpu: cpu.to_string(),
+                memory: memory.to_string(),
+            },
+            limits: None,
+        };
+        let staging = res("500m", "1Gi");
+        assert!(res("0.5", "1024Mi").smaller_requests(&staging).unwrap().is_empty());
+        let smaller = res("0.25", "2Gi").smaller_requests(&staging).unwrap();
+        assert_eq!(smaller, vec![("requests.cpu", "0.25".to_string(), "500m".to_string())]);
+        assert!(res("1 core", "1Gi").smaller_requests(&staging).is_err());
+    }
+
+    #[test]
+    fn resource_policy() {
+        let res = |req: (&str, &str), lim: Option<(&str, &str)>| ResourceRequirements {
+            requests: Resources {
+                cpu: req.0.to_string(),
+                memory: req.1.to_string(),
+            },
+            limits: lim.map(|l| Resources {
+                cpu: l.0.to_string(),
+                memory: l.1.to_string(),
+            }),
+        };
+        let policy = ResourcePolicy::default();
+        let warnings = policy
+            .verify("svc", "main", &res(("100m", "128Mi"), Some(("2", "1Gi"))))
+            .unwrap();
+        // 20 times the cpu request
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, WarningCode::HighLimitRatio);
+        // reversed request and limit
+        let err = policy
+            .verify("svc", "main", &res(("100m", "4Gi"), Some(("100m", "128Mi"))))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("svc"), "{}", err);
+        assert!(err.contains("128Mi") && err.contains("4Gi"), "{}", err);
+        // missing limits
+        assert!(policy
+            .verify("svc", "main", &res(("100m", "128Mi"), None))
+            .is_err());
+        let relaxed = ResourcePolicy {
+            requireLimits: false,
+            maxLimitRatio: None,
+        };
+        assert!(relaxed
+            .verify("svc", "main", &res(("100m", "128Mi"), None))
+            .is_ok());
+    }
+}
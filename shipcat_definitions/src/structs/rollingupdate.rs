@@ -1,7 +1,7 @@
-use super::Result;
+use super::{PersistentVolume, Result, Volume, VolumeAccessMode};
 
 // Untagged enum to get around the weird validation
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(untagged)]
 pub enum AvailabilityPolicy {
     Percentage(String),
@@ -61,8 +61,57 @@ impl AvailabilityPolicy {
     }
 }
 
+/// Kubernetes Deployment.spec.strategy.type
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub enum DeploymentStrategy {
+    /// Replace pods gradually, as tweaked by `rollingUpdate`
+    RollingUpdate,
+    /// Kill all existing pods before creating new ones
+    Recreate,
+}
+
+impl Default for DeploymentStrategy {
+    fn default() -> Self {
+        Self::RollingUpdate // kubernetes default
+    }
+}
+
+impl DeploymentStrategy {
+    /// Verify the strategy works with the rolling update parameters and the volumes
+    ///
+    /// Pods of a rolling update cannot mount `ReadWriteOnce` volumes while old pods hold them.
+    pub fn verify(
+        &self,
+        svc: &str,
+        rolling_update: Option<&RollingUpdate>,
+        pvs: &[PersistentVolume],
+        volumes: &[Volume],
+    ) -> Result<()> {
+        if *self == DeploymentStrategy::Recreate && rolling_update.is_some() {
+            bail!("{} sets rollingUpdate parameters with a Recreate strategy", svc);
+        }
+        if *self == DeploymentStrategy::RollingUpdate {
+            if let Some(pv) = pvs.iter().find(|pv| pv.accessMode == VolumeAccessMode::ReadWriteOnce) {
+                bail!(
+                    "{} uses a RollingUpdate strategy with ReadWriteOnce volume {} - new pods cannot mount it while old pods hold it, use `strategy: Recreate`",
+                    svc,
+                    pv.name
+                );
+            }
+            if let Some(v) = volumes.iter().find(|v| v.is_read_write_once()) {
+                bail!(
+                    "{} uses a RollingUpdate strategy with ReadWriteOnce claim {} - new pods cannot mount it while old pods hold it, use `strategy: Recreate`",
+                    svc,
+                    v.name
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Configuration parameters for Deployment.spec.strategy.rollingUpdate
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct RollingUpdate {
     /// How many replicas or percentage of replicas that can be down during rolling-update
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -167,7 +216,9 @@ impl RollingUpdate {
 
 #[cfg(test)]
 mod tests {
-    use super::{AvailabilityPolicy, RollingUpdate};
+    use super::{
+        AvailabilityPolicy, DeploymentStrategy, PersistentVolume, RollingUpdate, Volume, VolumeAccessMode,
+    };
 
     #[test]
     fn rollout_iteration_no_overflow() {
@@ -210,4 +261,33 @@ mod tests {
         };
         assert_eq!(rusurge.rollout_iterations(8), 4); // 2 dn 2 up (x4)
     }
+
+    #[test]
+    fn verify_strategy() {
+        let rolling = DeploymentStrategy::RollingUpdate;
+        let recreate = DeploymentStrategy::Recreate;
+        assert!(rolling.verify("fake-svc", None, &[], &[]).is_ok());
+        let mut pvs = vec![PersistentVolume {
+            name: "fake-cache".into(),
+            mountPath: "/cache".into(),
+            size: "1Gi".into(),
+            accessMode: VolumeAccessMode::ReadWriteOnce,
+        }];
+        let err = rolling.verify("fake-svc", None, &pvs, &[]).unwrap_err();
+        assert!(err.to_string().contains("use `strategy: Recreate`"));
+        assert!(recreate.verify("fake-svc", None, &pvs, &[]).is_ok());
+        let ru = RollingUpdate::default();
+        assert!(recreate.verify("fake-svc", Some(&ru), &pvs, &[]).is_err());
+        pvs[0].accessMode = VolumeAccessMode::ReadWriteMany;
+        assert!(rolling.verify("fake-svc", None, &pvs, &[]).is_ok());
+
+        let mut volumes: Vec<Volume> = vec![serde_yaml::from_str(
+            "name: uploads\npersistentVolumeClaim:\n  claimName: fake-uploads\n  size: 1Gi",
+        )
+        .unwrap()];
+        let err = rolling.verify("fake-svc", None, &pvs, &volumes).unwrap_err();
+        assert!(err.to_string().contains("ReadWriteOnce claim uploads"));
+        volumes[0].persistentVolumeClaim.as_mut().unwrap().size = None;
+        assert!(rolling.verify("fake-svc", None, &pvs, &volumes).is_ok());
+    }
 }
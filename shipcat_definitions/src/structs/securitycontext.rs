@@ -1,15 +1,264 @@
-/// Security context for ownership of volumes
+use super::{Container, ErrorKind, Result};
+use crate::{Warning, WarningCode};
+
+/// Security context for the pod
 ///
 /// Verbatim from [kubernetes SecurityContext](https://kubernetes.io/docs/tasks/configure-pod-container/security-context/#configure-volume-permission-and-ownership-change-policy-for-pods)
-#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug, JsonSchema)]
 #[serde(default)]
 pub struct SecurityContext {
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    runAsUser: Option<u32>,
+    pub runAsUser: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runAsGroup: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runAsNonRoot: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fsGroup: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fsGroupChangePolicy: Option<String>,
+}
+
+/// Security context for the main container
+///
+/// Verbatim from [kubernetes SecurityContext](https://kubernetes.io/docs/tasks/configure-pod-container/security-context/#set-the-security-context-for-a-container)
+/// Values set here take precedence over the pod level `securityContext`.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, JsonSchema)]
+#[serde(default)]
+pub struct ContainerSecurityContext {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runAsUser: Option<u32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    runAsGroup: Option<u32>,
+    pub runAsNonRoot: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    fsGroup: Option<u32>,
+    pub readOnlyRootFilesystem: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    fsGroupChangePolicy: Option<String>,
+    pub allowPrivilegeEscalation: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Capabilities>,
+}
+
+/// Linux capabilities to add or drop
+#[derive(Serialize, Deserialize, Default, Clone, Debug, JsonSchema)]
+#[serde(default)]
+pub struct Capabilities {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub drop: Vec<String>,
+}
+
+/// Security baseline that services in a region must declare
+///
+/// ```yaml
+/// securityPolicy:
+///   requireRunAsNonRoot: true
+///   requireReadOnlyRootFilesystem: true
+///   forbidPlaintextSecrets: true
+///   requirePinnedInitContainers: true
+/// ```
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct SecurityPolicy {
+    /// Require `runAsNonRoot: true` and a non-zero `runAsUser`
+    pub requireRunAsNonRoot: bool,
+    /// Require `readOnlyRootFilesystem: true` on the main container
+    pub requireReadOnlyRootFilesystem: bool,
+    /// Fail rather than warn when an `IN_VAULT` env var is overridden with a literal value
+    pub forbidPlaintextSecrets: bool,
+    /// Require init containers to have an image `version` other than `latest`
+    pub requirePinnedInitContainers: bool,
+    /// Require sidecars with an `image` to have a `version` other than `latest`
+    pub requirePinnedSidecars: bool,
+    /// Forbid `latest` as the `version` of the main image
+    ///
+    /// A missing version is allowed, as it is often supplied at deploy time.
+    pub requirePinnedImage: bool,
+}
+
+impl SecurityPolicy {
+    pub fn verify(
+        &self,
+        svc: &str,
+        pod: Option<&SecurityContext>,
+        container: Option<&ContainerSecurityContext>,
+    ) -> Result<()> {
+        if self.requireRunAsNonRoot {
+            let non_root = container
+                .and_then(|c| c.runAsNonRoot)
+                .or_else(|| pod.and_then(|p| p.runAsNonRoot));
+            if non_root != Some(true) {
+                bail!(ErrorKind::SecurityPolicyViolation(
+                    svc.into(),
+                    "runAsNonRoot must be true".into()
+                ));
+            }
+            let user = container
+                .and_then(|c| c.runAsUser)
+                .or_else(|| pod.and_then(|p| p.runAsUser));
+            if user.unwrap_or(0) == 0 {
+                bail!(ErrorKind::SecurityPolicyViolation(
+                    svc.into(),
+                    "runAsUser must be set to a non-zero uid".into()
+                ));
+            }
+        }
+        if self.requireReadOnlyRootFilesystem
+            && container.and_then(|c| c.readOnlyRootFilesystem) != Some(true)
+        {
+            bail!(ErrorKind::SecurityPolicyViolation(
+                svc.into(),
+                "containerSecurityContext.readOnlyRootFilesystem must be true".into()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that images are pinned to an explicit tag other than `latest`
+    ///
+    /// All unpinned containers are reported at once.
+    pub fn verify_pinned_images(
+        &self,
+        svc: &str,
+        version: Option<&str>,
+        init_containers: &[Container],
+        sidecars: &[Container],
+    ) -> Result<()> {
+        let unpinned = |c: &Container| c.version.as_ref().map_or(true, |v| v == "latest");
+        let mut offenders = vec![];
+        if self.requirePinnedImage && version == Some("latest") {
+            offenders.push("main image".to_string());
+        }
+        if self.requirePinnedInitContainers {
+            for c in init_containers.iter().filter(|c| unpinned(c)) {
+                offenders.push(format!("init container {}", c.name));
+            }
+        }
+        if self.requirePinnedSidecars {
+            for c in sidecars.iter().filter(|c| c.image.is_some() && unpinned(c)) {
+                offenders.push(format!("sidecar {}", c.name));
+            }
+        }
+        if !offenders.is_empty() {
+            bail!(ErrorKind::SecurityPolicyViolation(
+                svc.into(),
+                format!(
+                    "images must have a version other than latest: {}",
+                    offenders.join(", ")
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check env vars that were `IN_VAULT` in one manifest source, but set literally in an override
+    ///
+    /// These are most likely secrets that ended up in plain text in an override file.
+    /// Keys of containers other than the main one are prefixed with the container name.
+    pub fn verify_plaintext_secrets(&self, svc: &str, keys: &[String]) -> Result<Vec<Warning>> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let reason = format!(
+            "env {} overrides IN_VAULT with a literal value - it should probably be in vault",
+            keys.join(", ")
+        );
+        if self.forbidPlaintextSecrets {
+            bail!(ErrorKind::SecurityPolicyViolation(svc.into(), reason));
+        }
+        Ok(vec![Warning::new(
+            WarningCode::PlaintextSecret,
+            format!("{}: {}", svc, reason),
+        )])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Container, ContainerSecurityContext, SecurityContext, SecurityPolicy, WarningCode};
+
+    #[test]
+    fn security_policy() {
+        let policy = SecurityPolicy {
+            requireRunAsNonRoot: true,
+            requireReadOnlyRootFilesystem: true,
+            ..Default::default()
+        };
+        assert!(SecurityPolicy::default().verify("svc", None, None).is_ok());
+        assert!(policy.verify("svc", None, None).is_err());
+
+        let pod = SecurityContext {
+            runAsNonRoot: Some(true),
+            runAsUser: Some(1000),
+            ..Default::default()
+        };
+        let mut container = ContainerSecurityContext {
+            readOnlyRootFilesystem: Some(true),
+            ..Default::default()
+        };
+        assert!(policy.verify("svc", Some(&pod), Some(&container)).is_ok());
+
+        // container level values take precedence
+        container.runAsUser = Some(0);
+        let err = policy.verify("svc", Some(&pod), Some(&container)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "svc violates the region security policy: runAsUser must be set to a non-zero uid"
+        );
+        container.runAsUser = None;
+        container.readOnlyRootFilesystem = None;
+        assert!(policy.verify("svc", Some(&pod), Some(&container)).is_err());
+    }
+
+    #[test]
+    fn plaintext_secrets() {
+        let keys = vec!["API_KEY".to_string()];
+        let default = SecurityPolicy::default();
+        let warnings = default.verify_plaintext_secrets("svc", &keys).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, WarningCode::PlaintextSecret);
+        let policy = SecurityPolicy {
+            forbidPlaintextSecrets: true,
+            ..Default::default()
+        };
+        assert!(policy.verify_plaintext_secrets("svc", &[]).unwrap().is_empty());
+        let err = policy.verify_plaintext_secrets("svc", &keys).unwrap_err();
+        assert!(err.to_string().contains("env API_KEY overrides IN_VAULT"));
+    }
+
+    #[test]
+    fn pinned_images() {
+        let container = |name: &str, version: Option<&str>| Container {
+            name: name.into(),
+            image: Some("quay.io/babylonhealth/tools".into()),
+            version: version.map(String::from),
+            ..Default::default()
+        };
+        let init = vec![
+            container("migrate", Some("1.0.0")),
+            container("wait", Some("latest")),
+            container("setup", None),
+        ];
+        let sidecars = vec![container("proxy", None)];
+        let default = SecurityPolicy::default();
+        assert!(default
+            .verify_pinned_images("svc", Some("latest"), &init, &sidecars)
+            .is_ok());
+
+        let policy = SecurityPolicy {
+            requirePinnedInitContainers: true,
+            requirePinnedSidecars: true,
+            requirePinnedImage: true,
+            ..Default::default()
+        };
+        let err = policy
+            .verify_pinned_images("svc", Some("latest"), &init, &sidecars)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "svc violates the region security policy: images must have a version other than latest: \
+             main image, init container wait, init container setup, sidecar proxy"
+        );
+        assert!(policy.verify_pinned_images("svc", None, &init[..1], &[]).is_ok());
+    }
 }
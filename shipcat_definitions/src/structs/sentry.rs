@@ -7,7 +7,7 @@ use super::metadata::SlackChannel;
 ///   slack: C12ABYZ78
 ///   silent: true
 /// ```
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Sentry {
     pub slack: SlackChannel,
@@ -0,0 +1,121 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+
+use super::Result;
+
+/// ServiceAccount the pods run as
+///
+/// Either the name of an existing ServiceAccount:
+///
+/// ```yaml
+/// serviceAccount: webapp-reader
+/// ```
+///
+/// or a ServiceAccount generated alongside the service:
+///
+/// ```yaml
+/// serviceAccount:
+///   name: webapp
+///   annotations:
+///     eks.amazonaws.com/role-arn: arn:aws:iam::123456789012:role/webapp
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(from = "ServiceAccountSource")]
+pub struct ServiceAccount {
+    /// Name of the ServiceAccount
+    pub name: String,
+    /// Annotations on the generated ServiceAccount
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+    /// Whether the ServiceAccount is generated with the service
+    pub generate: bool,
+}
+
+/// The accepted input forms of a `ServiceAccount`
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ServiceAccountSource {
+    /// Name of an existing ServiceAccount
+    Name(String),
+    /// ServiceAccount to generate
+    Generated {
+        /// Name of the ServiceAccount
+        name: String,
+        /// Annotations, e.g. for cloud IAM role bindings
+        #[serde(default)]
+        annotations: BTreeMap<String, String>,
+        /// Set to false when the ServiceAccount is managed elsewhere
+        #[serde(default = "generate_default")]
+        generate: bool,
+    },
+}
+
+fn generate_default() -> bool {
+    true
+}
+
+impl From<ServiceAccountSource> for ServiceAccount {
+    fn from(src: ServiceAccountSource) -> Self {
+        match src {
+            ServiceAccountSource::Name(name) => ServiceAccount {
+                name,
+                ..Default::default()
+            },
+            ServiceAccountSource::Generated {
+                name,
+                annotations,
+                generate,
+            } => ServiceAccount {
+                name,
+                annotations,
+                generate,
+            },
+        }
+    }
+}
+
+impl ServiceAccount {
+    /// Verify the name is a valid kubernetes DNS subdomain
+    pub fn verify(&self) -> Result<()> {
+        let re = Regex::new(r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?(\.[a-z0-9]([-a-z0-9]*[a-z0-9])?)*$").unwrap();
+        if self.name.len() > 253 || !re.is_match(&self.name) {
+            bail!(
+                "serviceAccount {} must be a lower case DNS subdomain of at most 253 characters",
+                self.name
+            );
+        }
+        if !self.generate && !self.annotations.is_empty() {
+            bail!(
+                "serviceAccount {} has annotations, but is not generated by shipcat",
+                self.name
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceAccount;
+
+    #[test]
+    fn service_account_forms() {
+        let sa: ServiceAccount = serde_yaml::from_str("webapp-reader").unwrap();
+        assert_eq!(sa.name, "webapp-reader");
+        assert!(!sa.generate);
+        assert!(sa.verify().is_ok());
+
+        let sa: ServiceAccount =
+            serde_yaml::from_str("name: webapp\nannotations:\n  eks.amazonaws.com/role-arn: arn\n").unwrap();
+        assert!(sa.generate);
+        assert_eq!(sa.annotations["eks.amazonaws.com/role-arn"], "arn");
+        assert!(sa.verify().is_ok());
+
+        // serialized form reads back the same
+        let sa2: ServiceAccount = serde_yaml::from_str(&serde_yaml::to_string(&sa).unwrap()).unwrap();
+        assert_eq!(sa, sa2);
+
+        let bad: ServiceAccount = serde_yaml::from_str("Web_App").unwrap();
+        assert!(bad.verify().is_err());
+    }
+}
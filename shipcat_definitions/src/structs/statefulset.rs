@@ -0,0 +1,129 @@
+use std::collections::BTreeSet;
+
+use super::{PersistentVolume, Result};
+use crate::states::PrimaryWorkload;
+
+/// How a StatefulSet creates and deletes its pods
+///
+/// See [K8s pod management policy docs](https://kubernetes.io/docs/concepts/workloads/controllers/statefulset/#pod-management-policies).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum PodManagementPolicy {
+    /// One pod at a time, in order, waiting for each to be ready
+    OrderedReady,
+    /// All pods at once
+    Parallel,
+}
+
+impl Default for PodManagementPolicy {
+    fn default() -> Self {
+        PodManagementPolicy::OrderedReady
+    }
+}
+
+/// Settings only used when the workload is a `Statefulset`
+///
+/// ```yaml
+/// workload: Statefulset
+/// statefulSet:
+///   podManagementPolicy: Parallel
+///   volumeClaimTemplates:
+///   - name: data
+///     mountPath: /var/lib/data
+///     size: 10Gi
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct StatefulSetOptions {
+    /// Headless `Service` that gives the pods their network identity
+    ///
+    /// Defaults to the name of the service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serviceName: Option<String>,
+
+    /// How pods are created and deleted
+    #[serde(default)]
+    pub podManagementPolicy: PodManagementPolicy,
+
+    /// Volumes claimed for every pod, which keep their data when the pod is replaced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumeClaimTemplates: Vec<PersistentVolume>,
+}
+
+impl StatefulSetOptions {
+    pub fn verify(&self) -> Result<()> {
+        if let Some(sn) = &self.serviceName {
+            let valid = sn.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+            if sn.is_empty() || sn.len() > 63 || !valid {
+                bail!("statefulSet.serviceName '{}' must be a valid service name", sn);
+            }
+        }
+        let mut names = BTreeSet::new();
+        for vct in &self.volumeClaimTemplates {
+            vct.verify()?;
+            if !names.insert(&vct.name) {
+                bail!("statefulSet has more than one volumeClaimTemplate named {}", vct.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify that workload specific settings match the workload
+    ///
+    /// `statefulSet` needs a `Statefulset`, and a `Daemonset` runs one pod per node.
+    pub fn verify_workload(
+        svc: &str,
+        workload: &PrimaryWorkload,
+        options: Option<&StatefulSetOptions>,
+        autoscaling: bool,
+    ) -> Result<()> {
+        match workload {
+            PrimaryWorkload::Statefulset => {
+                if let Some(ss) = options {
+                    ss.verify()?;
+                }
+            }
+            PrimaryWorkload::Deployment | PrimaryWorkload::Daemonset => {
+                if options.is_some() {
+                    bail!("{} sets statefulSet, which needs workload: Statefulset", svc);
+                }
+            }
+        }
+        if let PrimaryWorkload::Daemonset = workload {
+            if autoscaling {
+                bail!("{} is a Daemonset, which can not use autoScaling", svc);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatefulSetOptions;
+    use crate::states::PrimaryWorkload;
+
+    #[test]
+    fn verify_workload() {
+        let verify = |workload, options: Option<&StatefulSetOptions>, autoscaling| {
+            StatefulSetOptions::verify_workload("fake-svc", &workload, options, autoscaling)
+        };
+        let mut ss = StatefulSetOptions::default();
+        let err = verify(PrimaryWorkload::Deployment, Some(&ss), false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "fake-svc sets statefulSet, which needs workload: Statefulset"
+        );
+        assert!(verify(PrimaryWorkload::Statefulset, Some(&ss), false).is_ok());
+        ss = serde_yaml::from_str(
+            "serviceName: fake-svc-headless\npodManagementPolicy: Parallel\nvolumeClaimTemplates:\n\
+             - name: data\n  mountPath: /data\n  size: 1Gi\n- name: data\n  mountPath: /logs\n  size: 1Gi\n",
+        )
+        .unwrap();
+        assert!(verify(PrimaryWorkload::Statefulset, Some(&ss), false).is_err());
+        ss.volumeClaimTemplates[1].name = "logs".into();
+        assert!(verify(PrimaryWorkload::Statefulset, Some(&ss), false).is_ok());
+
+        assert!(verify(PrimaryWorkload::Daemonset, None, false).is_ok());
+        assert!(verify(PrimaryWorkload::Daemonset, None, true).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use super::Result;
+use std::collections::BTreeMap;
+
+/// What the scheduler does with a `Pod` that would violate the constraint
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub enum WhenUnsatisfiable {
+    /// Leave the `Pod` pending
+    DoNotSchedule,
+    /// Schedule anyway, but prefer nodes that reduce the skew
+    ScheduleAnyway,
+}
+impl Default for WhenUnsatisfiable {
+    fn default() -> Self {
+        WhenUnsatisfiable::DoNotSchedule
+    }
+}
+
+/// Label selector for the `Pod`s a constraint counts
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct LabelSelector {
+    /// Labels the counted `Pod`s must have
+    #[serde(default)]
+    pub matchLabels: BTreeMap<String, String>,
+}
+
+/// Kubernetes topology spread constraint for a service
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TopologySpreadConstraint {
+    /// Maximum allowed difference in `Pod` count between two topology domains
+    pub maxSkew: u32,
+    /// Node label defining the topology domains, e.g. `topology.kubernetes.io/zone`
+    pub topologyKey: String,
+    /// Behaviour when the constraint cannot be satisfied
+    #[serde(default)]
+    pub whenUnsatisfiable: WhenUnsatisfiable,
+    /// Pods to count, left to the chart (the service's own pods) if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labelSelector: Option<LabelSelector>,
+}
+
+impl TopologySpreadConstraint {
+    pub fn verify(&self) -> Result<()> {
+        if self.maxSkew < 1 {
+            bail!("topologySpread.maxSkew must be at least 1");
+        }
+        if self.topologyKey.is_empty() {
+            bail!("topologySpread.topologyKey cannot be empty");
+        }
+        if let Some(ls) = &self.labelSelector {
+            if ls.matchLabels.is_empty() {
+                bail!("topologySpread.labelSelector needs at least one label in matchLabels");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TopologySpreadConstraint, WhenUnsatisfiable};
+
+    #[test]
+    fn verify() {
+        let tsc: TopologySpreadConstraint = serde_yaml::from_str(
+            "maxSkew: 1\ntopologyKey: topology.kubernetes.io/zone\nlabelSelector:\n  matchLabels:\n    app: fake-ask",
+        )
+        .unwrap();
+        assert_eq!(tsc.whenUnsatisfiable, WhenUnsatisfiable::DoNotSchedule);
+        assert!(tsc.verify().is_ok());
+
+        let noskew: TopologySpreadConstraint =
+            serde_yaml::from_str("maxSkew: 0\ntopologyKey: topology.kubernetes.io/zone").unwrap();
+        assert!(noskew.verify().is_err());
+
+        let unknown = serde_yaml::from_str::<TopologySpreadConstraint>(
+            "maxSkew: 1\ntopologyKey: topology.kubernetes.io/zone\nwhenUnsatisfiable: Sometimes",
+        );
+        assert!(unknown.is_err());
+    }
+}
@@ -1,11 +1,11 @@
-use super::Result;
-use std::collections::BTreeMap;
+use super::{resources::parse_memory, ErrorKind, Result, VolumeAccessMode};
+use std::{collections::BTreeMap, ops::Not};
 
 // These structs contain a straight translation of kubernetes volumes
 // TODO: cross reference better with
 // https://kubernetes.io/docs/concepts/storage/volumes/
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct VolumeSecretItem {
     #[serde(default = "volume_key")]
     pub key: String,
@@ -20,35 +20,35 @@ fn volume_default_mode() -> u32 {
     420
 } // 0o644
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct VolumeSecretDetail {
     pub secretName: String,
     pub items: Vec<VolumeSecretItem>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct ProjectedVolumeSecretSourceDetail {
     pub name: String,
     pub items: Vec<VolumeSecretItem>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct ProjectedVolumeSecretSource {
     pub secret: ProjectedVolumeSecretSourceDetail,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct ProjectedVolumeSecret {
     pub sources: Vec<ProjectedVolumeSecretSource>,
     // pub default_mode: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct DownwardApiWrapper {
     pub items: Vec<DownwardApiItem>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct DownwardApiItem {
     /// Kube path to string
     pub path: String,
@@ -56,7 +56,7 @@ pub struct DownwardApiItem {
     pub resourceFieldRef: DownWardApiResource,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct DownWardApiResource {
     /// Name of container TODO: default to service name
     pub containerName: String,
@@ -67,7 +67,78 @@ pub struct DownWardApiResource {
     pub divisor: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+/// A PersistentVolumeClaim mounted as a volume
+///
+/// Refers to an existing claim, or creates the claim when `size` is set.
+///
+/// ```yaml
+/// volumes:
+/// - name: uploads
+///   persistentVolumeClaim:
+///     claimName: webapp-uploads
+///     size: 20Gi
+///     storageClass: gp2
+///     accessModes: [ReadWriteMany]
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct PvcSource {
+    /// Name of the claim, in the namespace of the service
+    pub claimName: String,
+    /// Mount the claim read-only
+    #[serde(default, skip_serializing_if = "Not::not")]
+    pub readOnly: bool,
+    /// Size of the claim to create, e.g. `10Gi`
+    ///
+    /// The claim is expected to exist already when this is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    /// StorageClass of the claim to create, the cluster default if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storageClass: Option<String>,
+    /// Access modes of the claim to create, `ReadWriteOnce` if unset
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accessModes: Vec<VolumeAccessMode>,
+}
+
+impl PvcSource {
+    /// Whether shipcat creates the claim
+    pub fn is_inline(&self) -> bool {
+        self.size.is_some()
+    }
+
+    /// Whether the created claim can only be mounted by pods on one node
+    pub fn is_read_write_once(&self) -> bool {
+        self.is_inline()
+            && (self.accessModes.is_empty() || self.accessModes.contains(&VolumeAccessMode::ReadWriteOnce))
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        let re = regex::Regex::new(r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?$").unwrap();
+        if self.claimName.len() > 63 || !re.is_match(&self.claimName) {
+            bail!("PersistentVolumeClaim name '{}' is not a valid name", self.claimName);
+        }
+        match &self.size {
+            Some(size) => {
+                let bytes = parse_memory(size)?;
+                if bytes <= 0.0 {
+                    bail!("PersistentVolumeClaim {} needs a positive size", self.claimName);
+                }
+            }
+            None => {
+                if self.storageClass.is_some() || !self.accessModes.is_empty() {
+                    bail!(
+                        "PersistentVolumeClaim {} sets storageClass or accessModes without a size to create it with",
+                        self.claimName
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct Volume {
     pub name: String,
     /// A projection combines multiple volume items
@@ -78,8 +149,9 @@ pub struct Volume {
     pub secret: Option<VolumeSecretDetail>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub emptyDir: Option<BTreeMap<String, String>>,
-    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
-    pub persistentVolumeClaim: BTreeMap<String, String>,
+    /// A PersistentVolumeClaim, which is created when it has a `size`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistentVolumeClaim: Option<PvcSource>,
     /// Items from the Downward API
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub downwardAPI: Option<DownwardApiWrapper>,
@@ -87,12 +159,21 @@ pub struct Volume {
 
 impl Volume {
     pub fn verify(&self) -> Result<()> {
-        // TODO: verify stuff here
+        if let Some(pvc) = &self.persistentVolumeClaim {
+            pvc.verify()?;
+        }
         Ok(())
     }
+
+    /// Whether the volume is a created claim that only pods on one node can mount
+    pub fn is_read_write_once(&self) -> bool {
+        self.persistentVolumeClaim
+            .as_ref()
+            .map_or(false, |pvc| pvc.is_read_write_once())
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct VolumeMount {
     pub name: String,
     pub mountPath: String,
@@ -101,3 +182,115 @@ pub struct VolumeMount {
     #[serde(default)]
     pub readOnly: bool,
 }
+
+/// Verify that no two mount paths of a container overlap
+///
+/// Takes every mounted path with where it comes from.
+/// A path mounted twice, or inside another mount, shadows files of the other mount,
+/// so every such pair is reported as one error.
+pub fn verify_mount_paths(svc: &str, paths: &[(String, String)]) -> Result<()> {
+    let mut collisions = vec![];
+    for (i, (src, path)) in paths.iter().enumerate() {
+        let path = path.trim_end_matches('/');
+        for (other_src, other) in &paths[i + 1..] {
+            let other = other.trim_end_matches('/');
+            if path == other {
+                collisions.push(format!("{} and {} at {}", src, other_src, path));
+            } else if is_subpath(other, path) {
+                collisions.push(format!("{} at {} contains {} at {}", src, path, other_src, other));
+            } else if is_subpath(path, other) {
+                collisions.push(format!("{} at {} contains {} at {}", other_src, other, src, path));
+            }
+        }
+    }
+    if !collisions.is_empty() {
+        bail!(ErrorKind::MountCollision(svc.to_string(), collisions.join(", ")));
+    }
+    Ok(())
+}
+
+/// Whether `path` lies strictly inside the directory `parent`
+///
+/// Both paths are expected without trailing slashes.
+fn is_subpath(path: &str, parent: &str) -> bool {
+    path.starts_with(parent) && path[parent.len()..].starts_with('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_mount_paths, PvcSource, Volume, VolumeAccessMode};
+    use crate::ErrorKind;
+
+    #[test]
+    fn verify_pvc() {
+        let mut pvc = PvcSource {
+            claimName: "webapp-uploads".into(),
+            ..Default::default()
+        };
+        assert!(pvc.verify().is_ok());
+        assert!(!pvc.is_read_write_once());
+
+        pvc.storageClass = Some("gp2".into());
+        assert!(pvc.verify().is_err());
+        pvc.size = Some("20Gi".into());
+        assert!(pvc.verify().is_ok());
+        assert!(pvc.is_read_write_once());
+        pvc.accessModes = vec![VolumeAccessMode::ReadWriteMany];
+        assert!(!pvc.is_read_write_once());
+
+        pvc.size = Some("20 gigs".into());
+        assert!(pvc.verify().is_err());
+        pvc.size = Some("20Gi".into());
+        pvc.claimName = "Webapp_Uploads".into();
+        assert!(pvc.verify().is_err());
+    }
+
+    #[test]
+    fn read_write_once_volume() {
+        let mut v: Volume = serde_yaml::from_str("name: scratch\nemptyDir: {}").unwrap();
+        assert!(!v.is_read_write_once());
+        v = serde_yaml::from_str("name: uploads\npersistentVolumeClaim:\n  claimName: fake-uploads").unwrap();
+        assert!(!v.is_read_write_once());
+        v.persistentVolumeClaim.as_mut().unwrap().size = Some("1Gi".into());
+        assert!(v.is_read_write_once());
+    }
+
+    #[test]
+    fn mount_paths() {
+        let path = |src: &str, p: &str| (src.to_string(), p.to_string());
+        let mut paths = vec![
+            path("config settings.yml.j2", "/app/config/settings.yml"),
+            path("volumeMount data", "/app/data"),
+        ];
+        assert!(verify_mount_paths("fake-svc", &paths).is_ok());
+
+        // similar prefixes are not nested
+        paths[1].1 = "/app/config/settings.yml.bak".into();
+        assert!(verify_mount_paths("fake-svc", &paths).is_ok());
+
+        // mounts inside other mounts shadow them
+        paths[1].1 = "/app/data".into();
+        paths.push(path("volumeMount cache", "/app/data/cache/"));
+        let err = verify_mount_paths("fake-svc", &paths).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Mount paths collide in fake-svc: volumeMount data at /app/data contains volumeMount cache at /app/data/cache"
+        );
+
+        // all overlaps are reported
+        paths[2].1 = "/app/config/settings.yml".into();
+        paths.push(path("persistentVolume store", "/app"));
+        let err = verify_mount_paths("fake-svc", &paths).unwrap_err();
+        match err.kind() {
+            ErrorKind::MountCollision(svc, collisions) => {
+                assert_eq!(svc, "fake-svc");
+                assert!(collisions
+                    .contains("config settings.yml.j2 and volumeMount cache at /app/config/settings.yml"));
+                assert!(
+                    collisions.contains("persistentVolume store at /app contains volumeMount data at /app/data")
+                );
+            }
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+    }
+}
@@ -42,7 +42,7 @@ pub fn render_file_data(data: String, context: &Context) -> Result<String> {
     // TODO: should be async, but tera needs to expose it
     let result = tera
         .render("one_off", context)
-        .chain_err(|| ErrorKind::InvalidOneOffTemplate(data))?;
+        .chain_err(|| ErrorKind::TemplateRender(data))?;
     let mut xs = vec![];
     for l in result.lines() {
         // trim whitespace (mostly to satisfy linters)
@@ -81,6 +81,7 @@ impl Manifest {
         ctx.insert("environment", &reg.environment.to_string());
         ctx.insert("region", &reg.name.clone());
         ctx.insert("kafka", &self.kafka.clone());
+        ctx.insert("features", &self.features);
         ctx.insert("base_urls", &reg.base_urls);
         ctx.insert("kong", &reg.kong);
         ctx.insert("cluster", &reg.cluster.clone());
@@ -91,8 +92,8 @@ impl Manifest {
     /// Replace template in values with template result inplace
     pub fn template_configs(&mut self, reg: &Region) -> Result<()> {
         let ctx = self.make_template_context(reg)?;
-        if let Some(ref mut cfg) = self.configs {
-            for f in &mut cfg.files {
+        if let Some(ref mut cfgs) = self.configs {
+            for f in cfgs.iter_mut().flat_map(|cfg| cfg.files.iter_mut()) {
                 if let Some(ref mut v) = f.value {
                     let data: String = v.clone();
                     let svc = self.name.clone();
@@ -105,6 +106,31 @@ impl Manifest {
         Ok(())
     }
 
+    /// Template `serviceAnnotations` values inplace
+    ///
+    /// Uses the same context as evars, so values can refer to e.g. `{{ service }}`,
+    /// `{{ region }}` or region env vars like `{{ env.SSL_CERT_ARN }}`.
+    /// This happens before secrets, and secret templates are not allowed.
+    pub fn template_annotations(&mut self, reg: &Region) -> Result<()> {
+        let ctx = self.make_template_context(reg)?;
+        let svc = self.name.clone();
+        for (k, v) in self.serviceAnnotations.iter_mut() {
+            *v = one_off(v, &ctx).chain_err(|| ErrorKind::InvalidTemplate(svc.clone()))?;
+            if v.starts_with("SHIPCAT_SECRET::") {
+                bail!("serviceAnnotation {} in {} cannot be a secret", k, svc);
+            }
+        }
+        Ok(())
+    }
+
+    /// Render a template with the same context as config files
+    ///
+    /// Useful for trying out a `.j2` template against a service before adding it to `configs`.
+    pub fn render_template(&self, reg: &Region, data: String) -> Result<String> {
+        let ctx = self.make_template_context(reg)?;
+        render_file_data(data, &ctx).chain_err(|| ErrorKind::InvalidTemplate(self.name.clone()))
+    }
+
     /// Template evars - must happen before inline templates!
     pub fn template_evars(&mut self, reg: &Region) -> Result<()> {
         let ctx = self.make_template_context(reg)?;
@@ -150,6 +176,7 @@ impl VaultConfig {
     pub async fn template(&self, owned_mfs: Vec<String>, env: Environment) -> Result<String> {
         let mut ctx = Context::new();
         ctx.insert("folder", &self.folder);
+        ctx.insert("mount", &self.mount);
         ctx.insert("team_owned_services", &owned_mfs);
 
         let tpl = if env == Environment::Prod {
@@ -162,3 +189,24 @@ impl VaultConfig {
         Ok(res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{one_off, render_file_data};
+    use crate::ErrorKind;
+    use tera::Context;
+
+    #[test]
+    fn render_error_kind() {
+        let err = render_file_data("{{ missing }}".into(), &Context::new()).unwrap_err();
+        match err.kind() {
+            ErrorKind::TemplateRender(tpl) => assert_eq!(tpl, "{{ missing }}"),
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+        let err = one_off("{{ missing }}", &Context::new()).unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidOneOffTemplate(tpl) => assert_eq!(tpl, "{{ missing }}"),
+            _ => panic!("unexpected error kind {:?}", err.kind()),
+        }
+    }
+}
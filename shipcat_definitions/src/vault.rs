@@ -1,7 +1,13 @@
-use std::{collections::BTreeMap, env};
+use std::{
+    collections::BTreeMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use super::{Error, ErrorKind, Result, ResultExt};
 use crate::region::VaultConfig;
+use serde_json::json;
 
 fn default_addr() -> Result<String> {
     env::var("VAULT_ADDR").map_err(|_| ErrorKind::MissingVaultAddr.into())
@@ -29,6 +35,44 @@ fn default_token() -> Result<String> {
         .chain_err(|| ErrorKind::MissingVaultToken)
 }
 
+/// How to authenticate against vault, chosen by `VAULT_AUTH_METHOD`
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+enum AuthMethod {
+    /// A `VAULT_TOKEN` evar or a `~/.vault-token` file (the default)
+    Token,
+    /// An AppRole login with `VAULT_ROLE_ID` and `VAULT_SECRET_ID`
+    AppRole,
+    /// A kubernetes login as the `VAULT_KUBERNETES_ROLE` with the pod's service account token
+    Kubernetes,
+}
+
+/// Where kubernetes mounts the service account token in pods
+const SERVICE_ACCOUNT_TOKEN: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+fn default_auth_method() -> Result<AuthMethod> {
+    match env::var("VAULT_AUTH_METHOD").as_ref().map(String::as_str) {
+        Err(_) | Ok("token") => Ok(AuthMethod::Token),
+        Ok("approle") => Ok(AuthMethod::AppRole),
+        Ok("kubernetes") => Ok(AuthMethod::Kubernetes),
+        Ok(m) => bail!(
+            "Unsupported VAULT_AUTH_METHOD {} - expected token, approle or kubernetes",
+            m
+        ),
+    }
+}
+
+fn default_approle() -> Result<(String, String)> {
+    let role_id = env::var("VAULT_ROLE_ID").chain_err(|| ErrorKind::MissingVaultAppRole)?;
+    let secret_id = env::var("VAULT_SECRET_ID").chain_err(|| ErrorKind::MissingVaultAppRole)?;
+    Ok((role_id, secret_id))
+}
+
+fn default_kubernetes_role() -> Result<(String, String)> {
+    let role = env::var("VAULT_KUBERNETES_ROLE").chain_err(|| ErrorKind::MissingVaultKubernetesRole)?;
+    let jwt_path = env::var("VAULT_KUBERNETES_JWT_PATH").unwrap_or_else(|_| SERVICE_ACCOUNT_TOKEN.into());
+    Ok((role, jwt_path))
+}
+
 /// Secrets in vault values can be integers or strings
 ///
 /// If they are integers, we coerce them to strings
@@ -68,7 +112,128 @@ struct ListSecrets {
     data: BTreeMap<String, Vec<String>>,
 }
 
+/// Token information from vault's `auth/token/lookup-self`
+#[derive(Debug, Deserialize)]
+struct TokenLookup {
+    data: TokenData,
+}
+#[derive(Debug, Deserialize)]
+struct TokenData {
+    /// Seconds left before the token expires, 0 if it never does
+    ttl: u64,
+    #[serde(default)]
+    renewable: bool,
+}
+
+/// Token information from vault's `auth/token/renew-self` and logins
+#[derive(Debug, Deserialize)]
+struct TokenRenewal {
+    auth: TokenAuth,
+}
+#[derive(Debug, Deserialize)]
+struct TokenAuth {
+    /// Only set on logins
+    #[serde(default)]
+    client_token: String,
+    lease_duration: u64,
+    #[serde(default)]
+    renewable: bool,
+}
+
+/// How far ahead of token expiry we renew it
+const RENEWAL_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// What we know about the lifetime of our vault token
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenLife {
+    /// Not looked up yet
+    Unknown,
+    /// Token without a ttl (e.g. root tokens)
+    Unlimited,
+    /// Token that expires at a point in time
+    Expires { at: Instant, renewable: bool },
+}
+
+impl TokenLife {
+    fn from_ttl(ttl: u64, renewable: bool) -> Self {
+        if ttl == 0 {
+            TokenLife::Unlimited
+        } else {
+            TokenLife::Expires {
+                at: Instant::now() + Duration::from_secs(ttl),
+                renewable,
+            }
+        }
+    }
+
+    /// Whether the token needs to be looked up or renewed before use
+    fn needs_refresh(&self, now: Instant) -> bool {
+        match *self {
+            TokenLife::Unknown => true,
+            TokenLife::Unlimited => false,
+            TokenLife::Expires { at, .. } => at <= now + RENEWAL_MARGIN,
+        }
+    }
+}
+
+/// How long a secret read from vault is reused for
+const CACHE_TTL: Duration = Duration::from_secs(60);
+/// The most secrets kept in memory at once
+const CACHE_SIZE: usize = 1000;
+
+/// Secrets read recently, keyed by vault address and path
+///
+/// Services that share a secret only need it read once during a fleet operation.
+/// Entries expire after `CACHE_TTL` and the oldest is dropped when full,
+/// so secrets are not held for longer than needed.
+#[derive(Default)]
+struct SecretCache {
+    entries: BTreeMap<String, (Instant, String)>,
+    hits: usize,
+    misses: usize,
+}
+
+impl SecretCache {
+    fn get(&mut self, key: &str, now: Instant) -> Option<String> {
+        self.entries.retain(|_, (at, _)| now < *at + CACHE_TTL);
+        let res = self.entries.get(key).map(|(_, v)| v.clone());
+        if res.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        res
+    }
+
+    fn insert(&mut self, key: String, value: String, now: Instant) {
+        if self.entries.len() >= CACHE_SIZE && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (at, _))| *at)
+                .map(|(k, _)| k.clone());
+            if let Some(k) = oldest {
+                self.entries.remove(&k);
+            }
+        }
+        self.entries.insert(key, (now, value));
+    }
+}
+
+/// Vault url, auth method and who logs in with it (the token, AppRole role id or kubernetes role)
+type SessionKey = (String, AuthMethod, String);
+
+lazy_static! {
+    /// Regional clients, so that fleet operations share one session per vault and login
+    static ref SESSIONS: Mutex<BTreeMap<SessionKey, Vault>> = Mutex::new(BTreeMap::new());
+    /// Secrets read during this run
+    static ref SECRETS: Mutex<SecretCache> = Mutex::new(SecretCache::default());
+}
+
 /// Vault client with cached data
+///
+/// Clones share the same token session.
+#[derive(Clone)]
 pub struct Vault {
     /// Our HTTP client.  This can be configured to mock out the network.
     client: reqwest::Client,
@@ -78,6 +243,8 @@ pub struct Vault {
     token: String,
     /// Vault operation mode
     mode: Mode,
+    /// Lifetime of the token, tracked so that it can be renewed during long runs
+    life: Arc<Mutex<TokenLife>>,
 }
 
 /// Vault usage mode
@@ -100,14 +267,112 @@ impl Vault {
         )
     }
 
-    /// Initialize using VAULT_TOKEN evar + addr from the Region
-    pub fn regional(vc: &VaultConfig) -> Result<Vault> {
-        Vault::new(reqwest::Client::new(), &vc.url, default_token()?, Mode::Standard)
+    /// Initialize using the `VAULT_AUTH_METHOD` evar + addr from the Region
+    ///
+    /// Clients are cached per vault url and login, so repeated calls reuse one session.
+    pub async fn regional(vc: &VaultConfig) -> Result<Vault> {
+        let method = default_auth_method()?;
+        let identity = match method {
+            AuthMethod::Token => default_token()?,
+            AuthMethod::AppRole => default_approle()?.0,
+            AuthMethod::Kubernetes => default_kubernetes_role()?.0,
+        };
+        let session = (vc.url.clone(), method.clone(), identity);
+        if let Some(v) = SESSIONS.lock().unwrap().get(&session) {
+            return Ok(v.clone());
+        }
+        let v = match method {
+            AuthMethod::Token => {
+                Vault::new(reqwest::Client::new(), &vc.url, default_token()?, Mode::Standard)?
+            }
+            AuthMethod::AppRole => {
+                let (role_id, secret_id) = default_approle()?;
+                Vault::from_approle(&vc.url, &role_id, &secret_id).await?
+            }
+            AuthMethod::Kubernetes => {
+                let (role, jwt_path) = default_kubernetes_role()?;
+                Vault::from_kubernetes_auth(&vc.url, &role, &jwt_path).await?
+            }
+        };
+        SESSIONS.lock().unwrap().insert(session, v.clone());
+        Ok(v)
+    }
+
+    /// Initialize by logging in with an AppRole
+    ///
+    /// For automated pipelines where long-lived tokens are not available.
+    pub async fn from_approle(addr: &str, role_id: &str, secret_id: &str) -> Result<Vault> {
+        let body = json!({ "role_id": role_id, "secret_id": secret_id });
+        Vault::login(addr, "approle", body, None).await
+    }
+
+    /// Initialize by logging in with a kubernetes service account token
+    ///
+    /// For running inside the cluster, where the pod's service account is bound to a vault role.
+    pub async fn from_kubernetes_auth(addr: &str, role: &str, jwt_path: &str) -> Result<Vault> {
+        let jwt = std::fs::read_to_string(jwt_path)
+            .chain_err(|| format!("Failed to read service account token from {}", jwt_path))?;
+        let body = json!({ "role": role, "jwt": jwt.trim() });
+        // vault rejects unknown roles with a 400, and unbound service accounts with a 403
+        let denied = ErrorKind::VaultKubernetesRole(role.to_string());
+        Vault::login(addr, "kubernetes", body, Some(denied)).await
+    }
+
+    // Log in with a vault auth method and use the resulting token
+    //
+    // Client errors are explained by `denied` when given.
+    async fn login(
+        addr: &str,
+        method: &str,
+        body: serde_json::Value,
+        denied: Option<ErrorKind>,
+    ) -> Result<Vault> {
+        let client = reqwest::Client::new();
+        let url = reqwest::Url::parse(addr)?.join(&format!("v1/auth/{}/login", method))?;
+        debug!("POST {}", url);
+
+        let mkerr = || ErrorKind::VaultLogin(url.clone());
+        let res = client
+            .post(url.clone())
+            .body(body.to_string())
+            .send()
+            .await
+            .chain_err(&mkerr)?;
+        if !res.status().is_success() {
+            let status = res.status().to_owned();
+            let err: Error = ErrorKind::UnexpectedHttpStatus(status).into();
+            return match denied {
+                Some(d) if status.is_client_error() => Err(err).chain_err(|| d),
+                _ => Err(err).chain_err(&mkerr),
+            };
+        }
+        let login: TokenRenewal = serde_json::from_str(&res.text().await?).chain_err(&mkerr)?;
+
+        let v = Vault::new(client, addr, login.auth.client_token, Mode::Standard)?;
+        *v.life.lock().unwrap() = TokenLife::from_ttl(login.auth.lease_duration, login.auth.renewable);
+        Ok(v)
     }
 
     /// Initialize using dummy values and return garbage
     pub fn mocked(vc: &VaultConfig) -> Result<Vault> {
-        Vault::new(reqwest::Client::new(), &vc.url, default_token()?, Mode::Mocked)
+        // no requests are made, so no credentials are needed either
+        let token = default_token().unwrap_or_default();
+        Vault::new(reqwest::Client::new(), &vc.url, token, Mode::Mocked)
+    }
+
+    /// Initialize a client for another vault using the same token and mode
+    ///
+    /// The same vault keeps sharing this client's token session.
+    pub fn retarget(&self, vc: &VaultConfig) -> Result<Vault> {
+        if reqwest::Url::parse(&vc.url)? == self.addr {
+            return Ok(self.clone());
+        }
+        Vault::new(self.client.clone(), &vc.url, self.token.clone(), self.mode.clone())
+    }
+
+    /// Whether this client returns dummy data instead of real secrets
+    pub fn is_mocked(&self) -> bool {
+        self.mode == Mode::Mocked
     }
 
     fn new<U, S>(client: reqwest::Client, addr: U, token: S, mode: Mode) -> Result<Vault>
@@ -121,6 +386,7 @@ impl Vault {
             addr,
             mode,
             token: token.into(),
+            life: Arc::new(Mutex::new(TokenLife::Unknown)),
         })
     }
 
@@ -128,12 +394,90 @@ impl Vault {
         self.mode.clone()
     }
 
+    /// Number of secret reads served from the cache and from vault so far
+    pub fn cache_stats() -> (usize, usize) {
+        let cache = SECRETS.lock().unwrap();
+        (cache.hits, cache.misses)
+    }
+
+    /// Forget all cached secrets
+    pub fn clear_cache() {
+        SECRETS.lock().unwrap().entries.clear();
+    }
+
+    /// Ensure the token is valid for a while yet
+    ///
+    /// Looks up the token ttl on first use, and renews it when it is close to expiry,
+    /// so that long fleet operations do not start failing partway through.
+    async fn ensure_token(&self) -> Result<()> {
+        let mut life = *self.life.lock().unwrap();
+        let now = Instant::now();
+        if self.mode == Mode::Mocked || !life.needs_refresh(now) {
+            return Ok(());
+        }
+        if life == TokenLife::Unknown {
+            // tokens without permission to look themselves up can still read secrets
+            life = match self.token_request::<TokenLookup>(reqwest::Method::GET, "lookup-self").await {
+                Ok(res) => {
+                    debug!("Vault token expires in {}s", res.data.ttl);
+                    TokenLife::from_ttl(res.data.ttl, res.data.renewable)
+                }
+                Err(e) => {
+                    warn!("Failed to look up the vault token, so it will not be renewed: {}", e);
+                    TokenLife::Unlimited
+                }
+            };
+            *self.life.lock().unwrap() = life;
+            if !life.needs_refresh(now) {
+                return Ok(());
+            }
+        }
+        match life {
+            TokenLife::Expires { renewable: true, .. } => {
+                let res: TokenRenewal = self
+                    .token_request(reqwest::Method::POST, "renew-self")
+                    .await
+                    .chain_err(|| ErrorKind::VaultTokenRenewal)?;
+                debug!("Renewed vault token for {}s", res.auth.lease_duration);
+                *self.life.lock().unwrap() = TokenLife::from_ttl(res.auth.lease_duration, res.auth.renewable);
+            }
+            TokenLife::Expires { at, .. } if at <= now => bail!(ErrorKind::VaultTokenExpired),
+            _ => debug!("Vault token expires soon and is not renewable"),
+        }
+        Ok(())
+    }
+
+    // HTTP calls against vault's token api for our own token
+    async fn token_request<T>(&self, method: reqwest::Method, action: &str) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let url = self.addr.join(&format!("v1/auth/token/{}", action))?;
+        debug!("{} {}", method, url);
+
+        let res = self
+            .client
+            .request(method, url.clone())
+            .header("X-Vault-Token", self.token.clone())
+            .send()
+            .await
+            .chain_err(|| ErrorKind::Url(url.clone()))?;
+        if !res.status().is_success() {
+            let status = res.status().to_owned();
+            let err: Error = ErrorKind::UnexpectedHttpStatus(status).into();
+            return Err(err).chain_err(|| ErrorKind::Url(url.clone()));
+        }
+        let body = res.text().await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
     // The actual HTTP GET logic
     async fn get_secret(&self, path: &str) -> Result<Secret> {
+        self.ensure_token().await?;
         let url = self.addr.join(&format!("v1/{}", path))?;
         debug!("GET {}", url);
 
-        let mkerr = || ErrorKind::Url(url.clone());
+        let mkerr = || ErrorKind::VaultRead(url.clone());
         let res = self
             .client
             .get(url.clone())
@@ -158,10 +502,11 @@ impl Vault {
     ///
     /// Does a HTTP LIST on the folder a service is in and returns the keys
     pub async fn list(&self, path: &str) -> Result<Vec<String>> {
-        let url = self.addr.join(&format!("v1/secret/{}?list=true", path))?;
+        self.ensure_token().await?;
+        let url = self.addr.join(&format!("v1/{}?list=true", path))?;
         debug!("LIST {}", url);
 
-        let mkerr = || ErrorKind::Url(url.clone());
+        let mkerr = || ErrorKind::VaultRead(url.clone());
         let res = self
             .client
             .get(url.clone())
@@ -197,12 +542,19 @@ impl Vault {
     }
 
     /// Read secret from a Vault via an authenticated HTTP GET (or memory cache)
+    ///
+    /// The key is the full path to the secret, including the mount.
     pub async fn read(&self, key: &str) -> Result<String> {
-        let pth = format!("secret/{}", key);
+        let pth = key.to_string();
         if self.mode == Mode::Mocked {
             // arbitrary base64 encoded value so it's compatible with everything
             return Ok("aGVsbG8gd29ybGQ=".into());
         }
+        let cache_key = format!("{}{}", self.addr, pth);
+        if let Some(value) = SECRETS.lock().unwrap().get(&cache_key, Instant::now()) {
+            trace!("Using cached secret {}", pth);
+            return Ok(value);
+        }
 
         let secret = self
             .get_secret(&pth)
@@ -211,32 +563,79 @@ impl Vault {
 
         // NB: Currently assume each path in vault has a single `value`
         // Read the value key (which should exist)
-        secret
+        let value: String = secret
             .data
             .get("value")
-            .ok_or_else(|| ErrorKind::InvalidSecretForm(pth).into())
-            .map(|v| v.clone().into())
+            .ok_or_else(|| Error::from(ErrorKind::InvalidSecretForm(pth)))?
+            .clone()
+            .into();
+        SECRETS
+            .lock()
+            .unwrap()
+            .insert(cache_key, value.clone(), Instant::now());
+        Ok(value)
     }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use super::Vault;
+    use super::{SecretCache, TokenLife, Vault, CACHE_SIZE, CACHE_TTL, RENEWAL_MARGIN};
     use base64;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn token_refresh() {
+        let now = Instant::now();
+        assert!(TokenLife::Unknown.needs_refresh(now));
+        assert!(!TokenLife::Unlimited.needs_refresh(now));
+        assert_eq!(TokenLife::from_ttl(0, true), TokenLife::Unlimited);
+
+        let long = TokenLife::Expires {
+            at: now + RENEWAL_MARGIN + Duration::from_secs(60),
+            renewable: true,
+        };
+        assert!(!long.needs_refresh(now));
+        let short = TokenLife::Expires {
+            at: now + Duration::from_secs(60),
+            renewable: true,
+        };
+        assert!(short.needs_refresh(now));
+    }
+
+    #[test]
+    fn secret_cache() {
+        let now = Instant::now();
+        let mut cache = SecretCache::default();
+        assert_eq!(cache.get("secret/a", now), None);
+        cache.insert("secret/a".into(), "hello".into(), now);
+        assert_eq!(cache.get("secret/a", now), Some("hello".into()));
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+
+        // entries expire
+        assert_eq!(cache.get("secret/a", now + CACHE_TTL), None);
+        assert!(cache.entries.is_empty());
+
+        // and the oldest is dropped when full
+        for i in 0..CACHE_SIZE + 1 {
+            cache.insert(format!("secret/{}", i), "x".into(), now + Duration::from_millis(i as u64));
+        }
+        assert_eq!(cache.entries.len(), CACHE_SIZE);
+        assert!(!cache.entries.contains_key("secret/0"));
+    }
 
     #[tokio::test]
     async fn get_dev_secret() {
         let client = Vault::from_evars().unwrap();
-        let secret = client.read("dev-uk/test-shipcat/FAKE_SECRET").await.unwrap();
+        let secret = client.read("secret/dev-uk/test-shipcat/FAKE_SECRET").await.unwrap();
         assert_eq!(secret, "hello");
 
         // integers in vault coerced to strings
-        let secretnum = client.read("dev-uk/test-shipcat/FAKE_NUMBER").await.unwrap();
+        let secretnum = client.read("secret/dev-uk/test-shipcat/FAKE_NUMBER").await.unwrap();
         assert_eq!(secretnum, "-2");
 
         // secretfiles are valid base64
-        let secretfile = client.read("dev-uk/test-shipcat/fake-file").await.unwrap();
+        let secretfile = client.read("secret/dev-uk/test-shipcat/fake-file").await.unwrap();
         assert_eq!(secretfile, "aGVsbG8gd29ybGQgYmFzZTY0Cg==".to_string());
         if let Ok(b) = base64::decode(&secretfile) {
             let s = String::from_utf8(b).unwrap();
@@ -251,7 +650,7 @@ mod tests {
     #[ignore]
     async fn list_dev_secrets() {
         let client = Vault::from_evars().unwrap();
-        let mut secrets = client.list("dev-uk/test-shipcat").await.unwrap();
+        let mut secrets = client.list("secret/dev-uk/test-shipcat").await.unwrap();
         secrets.sort_unstable(); // ignore key order
         assert_eq!(secrets, vec![
             "FAKE_NUMBER".to_string(),
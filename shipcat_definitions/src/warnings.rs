@@ -0,0 +1,52 @@
+/// Kinds of soft issues found when verifying manifests
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningCode {
+    /// Kube-external services skip most validation
+    ExternalService,
+    /// Neither a health check nor a readiness probe is set
+    MissingHealthCheck,
+    /// Kong routes to a service without an `httpPort`
+    MissingHttpPort,
+    /// A port below 1024 needs extra capabilities to bind
+    PrivilegedPort,
+    /// A resource limit is far above its request
+    HighLimitRatio,
+    /// A dependency is disabled or not deployed in the region
+    DisabledDependency,
+    /// A long health check wait is used instead of a startup probe
+    SlowStartup,
+    /// The environment of a service is large enough to risk failing container starts
+    LargeEnv,
+    /// The environment of a service has more variables than recommended
+    ManyEnvVars,
+    /// A plain env value looks like a secret
+    PlaintextSecret,
+    /// Rollouts of the service are paused
+    Paused,
+    /// The version is not pinned by a lockfile in a region that requires one
+    UnlockedVersion,
+    /// The service runs in the network namespace of its node
+    HostNetwork,
+    /// The service is smaller in a region than in a region of a lower tier
+    SmallerThanLowerTier,
+    /// The service exposes no port and has no dependencies, cron jobs or sidecars
+    Standalone,
+}
+
+/// A soft issue found when verifying a manifest
+///
+/// These do not fail validation, but are returned alongside successful results
+/// so that callers can surface them, e.g. in a PR comment.
+#[derive(Serialize, Clone, Debug)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+}
+
+impl Warning {
+    /// Create a warning, and log it as well
+    pub fn new(code: WarningCode, message: String) -> Warning {
+        warn!("{}", message);
+        Warning { code, message }
+    }
+}
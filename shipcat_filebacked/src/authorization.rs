@@ -4,7 +4,7 @@ use shipcat_definitions::structs::Authorization;
 
 use super::{util::Build, Result};
 
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone)]
 pub struct AuthorizationSource {
     pub allowed_audiences: Option<Vec<String>>,
     pub allow_anonymous: Option<bool>,
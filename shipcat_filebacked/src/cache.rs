@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use walkdir::WalkDir;
+
+use shipcat_definitions::{Config, Manifest, Region, Result};
+
+use crate::manifest::ManifestSource;
+
+/// Modification times of every file a service is built from
+type SourceTimes = BTreeMap<PathBuf, SystemTime>;
+
+struct CacheEntry {
+    sources: SourceTimes,
+    base: Manifest,
+    completed: Option<(Instant, Manifest)>,
+}
+
+/// Opt-in cache of loaded manifests for long running processes
+///
+/// Manifests are keyed by service and region, and are reloaded when any file in the
+/// service's folder or in the base templates is added, removed or modified.
+/// Completed manifests contain secrets, so they are only reused within `secret_ttl`
+/// to respect vault lease durations.
+///
+/// Changes to `shipcat.conf` are not tracked; create a new cache after reloading the config.
+pub struct ManifestCache {
+    entries: BTreeMap<(String, String), CacheEntry>,
+    secret_ttl: Duration,
+}
+
+impl ManifestCache {
+    pub fn new(secret_ttl: Duration) -> Self {
+        ManifestCache {
+            entries: BTreeMap::new(),
+            secret_ttl,
+        }
+    }
+
+    /// Load a manifest like `shipcat_filebacked::load_manifest`, reusing unchanged results
+    pub async fn load_manifest(&mut self, service: &str, conf: &Config, reg: &Region) -> Result<Manifest> {
+        let key = (service.to_string(), reg.name.clone());
+        let mut sources = source_times(&ManifestSource::services_dir().join(service))?;
+        let bases = ManifestSource::bases_dir();
+        if bases.is_dir() {
+            sources.extend(source_times(&bases)?);
+        }
+        if let Some(entry) = self.entries.get(&key) {
+            if entry.sources == sources {
+                trace!("Using cached manifest for {} in {}", service, reg.name);
+                return Ok(entry.base.clone());
+            }
+        }
+        debug!("Loading manifest for {} in {}", service, reg.name);
+        let base = ManifestSource::load_manifest(service, conf, reg).await?;
+        self.entries.insert(key, CacheEntry {
+            sources,
+            base: base.clone(),
+            completed: None,
+        });
+        Ok(base)
+    }
+
+    /// Load a manifest with secrets, reusing it until the secrets are older than `secret_ttl`
+    pub async fn load_completed(&mut self, service: &str, conf: &Config, reg: &Region) -> Result<Manifest> {
+        let base = self.load_manifest(service, conf, reg).await?;
+        let key = (service.to_string(), reg.name.clone());
+        let entry = self.entries.get_mut(&key).expect("entry was just loaded");
+        if let Some((fetched, mf)) = &entry.completed {
+            if fetched.elapsed() < self.secret_ttl {
+                return Ok(mf.clone());
+            }
+        }
+        let completed = base.complete(reg).await?;
+        entry.completed = Some((Instant::now(), completed.clone()));
+        Ok(completed)
+    }
+
+    /// Forget all cached manifests for a service
+    pub fn invalidate(&mut self, service: &str) {
+        self.entries.retain(|(svc, _), _| svc != service);
+    }
+}
+
+fn source_times(dir: &Path) -> Result<SourceTimes> {
+    let mut res = BTreeMap::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        if entry.file_type().is_file() {
+            let modified = entry.metadata().map_err(|e| e.to_string())?.modified()?;
+            res.insert(entry.path().to_path_buf(), modified);
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        path::Path,
+        sync::Once,
+        time::Duration,
+    };
+
+    use super::{source_times, ManifestCache};
+    use crate::manifest::ManifestSource;
+    use shipcat_definitions::Config;
+
+    static START: Once = Once::new();
+
+    /// Run from the tests directory, which has a couple of fake services
+    fn setup() {
+        START.call_once(|| {
+            let pth = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("tests");
+            env::set_current_dir(fs::canonicalize(pth).unwrap()).unwrap();
+        });
+    }
+
+    #[test]
+    fn source_times_track_files() {
+        let dir = env::temp_dir().join(format!("shipcat-cache-{}", std::process::id()));
+        fs::create_dir_all(dir.join("configs")).unwrap();
+        fs::write(dir.join("manifest.yml"), "name: fake\n").unwrap();
+        let before = source_times(&dir).unwrap();
+        assert_eq!(before.len(), 1);
+
+        fs::write(dir.join("configs").join("app.conf.j2"), "x").unwrap();
+        let after = source_times(&dir).unwrap();
+        assert_ne!(before, after);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cached_manifests() {
+        setup();
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let mut cache = ManifestCache::new(Duration::from_secs(60));
+        let first = cache.load_manifest("fake-ask", &conf, &region).await.unwrap();
+        assert_eq!(cache.entries.len(), 1);
+        let second = cache.load_manifest("fake-ask", &conf, &region).await.unwrap();
+        assert_eq!(first.name, second.name);
+        assert_eq!(first.version, second.version);
+
+        cache.invalidate("fake-ask");
+        assert!(cache.entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn base_changes_invalidate() {
+        setup();
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+        let key = ("fake-storage".to_string(), region.name.clone());
+        let base = ManifestSource::bases_dir().join("web").join("shipcat.yml");
+
+        let mut cache = ManifestCache::new(Duration::from_secs(60));
+        cache.load_manifest("fake-storage", &conf, &region).await.unwrap();
+        let loaded = cache.entries[&key].sources[&base];
+
+        // touch the base template the service extends
+        let touched = loaded + Duration::from_secs(1);
+        let file = fs::OpenOptions::new().write(true).open(&base).unwrap();
+        file.set_modified(touched).unwrap();
+        cache.load_manifest("fake-storage", &conf, &region).await.unwrap();
+        assert_eq!(cache.entries[&key].sources[&base], touched);
+    }
+}
@@ -10,7 +10,7 @@ use std::collections::BTreeMap;
 
 use super::source::{ContainerBuildParams, ContainerSource};
 
-#[derive(Deserialize, Merge, Clone, Default)]
+#[derive(Serialize, Deserialize, Merge, Clone, Default)]
 #[serde(default, rename_all = "camelCase")]
 pub struct CronJobSource {
     pub schedule: Option<String>,
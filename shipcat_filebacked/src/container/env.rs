@@ -1,29 +1,627 @@
 use merge::Merge;
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+};
 
-use shipcat_definitions::{structs::EnvVars, Result};
+use shipcat_definitions::{
+    structs::{EnvValueFrom, EnvVars, KeySelector},
+    Result,
+};
 
 use crate::util::{Build, RelaxedString};
 
-#[derive(Deserialize, Clone, Default, Debug, PartialEq, Merge)]
-pub struct EnvVarsSource(BTreeMap<String, RelaxedString>);
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct EnvVarsSource(BTreeMap<String, EnvValueSource>);
 
-impl Build<EnvVars, ()> for EnvVarsSource {
-    fn build(self, params: &()) -> Result<EnvVars> {
+/// The value of a single env var
+///
+/// Either a plain value, set in every region:
+///
+/// ```yaml
+/// env:
+///   LOG_LEVEL: info
+/// ```
+///
+/// or a value that is only set in some regions:
+///
+/// ```yaml
+/// env:
+///   DEBUG_LOGGING:
+///     value: true
+///     regions: [dev-uk, dev-global]
+/// ```
+///
+/// or a value read from the downward API:
+///
+/// ```yaml
+/// env:
+///   POD_NAME:
+///     valueFrom:
+///       fieldRef:
+///         fieldPath: metadata.name
+/// ```
+///
+/// or the contents of a file in the service folder, without a trailing newline:
+///
+/// ```yaml
+/// env:
+///   ALLOWLIST:
+///     fromFile: allowlist.txt
+/// ```
+///
+/// or a value provided outside shipcat, e.g. injected by a mutating webhook,
+/// which can also be written as `PROVIDED_EXTERNALLY`:
+///
+/// ```yaml
+/// env:
+///   TRACING_ENDPOINT:
+///     external: true
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum EnvValueSource {
+    Plain(RelaxedString),
+    Conditional {
+        value: RelaxedString,
+        regions: Vec<String>,
+    },
+    ValueFrom {
+        #[serde(rename = "valueFrom")]
+        value_from: EnvValueFrom,
+    },
+    FromFile {
+        #[serde(rename = "fromFile")]
+        from_file: String,
+    },
+    External {
+        external: bool,
+    },
+}
+
+/// Plain value marking an env var as provided externally
+const EXTERNAL_PLACEHOLDER: &str = "PROVIDED_EXTERNALLY";
+
+impl EnvValueSource {
+    /// The literal value, if any
+    fn value(&self) -> Option<&str> {
+        match self {
+            EnvValueSource::Plain(v) => Some(v.as_str()),
+            EnvValueSource::Conditional { value, .. } => Some(value.as_str()),
+            EnvValueSource::ValueFrom { .. }
+            | EnvValueSource::FromFile { .. }
+            | EnvValueSource::External { .. } => None,
+        }
+    }
+
+    /// Whether the value is provided outside shipcat
+    fn is_external(&self) -> bool {
+        match self {
+            EnvValueSource::External { external } => *external,
+            _ => self.value() == Some(EXTERNAL_PLACEHOLDER),
+        }
+    }
+}
+
+/// Parameters for building env vars
+pub struct EnvBuildParams {
+    /// Region the manifest is built for
+    pub region: String,
+    /// Regions of the manifest, which conditional values must be limited to
+    pub regions: Vec<String>,
+    /// Service folder that `fromFile` values are read from
+    pub dir: PathBuf,
+}
+
+/// Split an append value like `+=-Xmx2g` or `+:=/opt/bin` into separator and suffix
+///
+/// A plain `+=` appends with a space, while `+<c>=` appends with the
+/// punctuation character `c` instead.
+fn parse_append(value: &str) -> Option<(&str, &str)> {
+    if value.starts_with("+=") {
+        return Some((" ", &value[2..]));
+    }
+    let bytes = value.as_bytes();
+    if bytes.len() >= 3 && bytes[0] == b'+' && bytes[2] == b'=' {
+        let sep = bytes[1];
+        if sep.is_ascii_punctuation() && sep != b'=' {
+            return Some((&value[1..2], &value[3..]));
+        }
+    }
+    None
+}
+
+/// Entries are replaced by the override, unless its value uses the append syntax
+///
+/// Appends are kept as-is when the key is missing from `self`, so that they can
+/// still be appended to an earlier source. Any that remain are set at build time.
+/// Appending to a conditional value keeps it limited to the same regions,
+/// while there is nothing to append to for a `valueFrom`, `fromFile` or external value, so they are replaced.
+/// Vault secrets are replaced too, but loading rejects appends to them first (see `overridden_secrets`).
+impl Merge for EnvVarsSource {
+    fn merge(self, other: Self) -> Self {
+        let Self(mut env) = self;
+        for (k, v) in other.0 {
+            let value = match (&v, env.get(&k)) {
+                (EnvValueSource::Plain(p), Some(base)) => match (parse_append(p.as_str()), base.value()) {
+                    (Some((sep, suffix)), Some(basev)) if EnvVars::vault_placeholder(basev).is_none() => {
+                        let appended = format!("{}{}{}", basev, sep, suffix).into();
+                        match base {
+                            EnvValueSource::Conditional { regions, .. } => EnvValueSource::Conditional {
+                                value: appended,
+                                regions: regions.clone(),
+                            },
+                            _ => EnvValueSource::Plain(appended),
+                        }
+                    }
+                    _ => v,
+                },
+                _ => v,
+            };
+            env.insert(k, value);
+        }
+        Self(env)
+    }
+}
+
+impl EnvVarsSource {
+    /// Keys that are vault secrets here, but set to a literal value in `other`
+    ///
+    /// Secrets that are provided externally instead are not literal values.
+    /// Appending to a secret is an error, as it would mix a literal into the secret.
+    pub fn overridden_secrets(&self, other: &Self) -> Result<Vec<String>> {
+        let is_secret = |v: &EnvValueSource| {
+            v.value()
+                .map_or(false, |v| EnvVars::vault_placeholder(v).is_some())
+        };
+        let mut keys = vec![];
+        for (k, v) in &other.0 {
+            if !self.0.get(k).map_or(false, is_secret) || is_secret(v) || v.is_external() {
+                continue;
+            }
+            if let EnvValueSource::Plain(p) = v {
+                if parse_append(p.as_str()).is_some() {
+                    bail!("Env var {} is a vault secret and can not be appended to", k);
+                }
+            }
+            keys.push(k.clone());
+        }
+        Ok(keys)
+    }
+
+    /// The env vars that are set in `region`, with conditional values resolved
+    pub fn in_region(&self, region: &str) -> Self {
+        let env = self
+            .0
+            .iter()
+            .filter_map(|(k, v)| match v {
+                EnvValueSource::Conditional { value, regions } => {
+                    if regions.iter().any(|r| r == region) {
+                        Some((k.clone(), EnvValueSource::Plain(value.clone())))
+                    } else {
+                        None
+                    }
+                }
+                _ => Some((k.clone(), v.clone())),
+            })
+            .collect();
+        Self(env)
+    }
+
+    /// Remove env vars inherited from the region that a service does not use
+    ///
+    /// Keys the service has set to a different value are kept.
+    pub fn exclude_inherited(&mut self, keys: &[String], inherited: &BTreeMap<String, String>) -> Result<()> {
+        for k in keys {
+            let region_value = match inherited.get(k) {
+                Some(v) => v,
+                None => bail!("excludeRegionEnv contains {} which is not set by the region", k),
+            };
+            if self.0.get(k).and_then(EnvValueSource::value) == Some(region_value.as_str()) {
+                self.0.remove(k);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Build<EnvVars, EnvBuildParams> for EnvVarsSource {
+    fn build(self, params: &EnvBuildParams) -> Result<EnvVars> {
         let Self(plain) = self;
-        let env = EnvVars::new(plain.build(params)?);
+        let mut built = BTreeMap::new();
+        let mut value_from = BTreeMap::new();
+        let mut external = BTreeSet::new();
+        for (k, v) in plain {
+            if let EnvValueSource::Conditional { regions, .. } = &v {
+                if let Some(r) = regions.iter().find(|r| !params.regions.contains(r)) {
+                    bail!(
+                        "Env var {} is conditional on region {} which is not in regions",
+                        k,
+                        r
+                    );
+                }
+                if !regions.contains(&params.region) {
+                    continue;
+                }
+            }
+            if v.is_external() {
+                external.insert(k);
+                continue;
+            }
+            let value = match v {
+                EnvValueSource::External { .. } => bail!(
+                    "Env var {} has external: false - remove it or give it a value",
+                    k
+                ),
+                EnvValueSource::ValueFrom { value_from: vf } => {
+                    value_from.insert(k, vf);
+                    continue;
+                }
+                EnvValueSource::FromFile { from_file } => {
+                    built.insert(k.clone(), read_env_file(&k, &from_file, params)?);
+                    continue;
+                }
+                EnvValueSource::Plain(v) | EnvValueSource::Conditional { value: v, .. } => v,
+            };
+            // an append without anything to append to becomes a set
+            let value = match parse_append(value.as_str()) {
+                Some((_, suffix)) => suffix.to_string(),
+                None => value.as_str().to_string(),
+            };
+            built.insert(k, value);
+        }
+        let mut env = EnvVars::new(built);
+        env.valueFrom = value_from;
+        env.external = external;
         // TODO: Inline
         env.verify()?;
         Ok(env)
     }
 }
 
+/// Read the value of env var `key` from a file in the service folder
+fn read_env_file(key: &str, file: &str, params: &EnvBuildParams) -> Result<String> {
+    if file.is_empty() || file.starts_with('/') || file.split('/').any(|p| p == "..") {
+        bail!(
+            "Env var {} must be read from a file inside the service folder",
+            key
+        );
+    }
+    let path = params.dir.join(file);
+    let data = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read env var {} from {}: {}", key, path.display(), e))?;
+    Ok(data.trim_end_matches('\n').to_string())
+}
+
+/// Keys of an existing `Secret` or `ConfigMap` to import as env vars
+///
+/// Each key becomes an individual env var with a `valueFrom` reference,
+/// so only the listed keys are exposed, optionally under a different name.
+///
+/// ```yaml
+/// envImports:
+/// - secret: platform-shared
+///   keys:
+///   - DATABASE_URL
+///   - key: api-token
+///     as: PLATFORM_API_TOKEN
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct EnvImportSource {
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub config_map: Option<String>,
+    pub keys: Vec<EnvImportKey>,
+}
+
+/// A key to import, either under its own name or renamed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum EnvImportKey {
+    Plain(String),
+    Renamed {
+        key: String,
+        #[serde(rename = "as")]
+        name: String,
+    },
+}
+
+impl EnvImportKey {
+    /// The key in the source object and the env var it is imported as
+    fn parts(&self) -> (&str, &str) {
+        match self {
+            EnvImportKey::Plain(k) => (k, k),
+            EnvImportKey::Renamed { key, name } => (key, name),
+        }
+    }
+}
+
+/// Add the keys of env imports to built env vars
+///
+/// Fails if a key is imported twice or is already set in `env`.
+pub fn import_env(env: &mut EnvVars, imports: Vec<EnvImportSource>) -> Result<()> {
+    for imp in imports {
+        let (source, secret) = match (imp.secret, imp.config_map) {
+            (Some(s), None) => (s, true),
+            (None, Some(c)) => (c, false),
+            _ => bail!("Env imports need exactly one of secret or configMap"),
+        };
+        if imp.keys.is_empty() {
+            bail!("Env import from {} does not list any keys", source);
+        }
+        for k in &imp.keys {
+            let (key, name) = k.parts();
+            if env.valueFrom.contains_key(name) {
+                bail!("Env var {} is imported more than once", name);
+            }
+            if env.plain.contains_key(name) {
+                bail!("Env var {} is imported from {} but also set in env", name, source);
+            }
+            let selector = Some(KeySelector {
+                name: source.clone(),
+                key: key.to_string(),
+            });
+            let vf = if secret {
+                EnvValueFrom {
+                    fieldRef: None,
+                    resourceFieldRef: None,
+                    secretKeyRef: selector,
+                    configMapKeyRef: None,
+                }
+            } else {
+                EnvValueFrom {
+                    fieldRef: None,
+                    resourceFieldRef: None,
+                    secretKeyRef: None,
+                    configMapKeyRef: selector,
+                }
+            };
+            env.valueFrom.insert(name.to_string(), vf);
+        }
+    }
+    env.verify()
+}
+
 impl<K: ToString, V: Into<RelaxedString>> From<BTreeMap<K, V>> for EnvVarsSource {
     fn from(v: BTreeMap<K, V>) -> Self {
         let mut env = BTreeMap::new();
         for (k, v) in v {
-            env.insert(k.to_string(), v.into());
+            env.insert(k.to_string(), EnvValueSource::Plain(v.into()));
         }
         EnvVarsSource(env)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use merge::Merge;
+    use std::{collections::BTreeMap, path::Path};
+
+    use super::{import_env, EnvBuildParams, EnvImportSource, EnvVarsSource};
+    use crate::util::Build;
+
+    fn env(pairs: &[(&str, &str)]) -> EnvVarsSource {
+        pairs.iter().cloned().collect::<BTreeMap<_, _>>().into()
+    }
+
+    fn params(region: &str) -> EnvBuildParams {
+        EnvBuildParams {
+            region: region.into(),
+            regions: vec!["dev-uk".into(), "prod-uk".into()],
+            dir: Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/services/fake-ask"),
+        }
+    }
+
+    #[test]
+    fn merge_overwrites() {
+        let merged = env(&[("A", "1"), ("B", "2")]).merge(env(&[("B", "3")]));
+        assert_eq!(merged, env(&[("A", "1"), ("B", "3")]));
+    }
+
+    #[test]
+    fn merge_appends() {
+        let base = env(&[("JAVA_OPTS", "-Xms1g"), ("PATH", "/bin")]);
+        let merged = base.merge(env(&[("JAVA_OPTS", "+=-Xmx2g"), ("PATH", "+:=/opt/bin")]));
+        assert_eq!(
+            merged,
+            env(&[("JAVA_OPTS", "-Xms1g -Xmx2g"), ("PATH", "/bin:/opt/bin")])
+        );
+    }
+
+    #[test]
+    fn append_without_base_is_set() {
+        let merged = env(&[]).merge(env(&[("JAVA_OPTS", "+=-Xmx2g")]));
+        // kept pending until built
+        let merged = merged.merge(env(&[("JAVA_OPTS", "+=-Xss1m")]));
+        assert_eq!(merged, env(&[("JAVA_OPTS", "+=-Xmx2g -Xss1m")]));
+        let built = merged.build(&params("dev-uk")).unwrap();
+        assert_eq!(built.plain["JAVA_OPTS"], "-Xmx2g -Xss1m");
+    }
+
+    #[test]
+    fn append_to_missing_key() {
+        let base = env(&[("A", "1")]);
+        let over = env(&[("JAVA_OPTS", "+=-Xmx2g")]);
+        assert!(base.overridden_secrets(&over).unwrap().is_empty());
+        let built = base.merge(over).build(&params("dev-uk")).unwrap();
+        assert_eq!(built.plain["A"], "1");
+        assert_eq!(built.plain["JAVA_OPTS"], "-Xmx2g");
+    }
+
+    #[test]
+    fn append_to_secret() {
+        let base = env(&[("API_KEY", "IN_VAULT"), ("DB_PASS", "IN_VAULT@central")]);
+        let err = base.overridden_secrets(&env(&[("API_KEY", "+=-suffix")])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Env var API_KEY is a vault secret and can not be appended to"
+        );
+        assert!(base.overridden_secrets(&env(&[("DB_PASS", "+,=x")])).is_err());
+
+        // never merged into the placeholder
+        let merged = base.merge(env(&[("API_KEY", "+=-suffix")]));
+        assert_eq!(
+            merged,
+            env(&[("API_KEY", "+=-suffix"), ("DB_PASS", "IN_VAULT@central")])
+        );
+    }
+
+    #[test]
+    fn plain_values_are_untouched() {
+        let built = env(&[("A", "+"), ("B", "+a=b"), ("C", "a+=b")])
+            .build(&params("dev-uk"))
+            .unwrap();
+        assert_eq!(built.plain["A"], "+");
+        assert_eq!(built.plain["B"], "+a=b");
+        assert_eq!(built.plain["C"], "a+=b");
+    }
+
+    #[test]
+    fn conditional_values() {
+        let yaml = "LOG_LEVEL: info\nDEBUG_LOGGING:\n  value: true\n  regions: [dev-uk]\n";
+        let source: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+
+        let dev = source.clone().build(&params("dev-uk")).unwrap();
+        assert_eq!(dev.plain["LOG_LEVEL"], "info");
+        assert_eq!(dev.plain["DEBUG_LOGGING"], "true");
+
+        let prod = source.clone().build(&params("prod-uk")).unwrap();
+        assert_eq!(prod.plain["LOG_LEVEL"], "info");
+        assert!(!prod.plain.contains_key("DEBUG_LOGGING"));
+
+        // appends stay conditional
+        let merged = source.merge(env(&[("DEBUG_LOGGING", "+,=verbose")]));
+        let dev = merged.clone().build(&params("dev-uk")).unwrap();
+        assert_eq!(dev.plain["DEBUG_LOGGING"], "true,verbose");
+        let prod = merged.build(&params("prod-uk")).unwrap();
+        assert!(!prod.plain.contains_key("DEBUG_LOGGING"));
+
+        let yaml = "DEBUG_LOGGING:\n  value: true\n  regions: [dev-mars]\n";
+        let unknown: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        assert!(unknown.build(&params("dev-uk")).is_err());
+    }
+
+    #[test]
+    fn value_from() {
+        let yaml = "POD_NAME:\n  valueFrom:\n    fieldRef:\n      fieldPath: metadata.name\n";
+        let source: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        let built = source.clone().build(&params("dev-uk")).unwrap();
+        assert!(built.plain.is_empty());
+        let vf = built.valueFrom["POD_NAME"].fieldRef.as_ref().unwrap();
+        assert_eq!(vf.fieldPath, "metadata.name");
+
+        // overriding with a plain value replaces it, even with the append syntax
+        let merged = source.merge(env(&[("POD_NAME", "+=x")]));
+        let built = merged.build(&params("dev-uk")).unwrap();
+        assert_eq!(built.plain["POD_NAME"], "x");
+        assert!(built.valueFrom.is_empty());
+
+        let yaml = "POD_NAME:\n  valueFrom:\n    fieldRef:\n      fieldPath: metadata.labels\n";
+        let unknown: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        assert!(unknown.build(&params("dev-uk")).is_err());
+
+        let yaml =
+            "MEM:\n  valueFrom:\n    resourceFieldRef:\n      resource: limits.memory\n      divisor: 1Mi\n";
+        let source: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        let built = source.build(&params("dev-uk")).unwrap();
+        assert_eq!(
+            built.valueFrom["MEM"].resourceFieldRef.as_ref().unwrap().resource,
+            "limits.memory"
+        );
+    }
+
+    #[test]
+    fn from_file() {
+        let yaml = "ALLOWLIST:\n  fromFile: allowlist.txt\n";
+        let source: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        let built = source.build(&params("dev-uk")).unwrap();
+        assert_eq!(built.plain["ALLOWLIST"], "10.0.0.0/8,192.168.0.0/16");
+
+        let yaml = "ALLOWLIST:\n  fromFile: missing.txt\n";
+        let missing: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        assert!(missing.build(&params("dev-uk")).is_err());
+
+        let yaml = "ALLOWLIST:\n  fromFile: ../fake-storage/manifest.yml\n";
+        let outside: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        assert!(outside.build(&params("dev-uk")).is_err());
+    }
+
+    #[test]
+    fn external_values() {
+        let yaml = "TRACING_ENDPOINT:\n  external: true\nCLUSTER_CA: PROVIDED_EXTERNALLY\nA: '1'\n";
+        let source: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        let built = source.build(&params("dev-uk")).unwrap();
+        assert_eq!(built.plain.keys().collect::<Vec<_>>(), vec!["A"]);
+        assert_eq!(built.external.iter().collect::<Vec<_>>(), vec!["CLUSTER_CA", "TRACING_ENDPOINT"]);
+
+        // moving a secret to an external provider is not a plaintext override
+        let base = env(&[("CLUSTER_CA", "IN_VAULT")]);
+        let over = env(&[("CLUSTER_CA", "PROVIDED_EXTERNALLY")]);
+        assert!(base.overridden_secrets(&over).unwrap().is_empty());
+
+        let yaml = "TRACING_ENDPOINT:\n  external: false\n";
+        let disabled: EnvVarsSource = serde_yaml::from_str(yaml).unwrap();
+        assert!(disabled.build(&params("dev-uk")).is_err());
+    }
+
+    #[test]
+    fn overridden_secrets() {
+        let base = env(&[
+            ("API_KEY", "IN_VAULT"),
+            ("DB_PASS", "IN_VAULT@central"),
+            ("A", "1"),
+        ]);
+        let over = env(&[("API_KEY", "hunter2"), ("DB_PASS", "IN_VAULT"), ("A", "2")]);
+        assert_eq!(base.overridden_secrets(&over).unwrap(), vec!["API_KEY".to_string()]);
+        assert!(over.overridden_secrets(&base).unwrap().is_empty());
+    }
+
+    #[test]
+    fn env_imports() {
+        let yaml = "- secret: platform-shared\n  keys:\n  - DATABASE_URL\n  - key: api-token\n    as: API_TOKEN\n";
+        let imports: Vec<EnvImportSource> = serde_yaml::from_str(yaml).unwrap();
+        let mut built = env(&[("A", "1")]).build(&params("dev-uk")).unwrap();
+        import_env(&mut built, imports.clone()).unwrap();
+        let token = built.valueFrom["API_TOKEN"].secretKeyRef.as_ref().unwrap();
+        assert_eq!(token.name, "platform-shared");
+        assert_eq!(token.key, "api-token");
+        assert_eq!(
+            built.valueFrom["DATABASE_URL"].secretKeyRef.as_ref().unwrap().key,
+            "DATABASE_URL"
+        );
+
+        // importing the same names twice
+        let mut twice = imports.clone();
+        twice.extend(imports.clone());
+        let mut built = env(&[]).build(&params("dev-uk")).unwrap();
+        assert!(import_env(&mut built, twice).is_err());
+
+        // colliding with inline env
+        let mut built = env(&[("API_TOKEN", "hunter2")]).build(&params("dev-uk")).unwrap();
+        assert!(import_env(&mut built, imports).is_err());
+
+        let yaml = "- secret: platform-shared\n  configMap: platform-config\n  keys: [A]\n";
+        let both: Vec<EnvImportSource> = serde_yaml::from_str(yaml).unwrap();
+        let mut built = env(&[]).build(&params("dev-uk")).unwrap();
+        assert!(import_env(&mut built, both).is_err());
+    }
+
+    #[test]
+    fn exclude_inherited() {
+        let region: BTreeMap<String, String> = vec![("SSL_CERT", "arn"), ("ZIPKIN_URL", "http://zipkin")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let mut merged = env(&[("SSL_CERT", "arn"), ("ZIPKIN_URL", "http://tracing"), ("A", "1")]);
+        let keys = vec!["SSL_CERT".to_string(), "ZIPKIN_URL".to_string()];
+        merged.exclude_inherited(&keys, &region).unwrap();
+        // overridden by the service, so kept
+        assert_eq!(merged, env(&[("ZIPKIN_URL", "http://tracing"), ("A", "1")]));
+
+        // only region keys can be excluded
+        assert!(merged.exclude_inherited(&["A".to_string()], &region).is_err());
+    }
+}
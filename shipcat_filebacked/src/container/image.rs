@@ -4,7 +4,7 @@ use shipcat_definitions::Result;
 
 use crate::util::Build;
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ImageNameSource(String);
 
 impl Build<String, ()> for ImageNameSource {
@@ -26,8 +26,8 @@ impl Build<String, ()> for ImageNameSource {
     }
 }
 
-#[derive(Deserialize, Clone)]
-pub struct ImageTagSource(String);
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImageTagSource(pub String);
 
 impl Build<String, ()> for ImageTagSource {
     fn build(self, _: &()) -> Result<String> {
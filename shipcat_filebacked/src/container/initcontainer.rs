@@ -3,8 +3,8 @@ use shipcat_definitions::{structs::Container, Result};
 use super::source::{ContainerBuildParams, ContainerSource};
 use crate::util::{Build, Require};
 
-#[derive(Deserialize, Clone, Default)]
-pub struct InitContainerSource(ContainerSource);
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct InitContainerSource(pub ContainerSource);
 
 impl Build<Container, ContainerBuildParams> for InitContainerSource {
     fn build(self, params: &ContainerBuildParams) -> Result<Container> {
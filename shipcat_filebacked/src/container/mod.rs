@@ -1,11 +1,11 @@
 mod source;
-pub use source::ContainerBuildParams;
+pub use source::{ContainerBuildParams, ContainerSource};
 
 mod env;
 mod image;
 mod resources;
 
-pub use env::EnvVarsSource;
+pub use env::{import_env, EnvBuildParams, EnvImportSource, EnvVarsSource};
 pub use image::{ImageNameSource, ImageTagSource};
 pub use resources::ResourceRequirementsSource;
 
@@ -7,7 +7,7 @@ use shipcat_definitions::{
 
 use crate::util::Build;
 
-#[derive(Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct PortName(String);
 
 impl Build<String, ()> for PortName {
@@ -24,7 +24,7 @@ impl Build<String, ()> for PortName {
     }
 }
 
-#[derive(Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(default, rename_all = "camelCase", deny_unknown_fields)]
 pub struct PortSource {
     /// Name of the port
@@ -35,6 +35,8 @@ pub struct PortSource {
     pub service_port: Option<u32>,
     /// Port protocol
     pub protocol: Option<PortProtocol>,
+    /// Port to expose on the node
+    pub host_port: Option<u32>,
 }
 
 impl Build<Port, ()> for PortSource {
@@ -44,6 +46,7 @@ impl Build<Port, ()> for PortSource {
             port: self.port,
             service_port: self.service_port.unwrap_or(self.port),
             protocol: self.protocol.unwrap_or_default(),
+            host_port: self.host_port,
         })
     }
 }
@@ -5,11 +5,11 @@ use shipcat_definitions::{
 
 use crate::util::{Build, RelaxedString, Require};
 
-#[derive(Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(default, rename_all = "camelCase", deny_unknown_fields)]
 pub struct ResourceRequirementsSource {
     pub requests: ResourcesSource,
-    pub limits: ResourcesSource,
+    pub limits: Option<ResourcesSource>,
 }
 
 impl Build<ResourceRequirements<String>, ()> for ResourceRequirementsSource {
@@ -23,7 +23,7 @@ impl Build<ResourceRequirements<String>, ()> for ResourceRequirementsSource {
     }
 }
 
-#[derive(Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(default, rename_all = "camelCase", deny_unknown_fields)]
 pub struct ResourcesSource {
     pub cpu: Option<RelaxedString>,
@@ -34,7 +34,7 @@ impl Build<Resources<String>, ()> for ResourcesSource {
     fn build(self, params: &()) -> Result<Resources<String>> {
         Ok(Resources {
             cpu: self.cpu.require("cpu")?.build(params)?,
-            memory: self.memory.require("cpu")?.build(params)?,
+            memory: self.memory.require("memory")?.build(params)?,
         })
     }
 }
@@ -3,8 +3,8 @@ use shipcat_definitions::{structs::Container, Result};
 use super::source::{ContainerBuildParams, ContainerSource};
 use crate::util::Build;
 
-#[derive(Deserialize, Clone, Default)]
-pub struct SidecarSource(ContainerSource);
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SidecarSource(pub ContainerSource);
 
 impl Build<Container, ContainerBuildParams> for SidecarSource {
     fn build(self, params: &ContainerBuildParams) -> Result<Container> {
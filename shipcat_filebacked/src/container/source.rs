@@ -3,7 +3,7 @@ use regex::Regex;
 
 use shipcat_definitions::{
     structs::{Container, Probe, VolumeMount},
-    Result,
+    Result, ResultExt,
 };
 
 use crate::util::{Build, Require};
@@ -12,10 +12,10 @@ use super::{
     image::{ImageNameSource, ImageTagSource},
     port::PortSource,
     resources::ResourceRequirementsSource,
-    EnvVarsSource,
+    EnvBuildParams, EnvVarsSource,
 };
 
-#[derive(Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ContainerName(String);
 
 impl Build<String, ()> for ContainerName {
@@ -30,7 +30,7 @@ impl Build<String, ()> for ContainerName {
 }
 
 /// Source configuration for a K8s container, deserialized from a service manifest.
-#[derive(Deserialize, Merge, Clone, Default)]
+#[derive(Serialize, Deserialize, Merge, Clone, Default)]
 #[serde(default, rename_all = "camelCase", deny_unknown_fields)]
 pub struct ContainerSource {
     pub name: Option<ContainerName>,
@@ -51,8 +51,27 @@ pub struct ContainerSource {
     pub volume_mounts: Option<Vec<VolumeMount>>,
 }
 
+impl ContainerSource {
+    /// Env vars that are vault secrets here, but set to a literal value in `other`
+    ///
+    /// Only containers of the same name are compared, and keys are prefixed with that name.
+    pub fn overridden_secrets(&self, other: &Self) -> Result<Vec<String>> {
+        match (&self.name, &other.name) {
+            (Some(ContainerName(a)), Some(ContainerName(b))) if a == b => Ok(self
+                .env
+                .overridden_secrets(&other.env)
+                .chain_err(|| format!("Invalid env override for container {}", a))?
+                .into_iter()
+                .map(|k| format!("{}.{}", a, k))
+                .collect()),
+            _ => Ok(vec![]),
+        }
+    }
+}
+
 pub struct ContainerBuildParams {
     pub main_envs: EnvVarsSource,
+    pub env: EnvBuildParams,
 }
 
 impl Build<Container, ContainerBuildParams> for ContainerSource {
@@ -78,7 +97,7 @@ impl Build<Container, ContainerBuildParams> for ContainerSource {
             resources: self.resources.build(&())?,
 
             command: self.command.unwrap_or_default(),
-            env: env.build(&())?,
+            env: env.build(&params.env)?,
 
             readiness_probe: self.readiness_probe,
             liveness_probe: self.liveness_probe,
@@ -9,7 +9,7 @@ use super::source::{ContainerBuildParams, ContainerSource};
 use crate::util::{Build, RelaxedString, Require};
 use std::collections::BTreeMap;
 
-#[derive(Deserialize, Merge, Clone, Default)]
+#[derive(Serialize, Deserialize, Merge, Clone, Default)]
 #[serde(default, rename_all = "camelCase")]
 pub struct WorkerSource {
     pub replica_count: Option<u32>,
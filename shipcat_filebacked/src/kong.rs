@@ -2,7 +2,9 @@ use merge::Merge;
 use std::collections::BTreeMap;
 
 use shipcat_definitions::{
-    structs::{Authentication, Authorization, BabylonAuthHeader, Cors, Kong, PiiRegionHeader},
+    structs::{
+        Authentication, Authorization, BabylonAuthHeader, Cors, CorsPolicy, Kong, PiiRegionHeader, RateLimit,
+    },
     KongConfig, Region, Result,
 };
 
@@ -11,7 +13,7 @@ use super::{
     util::{Build, Enabled, EnabledMap},
 };
 
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone)]
 #[serde(default)]
 pub struct KongApisSource {
     /// Default values to merge into every API
@@ -94,7 +96,7 @@ impl KongApisSource {
 }
 
 
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone)]
 #[serde(default, deny_unknown_fields)]
 pub struct KongSource {
     pub upstream_url: Option<String>,
@@ -103,6 +105,8 @@ pub struct KongSource {
     pub strip_uri: Option<bool>,
     pub preserve_host: Option<bool>,
     pub cors: Option<Cors>,
+    pub cors_policy: Option<CorsPolicy>,
+    pub rate_limit: Option<RateLimit>,
     pub additional_internal_ips: Option<Vec<String>>,
 
     pub internal: Option<bool>,
@@ -146,6 +150,7 @@ impl Build<Kong, KongBuildParams> for KongSource {
         let (auth, authorization) = KongSource::build_auth(self.auth, self.authorization)?;
 
         let preserve_host = self.preserve_host.unwrap_or(true);
+        let cors = KongSource::build_cors(self.cors, self.cors_policy)?;
 
         Ok(Kong {
             name: name.to_string(),
@@ -163,7 +168,8 @@ impl Build<Kong, KongBuildParams> for KongSource {
             pii_region_header: self.pii_region_header,
             strip_uri: self.strip_uri.unwrap_or_default(),
             preserve_host,
-            cors: self.cors,
+            cors,
+            rate_limit: self.rate_limit,
             additional_internal_ips: self.additional_internal_ips.unwrap_or_default(),
             babylon_auth_header: self.babylon_auth_header,
             upstream_connect_timeout: self.upstream_connect_timeout,
@@ -196,6 +202,17 @@ impl KongSource {
         })
     }
 
+    fn build_cors(cors: Option<Cors>, policy: Option<CorsPolicy>) -> Result<Option<Cors>> {
+        Ok(match (cors, policy) {
+            (Some(_), Some(_)) => bail!("cors and cors_policy are mutually exclusive"),
+            (None, Some(p)) => {
+                p.verify()?;
+                Some(p.expand())
+            }
+            (cors, None) => cors,
+        })
+    }
+
     fn build_hosts(&self, base_url: &str) -> Result<Vec<String>> {
         Ok(self
             .hosts
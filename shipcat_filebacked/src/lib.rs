@@ -14,19 +14,59 @@ pub use crate::simple::SimpleManifest;
 mod kong;
 
 mod load;
+mod overrides;
+pub use crate::overrides::AppliedOverride;
+
+mod cache;
+pub use crate::cache::ManifestCache;
 mod util;
 
 use manifest::ManifestSource;
-use shipcat_definitions::{BaseManifest, Config, Manifest, Region, Result};
+use shipcat_definitions::{BaseManifest, Config, Manifest, Region, Result, Vault};
+use std::collections::BTreeMap;
 
 pub async fn load_manifest(service: &str, conf: &Config, reg: &Region) -> Result<Manifest> {
     ManifestSource::load_manifest(service, conf, reg).await
 }
 
+/// Load the fully resolved env of a service in a region
+///
+/// See `Manifest::env_for_region`.
+pub async fn load_env(
+    service: &str,
+    conf: &Config,
+    reg: &Region,
+    vault: &Vault,
+    mask: bool,
+) -> Result<BTreeMap<String, String>> {
+    load_manifest(service, conf, reg)
+        .await?
+        .env_for_region(reg, vault, mask)
+        .await
+}
+
+/// Pin the version of a service in a region
+///
+/// Writes `services/<name>/<region>.lock`, which takes precedence over versions in manifests.
+pub async fn lock_version(service: &str, reg: &Region, version: &str) -> Result<()> {
+    ManifestSource::lock_version(service, reg, version).await
+}
+
+/// Fields set by the override files of a service in a region
+///
+/// Lists what every environment, region, `VERSION` and lock file changes, in the order they are applied.
+pub async fn explain_overrides(service: &str, conf: &Config, reg: &Region) -> Result<Vec<AppliedOverride>> {
+    ManifestSource::explain_overrides(service, conf, reg).await
+}
+
 pub async fn load_metadata(service: &str, conf: &Config, reg: &Region) -> Result<SimpleManifest> {
     ManifestSource::load_metadata(service, conf, reg).await
 }
 
+pub async fn load_base(service: &str, conf: &Config) -> Result<BaseManifest> {
+    ManifestSource::load_base(service, conf).await
+}
+
 pub async fn all(conf: &Config) -> Result<Vec<BaseManifest>> {
     ManifestSource::all(conf).await
 }
@@ -34,3 +74,8 @@ pub async fn all(conf: &Config) -> Result<Vec<BaseManifest>> {
 pub async fn available(conf: &Config, reg: &Region) -> Result<Vec<SimpleManifest>> {
     ManifestSource::available(conf, reg).await
 }
+
+/// Names of the services that deploy to a region, sorted
+pub async fn deployable(conf: &Config, reg: &Region) -> Result<Vec<String>> {
+    ManifestSource::deployable(conf, reg).await
+}
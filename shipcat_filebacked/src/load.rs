@@ -1,4 +1,7 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 use merge::Merge;
 use serde::de::DeserializeOwned;
@@ -6,7 +9,17 @@ use shipcat_definitions::{Config, ErrorKind, Manifest, Region, Result, ResultExt
 use walkdir::WalkDir;
 
 use super::{authorization::AuthorizationSource, util::Enabled, BaseManifest, SimpleManifest};
-use crate::manifest::{ManifestDefaults, ManifestOverrides, ManifestSource};
+use crate::{
+    container::ImageTagSource,
+    manifest::{ManifestDefaults, ManifestOverrides, ManifestParent, ManifestSource, VersionLock},
+    overrides::{AppliedOverride, OverrideLog},
+    util::Build,
+};
+
+/// Keys of `ManifestSource` that are not overrides
+const MANIFEST_ONLY_KEYS: &[&str] = &["name", "extends", "external", "disabled", "regions", "metadata"];
+/// Keys of `ManifestParent` that are not overrides
+const PARENT_ONLY_KEYS: &[&str] = &["extends"];
 
 impl ManifestSource {
     pub async fn load_manifest(service: &str, conf: &Config, reg: &Region) -> Result<Manifest> {
@@ -28,6 +41,16 @@ impl ManifestSource {
     }
 
     async fn load_merged(service: &str, conf: &Config, reg: &Region) -> Result<Self> {
+        Self::merge_sources(service, conf, reg, &mut OverrideLog::disabled()).await
+    }
+
+    /// Merge all sources of a manifest in a region, recording the overrides in `log`
+    async fn merge_sources(
+        service: &str,
+        conf: &Config,
+        reg: &Region,
+        log: &mut OverrideLog,
+    ) -> Result<Self> {
         let dir = Self::services_dir().join(service);
 
         if !dir.exists() {
@@ -40,26 +63,192 @@ impl ManifestSource {
 
         let source_path = Self::services_dir().join(service).join("manifest.yml");
         debug!("Loading service manifest from {:?}", source_path);
-        let source: ManifestSource = read_from(&source_path).await?;
+        let mut source: ManifestSource = read_from(&source_path).await?;
+        if conf.strictManifests {
+            let data = tokio::fs::read_to_string(&source_path).await?;
+            Self::verify_known_keys(&data, MANIFEST_ONLY_KEYS)
+                .chain_err(|| format!("Manifest file {} has unknown keys", source_path.display()))?;
+        }
+        // vault secrets overridden with literal values
+        let mut plaintext = vec![];
+        if let Some(extends) = source.extends.clone() {
+            let (inherited, overridden) = Self::load_parents(service, &extends, log).await?;
+            plaintext.extend(overridden);
+            let secrets = inherited
+                .overridden_secrets(&source.overrides)
+                .chain_err(|| format!("Failed to merge {}", source_path.display()))?;
+            plaintext.extend(secrets);
+            source.overrides = log.merge(&source_path, inherited, source.overrides, true)?;
+        }
+        let secrets = defaults
+            .env
+            .overridden_secrets(&source.overrides.defaults.env)
+            .chain_err(|| format!("Failed to merge {}", source_path.display()))?;
+        plaintext.extend(secrets);
         let mut manifest = defaults.merge_source(source);
 
+        // a version written by CI overrides the manifest, but not environment or region overrides
+        let version_path = dir.join("VERSION");
+        if version_path.is_file() {
+            debug!("Loading version from {:?}", version_path);
+            let data = tokio::fs::read_to_string(&version_path).await?;
+            let version = Self::parse_version_file(&data)
+                .chain_err(|| format!("Invalid version file {}", version_path.display()))?;
+            let version = ManifestOverrides {
+                version: Some(version),
+                ..Default::default()
+            };
+            manifest.overrides = log.merge(&version_path, manifest.overrides, version, false)?;
+        }
+
         let env_path = dir.join(format!("{}.yml", reg.environment.to_string()));
         if env_path.is_file() {
             debug!("Loading service overrides from {:?}", env_path);
             let env: ManifestOverrides = read_from(&env_path).await?;
-            manifest = manifest.merge_overrides(env);
+            let secrets = manifest
+                .overrides
+                .overridden_secrets(&env)
+                .chain_err(|| format!("Failed to merge {}", env_path.display()))?;
+            plaintext.extend(secrets);
+            manifest.overrides = log
+                .merge(&env_path, manifest.overrides, env, false)
+                .chain_err(|| format!("Failed to merge {}", env_path.display()))?;
         }
 
         let region_path = dir.join(format!("{}.yml", reg.name));
         if region_path.is_file() {
             debug!("Loading service overrides from {:?}", region_path);
             let region: ManifestOverrides = read_from(&region_path).await?;
-            manifest = manifest.merge_overrides(region);
+            let secrets = manifest
+                .overrides
+                .overridden_secrets(&region)
+                .chain_err(|| format!("Failed to merge {}", region_path.display()))?;
+            plaintext.extend(secrets);
+            manifest.overrides = log
+                .merge(&region_path, manifest.overrides, region, false)
+                .chain_err(|| format!("Failed to merge {}", region_path.display()))?;
+        }
+        manifest.plaintext_secrets = plaintext;
+
+        // a lockfile pins the version above everything else
+        let lock_path = Self::lock_path(service, reg);
+        if lock_path.is_file() {
+            debug!("Loading version lock from {:?}", lock_path);
+            let lock: VersionLock = read_from(&lock_path).await?;
+            let lock = ManifestOverrides {
+                version: Some(lock.version),
+                ..Default::default()
+            };
+            manifest.overrides = log.merge(&lock_path, manifest.overrides, lock, false)?;
+            manifest.version_locked = true;
         }
 
         Ok(manifest)
     }
 
+    /// Fields set by the override files of a service in a region
+    ///
+    /// Recorded while merging the sources exactly as when loading the manifest.
+    /// Base templates and `manifest.yml` are the starting point, so for them only
+    /// the values that `manifest.yml` replaces in its bases are listed.
+    pub async fn explain_overrides(
+        service: &str,
+        conf: &Config,
+        reg: &Region,
+    ) -> Result<Vec<AppliedOverride>> {
+        let mut log = OverrideLog::new(&reg.name);
+        Self::merge_sources(service, conf, reg, &mut log).await?;
+        Ok(log.applied)
+    }
+
+    /// Pin the version of a service in a region by writing its lockfile
+    pub async fn lock_version(service: &str, reg: &Region, version: &str) -> Result<()> {
+        let dir = Self::services_dir().join(service);
+        if !dir.exists() {
+            bail!("Service folder {} does not exist", dir.display())
+        }
+        let version = ImageTagSource(version.to_string()).build(&())?;
+        reg.versioningScheme.verify(&version)?;
+        let path = Self::lock_path(service, reg);
+        debug!("Writing version lock to {:?}", path);
+        tokio::fs::write(&path, format!("version: {}\n", version)).await?;
+        Ok(())
+    }
+
+    /// Check that every key of a `manifest.yml` or base template is known to shipcat
+    ///
+    /// Keys are otherwise ignored, so a misspelled key silently falls back to its default.
+    /// Everything but the `own_keys` of the file is checked as strictly as overrides.
+    fn verify_known_keys(data: &str, own_keys: &[&str]) -> Result<()> {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(data)?;
+        if let serde_yaml::Value::Mapping(m) = &mut value {
+            for k in own_keys {
+                m.remove(&serde_yaml::Value::String(k.to_string()));
+            }
+        }
+        serde_yaml::from_value::<ManifestOverrides>(value)?;
+        Ok(())
+    }
+
+    /// Parse the contents of a `VERSION` file, which must be a single version
+    fn parse_version_file(data: &str) -> Result<ImageTagSource> {
+        let version = data.trim();
+        if version.is_empty() || version.contains(char::is_whitespace) {
+            bail!("expected a single version, found {:?}", data);
+        }
+        let version = ImageTagSource(version.to_string());
+        version.clone().build(&())?;
+        Ok(version)
+    }
+
+    fn lock_path(service: &str, reg: &Region) -> PathBuf {
+        Self::services_dir()
+            .join(service)
+            .join(format!("{}.lock", reg.name))
+    }
+
+    /// Resolve the chain of base templates a manifest extends into one set of overrides
+    ///
+    /// Bases are merged from the root of the chain down, so everything overrides what it extends.
+    /// Also returns the vault secrets that were overridden with literal values along the way.
+    async fn load_parents(
+        service: &str,
+        extends: &str,
+        log: &mut OverrideLog,
+    ) -> Result<(ManifestOverrides, Vec<String>)> {
+        let mut chain = vec![service.to_string()];
+        let mut parents = vec![];
+        let mut next = Some(extends.to_string());
+        while let Some(name) = next {
+            if chain.contains(&name) {
+                bail!("Manifest inheritance cycle: {} -> {}", chain.join(" -> "), name);
+            }
+            let path = Self::bases_dir().join(&name).join("shipcat.yml");
+            if !path.is_file() {
+                bail!("Base manifest {} does not exist in {}", name, path.display());
+            }
+            debug!("Loading base manifest from {:?}", path);
+            let parent: ManifestParent = read_from(&path).await?;
+            let data = tokio::fs::read_to_string(&path).await?;
+            Self::verify_known_keys(&data, PARENT_ONLY_KEYS)
+                .chain_err(|| format!("Base manifest {} has unknown keys", path.display()))?;
+            next = parent.extends;
+            parents.push((path, parent.overrides));
+            chain.push(name);
+        }
+
+        let mut plaintext = vec![];
+        let mut merged = ManifestOverrides::default();
+        for (path, p) in parents.into_iter().rev() {
+            let secrets = merged
+                .overridden_secrets(&p)
+                .chain_err(|| format!("Failed to merge {}", path.display()))?;
+            plaintext.extend(secrets);
+            merged = log.merge(&path, merged, p, true)?;
+        }
+        Ok((merged, plaintext))
+    }
+
     fn all_names() -> Vec<String> {
         let mut res: Vec<_> = WalkDir::new(&ManifestSource::services_dir())
             .min_depth(1)
@@ -75,30 +264,60 @@ impl ManifestSource {
                 let svcname = svccomp.as_os_str().to_str().unwrap();
                 svcname.to_string()
             })
+            // base templates and other special folders are not services
+            .filter(|svc| !svc.starts_with('_'))
             .collect();
         res.sort();
         res
     }
 
+    /// Check that no two service folders have names that collide
+    ///
+    /// Names differing only by case clash on case-insensitive filesystems,
+    /// and names that normalize to the same DNS label clash in kubernetes.
+    fn verify_unique_names(names: &[String]) -> Result<()> {
+        let mut seen = BTreeMap::new();
+        for name in names {
+            let normalized = name
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+            if let Some(prev) = seen.insert(normalized, name) {
+                let dir = Self::services_dir();
+                bail!(ErrorKind::ServiceNameCollision(
+                    dir.join(prev).display().to_string(),
+                    dir.join(name).display().to_string()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn load_base(service: &str, conf: &Config) -> Result<BaseManifest> {
+        let source_path = Self::services_dir().join(service).join("manifest.yml");
+        debug!("Loading service manifest from {:?}", source_path);
+        let source: ManifestSource = read_from(&source_path)
+            .await
+            .chain_err(|| ErrorKind::InvalidManifest(service.to_string()))?;
+        source
+            .build_base(conf)
+            .chain_err(|| ErrorKind::InvalidManifest(service.to_string()))
+    }
+
     pub async fn all(conf: &Config) -> Result<Vec<BaseManifest>> {
+        let names = Self::all_names();
+        Self::verify_unique_names(&names)?;
         let mut all = vec![];
-        for service in Self::all_names() {
-            let source_path = Self::services_dir().join(&service).join("manifest.yml");
-            debug!("Loading service manifest from {:?}", source_path);
-            let source: ManifestSource = read_from(&source_path)
-                .await
-                .chain_err(|| ErrorKind::InvalidManifest(service.clone()))?;
-            let manifest = source
-                .build_base(conf)
-                .chain_err(|| ErrorKind::InvalidManifest(service.clone()))?;
-            all.push(manifest);
+        for service in names {
+            all.push(Self::load_base(&service, conf).await?);
         }
         Ok(all)
     }
 
     pub async fn available(conf: &Config, reg: &Region) -> Result<Vec<SimpleManifest>> {
+        let names = Self::all_names();
+        Self::verify_unique_names(&names)?;
         let mut available = vec![];
-        for service in Self::all_names() {
+        for service in names {
             let manifest = Self::load_metadata(&service, conf, reg)
                 .await
                 .chain_err(|| ErrorKind::InvalidManifest(service.clone()))?;
@@ -109,9 +328,26 @@ impl ManifestSource {
         Ok(available)
     }
 
-    fn services_dir() -> PathBuf {
+    /// Names of the services that deploy to a region, sorted
+    ///
+    /// These are listed in `regions`, not disabled in the region and not kube-external.
+    pub async fn deployable(conf: &Config, reg: &Region) -> Result<Vec<String>> {
+        let mut names = Self::available(conf, reg)
+            .await?
+            .into_iter()
+            .map(|mf| mf.base.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    pub(crate) fn services_dir() -> PathBuf {
         Path::new(".").join("services")
     }
+
+    pub(crate) fn bases_dir() -> PathBuf {
+        Self::services_dir().join("_bases")
+    }
 }
 
 impl ManifestDefaults {
@@ -127,6 +363,8 @@ impl ManifestDefaults {
     fn from_region(reg: &Region) -> Result<Self> {
         let mut defs = Self::default();
         defs.env = reg.env.clone().into();
+        defs.service_account = reg.defaults.serviceAccount.clone();
+        defs.image_prefix = reg.defaults.imagePrefix.clone();
         if let Some(authz) = reg.defaults.kong.authorization.clone() {
             defs.kong_apis.defaults.authorization = Enabled {
                 enabled: None,
@@ -147,6 +385,7 @@ impl ManifestDefaults {
             };
         }
         defs.kong.item.authorization.enabled = Some(reg.defaults.kong.authorizationEnabled);
+        defs.kong_apis.defaults.rate_limit = reg.defaults.kong.rate_limit.clone();
         Ok(defs)
     }
 }
@@ -172,7 +411,8 @@ async fn read_from<T: DeserializeOwned>(path: &PathBuf) -> Result<T> {
 mod tests {
     use std::{env, fs, path::Path};
 
-    use super::ManifestSource;
+    use super::{ManifestSource, MANIFEST_ONLY_KEYS, PARENT_ONLY_KEYS};
+    use crate::overrides::OverrideLog;
     use shipcat_definitions::Config;
 
     fn setup() {
@@ -194,6 +434,34 @@ mod tests {
         assert_eq!(manifest.name, "fake-ask".to_string());
     }
 
+    #[tokio::test]
+    async fn explain_fake_ask_overrides() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let applied = ManifestSource::explain_overrides("fake-ask", &conf, &region)
+            .await
+            .unwrap();
+        let summary = applied
+            .iter()
+            .map(|a| (a.file.trim_start_matches("./services/fake-ask/"), a.path.as_str(), a.is_noop()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("dev.yml", "version", false),
+                ("dev.yml", "env.EXTRA_URL", false),
+                ("dev.yml", "env.MODE", false),
+                // dev-uk.yml repeats the version of dev.yml
+                ("dev-uk.yml", "version", true),
+                ("dev-uk.yml", "env.EXTRA_URL", false),
+            ]
+        );
+        assert_eq!(applied[4].old, Some("https://blort/".into()));
+    }
+
     #[tokio::test]
     async fn load_fake_ask_metadata() {
         setup();
@@ -207,6 +475,100 @@ mod tests {
         assert_eq!(manifest.base.name, "fake-ask".to_string());
         assert_eq!(manifest.version, Some("1.6.0".into()));
         assert_eq!(manifest.image, Some("quay.io/babylonhealth/fake-ask".into()));
+
+        // regions can mirror images in their own registry
+        let region = conf.get_region("dev-global").unwrap();
+        let manifest = ManifestSource::load_metadata("fake-ask", &conf, &region)
+            .await
+            .unwrap();
+        assert_eq!(
+            manifest.image,
+            Some("registry-global.internal/babylonhealth/fake-ask".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn load_extended() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let manifest = ManifestSource::load_manifest("fake-storage", &conf, &region)
+            .await
+            .unwrap();
+        assert_eq!(manifest.health.unwrap().wait, 30);
+        assert_eq!(manifest.env.plain["INSTANCE_TYPE"], "web");
+        // maps merge with the region overrides
+        assert_eq!(manifest.env.plain["RAILS_ENV"], "development");
+
+        // the real base parses and passes the key check
+        let (base, _) = ManifestSource::load_parents("fake-storage", "web", &mut OverrideLog::disabled())
+            .await
+            .unwrap();
+        assert_eq!(base.health.unwrap().wait, 30);
+
+        let res = ManifestSource::load_parents("fake-storage", "cycle-a", &mut OverrideLog::disabled()).await;
+        let err = res.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Manifest inheritance cycle: fake-storage -> cycle-a -> cycle-b -> cycle-a"
+        );
+        let res = ManifestSource::load_parents("fake-storage", "missing", &mut OverrideLog::disabled()).await;
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn unique_names() {
+        let names = vec!["fake-ask".to_string(), "fake-storage".to_string()];
+        assert!(ManifestSource::verify_unique_names(&names).is_ok());
+
+        let names = vec!["Fake-Ask".to_string(), "fake-ask".to_string()];
+        let err = ManifestSource::verify_unique_names(&names).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Services in ./services/Fake-Ask and ./services/fake-ask have colliding names"
+        );
+
+        let names = vec!["fake_ask".to_string(), "fake-ask".to_string()];
+        assert!(ManifestSource::verify_unique_names(&names).is_err());
+    }
+
+    #[tokio::test]
+    async fn load_resource_tier() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let manifest = ManifestSource::load_manifest("out-of-region", &conf, &region)
+            .await
+            .unwrap();
+        assert_eq!(manifest.resourceTier, Some("small".into()));
+        let res = manifest.resources.unwrap();
+        assert_eq!(res.requests.cpu, "100m");
+        assert_eq!(res.limits.unwrap().memory, "512Mi");
+    }
+
+    #[tokio::test]
+    async fn load_version_lock() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-ops").unwrap();
+        let manifest = ManifestSource::load_manifest("out-of-region", &conf, &region)
+            .await
+            .unwrap();
+        assert_eq!(manifest.version, Some("1.2.3".into()));
+        assert!(manifest.versionLocked);
+
+        let region = conf.get_region("dev-uk").unwrap();
+        let manifest = ManifestSource::load_manifest("out-of-region", &conf, &region)
+            .await
+            .unwrap();
+        assert!(!manifest.versionLocked);
+        let res = ManifestSource::lock_version("out-of-region", &region, "-invalid").await;
+        assert!(res.is_err());
     }
 
     #[tokio::test]
@@ -246,4 +608,44 @@ mod tests {
         let manifest = &available[1];
         assert_eq!(manifest.base.name, "fake-storage".to_string());
     }
+
+    #[tokio::test]
+    async fn deployable() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+        let names = ManifestSource::deployable(&conf, &region).await.unwrap();
+        assert_eq!(names, vec!["fake-ask", "fake-storage"]);
+
+        let region = conf.get_region("dev-ops").unwrap();
+        let names = ManifestSource::deployable(&conf, &region).await.unwrap();
+        assert_eq!(names, vec!["out-of-region"]);
+    }
+
+    #[test]
+    fn parse_version_file() {
+        let version = ManifestSource::parse_version_file("1.2.3\n").unwrap();
+        assert_eq!(version.0, "1.2.3");
+        assert!(ManifestSource::parse_version_file("").is_err());
+        assert!(ManifestSource::parse_version_file("1.2.3\n1.2.4\n").is_err());
+        assert!(ManifestSource::parse_version_file("-1.2.3").is_err());
+    }
+
+    #[test]
+    fn verify_known_keys() {
+        let valid = "name: fake-ask\nregions: [dev-uk]\nreplicaCount: 2\n";
+        assert!(ManifestSource::verify_known_keys(valid, MANIFEST_ONLY_KEYS).is_ok());
+        let typo = "name: fake-ask\nregions: [dev-uk]\nreplicacount: 2\n";
+        let err = ManifestSource::verify_known_keys(typo, MANIFEST_ONLY_KEYS).unwrap_err();
+        assert!(err.to_string().contains("replicacount"));
+
+        // bases can only extend other bases on top of the overrides
+        let web = Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/services/_bases/web/shipcat.yml");
+        let base = fs::read_to_string(web).unwrap();
+        assert!(ManifestSource::verify_known_keys(&base, PARENT_ONLY_KEYS).is_ok());
+        let base = format!("extends: web\n{}", base);
+        assert!(ManifestSource::verify_known_keys(&base, PARENT_ONLY_KEYS).is_ok());
+        assert!(ManifestSource::verify_known_keys(valid, PARENT_ONLY_KEYS).is_err());
+    }
 }
@@ -4,8 +4,10 @@ use std::collections::BTreeMap;
 use shipcat_definitions::{
     structs::{
         autoscaling::AutoScaling, security::DataHandling, tolerations::Tolerations, volume::Volume,
-        ConfigMap, Dependency, DestinationRule, EventStream, Gate, HealthCheck, HostAlias, Kafka, LifeCycle,
-        Metadata, NotificationMode, PersistentVolume, Probe, Rbac, RollingUpdate, SecurityContext, VaultOpts,
+        AlertRule, ConfigMappedFile, ConfigMaps, ContainerSecurityContext, Dependency, DeploymentStrategy,
+        DestinationRule, EventStream, Gate, GracefulShutdown, HealthCheck, HostAlias, Kafka, LifeCycle,
+        Metadata, NotificationMode, PersistentVolume, Probe, Rbac, ResourceRequirements, RollingUpdate,
+        SecurityContext, ServiceAccount, StatefulSetOptions, TopologySpreadConstraint, VaultOpts,
         VolumeMount,
     },
     BaseManifest, Config, Manifest, PrimaryWorkload, Region, Result,
@@ -13,13 +15,15 @@ use shipcat_definitions::{
 
 use super::{
     container::{
-        ContainerBuildParams, CronJobSource, EnvVarsSource, ImageNameSource, ImageTagSource,
-        InitContainerSource, PortSource, ResourceRequirementsSource, SidecarSource, WorkerSource,
+        import_env, ContainerBuildParams, ContainerSource, CronJobSource, EnvBuildParams, EnvImportSource,
+        EnvVarsSource, ImageNameSource, ImageTagSource, InitContainerSource, PortSource,
+        ResourceRequirementsSource, SidecarSource, WorkerSource,
     },
     kong::{KongApisBuildParams, KongApisSource, KongSource},
     newrelic_source::NewrelicSource,
+    overrides::MergeStrategy,
     sentry_source::SentrySource,
-    util::{Build, Enabled, RelaxedString, Require},
+    util::{Build, Enabled, RelaxedString, Require, ShipcatIgnore},
     SimpleManifest,
 };
 
@@ -28,30 +32,66 @@ use super::{
 #[serde(default, rename_all = "camelCase")]
 pub struct ManifestSource {
     pub name: Option<String>,
+    /// Name of a base template in `services/_bases` to inherit from
+    pub extends: Option<String>,
     pub external: bool,
     pub disabled: bool,
     pub regions: Vec<String>,
     pub metadata: Option<Metadata>,
+    /// Whether the version comes from a region lockfile
+    #[serde(skip)]
+    pub version_locked: bool,
+    /// Env vars that were vault secrets in one source, but set literally in a later one
+    #[serde(skip)]
+    pub plaintext_secrets: Vec<String>,
 
     #[serde(flatten)]
     pub overrides: ManifestOverrides,
 }
 
+/// Base template for manifests, deserialized from `services/_bases/<name>/shipcat.yml`
+///
+/// Holds the same properties as the overrides, and can itself extend another base.
+/// Like overrides, unknown keys are rejected rather than silently ignored.
+/// Serde cannot deny unknown fields next to the flattened overrides, so loading checks the keys.
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ManifestParent {
+    pub extends: Option<String>,
+
+    #[serde(flatten)]
+    pub overrides: ManifestOverrides,
+}
+
+/// Version pinned for a region, deserialized from `services/<name>/<region>.lock`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VersionLock {
+    pub version: ImageTagSource,
+}
+
 /// Manifest overrides, deserialized from `dev-uk.yml`/`prod.yml` etc.
-#[derive(Deserialize, Default, Merge, Clone)]
+///
+/// Unknown keys are errors, so that a typo does not silently drop an override.
+#[derive(Serialize, Deserialize, Default, Merge, Clone)]
 #[serde(default, deny_unknown_fields, rename_all = "camelCase")]
 pub struct ManifestOverrides {
     pub workload: Option<PrimaryWorkload>,
+    pub stateful_set: Option<StatefulSetOptions>,
     pub publicly_accessible: Option<bool>,
     pub image: Option<ImageNameSource>,
     pub image_size: Option<u32>,
     pub version: Option<ImageTagSource>,
     pub command: Option<Vec<String>>,
     pub security_context: Option<SecurityContext>,
+    pub container_security_context: Option<ContainerSecurityContext>,
     pub data_handling: Option<DataHandling>,
     pub resources: Option<ResourceRequirementsSource>,
+    pub resource_tier: Option<String>,
     pub secret_files: BTreeMap<String, String>,
-    pub configs: Option<ConfigMap>,
+    pub env_imports: Option<Vec<EnvImportSource>>,
+    pub exclude_region_env: Option<Vec<String>>,
+    pub configs: Option<ConfigMaps>,
     pub vault: Option<VaultOpts>,
     pub http_port: Option<u32>,
     pub ports: Option<Vec<PortSource>>,
@@ -61,13 +101,22 @@ pub struct ManifestOverrides {
     pub destination_rules: Option<Vec<DestinationRule>>,
     pub workers: Option<Vec<WorkerSource>>,
     pub sidecars: Option<Vec<SidecarSource>>,
+    pub debug_container: Option<SidecarSource>,
     pub readiness_probe: Option<Probe>,
     pub liveness_probe: Option<Probe>,
     pub lifecycle: Option<LifeCycle>,
+    pub graceful_shutdown: Option<GracefulShutdown>,
     pub rolling_update: Option<RollingUpdate>,
+    pub strategy: Option<DeploymentStrategy>,
+    pub paused: Option<bool>,
     pub auto_scaling: Option<AutoScaling>,
     pub tolerations: Option<Vec<Tolerations>>,
+    pub topology_spread: Option<Vec<TopologySpreadConstraint>>,
     pub host_aliases: Option<Vec<HostAlias>>,
+    pub host_network: Option<bool>,
+    pub standalone: Option<bool>,
+    pub dns_policy: Option<String>,
+    pub readiness_gates: Option<Vec<String>>,
     pub init_containers: Option<Vec<InitContainerSource>>,
     pub volumes: Option<Vec<Volume>>,
     pub volume_mounts: Option<Vec<VolumeMount>>,
@@ -76,28 +125,33 @@ pub struct ManifestOverrides {
     pub service_annotations: BTreeMap<String, String>,
     pub pod_annotations: BTreeMap<String, RelaxedString>,
     pub labels: BTreeMap<String, RelaxedString>,
+    pub features: BTreeMap<String, bool>,
     pub gate: Option<Gate>,
     pub kafka: Option<Kafka>,
     pub source_ranges: Option<Vec<String>>,
     pub rbac: Option<Vec<Rbac>>,
     pub sentry: Option<SentrySource>,
     pub event_streams: Option<Vec<EventStream>>,
+    pub alerts: Option<Vec<AlertRule>>,
     //  to have this section merge alerts sub-field deeply
     //      we have to avoid using Option
     pub newrelic: NewrelicSource,
     pub upgrade_notifications: Option<NotificationMode>,
+    /// How fields of this file combine with the sources it overrides
+    pub merge_strategy: BTreeMap<String, MergeStrategy>,
 
     #[serde(flatten)]
     pub defaults: ManifestDefaults,
 }
 
 /// Global/regional manifest defaults, deserialized from `shipcat.conf` etc.
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone)]
 #[serde(default, deny_unknown_fields, rename_all = "camelCase")]
 pub struct ManifestDefaults {
     pub image_prefix: Option<String>,
     pub chart: Option<String>,
     pub replica_count: Option<u32>,
+    pub service_account: Option<ServiceAccount>,
     pub env: EnvVarsSource,
     pub kong_apis: KongApisSource,
     // TODO: Migrate to kong_apis
@@ -110,15 +164,39 @@ impl ManifestSource {
     pub async fn build(self, (conf, region): &(Config, Region)) -> Result<Manifest> {
         let simple = self.build_simple(conf, region)?;
         let name = simple.base.name;
+        let canonical_name = region.serviceNaming.apply(&name);
         let data_handling = self.build_data_handling();
         let kafka = self.build_kafka(&name, region);
-        let configs = self.build_configs(&name).await?;
+        let configs = self.build_configs(&name, &canonical_name).await?;
+        let resources = self.build_resources(conf)?;
 
         let overrides = self.overrides;
-        let defaults = overrides.defaults;
+        let mut defaults = overrides.defaults;
+        if let Some(keys) = &overrides.exclude_region_env {
+            defaults.env.exclude_inherited(keys, &region.env)?;
+        }
 
+        let env_build_params = EnvBuildParams {
+            region: region.name.clone(),
+            regions: simple.base.regions.clone(),
+            dir: ManifestSource::services_dir().join(&name),
+        };
+        let mut env = defaults.env.clone().build(&env_build_params)?;
+        import_env(&mut env, overrides.env_imports.unwrap_or_default())?;
         let container_build_params = ContainerBuildParams {
-            main_envs: defaults.env.clone(),
+            main_envs: defaults.env,
+            env: env_build_params,
+        };
+
+        let (lifecycle, termination_grace_period) = match &overrides.graceful_shutdown {
+            Some(_) if overrides.lifecycle.is_some() => {
+                bail!("Cannot use both gracefulShutdown and lifecycle in {}", name)
+            }
+            Some(gs) => (
+                Some(gs.lifecycle(overrides.http_port)),
+                Some(gs.terminationGracePeriodSeconds),
+            ),
+            None => (overrides.lifecycle, None),
         };
 
         let team_notifications = simple
@@ -146,10 +224,14 @@ impl ManifestSource {
             version: simple.version,
             command: overrides.command.unwrap_or_default(),
             securityContext: overrides.security_context,
+            containerSecurityContext: overrides.container_security_context,
+            serviceAccount: defaults.service_account,
             dataHandling: data_handling,
-            resources: overrides.resources.build(&())?,
+            resources: resources,
+            resourceTier: overrides.resource_tier,
             replicaCount: defaults.replica_count,
-            env: defaults.env.build(&())?,
+            paused: overrides.paused.unwrap_or_default(),
+            env: env,
             secretFiles: overrides.secret_files,
             configs: configs,
             vault: overrides.vault,
@@ -167,13 +249,23 @@ impl ManifestSource {
                 .sidecars
                 .unwrap_or_default()
                 .build(&container_build_params)?,
+            debugContainer: overrides.debug_container.build(&container_build_params)?,
+            debug: false,
             readinessProbe: overrides.readiness_probe,
             livenessProbe: overrides.liveness_probe,
-            lifecycle: overrides.lifecycle,
+            lifecycle,
+            gracefulShutdown: overrides.graceful_shutdown,
+            terminationGracePeriodSeconds: termination_grace_period,
             rollingUpdate: overrides.rolling_update,
+            strategy: overrides.strategy,
             autoScaling: overrides.auto_scaling,
             tolerations: overrides.tolerations.unwrap_or_default(),
+            topologySpread: overrides.topology_spread.unwrap_or_default(),
             hostAliases: overrides.host_aliases.unwrap_or_default(),
+            hostNetwork: overrides.host_network.unwrap_or_default(),
+            standalone: overrides.standalone.unwrap_or_default(),
+            dnsPolicy: overrides.dns_policy,
+            readinessGates: overrides.readiness_gates.unwrap_or_default(),
             initContainers: overrides
                 .init_containers
                 .unwrap_or_default()
@@ -188,6 +280,7 @@ impl ManifestSource {
             serviceAnnotations: overrides.service_annotations,
             podAnnotations: overrides.pod_annotations.build(&())?,
             labels: overrides.labels.build(&())?,
+            features: overrides.features,
             kongApis: simple.kong_apis,
             gate: overrides.gate,
             kafka: kafka,
@@ -199,14 +292,19 @@ impl ManifestSource {
                 .map(|sentry| sentry.build(&team_notifications))
                 .transpose()?,
             eventStreams: overrides.event_streams.unwrap_or_default(),
+            alerts: overrides.alerts.unwrap_or_default(),
             upgradeNotifications: Default::default(),
             region: region.name.clone(),
             environment: region.environment.to_string(),
             namespace: region.namespace.clone(),
+            canonicalName: canonical_name,
+            versionLocked: self.version_locked,
+            plaintextSecrets: self.plaintext_secrets,
             uid: Default::default(),
             secrets: Default::default(),
             state: Default::default(),
             workload: overrides.workload.unwrap_or_default(),
+            statefulSet: overrides.stateful_set,
         })
     }
 }
@@ -270,6 +368,7 @@ impl ManifestSource {
     fn build_metadata(&self, conf: &Config) -> Result<Metadata> {
         let name = self.name.as_ref().expect("manifest name");
         let mut md = self.metadata.clone().require("metadata")?;
+        md.populate_teams();
 
         if let Some(s) = conf.owners.squads.get(&md.team) {
             md.squad = Some(s.name.clone());
@@ -310,6 +409,21 @@ impl ManifestSource {
         })
     }
 
+    /// Explicit resources, or those of the referenced resource tier
+    ///
+    /// Unknown tiers are left for `Manifest::verify` to report.
+    fn build_resources(&self, conf: &Config) -> Result<Option<ResourceRequirements<String>>> {
+        let overrides = &self.overrides;
+        if let Some(res) = &overrides.resources {
+            return res.clone().build(&()).map(Some);
+        }
+        let tier = overrides
+            .resource_tier
+            .as_ref()
+            .and_then(|t| conf.defaults.resourceTiers.get(t));
+        Ok(tier.cloned())
+    }
+
     // TODO: Extract KafkaSource
     fn build_kafka(&self, service: &str, reg: &Region) -> Option<Kafka> {
         let original = &self.overrides.kafka;
@@ -320,22 +434,170 @@ impl ManifestSource {
     }
 
     // TODO: Extract ConfigsSource
-    async fn build_configs(&self, service: &str) -> Result<Option<ConfigMap>> {
+    async fn build_configs(&self, service: &str, canonical_name: &str) -> Result<Option<ConfigMaps>> {
         let original = &self.overrides.configs;
         if original.is_none() {
             return Ok(None);
         }
         let mut configs = original.clone().unwrap();
-        for f in &mut configs.files {
-            f.value = Some(read_template_file(service, &f.name).await?);
+        for (i, cfg) in configs.iter_mut().enumerate() {
+            if cfg.name.is_none() {
+                cfg.name = Some(cfg.implicit_name(canonical_name, i));
+            }
+            if let Some(dir) = &cfg.directory {
+                cfg.files.extend(list_config_directory(service, dir)?);
+            }
+            for f in &mut cfg.files {
+                f.value = Some(read_template_file(service, &f.name).await?);
+            }
         }
         Ok(Some(configs))
     }
+}
+
+impl ManifestOverrides {
+    /// Env vars that are vault secrets here, but set to a literal value in `other`
+    ///
+    /// Covers the main env, as well as workers, sidecars, init containers and cron jobs of the same name.
+    /// Fails if `other` appends to one of these secrets.
+    pub fn overridden_secrets(&self, other: &Self) -> Result<Vec<String>> {
+        fn containers<T>(
+            ours: &Option<Vec<T>>,
+            theirs: &Option<Vec<T>>,
+            c: fn(&T) -> &ContainerSource,
+        ) -> Result<Vec<String>> {
+            let mut keys = vec![];
+            if let (Some(ours), Some(theirs)) = (ours, theirs) {
+                for o in ours {
+                    for t in theirs {
+                        keys.extend(c(o).overridden_secrets(c(t))?);
+                    }
+                }
+            }
+            Ok(keys)
+        }
+        let mut keys = self.defaults.env.overridden_secrets(&other.defaults.env)?;
+        keys.extend(containers(&self.workers, &other.workers, |w| &w.container)?);
+        keys.extend(containers(&self.sidecars, &other.sidecars, |s| &s.0)?);
+        keys.extend(containers(&self.init_containers, &other.init_containers, |i| &i.0)?);
+        keys.extend(containers(&self.cron_jobs, &other.cron_jobs, |c| &c.container)?);
+        if let (Some(ours), Some(theirs)) = (&self.debug_container, &other.debug_container) {
+            keys.extend(ours.0.overridden_secrets(&theirs.0)?);
+        }
+        Ok(keys)
+    }
 
-    pub(crate) fn merge_overrides(mut self, other: ManifestOverrides) -> Self {
-        self.overrides = self.overrides.merge(other);
-        self
+    /// Merge `other` into these overrides, following the `mergeStrategy` of `other`
+    ///
+    /// Maps merge by default and can be replaced as a whole, which also clears them if `other` does not set them.
+    /// Lists are replaced by default and can be appended to instead.
+    /// Strategies only apply to the file setting them, so are not kept in the result.
+    pub(crate) fn merge_with_strategy(mut self, mut other: Self) -> Result<Self> {
+        use MergeStrategy::{Append, Merge as Deep, Replace};
+        fn append<T>(base: &mut Option<Vec<T>>, over: &mut Option<Vec<T>>) {
+            if let Some(b) = base {
+                if let Some(o) = over.take() {
+                    b.extend(o);
+                }
+            }
+        }
+
+        // a resourceTier replaces the resources of earlier sources, and the other way around
+        if other.resource_tier.is_some() {
+            self.resources = None;
+        }
+        if other.resources.is_some() {
+            self.resource_tier = None;
+        }
+
+        self.merge_strategy.clear();
+        for (field, strategy) in std::mem::take(&mut other.merge_strategy) {
+            match (field.as_str(), strategy) {
+                ("env", Deep)
+                | ("labels", Deep)
+                | ("podAnnotations", Deep)
+                | ("serviceAnnotations", Deep)
+                | ("features", Deep)
+                | ("secretFiles", Deep) => {}
+                ("env", Replace) => self.defaults.env = Default::default(),
+                ("labels", Replace) => self.labels.clear(),
+                ("podAnnotations", Replace) => self.pod_annotations.clear(),
+                ("serviceAnnotations", Replace) => self.service_annotations.clear(),
+                ("features", Replace) => self.features.clear(),
+                ("secretFiles", Replace) => self.secret_files.clear(),
+                ("command", Replace)
+                | ("dependencies", Replace)
+                | ("workers", Replace)
+                | ("sidecars", Replace)
+                | ("initContainers", Replace)
+                | ("cronJobs", Replace)
+                | ("hostAliases", Replace)
+                | ("tolerations", Replace)
+                | ("volumes", Replace)
+                | ("volumeMounts", Replace)
+                | ("persistentVolumes", Replace)
+                | ("envImports", Replace)
+                | ("alerts", Replace) => {}
+                ("command", Append) => append(&mut self.command, &mut other.command),
+                ("dependencies", Append) => append(&mut self.dependencies, &mut other.dependencies),
+                ("workers", Append) => append(&mut self.workers, &mut other.workers),
+                ("sidecars", Append) => append(&mut self.sidecars, &mut other.sidecars),
+                ("initContainers", Append) => append(&mut self.init_containers, &mut other.init_containers),
+                ("cronJobs", Append) => append(&mut self.cron_jobs, &mut other.cron_jobs),
+                ("hostAliases", Append) => append(&mut self.host_aliases, &mut other.host_aliases),
+                ("tolerations", Append) => append(&mut self.tolerations, &mut other.tolerations),
+                ("volumes", Append) => append(&mut self.volumes, &mut other.volumes),
+                ("volumeMounts", Append) => append(&mut self.volume_mounts, &mut other.volume_mounts),
+                ("persistentVolumes", Append) => {
+                    append(&mut self.persistent_volumes, &mut other.persistent_volumes)
+                }
+                ("envImports", Append) => append(&mut self.env_imports, &mut other.env_imports),
+                ("alerts", Append) => append(&mut self.alerts, &mut other.alerts),
+                (_, s) => bail!("mergeStrategy {} is not supported for {}", s.as_str(), field),
+            }
+        }
+        Ok(self.merge(other))
+    }
+}
+
+/// Config files in a service's config directory that are not excluded by `.shipcatignore`
+fn list_config_directory(svc: &str, dir: &str) -> Result<Vec<ConfigMappedFile>> {
+    use std::path::Path;
+    use walkdir::WalkDir;
+    let pth = Path::new(".").join("services").join(svc).join(dir);
+    if !pth.is_dir() {
+        bail!("Config directory {} does not exist", pth.display());
+    }
+    let ignore = ShipcatIgnore::read(&pth.join(".shipcatignore"))?;
+    let mut files = vec![];
+    let walker = WalkDir::new(&pth)
+        .min_depth(1)
+        .max_depth(1)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()));
+    for entry in walker {
+        let entry = entry.map_err(|e| format!("Failed to read config directory {}: {}", pth.display(), e))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        // only templates are picked up, e.g. not a README or editor swap files
+        if !name.ends_with(".j2") || ignore.is_ignored(&name) {
+            debug!("Skipping ignored config file {}/{}", dir, name);
+            continue;
+        }
+        files.push(ConfigMappedFile {
+            dest: name.trim_end_matches(".j2").to_string(),
+            name: format!("{}/{}", dir.trim_end_matches('/'), name),
+            value: None,
+        });
+    }
+    if files.is_empty() {
+        bail!(
+            "No .j2 config files left in {} after applying .shipcatignore",
+            pth.display()
+        );
     }
+    Ok(files)
 }
 
 async fn read_template_file(svc: &str, tmpl: &str) -> Result<String> {
@@ -376,7 +638,7 @@ mod tests {
     use merge::Merge;
     use std::collections::BTreeMap;
 
-    use super::ManifestDefaults;
+    use super::{ManifestDefaults, ManifestOverrides, ManifestParent};
 
     #[test]
     fn merge() {
@@ -415,4 +677,93 @@ mod tests {
         expected_env.insert("c", "override-c");
         assert_eq!(merged.env, expected_env.into());
     }
+
+    #[test]
+    fn merge_with_strategy() {
+        let base: ManifestOverrides =
+            serde_yaml::from_str("labels:\n  a: x\ninitContainers:\n- name: migrate\n  image: alpine\n")
+                .unwrap();
+        let over: ManifestOverrides = serde_yaml::from_str(
+            "mergeStrategy:\n  labels: replace\n  initContainers: append\n\
+             initContainers:\n- name: warmup\n  image: alpine\n",
+        )
+        .unwrap();
+        let merged = base.clone().merge_with_strategy(over).unwrap();
+        assert!(merged.labels.is_empty());
+        assert_eq!(merged.init_containers.unwrap().len(), 2);
+        assert!(merged.merge_strategy.is_empty());
+
+        // defaults match plain merging
+        let over: ManifestOverrides = serde_yaml::from_str(
+            "mergeStrategy:\n  labels: merge\n  initContainers: replace\n\
+             initContainers:\n- name: warmup\n  image: alpine\n",
+        )
+        .unwrap();
+        let merged = base.clone().merge_with_strategy(over).unwrap();
+        assert_eq!(merged.labels.len(), 1);
+        assert_eq!(merged.init_containers.unwrap().len(), 1);
+
+        let over: ManifestOverrides = serde_yaml::from_str("mergeStrategy:\n  labels: append\n").unwrap();
+        let err = base.merge_with_strategy(over).err().unwrap();
+        assert_eq!(err.to_string(), "mergeStrategy append is not supported for labels");
+    }
+
+    #[test]
+    fn merge_resource_tier() {
+        let base: ManifestOverrides =
+            serde_yaml::from_str("resources:\n  requests:\n    cpu: 100m\n    memory: 100Mi\n").unwrap();
+        let over: ManifestOverrides = serde_yaml::from_str("resourceTier: large\n").unwrap();
+        let merged = base.merge_with_strategy(over).unwrap();
+        assert!(merged.resources.is_none());
+        assert_eq!(merged.resource_tier, Some("large".into()));
+
+        let over: ManifestOverrides =
+            serde_yaml::from_str("resources:\n  requests:\n    cpu: 1\n    memory: 1Gi\n").unwrap();
+        let merged = merged.merge_with_strategy(over).unwrap();
+        assert!(merged.resources.is_some());
+        assert!(merged.resource_tier.is_none());
+    }
+
+    #[test]
+    fn overridden_secrets() {
+        let base: ManifestOverrides = serde_yaml::from_str(
+            "env:\n  API_KEY: IN_VAULT\n\
+             workers:\n- name: queue\n  command: [work]\n  env:\n    QUEUE_KEY: IN_VAULT\n\
+             cronJobs:\n- name: backup\n  schedule: '0 0 * * *'\n  env:\n    DB_PASS: IN_VAULT\n",
+        )
+        .unwrap();
+        let over: ManifestOverrides = serde_yaml::from_str(
+            "env:\n  API_KEY: hunter2\n\
+             workers:\n- name: queue\n  command: [work]\n  env:\n    QUEUE_KEY: hunter2\n\
+             cronJobs:\n- name: restore\n  schedule: '0 0 * * *'\n  env:\n    DB_PASS: hunter2\n",
+        )
+        .unwrap();
+        // cron jobs of a different name are not overrides
+        assert_eq!(
+            base.overridden_secrets(&over).unwrap(),
+            vec!["API_KEY".to_string(), "queue.QUEUE_KEY".to_string()]
+        );
+        assert!(over.overridden_secrets(&base).unwrap().is_empty());
+
+        let append: ManifestOverrides = serde_yaml::from_str(
+            "workers:\n- name: queue\n  command: [work]\n  env:\n    QUEUE_KEY: +=suffix\n",
+        )
+        .unwrap();
+        assert!(base.overridden_secrets(&append).is_err());
+    }
+
+    #[test]
+    fn unknown_override_keys() {
+        let ok: Result<ManifestOverrides, _> = serde_yaml::from_str("resources:\n  requests:\n    cpu: 100m\n");
+        assert!(ok.is_ok());
+
+        let typo = "resorces:\n  requests:\n    cpu: 100m\n";
+        let err = serde_yaml::from_str::<ManifestOverrides>(typo).err().unwrap();
+        assert!(err.to_string().contains("resorces"));
+        // bases hold overrides next to their own keys
+        let base = "extends: web\nhealth:\n  uri: /health\n  wait: 30\nenv:\n  INSTANCE_TYPE: web\n";
+        let base: ManifestParent = serde_yaml::from_str(base).unwrap();
+        assert_eq!(base.extends, Some("web".to_string()));
+        assert_eq!(base.overrides.health.unwrap().wait, 30);
+    }
 }
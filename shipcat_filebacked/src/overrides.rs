@@ -0,0 +1,318 @@
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+
+use shipcat_definitions::Result;
+
+use crate::manifest::ManifestOverrides;
+
+/// How a file combines a field with the sources it overrides
+///
+/// Set per field with `mergeStrategy` in the file, e.g. `mergeStrategy: { initContainers: append }`.
+/// Maps default to `merge` and lists to `replace`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeStrategy {
+    /// Drop the earlier value, keeping only the value of this file
+    Replace,
+    /// Add the entries of a map, replacing those with the same key
+    Merge,
+    /// Add the items of a list after the earlier ones
+    Append,
+}
+
+impl MergeStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MergeStrategy::Replace => "replace",
+            MergeStrategy::Merge => "merge",
+            MergeStrategy::Append => "append",
+        }
+    }
+}
+
+/// A field set by an override file
+///
+/// Paths use the keys of the files, e.g. `resources` or `env.LOG_LEVEL`.
+/// Maps that merge key by key, like `env` and `labels`, are listed per key,
+/// while other values are listed as a whole with the value they ended up with.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AppliedOverride {
+    /// File the override was read from
+    pub file: String,
+    /// Path of the overridden field
+    pub path: String,
+    /// Value before the override, if an earlier file set it
+    pub old: Option<Value>,
+    /// Value after the override, null if it was cleared
+    pub new: Value,
+}
+
+impl AppliedOverride {
+    /// Whether the override sets the value it already had
+    ///
+    /// These are usually stale, e.g. left behind after the default was changed to match.
+    pub fn is_noop(&self) -> bool {
+        self.old.as_ref() == Some(&self.new)
+    }
+}
+
+/// Fields that merge key by key, unless replaced with a `mergeStrategy`
+const MERGED_MAPS: &[&str] = &[
+    "env",
+    "labels",
+    "podAnnotations",
+    "serviceAnnotations",
+    "features",
+    "secretFiles",
+];
+
+/// Overrides applied while merging the sources of a manifest
+///
+/// Merges go through `ManifestOverrides::merge_with_strategy` either way,
+/// but only a log created for a region records what they change.
+pub(crate) struct OverrideLog {
+    region: Option<String>,
+    pub(crate) applied: Vec<AppliedOverride>,
+}
+
+impl OverrideLog {
+    /// A log that merges without recording anything
+    pub(crate) fn disabled() -> Self {
+        OverrideLog {
+            region: None,
+            applied: vec![],
+        }
+    }
+
+    /// A log recording overrides, with conditional env vars resolved for `region`
+    pub(crate) fn new(region: &str) -> Self {
+        OverrideLog {
+            region: Some(region.to_string()),
+            applied: vec![],
+        }
+    }
+
+    /// Merge the overrides of `file` into `base`, recording every field they change
+    ///
+    /// With `replacements_only`, fields that no earlier file set are merged without being recorded.
+    pub(crate) fn merge(
+        &mut self,
+        file: &Path,
+        base: ManifestOverrides,
+        other: ManifestOverrides,
+        replacements_only: bool,
+    ) -> Result<ManifestOverrides> {
+        let region = match &self.region {
+            Some(r) => r.clone(),
+            None => return base.merge_with_strategy(other),
+        };
+        let replaced = other
+            .merge_strategy
+            .iter()
+            .filter(|(_, s)| **s == MergeStrategy::Replace)
+            .map(|(f, _)| f.clone())
+            .collect::<Vec<_>>();
+        let old = values(&base, Some(&region))?;
+        let set = values(&other, None)?;
+        let merged = base.merge_with_strategy(other)?;
+        let new = values(&merged, Some(&region))?;
+
+        let file = file.display().to_string();
+        let mut record = |path: String, old: Option<&Value>, new: Option<&Value>| {
+            let old = old.filter(|v| is_set(v)).map(pruned);
+            if old.is_some() || !replacements_only {
+                self.applied.push(AppliedOverride {
+                    file: file.clone(),
+                    path,
+                    old,
+                    new: new.map(pruned).unwrap_or(Value::Null),
+                });
+            }
+        };
+        for (k, v) in &set {
+            if !is_set(v) {
+                continue;
+            }
+            let field = key(k);
+            match v {
+                Value::Mapping(entries)
+                    if MERGED_MAPS.contains(&field.as_str()) && !replaced.contains(&field) =>
+                {
+                    for (ek, ev) in entries {
+                        if is_set(ev) {
+                            let path = format!("{}.{}", field, key(ek));
+                            record(path, entry(&old, k, ek), entry(&new, k, ek));
+                        }
+                    }
+                }
+                _ => record(field, old.get(k), new.get(k)),
+            }
+        }
+        // fields cleared without being set, e.g. by a `resourceTier` or a `replace` strategy
+        for (k, v) in &old {
+            if is_set(v) && !set.get(k).map_or(false, is_set) && new.get(k) != Some(v) {
+                record(key(k), Some(v), new.get(k));
+            }
+        }
+        Ok(merged)
+    }
+}
+
+/// Serialized fields of overrides, with the env of a region if given
+fn values(overrides: &ManifestOverrides, region: Option<&str>) -> Result<Mapping> {
+    let mut overrides = overrides.clone();
+    if let Some(r) = region {
+        overrides.defaults.env = overrides.defaults.env.in_region(r);
+    }
+    match serde_yaml::to_value(&overrides)? {
+        Value::Mapping(mut m) => {
+            m.remove(&Value::String("mergeStrategy".into()));
+            Ok(m)
+        }
+        _ => Ok(Mapping::new()),
+    }
+}
+
+/// Value of `entry` in the map `field`
+fn entry<'a>(values: &'a Mapping, field: &Value, entry: &Value) -> Option<&'a Value> {
+    match values.get(field) {
+        Some(Value::Mapping(m)) => m.get(entry),
+        _ => None,
+    }
+}
+
+/// Whether a serialized value is set, rather than left empty
+fn is_set(v: &Value) -> bool {
+    match v {
+        Value::Null => false,
+        Value::Mapping(m) => m.iter().any(|(_, v)| is_set(v)),
+        _ => true,
+    }
+}
+
+/// A serialized value without the fields that are not set
+fn pruned(v: &Value) -> Value {
+    match v {
+        Value::Mapping(m) => Value::Mapping(
+            m.iter()
+                .filter(|(_, v)| is_set(v))
+                .map(|(k, v)| (k.clone(), pruned(v)))
+                .collect(),
+        ),
+        Value::Sequence(s) => Value::Sequence(s.iter().map(pruned).collect()),
+        _ => v.clone(),
+    }
+}
+
+fn key(k: &Value) -> String {
+    match k {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => "~".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OverrideLog;
+    use crate::manifest::ManifestOverrides;
+    use serde_yaml::Value;
+    use std::path::Path;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    fn overrides(s: &str) -> ManifestOverrides {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn applied_overrides() {
+        let mut log = OverrideLog::new("dev-uk");
+        let base = "replicaCount: 2\nenv:\n  A: '1'\n  B: '2'\n  OPTS: -Xmx1g\n\
+                    resources:\n  requests:\n    cpu: 100m\n    memory: 1Gi\n";
+        let mf = log
+            .merge(
+                Path::new("manifest.yml"),
+                Default::default(),
+                overrides(base),
+                true,
+            )
+            .unwrap();
+        assert!(log.applied.is_empty());
+
+        let dev = "replicaCount: 1\nenv:\n  B: '2'\n  C: '3'\n  OPTS: +=-Xms1g\n";
+        let mf = log
+            .merge(Path::new("dev.yml"), mf, overrides(dev), false)
+            .unwrap();
+        let dev_uk = "resourceTier: large\nenv:\n  A:\n    value: '2'\n    regions: [dev-global]\n";
+        log.merge(Path::new("dev-uk.yml"), mf, overrides(dev_uk), false)
+            .unwrap();
+        let applied = log
+            .applied
+            .iter()
+            .map(|a| (a.file.as_str(), a.path.as_str(), a.is_noop()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            applied,
+            vec![
+                ("dev.yml", "replicaCount", false),
+                ("dev.yml", "env.B", true),
+                ("dev.yml", "env.C", false),
+                ("dev.yml", "env.OPTS", false),
+                ("dev-uk.yml", "resourceTier", false),
+                ("dev-uk.yml", "env.A", false),
+                // cleared by the resourceTier
+                ("dev-uk.yml", "resources", false),
+            ]
+        );
+        assert_eq!(log.applied[0].old, Some(yaml("2")));
+        assert_eq!(log.applied[2].old, None);
+        // appends end up with the appended value
+        assert_eq!(log.applied[3].new, yaml("-Xmx1g -Xms1g"));
+        // conditional values for other regions remove the env var
+        assert_eq!(log.applied[5].old, Some(yaml("'1'")));
+        assert_eq!(log.applied[5].new, Value::Null);
+        assert_eq!(log.applied[6].new, Value::Null);
+    }
+
+    #[test]
+    fn applied_merge_strategies() {
+        let mut log = OverrideLog::new("dev-uk");
+        let base = "env:\n  A: '1'\ninitContainers:\n- name: migrate\n  image: alpine\n";
+        let mf = log
+            .merge(
+                Path::new("manifest.yml"),
+                Default::default(),
+                overrides(base),
+                true,
+            )
+            .unwrap();
+
+        let over = "mergeStrategy:\n  env: replace\n  initContainers: append\n\
+                    initContainers:\n- name: warmup\n  image: alpine\n";
+        log.merge(Path::new("dev.yml"), mf, overrides(over), false)
+            .unwrap();
+        let paths = log.applied.iter().map(|a| a.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["initContainers", "env"]);
+        assert_eq!(log.applied[0].new.as_sequence().unwrap().len(), 2);
+        assert_eq!(log.applied[1].new, yaml("{}"));
+    }
+
+    #[test]
+    fn disabled_log() {
+        let mut log = OverrideLog::disabled();
+        let mf = log
+            .merge(
+                Path::new("dev.yml"),
+                Default::default(),
+                overrides("replicaCount: 1\n"),
+                false,
+            )
+            .unwrap();
+        assert_eq!(mf.defaults.replica_count, Some(1));
+        assert!(log.applied.is_empty());
+    }
+}
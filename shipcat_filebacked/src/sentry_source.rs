@@ -14,7 +14,7 @@ use crate::util::Build;
 /// if you find sentry too noisy you are able to mute it with true
 ///   silent: true
 /// ```
-#[derive(Debug, Default, Clone, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct SentrySource {
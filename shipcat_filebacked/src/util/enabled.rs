@@ -23,7 +23,7 @@ use super::Build;
 ///     value: 3
 /// bar: ~
 /// ```
-#[derive(Deserialize, Default, Clone, PartialEq, Merge)]
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Merge)]
 #[cfg_attr(test, derive(Debug, Copy))]
 #[serde(default, deny_unknown_fields)]
 pub struct Enabled<T: Merge> {
@@ -46,7 +46,7 @@ impl<S: Build<B, P> + Merge, B, P> Build<Option<B>, P> for Enabled<S> {
 /// EnabledMap is a map where each value is wrapped in an Enabled.
 ///
 /// It can be built into a map which flattens the Enabled wrappers, so disabled values are excluded.
-#[derive(Deserialize, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
 #[cfg_attr(test, derive(Debug))]
 pub struct EnabledMap<K: Clone + std::hash::Hash + Ord, V: Clone + Default + Merge>(BTreeMap<K, Enabled<V>>);
 
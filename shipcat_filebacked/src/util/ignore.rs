@@ -0,0 +1,200 @@
+use regex::Regex;
+use std::path::Path;
+
+use shipcat_definitions::Result;
+
+/// Patterns read from a `.shipcatignore` file
+///
+/// Uses a subset of gitignore syntax matched against file names:
+///
+/// ```text
+/// # comments and blank lines are skipped
+/// *.bak
+/// generated-?.json
+/// !keep.json.j2
+/// ```
+///
+/// The last matching pattern decides, so negations can re-include files.
+/// A `**/` matches any number of directories, including none, so `**/local.j2` matches `local.j2`.
+/// Patterns ending with a slash only match directories, and are thus ignored here.
+#[derive(Default)]
+pub struct ShipcatIgnore {
+    patterns: Vec<(Regex, bool)>,
+}
+
+impl ShipcatIgnore {
+    /// Read ignore patterns from a file, or no patterns if it does not exist
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        debug!("Reading ignore patterns from {}", path.display());
+        let data = std::fs::read_to_string(path)?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut patterns = vec![];
+        for line in data.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') || line.ends_with('/') {
+                continue;
+            }
+            let (glob, negated) = if line.starts_with('!') {
+                (&line[1..], true)
+            } else {
+                (line.trim_start_matches('\\'), false)
+            };
+            let glob = glob.trim_start_matches('/');
+            patterns.push((glob_to_regex(glob)?, negated));
+        }
+        Ok(ShipcatIgnore { patterns })
+    }
+
+    /// Whether a file name is excluded by these patterns
+    pub fn is_ignored(&self, name: &str) -> bool {
+        let mut ignored = false;
+        for (re, negated) in &self.patterns {
+            if re.is_match(name) {
+                ignored = !negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// Translate a glob into an anchored regex
+///
+/// Literal segments are escaped with `regex::escape` before the wildcards are expanded,
+/// so regex metacharacters like `+`, `(` or `|` only match themselves.
+/// A `[` without a closing `]` is literal as well.
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let mut re = String::from("^");
+    let mut literal = String::new();
+    let mut rest = glob;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        let wildcard = match c {
+            '*' if rest.starts_with("*/") => {
+                rest = &rest[2..];
+                "(?:.*/)?".to_string()
+            }
+            '*' if rest.starts_with('*') => {
+                rest = &rest[1..];
+                ".*".to_string()
+            }
+            '*' => "[^/]*".to_string(),
+            '?' => "[^/]".to_string(),
+            '[' if rest.contains(']') => {
+                let end = rest.find(']').unwrap();
+                let (negated, class) = match &rest[..end] {
+                    cls if cls.starts_with('!') => (true, &cls[1..]),
+                    cls => (false, cls),
+                };
+                rest = &rest[end + 1..];
+                let class = class.replace('\\', "\\\\").replace('[', "\\[");
+                format!("[{}{}]", if negated { "^" } else { "" }, class)
+            }
+            c => {
+                literal.push(c);
+                continue;
+            }
+        };
+        re.push_str(&regex::escape(&literal));
+        literal.clear();
+        re.push_str(&wildcard);
+    }
+    re.push_str(&regex::escape(&literal));
+    re.push('$');
+    match Regex::new(&re) {
+        Ok(r) => Ok(r),
+        Err(e) => bail!("Invalid ignore pattern '{}': {}", glob, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShipcatIgnore;
+
+    #[test]
+    fn ignore_patterns() {
+        let ign = ShipcatIgnore::parse(
+            "# generated stuff\n\n*.bak\ngenerated-?.json.j2\n/cache.j2\nbuild/\n[ab]ux.j2\n!keep.bak\n",
+        )
+        .unwrap();
+        assert!(ign.is_ignored("foo.bak"));
+        assert!(!ign.is_ignored("keep.bak"));
+        assert!(ign.is_ignored("generated-1.json.j2"));
+        assert!(!ign.is_ignored("generated-10.json.j2"));
+        assert!(ign.is_ignored("cache.j2"));
+        assert!(ign.is_ignored("aux.j2"));
+        assert!(!ign.is_ignored("cux.j2"));
+        assert!(!ign.is_ignored("build"));
+        assert!(!ign.is_ignored("config.ini.j2"));
+    }
+
+    #[test]
+    fn ignore_metacharacters() {
+        let ign = ShipcatIgnore::parse("foo+bar.j2\na(b).j2\n{x|y}.j2\nc[.j2\n").unwrap();
+        assert!(ign.is_ignored("foo+bar.j2"));
+        assert!(!ign.is_ignored("fooobar.j2"));
+        assert!(ign.is_ignored("a(b).j2"));
+        assert!(!ign.is_ignored("ab.j2"));
+        assert!(ign.is_ignored("{x|y}.j2"));
+        assert!(!ign.is_ignored("x.j2"));
+        assert!(ign.is_ignored("c[.j2"));
+    }
+
+    #[test]
+    fn ignore_double_star() {
+        let ign = ShipcatIgnore::parse("**/local.j2
+secrets/**/*.j2
+out/**
+").unwrap();
+        assert!(ign.is_ignored("local.j2"));
+        assert!(ign.is_ignored("a/b/local.j2"));
+        assert!(!ign.is_ignored("notlocal.j2"));
+        assert!(ign.is_ignored("secrets/x.j2"));
+        assert!(ign.is_ignored("secrets/a/b/x.j2"));
+        assert!(!ign.is_ignored("secretsx.j2"));
+        assert!(ign.is_ignored("out/a/b.j2"));
+        assert!(!ign.is_ignored("out"));
+    }
+
+    #[test]
+    fn ignore_directories() {
+        // directory patterns never match the files we look at
+        let ign = ShipcatIgnore::parse("tmp/
+*.j2/
+").unwrap();
+        assert!(!ign.is_ignored("tmp"));
+        assert!(!ign.is_ignored("config.j2"));
+    }
+
+    #[test]
+    fn ignore_negation() {
+        // the last matching pattern wins
+        let ign = ShipcatIgnore::parse("*.j2
+!keep.j2
+keep.j2
+!local-*.j2
+").unwrap();
+        assert!(ign.is_ignored("keep.j2"));
+        assert!(!ign.is_ignored("local-dev.j2"));
+        assert!(ign.is_ignored("other.j2"));
+        // negating something that was never ignored is a no-op
+        let ign = ShipcatIgnore::parse("!keep.j2
+").unwrap();
+        assert!(!ign.is_ignored("keep.j2"));
+        // escaped leading characters are literal
+        let ign = ShipcatIgnore::parse("\\!bang.j2\n\\#hash.j2\n").unwrap();
+        assert!(ign.is_ignored("!bang.j2"));
+        assert!(ign.is_ignored("#hash.j2"));
+    }
+
+    #[test]
+    fn empty_ignore() {
+        let ign = ShipcatIgnore::parse("").unwrap();
+        assert!(!ign.is_ignored("anything.j2"));
+    }
+}
@@ -1,9 +1,11 @@
 mod build;
 mod enabled;
+mod ignore;
 mod relaxedstring;
 mod require;
 
 pub use build::Build;
 pub use enabled::{Enabled, EnabledMap};
+pub use ignore::ShipcatIgnore;
 pub use relaxedstring::RelaxedString;
 pub use require::Require;
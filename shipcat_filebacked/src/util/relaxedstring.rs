@@ -15,6 +15,12 @@ impl<T: ToString> From<T> for RelaxedString {
     }
 }
 
+impl RelaxedString {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl Build<String, ()> for RelaxedString {
     fn build(self, _: &()) -> shipcat_definitions::Result<String> {
         Ok(self.0)